@@ -0,0 +1,84 @@
+//! Peak memory while turning a large response body into items.
+//!
+//! Run with `cargo bench --bench large_page -- [size_mb]` (default 8 MB). Reports
+//! how far the process high-water mark rises above the size of the page itself;
+//! a zero-copy pipeline keeps this close to one page's worth of extra memory.
+
+use bytes::Bytes;
+use crawler::cleanup::clean_html;
+use crawler::config::schema::{HtmlCleanupConfig, SelectorConfig};
+use crawler::extract::Extractor;
+use crawler::http::body::Body;
+use crawler::metrics::memory::peak_rss_bytes;
+use crawler::spider::{ExtractionRule, ExtractionType};
+use std::collections::HashMap;
+use std::time::Instant;
+
+fn generate_page(target_bytes: usize) -> Vec<u8> {
+    let mut html = String::with_capacity(target_bytes + 1024);
+    html.push_str("<html><head><title>bench</title><script>var x = 1;</script></head><body>");
+    let mut i = 0;
+    while html.len() < target_bytes {
+        html.push_str(&format!(
+            "<div class=\"quote\" onclick=\"track({i})\"><span class=\"text\">Quote number {i}</span>\
+             <small class=\"author\">Author {i}</small><!-- row {i} --></div>"
+        ));
+        i += 1;
+    }
+    html.push_str("</body></html>");
+    html.into_bytes()
+}
+
+fn main() {
+    let size_mb: usize = std::env::args()
+        .skip(1)
+        .find_map(|arg| arg.parse().ok())
+        .unwrap_or(8);
+    let page = generate_page(size_mb * 1024 * 1024);
+    let page_len = page.len();
+
+    let mut rules = HashMap::new();
+    rules.insert(
+        "text".to_string(),
+        ExtractionRule {
+            selector: SelectorConfig::Simple("css:.text".to_string()),
+            extract: ExtractionType::Text,
+            ..Default::default()
+        },
+    );
+    let extractor = Extractor::new(Some(SelectorConfig::Simple("css:.quote".to_string())), rules);
+    let cleanup = HtmlCleanupConfig {
+        strip_scripts: true,
+        strip_comments: true,
+        strip_event_handlers: true,
+        ..Default::default()
+    };
+
+    let baseline = peak_rss_bytes().unwrap_or(0);
+    let start = Instant::now();
+
+    let body = Body {
+        bytes: Bytes::from(page),
+        wire_bytes: page_len,
+        content_type: Some("text/html; charset=utf-8".to_string()),
+    };
+    let html = clean_html(body.into_text(), &cleanup);
+    let items = extractor.extract(html, "http://bench.local/");
+
+    let elapsed = start.elapsed();
+    let peak = peak_rss_bytes().unwrap_or(0);
+    let growth = peak.saturating_sub(baseline);
+
+    println!("page size:        {:.1} MB", page_len as f64 / 1_048_576.0);
+    println!("items extracted:  {}", items.len());
+    println!("elapsed:          {:.1?}", elapsed);
+    if peak > 0 {
+        println!(
+            "peak RSS growth:  {:.1} MB ({:.2}x page size)",
+            growth as f64 / 1_048_576.0,
+            growth as f64 / page_len as f64
+        );
+    } else {
+        println!("peak RSS growth:  unavailable on this platform");
+    }
+}
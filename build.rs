@@ -0,0 +1,14 @@
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    // The gRPC service is generated from proto/crawler.proto, with a bundled
+    // protoc unless PROTOC points to one
+    #[cfg(feature = "grpc")]
+    {
+        let mut config = prost_build::Config::new();
+        if std::env::var_os("PROTOC").is_none() {
+            config.protoc_executable(protoc_bin_vendored::protoc_bin_path()?);
+        }
+        tonic_prost_build::configure()
+            .compile_with_config(config, &["proto/crawler.proto"], &["proto"])?;
+    }
+    Ok(())
+}
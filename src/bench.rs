@@ -0,0 +1,148 @@
+//! Reproducible throughput benchmark: a local axum server serves generated
+//! pages and the engine crawls them at increasing concurrency levels.
+
+use crate::config::schema::SelectorConfig;
+use crate::crawler::CrawlerEngine;
+use crate::error::Result;
+use crate::metrics::collector::MetricsCollector;
+use crate::metrics::memory::peak_rss_bytes;
+use crate::output::null::NullOutput;
+use crate::spider::{ExtractionRule, ExtractionType, GenericSpider};
+use axum::extract::{Path, State};
+use axum::response::Html;
+use axum::routing::get;
+use axum::Router;
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::sync::atomic::Ordering;
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Clone)]
+pub struct BenchOptions {
+    /// Pages crawled per concurrency level
+    pub pages: usize,
+    /// Approximate size of each generated page
+    pub page_kb: usize,
+    /// Artificial server latency added to every response
+    pub latency_ms: u64,
+    pub concurrency_levels: Vec<usize>,
+}
+
+#[derive(Debug, Clone)]
+pub struct BenchResult {
+    pub concurrency: usize,
+    pub pages: u64,
+    pub items: u64,
+    pub elapsed_secs: f64,
+    pub requests_per_second: f64,
+    pub p50_ms: u64,
+    pub p99_ms: u64,
+    pub peak_rss_bytes: u64,
+}
+
+#[derive(Clone)]
+struct ServerState {
+    page_kb: usize,
+    latency: Duration,
+}
+
+/// Runs one crawl per concurrency level against a freshly started local server.
+pub async fn run(options: &BenchOptions) -> Result<Vec<BenchResult>> {
+    let addr = start_server(ServerState {
+        page_kb: options.page_kb,
+        latency: Duration::from_millis(options.latency_ms),
+    })
+    .await?;
+
+    let mut results = Vec::new();
+    for &concurrency in &options.concurrency_levels {
+        results.push(run_level(addr, options.pages, concurrency.max(1)).await?);
+    }
+    Ok(results)
+}
+
+async fn run_level(addr: SocketAddr, pages: usize, concurrency: usize) -> Result<BenchResult> {
+    let urls = (0..pages)
+        .map(|i| format!("http://{}/page/{}/{}", addr, concurrency, i))
+        .collect();
+
+    let mut rules = HashMap::new();
+    for (field, class) in [("text", "text"), ("author", "author")] {
+        rules.insert(
+            field.to_string(),
+            ExtractionRule {
+                selector: SelectorConfig::Simple(format!("css:.{}", class)),
+                extract: ExtractionType::Text,
+                ..Default::default()
+            },
+        );
+    }
+
+    let output = NullOutput::new();
+    let items = output.counter();
+    let metrics = Arc::new(MetricsCollector::new());
+    let spider = GenericSpider::new(
+        format!("bench-c{}", concurrency),
+        urls,
+        Some(SelectorConfig::Simple("css:.quote".to_string())),
+        rules,
+        Box::new(output),
+    )
+    .with_metrics(metrics.clone());
+
+    let engine = CrawlerEngine::new(Duration::ZERO, concurrency, Some(metrics));
+    let start = Instant::now();
+    let report = engine.run(Arc::new(spider)).await?;
+    let elapsed = start.elapsed().as_secs_f64();
+
+    let snapshot = report.metrics;
+    Ok(BenchResult {
+        concurrency,
+        pages: snapshot.urls_processed,
+        items: items.load(Ordering::Relaxed),
+        elapsed_secs: elapsed,
+        requests_per_second: if elapsed > 0.0 {
+            snapshot.requests_total as f64 / elapsed
+        } else {
+            0.0
+        },
+        p50_ms: snapshot.p50_response_time_ms,
+        p99_ms: snapshot.p99_response_time_ms,
+        peak_rss_bytes: peak_rss_bytes().unwrap_or(0),
+    })
+}
+
+async fn start_server(state: ServerState) -> Result<SocketAddr> {
+    let app = Router::new()
+        .route("/page/{run}/{id}", get(page))
+        .with_state(state);
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await?;
+    let addr = listener.local_addr()?;
+    tokio::spawn(async move {
+        if let Err(e) = axum::serve(listener, app).await {
+            log::error!("Bench server stopped: {}", e);
+        }
+    });
+    Ok(addr)
+}
+
+async fn page(State(state): State<ServerState>, Path((_run, id)): Path<(usize, usize)>) -> Html<String> {
+    if !state.latency.is_zero() {
+        tokio::time::sleep(state.latency).await;
+    }
+
+    let target = state.page_kb * 1024;
+    let mut html = format!("<html><head><title>Page {}</title></head><body>", id);
+    let mut n = 0;
+    while html.len() < target {
+        html.push_str(&format!(
+            "<div class=\"quote\"><span class=\"text\">Quote {}-{}</span>\
+             <small class=\"author\">Author {}</small></div>",
+            id, n, n
+        ));
+        n += 1;
+    }
+    html.push_str("</body></html>");
+    Html(html)
+}
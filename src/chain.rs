@@ -0,0 +1,217 @@
+//! Spiders chained into stages with `feeds`: once a spider finishes, the URLs
+//! in its items start the spider of the next config, e.g. a listing or sitemap
+//! spider feeding a detail spider. Each stage keeps its own rules, settings
+//! and output, while all of them share the run ID and metrics.
+
+use crate::config::ConfigLoader;
+use crate::config::schema::{FeedsConfig, SpiderConfig};
+use crate::error::{Error, Result};
+use crate::metrics::collector::MetricsCollector;
+use crate::notify::WebhookNotifier;
+use crate::rate_limit::RateLimiter;
+use crate::report::CrawlReport;
+use crate::spider::Spider;
+use crate::state::StateDb;
+use async_trait::async_trait;
+use serde_json::Value;
+use std::collections::HashSet;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use url::Url;
+
+/// The URLs a stage collects from its items for the next one.
+#[derive(Debug)]
+pub struct Feed {
+    /// Config of the next stage
+    pub config: PathBuf,
+    url_field: String,
+    urls: Mutex<(Vec<String>, HashSet<String>)>,
+}
+
+impl Feed {
+    pub fn new(config: &FeedsConfig) -> Self {
+        Self {
+            config: config.config.clone(),
+            url_field: config.url_field.clone(),
+            urls: Mutex::new((Vec::new(), HashSet::new())),
+        }
+    }
+
+    /// Collects the URL, or list of URLs, in the item's `url_field`. Relative
+    /// URLs are resolved against the page the item was found on.
+    fn collect(&self, item: &Value) {
+        let Some(value) = item.get(&self.url_field) else {
+            return;
+        };
+        let base = item
+            .get("_final_url")
+            .and_then(Value::as_str)
+            .and_then(|url| Url::parse(url).ok());
+        let values = match value {
+            Value::Array(values) => values.iter().collect(),
+            value => vec![value],
+        };
+        let Ok(mut urls) = self.urls.lock() else {
+            return;
+        };
+        let (order, seen) = &mut *urls;
+        for url in values.into_iter().filter_map(Value::as_str) {
+            let url = url.trim();
+            let resolved = match &base {
+                Some(base) => base.join(url).ok(),
+                None => Url::parse(url).ok(),
+            };
+            match resolved {
+                Some(url) if matches!(url.scheme(), "http" | "https") => {
+                    if seen.insert(url.to_string()) {
+                        order.push(url.to_string());
+                    }
+                }
+                _ => log::debug!("Not feeding '{}' from `{}`: not an HTTP URL", url, self.url_field),
+            }
+        }
+    }
+
+    /// The URLs collected so far, in the order they were found.
+    pub fn take_urls(&self) -> Vec<String> {
+        self.urls
+            .lock()
+            .map(|mut urls| std::mem::take(&mut urls.0))
+            .unwrap_or_default()
+    }
+}
+
+/// A spider whose items also feed the next stage of a chain.
+pub struct FeedingSpider {
+    inner: Arc<dyn Spider>,
+    feed: Arc<Feed>,
+}
+
+impl FeedingSpider {
+    pub fn new(inner: Arc<dyn Spider>, feed: Arc<Feed>) -> Self {
+        Self { inner, feed }
+    }
+}
+
+#[async_trait]
+impl Spider for FeedingSpider {
+    fn name(&self) -> String {
+        self.inner.name()
+    }
+
+    fn start_urls(&self) -> Vec<String> {
+        self.inner.start_urls()
+    }
+
+    fn fingerprint(&self, url: &str) -> String {
+        self.inner.fingerprint(url)
+    }
+
+    async fn scrape(&self, url: String) -> Result<(Vec<Value>, Vec<String>)> {
+        self.inner.scrape(url).await
+    }
+
+    async fn process(&self, item: Value) -> Result<()> {
+        self.feed.collect(&item);
+        self.inner.process(item).await
+    }
+
+    async fn process_batch(&self, items: Vec<Value>) -> Result<()> {
+        for item in &items {
+            self.feed.collect(item);
+        }
+        self.inner.process_batch(items).await
+    }
+
+    fn link_priority(&self, url: &str) -> i64 {
+        self.inner.link_priority(url)
+    }
+
+    fn output_concurrency(&self) -> usize {
+        self.inner.output_concurrency()
+    }
+
+    async fn flush(&self) -> Result<()> {
+        self.inner.flush().await
+    }
+
+    async fn close(&self) -> Result<()> {
+        self.inner.close().await
+    }
+
+    async fn on_start(&self) {
+        self.inner.on_start().await
+    }
+
+    fn use_rate_limiter(&self, limiter: Arc<RateLimiter>) {
+        self.inner.use_rate_limiter(limiter)
+    }
+
+    async fn on_finish(&self, report: &CrawlReport) {
+        self.inner.on_finish(report).await
+    }
+}
+
+/// `spider` wrapped to feed the stage after it, if its config has `feeds`.
+pub fn feeding(spider: Arc<dyn Spider>, config: &SpiderConfig) -> (Arc<dyn Spider>, Option<Arc<Feed>>) {
+    match &config.feeds {
+        Some(feeds) => {
+            let feed = Arc::new(Feed::new(feeds));
+            (Arc::new(FeedingSpider::new(spider, feed.clone())), Some(feed))
+        }
+        None => (spider, None),
+    }
+}
+
+/// Runs the stages after the first one, each started with the URLs the stage
+/// before it collected, until a stage's config has no `feeds`. Stops early
+/// when a stage collects no URLs. Returns the report of the last stage run,
+/// whose metrics cover every stage since they share the collector.
+pub async fn run_stages(
+    first: Arc<Feed>,
+    first_config: &SpiderConfig,
+    run_id: &str,
+    metrics: Arc<MetricsCollector>,
+    multi: Option<Arc<indicatif::MultiProgress>>,
+) -> Result<Option<CrawlReport>> {
+    let mut last = None;
+    let mut visited: HashSet<String> = HashSet::from([first_config.name.clone()]);
+    let mut next = Some(first);
+    while let Some(feed) = next.take() {
+        let urls = feed.take_urls();
+        let mut config = ConfigLoader::load(&feed.config)?;
+        if !visited.insert(config.name.clone()) {
+            return Err(Error::Config(format!(
+                "Spider '{}' appears twice in the chain of `feeds`",
+                config.name
+            )));
+        }
+        if urls.is_empty() {
+            log::warn!("No URLs to start {} with, ending the chain", config.name);
+            break;
+        }
+        log::info!("Starting stage {} with {} URLs", config.name, urls.len());
+        if let Some(output) = &mut config.output {
+            output.expand_run_id(run_id);
+        }
+
+        let mut spider = ConfigLoader::create_spider(&config, multi.clone(), metrics.clone()).await?;
+        let mut engine = ConfigLoader::create_engine(&config, metrics.clone())
+            .await?
+            .with_run_id(run_id.to_string())
+            .with_seed_urls(urls);
+        if let Some(incremental) = &config.incremental {
+            let state = StateDb::open(incremental).await?;
+            spider = spider.with_state(state.clone());
+            engine = engine.with_state(state, true);
+        }
+        if let Some(notify) = &config.notify {
+            engine = engine.with_notifier(WebhookNotifier::new(notify)?);
+        }
+
+        let (spider, feed) = feeding(Arc::new(spider), &config);
+        last = Some(engine.run(spider).await?);
+        next = feed;
+    }
+    Ok(last)
+}
@@ -0,0 +1,91 @@
+use crate::config::schema::HtmlCleanupConfig;
+use regex::Regex;
+use select::document::Document;
+use select::predicate::Text;
+use std::borrow::Cow;
+use std::sync::LazyLock;
+
+static SCRIPT: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?is)<script\b.*?</script\s*>").unwrap());
+static STYLE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?is)<style\b.*?</style\s*>").unwrap());
+static COMMENT: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"(?s)<!--.*?-->").unwrap());
+static EVENT_HANDLER: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r#"(?i)\s+on[a-z]+\s*=\s*("[^"]*"|'[^']*'|[^\s>]+)"#).unwrap()
+});
+
+/// Prepares a page for extraction according to the spider's cleanup options.
+///
+/// The byte range is applied first, in place, so the remaining passes only scan
+/// the part of the document that will actually be parsed. Passes that match
+/// nothing hand the buffer back untouched instead of copying it.
+pub fn clean_html(mut html: String, options: &HtmlCleanupConfig) -> String {
+    if let Some((start, end)) = options.byte_range {
+        let (start, end) = char_boundaries(&html, start, end);
+        html.truncate(end);
+        html.drain(..start);
+    }
+
+    if options.strip_scripts {
+        html = strip(html, &SCRIPT);
+    }
+    if options.strip_styles {
+        html = strip(html, &STYLE);
+    }
+    if options.strip_comments {
+        html = strip(html, &COMMENT);
+    }
+    if options.strip_event_handlers {
+        html = strip(html, &EVENT_HANDLER);
+    }
+
+    html
+}
+
+fn strip(html: String, pattern: &Regex) -> String {
+    let replaced = match pattern.replace_all(&html, "") {
+        Cow::Owned(stripped) => Some(stripped),
+        Cow::Borrowed(_) => None,
+    };
+    replaced.unwrap_or(html)
+}
+
+/// The words of `html` outside of scripts and styles, as by [`normalize_words`].
+pub fn visible_text(html: &str) -> String {
+    let document = Document::from(html);
+    let text: Vec<String> = document
+        .find(Text)
+        .filter(|node| {
+            !node
+                .parent()
+                .and_then(|parent| parent.name())
+                .is_some_and(|name| matches!(name, "script" | "style" | "noscript" | "template"))
+        })
+        .filter_map(|node| node.as_text().map(normalize_words))
+        .filter(|text| !text.is_empty())
+        .collect();
+    text.join(" ")
+}
+
+/// Lowercase words of `text`, split on anything but letters and digits and
+/// joined with single spaces.
+pub fn normalize_words(text: &str) -> String {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Clamps `[start, end)` to `s`, widening it to the nearest UTF-8 character boundaries.
+fn char_boundaries(s: &str, start: usize, end: usize) -> (usize, usize) {
+    let mut start = start.min(s.len());
+    while !s.is_char_boundary(start) {
+        start -= 1;
+    }
+    let mut end = end.clamp(start, s.len());
+    while !s.is_char_boundary(end) {
+        end += 1;
+    }
+    (start, end)
+}
@@ -0,0 +1,244 @@
+//! What changed between two runs of a spider: items, failures, latency and
+//! per-field coverage, with warnings for the changes that usually mean the
+//! target site changed its markup or started blocking.
+
+use crate::error::{Error, Result};
+use crate::metrics::snapshot::MetricsSnapshot;
+use crate::state::StateDb;
+use crate::stats::RunStats;
+use std::collections::{BTreeSet, HashSet};
+use std::path::Path;
+
+/// Share of items lost that counts as a drop.
+const ITEMS_DROP: f64 = 0.2;
+/// Percentage points of field coverage lost that point to a markup change.
+const COVERAGE_DROP: f64 = 20.0;
+/// Percentage points of success rate lost that point to blocking.
+const SUCCESS_RATE_DROP: f64 = 10.0;
+/// Growth of the median response time that counts as a regression.
+const LATENCY_REGRESSION: f64 = 0.5;
+
+/// One side of a comparison.
+#[derive(Debug, Clone)]
+pub struct RunRecord {
+    pub run_id: String,
+    pub spider: Option<String>,
+    pub metrics: MetricsSnapshot,
+    /// `(url, error kind)` of every failed request, when an event log has them
+    pub failures: Option<Vec<(String, String)>>,
+}
+
+impl RunRecord {
+    /// Reads the metrics written by `run --metrics-out`.
+    pub fn from_metrics_file(path: &Path) -> Result<Self> {
+        let content =
+            std::fs::read_to_string(path).map_err(|e| Error::Config(format!("{}: {}", path.display(), e)))?;
+        let metrics: MetricsSnapshot = serde_json::from_str(&content)
+            .map_err(|e| Error::Config(format!("{}: not a metrics file: {}", path.display(), e)))?;
+        Ok(Self {
+            run_id: metrics.run_id.clone(),
+            spider: None,
+            metrics,
+            failures: None,
+        })
+    }
+
+    /// Reads a finished run recorded in a state database.
+    pub async fn from_state(state: &StateDb, run_id: &str) -> Result<Self> {
+        let (spider, metrics) = state.run_metrics(run_id).await?.ok_or_else(|| {
+            Error::Config(format!("No finished run {} in {}", run_id, state.path()))
+        })?;
+        Ok(Self {
+            run_id: run_id.to_string(),
+            spider: Some(spider),
+            metrics,
+            failures: None,
+        })
+    }
+
+    /// Takes the run's failures from the runs of an event log, if it's there.
+    pub fn with_failures_from(mut self, runs: &[RunStats]) -> Self {
+        if let Some(run) = runs.iter().find(|run| run.run_id == self.run_id) {
+            self.failures = Some(run.failures.clone());
+            if self.spider.is_none() {
+                self.spider = run.spider.clone();
+            }
+        }
+        self
+    }
+}
+
+/// A number before and after.
+#[derive(Debug, Clone, Copy)]
+pub struct Change {
+    pub before: f64,
+    pub after: f64,
+}
+
+impl Change {
+    pub fn delta(&self) -> f64 {
+        self.after - self.before
+    }
+
+    /// Relative change, or `None` from zero.
+    pub fn ratio(&self) -> Option<f64> {
+        (self.before != 0.0).then(|| self.delta() / self.before)
+    }
+}
+
+/// Coverage of a field in both runs, in percent; `None` where a run lacks it.
+#[derive(Debug, Clone)]
+pub struct FieldChange {
+    pub field: String,
+    pub before: Option<f64>,
+    pub after: Option<f64>,
+}
+
+impl FieldChange {
+    /// Percentage points lost, where the field is in both runs.
+    pub fn drop(&self) -> Option<f64> {
+        Some(self.before? - self.after?)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Comparison {
+    pub items: Change,
+    pub pages: Change,
+    pub requests_failed: Change,
+    pub requests_blocked: Change,
+    /// In percent
+    pub success_rate: Change,
+    pub p50_response_time_ms: Change,
+    pub p99_response_time_ms: Change,
+    pub fields: Vec<FieldChange>,
+    /// Failures of the second run at URLs that didn't fail in the first, if both have failures
+    pub new_failures: Option<Vec<(String, String)>>,
+    /// URLs that failed in the first run and not in the second
+    pub fixed_failures: Option<usize>,
+    /// Changes that suggest the site changed or started blocking
+    pub warnings: Vec<String>,
+}
+
+/// Compares run `after` with run `before`.
+pub fn compare(before: &RunRecord, after: &RunRecord) -> Comparison {
+    let (a, b) = (&before.metrics, &after.metrics);
+    let change = |f: fn(&MetricsSnapshot) -> u64| Change {
+        before: f(a) as f64,
+        after: f(b) as f64,
+    };
+
+    let names: BTreeSet<&String> = a.field_coverage.keys().chain(b.field_coverage.keys()).collect();
+    let fields = names
+        .into_iter()
+        .map(|field| FieldChange {
+            field: field.clone(),
+            before: a.field_coverage.get(field).map(|c| c.ratio() * 100.0),
+            after: b.field_coverage.get(field).map(|c| c.ratio() * 100.0),
+        })
+        .collect();
+
+    let (new_failures, fixed_failures) = match (&before.failures, &after.failures) {
+        (Some(old), Some(new)) => {
+            let old_urls: HashSet<&str> = old.iter().map(|(url, _)| url.as_str()).collect();
+            let new_urls: HashSet<&str> = new.iter().map(|(url, _)| url.as_str()).collect();
+            let mut seen = HashSet::new();
+            let added = new
+                .iter()
+                .filter(|(url, _)| !old_urls.contains(url.as_str()) && seen.insert(url.as_str()))
+                .cloned()
+                .collect();
+            (Some(added), Some(old_urls.difference(&new_urls).count()))
+        }
+        _ => (None, None),
+    };
+
+    let mut comparison = Comparison {
+        items: change(|m| m.items_extracted),
+        pages: change(|m| m.urls_processed),
+        requests_failed: change(|m| m.requests_failed),
+        requests_blocked: change(|m| m.requests_blocked),
+        success_rate: Change {
+            before: a.success_rate,
+            after: b.success_rate,
+        },
+        p50_response_time_ms: change(|m| m.p50_response_time_ms),
+        p99_response_time_ms: change(|m| m.p99_response_time_ms),
+        fields,
+        new_failures,
+        fixed_failures,
+        warnings: Vec::new(),
+    };
+    comparison.warnings = warnings(&comparison);
+    comparison
+}
+
+/// Fields whose coverage fell by more than `max_drop` percentage points from
+/// `before` to `after`, among those seen on at least `min_pages` pages in both.
+pub fn coverage_drift(
+    before: &MetricsSnapshot,
+    after: &MetricsSnapshot,
+    max_drop: f64,
+    min_pages: u64,
+) -> Vec<FieldChange> {
+    before
+        .field_coverage
+        .iter()
+        .filter_map(|(field, old)| {
+            let new = after.field_coverage.get(field)?;
+            (old.pages() >= min_pages.max(1) && new.pages() >= min_pages.max(1)).then(|| FieldChange {
+                field: field.clone(),
+                before: Some(old.ratio() * 100.0),
+                after: Some(new.ratio() * 100.0),
+            })
+        })
+        .filter(|change| change.drop().is_some_and(|drop| drop > max_drop))
+        .collect()
+}
+
+fn warnings(comparison: &Comparison) -> Vec<String> {
+    let mut warnings = Vec::new();
+    if comparison.items.ratio().is_some_and(|ratio| ratio <= -ITEMS_DROP) {
+        warnings.push(format!(
+            "Items dropped from {} to {}",
+            comparison.items.before, comparison.items.after
+        ));
+    }
+    for field in &comparison.fields {
+        match (field.before, field.after, field.drop()) {
+            (_, _, Some(drop)) if drop >= COVERAGE_DROP => warnings.push(format!(
+                "Field `{}` coverage fell from {:.1}% to {:.1}%: its selector may no longer match the markup",
+                field.field,
+                field.before.unwrap_or_default(),
+                field.after.unwrap_or_default()
+            )),
+            (Some(before), None, _) if before > 0.0 => {
+                warnings.push(format!("Field `{}` is missing from the second run", field.field))
+            }
+            _ => {}
+        }
+    }
+    if comparison.requests_blocked.delta() > 0.0 {
+        warnings.push(format!(
+            "Blocked responses rose from {} to {}: the site may be blocking the crawler",
+            comparison.requests_blocked.before, comparison.requests_blocked.after
+        ));
+    }
+    if -comparison.success_rate.delta() >= SUCCESS_RATE_DROP {
+        warnings.push(format!(
+            "Success rate fell from {:.1}% to {:.1}%",
+            comparison.success_rate.before, comparison.success_rate.after
+        ));
+    }
+    if comparison
+        .p50_response_time_ms
+        .ratio()
+        .is_some_and(|ratio| ratio >= LATENCY_REGRESSION)
+    {
+        warnings.push(format!(
+            "Median response time rose from {}ms to {}ms",
+            comparison.p50_response_time_ms.before, comparison.p50_response_time_ms.after
+        ));
+    }
+    warnings
+}
@@ -0,0 +1,109 @@
+//! Configuration from the environment, for containers that run a spider
+//! without a config file mounted.
+//!
+//! - `CRAWLER_CONFIG_JSON` holds a whole config as JSON
+//! - `CRAWLER_CONFIG` holds the path of a config file
+//! - `CRAWLER__<SETTING>` replaces one setting of either, with `__` between
+//!   the keys of nested settings: `CRAWLER__LIMITS__MAX_PAGES=100`
+//!
+//! Setting values are read as JSON where they parse as JSON, and as text
+//! otherwise, so `CRAWLER__NAME=shop` and `CRAWLER__START_URLS='["https://…"]'`
+//! both work.
+
+use crate::error::{Error, Result};
+use serde_json::{Map, Value};
+use std::path::PathBuf;
+
+pub const CONFIG_JSON_VAR: &str = "CRAWLER_CONFIG_JSON";
+pub const CONFIG_PATH_VAR: &str = "CRAWLER_CONFIG";
+pub const SETTING_PREFIX: &str = "CRAWLER__";
+
+/// Where a config comes from.
+#[derive(Debug, Clone)]
+pub enum ConfigSource {
+    File(PathBuf),
+    /// The JSON in `CRAWLER_CONFIG_JSON`
+    Json(Value),
+}
+
+impl ConfigSource {
+    /// `path` if given, otherwise the config named by the environment.
+    pub fn resolve(path: Option<PathBuf>) -> Result<Self> {
+        if let Some(path) = path {
+            return Ok(ConfigSource::File(path));
+        }
+        if let Some(json) = var(CONFIG_JSON_VAR)? {
+            let value = serde_json::from_str(&json)
+                .map_err(|e| Error::Config(format!("{}: {}", CONFIG_JSON_VAR, e)))?;
+            return Ok(ConfigSource::Json(value));
+        }
+        // Read as an OS string, so a path needn't be valid UTF-8
+        if let Some(path) = std::env::var_os(CONFIG_PATH_VAR).filter(|path| !path.is_empty()) {
+            return Ok(ConfigSource::File(PathBuf::from(path)));
+        }
+        Err(Error::Config(format!(
+            "No config given: pass --config, or set {} or {}",
+            CONFIG_JSON_VAR, CONFIG_PATH_VAR
+        )))
+    }
+}
+
+impl std::fmt::Display for ConfigSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConfigSource::File(path) => write!(f, "{}", path.display()),
+            ConfigSource::Json(_) => write!(f, "${}", CONFIG_JSON_VAR),
+        }
+    }
+}
+
+/// A variable's value, or `None` if it is unset or empty.
+fn var(name: &str) -> Result<Option<String>> {
+    match std::env::var(name) {
+        Ok(value) if value.trim().is_empty() => Ok(None),
+        Ok(value) => Ok(Some(value)),
+        Err(std::env::VarError::NotPresent) => Ok(None),
+        Err(e) => Err(Error::Config(format!("{}: {}", name, e))),
+    }
+}
+
+/// The `CRAWLER__` settings of the environment, as key paths and values.
+pub fn settings() -> Vec<(Vec<String>, Value)> {
+    let mut settings: Vec<_> = std::env::vars()
+        .filter_map(|(name, value)| {
+            let keys: Vec<String> = name
+                .strip_prefix(SETTING_PREFIX)?
+                .split("__")
+                .map(str::to_ascii_lowercase)
+                .collect();
+            if keys.iter().any(String::is_empty) {
+                return None;
+            }
+            let value = serde_json::from_str(&value).unwrap_or(Value::String(value));
+            Some((keys, value))
+        })
+        .collect();
+    // Parents before their children, so `CRAWLER__LIMITS` doesn't undo `CRAWLER__LIMITS__MAX_PAGES`
+    settings.sort_by(|(a, _), (b, _)| a.len().cmp(&b.len()).then_with(|| a.cmp(b)));
+    settings
+}
+
+/// Applies the `CRAWLER__` settings of the environment to `config`.
+pub fn apply_settings(config: &mut Value) {
+    for (keys, value) in settings() {
+        set(config, &keys, value);
+    }
+}
+
+fn set(config: &mut Value, keys: &[String], value: Value) {
+    let Some((key, rest)) = keys.split_first() else {
+        *config = value;
+        return;
+    };
+    if !config.is_object() {
+        *config = Value::Object(Map::new());
+    }
+    if let Value::Object(map) = config {
+        set(map.entry(key.clone()).or_insert(Value::Null), rest, value);
+    }
+}
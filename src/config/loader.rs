@@ -1,11 +1,37 @@
 use crate::error::{Error, Result};
 use crate::spider::GenericSpider;
-use crate::output::{OutputHandler, console::ConsoleOutput, json::JsonOutput, csv::CsvOutput, sqlite::SqliteOutput};
-use crate::config::schema::{SpiderConfig, OutputConfig};
+use crate::output::{OutputHandler, console::ConsoleOutput, json::JsonOutput, csv::CsvOutput, sqlite::SqliteOutput, object_storage::ObjectStoreOutput, parquet::ParquetOutput, stdout::StdoutOutput, xlsx::XlsxOutput};
+use crate::config::env::{self, ConfigSource};
+use crate::config::{params, presets};
+use crate::config::schema::{Delay, FrontierKind, SelectorConfig, SessionConfig, SigningConfig, SourceConfig, SpiderConfig, OutputConfig};
+use crate::crawl_window::CrawlWindow;
+use crate::crawler::CrawlerEngine;
+use crate::frontier::DiskQueue;
+use crate::extract::Extractor;
+use crate::fail_on::FailCondition;
+use crate::item_filter::ItemFilter;
+use crate::item_id::ItemId;
+use crate::external_links::ExternalLinks;
+use crate::relevance::RelevanceScorer;
+use crate::spider::{ExtractionRule, RuleSource};
+use crate::http::signing::signer;
+use crate::http::{
+    BandwidthLimiter, BlockDetector, BudgetTransport, CachingTransport, HttpTransport, PageCache, OAuth2Transport, ProxyTransport, RequestBudget, SessionTransport, SigningTransport, Transport, WarmupTransport, WaybackTransport,
+};
+use crate::language::LanguageFilter;
+use crate::page_types::PageTypes;
+use crate::pdf::PdfExtractor;
+use crate::render::Renderer;
+use crate::url_rewrite::UrlRewrite;
+use crate::metrics::collector::MetricsCollector;
+use crate::paths;
+use crate::state::StateDb;
 use std::collections::HashSet;
 use std::fs;
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
+use std::time::Duration;
+use url::Url;
 use validator::Validate;
 
 pub struct ConfigLoader;
@@ -22,9 +48,7 @@ impl ConfigLoader {
         visited: &mut HashSet<PathBuf>,
         is_parent_load: bool,
     ) -> Result<SpiderConfig> {
-        let path = fs::canonicalize(path).map_err(|e| {
-            Error::Config(format!("{}: {}", path.display(), e))
-        })?;
+        let path = paths::canonicalize(path)?;
 
         if visited.contains(&path) {
             return Err(Error::Config(format!(
@@ -34,15 +58,27 @@ impl ConfigLoader {
         }
         visited.insert(path.clone());
 
-        let config = Self::load_file(&path)?;
+        let mut config = Self::load_file(&path)?;
+        // Preset files are relative to the config that lists them, not to the one extending it
+        if let Some(dir) = path.parent() {
+            for preset_file in config.use_presets.iter_mut() {
+                *preset_file = paths::resolve(dir, preset_file);
+            }
+            for test in config.tests.iter_mut() {
+                test.file = paths::resolve(dir, &test.file);
+            }
+            if let Some(feeds) = &mut config.feeds {
+                feeds.config = paths::resolve(dir, &feeds.config);
+            }
+        }
 
-        let final_config = if let Some(parent_path_str) = &config.extends {
-            let parent_path = path.parent()
+        let mut final_config = if let Some(parent_path_str) = &config.extends {
+            let dir = path.parent()
                 .ok_or_else(|| Error::Config(format!(
                     "Cannot determine parent directory for {}",
                     path.display()
-                )))?
-                .join(parent_path_str);
+                )))?;
+            let parent_path = paths::resolve(dir, Path::new(parent_path_str));
 
             let parent_config = Self::load_with_inheritance(&parent_path, visited, true)?;
             Self::merge_configs(parent_config, config)
@@ -51,9 +87,154 @@ impl ConfigLoader {
         };
 
         if !is_parent_load {
-            final_config.validate()
-                .map_err(|e| Error::Validation(e))?;
+            final_config = Self::finish(final_config)?;
+        }
+
+        Ok(final_config)
+    }
+
+    /// Like [`ConfigLoader::load`], with the top-level settings in `params`,
+    /// such as `start_urls`, replacing those of the file.
+    pub fn load_with_params<P: AsRef<Path>>(path: P, params: &serde_json::Map<String, serde_json::Value>) -> Result<SpiderConfig> {
+        let mut visited = HashSet::new();
+        let config = Self::load_with_inheritance(path.as_ref(), &mut visited, true)?;
+        let mut value = serde_json::to_value(config)?;
+        if let serde_json::Value::Object(map) = &mut value {
+            map.extend(params.clone());
+        }
+        Self::finish(serde_json::from_value(value)?)
+    }
+
+    /// Loads the config of `source`, with the `CRAWLER__` settings of the
+    /// environment applied on top.
+    pub fn load_source(source: &ConfigSource) -> Result<SpiderConfig> {
+        match source {
+            ConfigSource::File(path) if env::settings().is_empty() => Self::load(path),
+            ConfigSource::File(path) => {
+                let mut visited = HashSet::new();
+                let config = Self::load_with_inheritance(path, &mut visited, true)?;
+                let mut value = serde_json::to_value(config)?;
+                env::apply_settings(&mut value);
+                Self::finish(serde_json::from_value(value)?)
+            }
+            ConfigSource::Json(value) => {
+                let mut value = value.clone();
+                env::apply_settings(&mut value);
+                Self::from_json(value)
+            }
+        }
+    }
+
+    /// Reads a config given as JSON, e.g. in a job submitted to `crawler serve`.
+    /// `extends` and presets are relative to the working directory.
+    pub fn from_json(value: serde_json::Value) -> Result<SpiderConfig> {
+        let config: SpiderConfig = serde_json::from_value(value)?;
+        let config = match &config.extends {
+            Some(parent_path) => {
+                let mut visited = HashSet::new();
+                let parent_config = Self::load_with_inheritance(Path::new(parent_path), &mut visited, true)?;
+                Self::merge_configs(parent_config, config)
+            }
+            None => config,
+        };
+        Self::finish(config)
+    }
+
+    /// Expands presets and `params`, and checks the complete config.
+    fn finish(mut final_config: SpiderConfig) -> Result<SpiderConfig> {
+        presets::expand(&mut final_config)?;
+        params::expand(&mut final_config)?;
+        let page_type_rules = final_config.page_types.iter().flat_map(|t| &t.rules);
+        for (name, rule) in final_config.extraction_rules.iter().chain(page_type_rules) {
+            if rule.reads_page() && rule.selector.to_query_string().is_empty() {
+                return Err(Error::Config(format!(
+                    "Rule '{}' needs a selector, an `if` condition or a `source`",
+                    name
+                )));
+            }
+            if rule.source == RuleSource::Header
+                && rule
+                    .name
+                    .as_deref()
+                    .is_none_or(|header| reqwest::header::HeaderName::from_bytes(header.trim().as_bytes()).is_err())
+            {
+                return Err(Error::Config(format!(
+                    "Rule '{}' reads a header, but has no valid header `name`",
+                    name
+                )));
+            }
+        }
+        PageTypes::new(&final_config.page_types)?;
+        if let Some(filter) = &final_config.item_filter {
+            ItemFilter::new(filter)?;
+        }
+        if let Some(SourceConfig::Wayback(wayback)) = &final_config.source {
+            WaybackTransport::validate(wayback)?;
+        }
+        if let Some(budget) = &final_config.request_budget {
+            RequestBudget::validate(budget)?;
+        }
+        WarmupTransport::validate(&final_config.warmup)?;
+        for condition in &final_config.fail_on {
+            condition.parse::<FailCondition>()?.validate(&final_config)?;
+        }
+        if let Some(window) = &final_config.crawl_window {
+            CrawlWindow::new(window)?;
+        }
+        if let Some(rewrite) = &final_config.url_rewrite {
+            UrlRewrite::new(rewrite)?;
+        }
+        if let Some(dedup) = &final_config.dedup
+            && !(dedup.fp_rate > 0.0 && dedup.fp_rate < 1.0)
+        {
+            return Err(Error::Config(format!(
+                "dedup.fp_rate must be between 0 and 1, got {}",
+                dedup.fp_rate
+            )));
+        }
+        if let Some(near) = final_config.dedup.as_ref().and_then(|dedup| dedup.near_duplicates.as_ref())
+            && !(near.threshold > 0.0 && near.threshold <= 1.0)
+        {
+            return Err(Error::Config(format!(
+                "dedup.near_duplicates.threshold must be above 0 and at most 1, got {}",
+                near.threshold
+            )));
+        }
+        if final_config.item_id.as_ref().is_some_and(|item_id| item_id.fields.is_empty()) {
+            return Err(Error::Config("item_id.fields must name at least one field".to_string()));
         }
+        if let Some(relevance) = &final_config.relevance {
+            if relevance.keywords.iter().all(|keyword| keyword.trim().is_empty()) {
+                return Err(Error::Config("relevance.keywords must name at least one keyword".to_string()));
+            }
+            if relevance.min_score.is_nan() || relevance.min_score < 0.0 {
+                return Err(Error::Config(format!(
+                    "relevance.min_score must be at least 0, got {}",
+                    relevance.min_score
+                )));
+            }
+        }
+        if final_config.feeds.as_ref().is_some_and(|feeds| feeds.url_field.trim().is_empty()) {
+            return Err(Error::Config("feeds.url_field must name a field".to_string()));
+        }
+        if final_config.extraction_timeout_secs == Some(0) {
+            return Err(Error::Config("extraction_timeout_secs must be at least 1".to_string()));
+        }
+        if final_config.frontier.as_ref().is_some_and(|frontier| frontier.window == 0) {
+            return Err(Error::Config("frontier.window must be at least 1".to_string()));
+        }
+        if final_config.coverage_alert.is_some() && final_config.incremental.is_none() {
+            return Err(Error::Config(
+                "coverage_alert needs `incremental`, whose state database holds the previous run".to_string(),
+            ));
+        }
+        if final_config.request_budget.is_some() && final_config.incremental.is_none() {
+            return Err(Error::Config(
+                "request_budget needs `incremental`, whose state database keeps the count across runs".to_string(),
+            ));
+        }
+        final_config.validate()
+            .map_err(|e| Error::Validation(e))?;
 
         Ok(final_config)
     }
@@ -62,7 +243,7 @@ impl ConfigLoader {
         let content = fs::read_to_string(path)
             .map_err(|e| Error::Config(format!("{}: {}", path.display(), e)))?;
 
-        match path.extension().and_then(|ext| ext.to_str()) {
+        match paths::extension(path).as_deref() {
             Some("json") => {
                 let config: SpiderConfig = serde_json::from_str(&content)?;
                 Ok(config)
@@ -89,18 +270,172 @@ impl ConfigLoader {
         if !child.start_urls.is_empty() {
             parent.start_urls = child.start_urls;
         }
+        if !child.params.is_empty() {
+            parent.params = child.params;
+        }
         if child.root_selector.is_some() {
             parent.root_selector = child.root_selector;
         }
         if child.concurrency != 2 {
             parent.concurrency = child.concurrency;
         }
-        if child.delay_ms != 500 {
+        if child.delay_ms != Delay::Fixed(500) {
             parent.delay_ms = child.delay_ms;
         }
+        if child.delay_jitter_ms != 0 {
+            parent.delay_jitter_ms = child.delay_jitter_ms;
+        }
+        if child.parser_threads.is_some() {
+            parent.parser_threads = child.parser_threads;
+        }
+        if child.extraction_timeout_secs.is_some() {
+            parent.extraction_timeout_secs = child.extraction_timeout_secs;
+        }
         if child.output.is_some() {
             parent.output = child.output;
         }
+        if child.redirects.is_some() {
+            parent.redirects = child.redirects;
+        }
+        if child.head_check.is_some() {
+            parent.head_check = child.head_check;
+        }
+        if child.block_detection.is_some() {
+            parent.block_detection = child.block_detection;
+        }
+        if child.dns.is_some() {
+            parent.dns = child.dns;
+        }
+        if child.compression.is_some() {
+            parent.compression = child.compression;
+        }
+        if child.max_bandwidth_kbps.is_some() {
+            parent.max_bandwidth_kbps = child.max_bandwidth_kbps;
+        }
+        if child.http.is_some() {
+            parent.http = child.http;
+        }
+        if child.tls.is_some() {
+            parent.tls = child.tls;
+        }
+        if child.proxy_pool.is_some() {
+            parent.proxy_pool = child.proxy_pool;
+        }
+        if child.sessions.is_some() {
+            parent.sessions = child.sessions;
+        }
+        if !child.warmup.is_empty() {
+            parent.warmup = child.warmup;
+        }
+        if child.signing.is_some() {
+            parent.signing = child.signing;
+        }
+        if child.source.is_some() {
+            parent.source = child.source;
+        }
+        if child.auth.is_some() {
+            parent.auth = child.auth;
+        }
+        if child.html_cleanup.is_some() {
+            parent.html_cleanup = child.html_cleanup;
+        }
+        if child.retry.is_some() {
+            parent.retry = child.retry;
+        }
+        if child.limits.is_some() {
+            parent.limits = child.limits;
+        }
+        if child.request_budget.is_some() {
+            parent.request_budget = child.request_budget;
+        }
+        if child.sample.is_some() {
+            parent.sample = child.sample;
+        }
+        if child.shutdown_grace_secs.is_some() {
+            parent.shutdown_grace_secs = child.shutdown_grace_secs;
+        }
+        if child.memory_limit_mb.is_some() {
+            parent.memory_limit_mb = child.memory_limit_mb;
+        }
+        if child.crawl_window.is_some() {
+            parent.crawl_window = child.crawl_window;
+        }
+        if child.pdf.is_some() {
+            parent.pdf = child.pdf;
+        }
+        if child.output_batch.is_some() {
+            parent.output_batch = child.output_batch;
+        }
+        if child.output_concurrency.is_some() {
+            parent.output_concurrency = child.output_concurrency;
+        }
+        if child.render.is_some() {
+            parent.render = child.render;
+        }
+        if child.page_item.is_some() {
+            parent.page_item = child.page_item;
+        }
+        if child.detect_language {
+            parent.detect_language = true;
+        }
+        if !child.only_languages.is_empty() {
+            parent.only_languages = child.only_languages;
+        }
+        if child.logging.is_some() {
+            parent.logging = child.logging;
+        }
+        if child.notify.is_some() {
+            parent.notify = child.notify;
+        }
+        if child.coverage_alert.is_some() {
+            parent.coverage_alert = child.coverage_alert;
+        }
+        if child.item_filter.is_some() {
+            parent.item_filter = child.item_filter;
+        }
+        if child.item_id.is_some() {
+            parent.item_id = child.item_id;
+        }
+        if child.external_links.is_some() {
+            parent.external_links = child.external_links;
+        }
+        if child.relevance.is_some() {
+            parent.relevance = child.relevance;
+        }
+        if child.feeds.is_some() {
+            parent.feeds = child.feeds;
+        }
+        if !child.tests.is_empty() {
+            parent.tests = child.tests;
+        }
+        if !child.fail_on.is_empty() {
+            parent.fail_on = child.fail_on;
+        }
+        if !child.page_types.is_empty() {
+            parent.page_types = child.page_types;
+        }
+        if child.url_rewrite.is_some() {
+            parent.url_rewrite = child.url_rewrite;
+        }
+        if child.dedup.is_some() {
+            parent.dedup = child.dedup;
+        }
+        if child.frontier.is_some() {
+            parent.frontier = child.frontier;
+        }
+        if child.incremental.is_some() {
+            parent.incremental = child.incremental;
+        }
+        if child.page_cache.is_some() {
+            parent.page_cache = child.page_cache;
+        }
+
+        for name in child.preset {
+            if !parent.preset.contains(&name) {
+                parent.preset.push(name);
+            }
+        }
+        parent.use_presets.extend(child.use_presets);
 
         for (key, rule) in child.extraction_rules {
             parent.extraction_rules.insert(key, rule);
@@ -110,29 +445,251 @@ impl ConfigLoader {
         parent
     }
 
-    pub async fn create_spider(
-        config: &SpiderConfig,
+    /// Opens the output handler described by `config`, defaulting to the console.
+    /// `fields` are the expected item fields, used for table headers.
+    pub async fn create_output(
+        config: Option<&OutputConfig>,
         multi: Option<Arc<indicatif::MultiProgress>>,
-    ) -> Result<GenericSpider> {
-        let handler: Box<dyn OutputHandler> = if let Some(out_config) = &config.output {
+        fields: &[String],
+    ) -> Result<Box<dyn OutputHandler>> {
+        let handler: Box<dyn OutputHandler> = if let Some(out_config) = config {
             match out_config {
-                OutputConfig::Console => Box::new(ConsoleOutput::new(multi)),
-                OutputConfig::Json { path } => Box::new(JsonOutput::new(PathBuf::from(path))?),
-                OutputConfig::Csv { path } => Box::new(CsvOutput::new(PathBuf::from(path))?),
-                OutputConfig::Sqlite { path, table } => {
-                    Box::new(SqliteOutput::new(PathBuf::from(path), table.clone()).await?)
+                OutputConfig::Console(options) => Box::new(
+                    ConsoleOutput::with_options(multi, options.clone()).with_columns(fields.to_vec()),
+                ),
+                OutputConfig::Json { path, append } => Box::new(JsonOutput::new(PathBuf::from(path), *append)?),
+                OutputConfig::Csv { path, append, nested } => Box::new(
+                    CsvOutput::new(PathBuf::from(path), *append)?.with_nested(nested.clone()),
+                ),
+                OutputConfig::Sqlite { path, table, columns, dead_letter, pragmas, nested } => {
+                    let output = SqliteOutput::new(PathBuf::from(path), table.clone(), pragmas)
+                        .await?
+                        .with_nested(nested.clone());
+                    if columns.is_empty() {
+                        Box::new(output)
+                    } else {
+                        let dead_letter = dead_letter
+                            .clone()
+                            .unwrap_or_else(|| format!("{}.rejected.ndjson", path));
+                        Box::new(output.with_schema(columns.clone(), PathBuf::from(dead_letter)).await?)
+                    }
                 }
+                OutputConfig::Parquet { path } => Box::new(ParquetOutput::new(PathBuf::from(path))?),
+                OutputConfig::Xlsx { path } => Box::new(XlsxOutput::new(PathBuf::from(path))?),
+                OutputConfig::ObjectStore { url, options, items_per_object } => {
+                    Box::new(ObjectStoreOutput::new(url, options, *items_per_object)?)
+                }
+                OutputConfig::Stdout { format } => Box::new(StdoutOutput::new(*format)),
             }
         } else {
             Box::new(ConsoleOutput::new(multi))
         };
+        Ok(handler)
+    }
 
-        Ok(GenericSpider::new(
+    pub async fn create_spider(
+        config: &SpiderConfig,
+        multi: Option<Arc<indicatif::MultiProgress>>,
+        metrics: Arc<MetricsCollector>,
+    ) -> Result<GenericSpider> {
+        let mut fields: Vec<String> = config
+            .extraction_rules
+            .keys()
+            .chain(config.page_types.iter().flat_map(|t| t.rules.keys()))
+            .cloned()
+            .collect();
+        fields.sort();
+        fields.dedup();
+        let handler = Self::create_output(config.output.as_ref(), multi.clone(), &fields).await?;
+        let mut output_pool = Vec::new();
+        let output_concurrency = config.output_concurrency.unwrap_or(1);
+        if output_concurrency > 1 {
+            if handler.pool_safe() {
+                for _ in 1..output_concurrency {
+                    output_pool.push(Self::create_output(config.output.as_ref(), multi.clone(), &fields).await?);
+                }
+            } else {
+                log::warn!("This output can't be written by several instances at once, ignoring output_concurrency");
+            }
+        }
+
+        let url_rewrite = config.url_rewrite.as_ref().map(UrlRewrite::new).transpose()?;
+        let start_urls = match &url_rewrite {
+            Some(rewrite) => config.start_urls.iter().map(|url| rewrite.apply(url)).collect(),
+            None => config.start_urls.clone(),
+        };
+        let mut spider = GenericSpider::new(
             config.name.clone(),
-            config.start_urls.clone(),
+            start_urls,
             config.root_selector.clone(),
             config.extraction_rules.clone(),
             handler,
-        ))
+        )
+        .with_output_pool(output_pool);
+        let budget = match (&config.request_budget, &config.incremental) {
+            (Some(budget), Some(incremental)) => {
+                let state = StateDb::open(incremental).await?;
+                Some(Arc::new(RequestBudget::open(budget, state, &config.name).await?))
+            }
+            _ => None,
+        };
+        let base: Arc<dyn Transport> = match &config.proxy_pool {
+            Some(pool) => {
+                // The pool retries blocked responses on its own, out of sight of the layers around it
+                let mut proxy = ProxyTransport::new(config, pool, metrics.clone())?;
+                if let Some(budget) = &budget {
+                    proxy = proxy.with_budget(budget.clone());
+                }
+                Arc::new(proxy)
+            }
+            None => Arc::new(HttpTransport::new(crate::http::build_client(config, &metrics)?)),
+        };
+        let mut transport = base.clone();
+        // Right around the client, so every request that goes out counts but cache hits don't
+        if let Some(budget) = budget {
+            transport = Arc::new(BudgetTransport::new(transport, budget));
+        }
+        // Inside authentication and sessions, so it sees the requests as they go
+        // out and keeps credentialed ones apart from those of other spiders
+        if let Some(page_cache) = &config.page_cache {
+            let cache = PageCache::open(&page_cache.path).await?;
+            let max_body_bytes = config.compression.clone().unwrap_or_default().max_body_bytes;
+            let mut caching = CachingTransport::new(transport, cache, page_cache, max_body_bytes, metrics.clone())?;
+            if let Some(SigningConfig::HmacSha256(signing)) = &config.signing {
+                caching = caching.with_credential_header(&signing.header)?;
+            }
+            transport = Arc::new(caching);
+        }
+        if let Some(signing) = &config.signing {
+            transport = Arc::new(SigningTransport::new(transport, signer(signing)?));
+        }
+        // Outside the signer, so a signature can cover the token. Inside the
+        // Wayback layer, which makes archive requests look foreign to it
+        if let Some(auth) = &config.auth {
+            // Archive URLs among the start URLs stand for the sites they archive
+            let sites: Vec<String> = match &config.source {
+                Some(SourceConfig::Wayback(wayback)) => {
+                    let wayback = WaybackTransport::new(base.clone(), wayback)?;
+                    config
+                        .start_urls
+                        .iter()
+                        .filter_map(|url| Url::parse(url).ok())
+                        .map(|url| wayback.unwrap_url(&url).unwrap_or(url).to_string())
+                        .collect()
+                }
+                None => config.start_urls.clone(),
+            };
+            let oauth = OAuth2Transport::new(transport, base, auth, &sites)?;
+            oauth.authenticate().await?;
+            transport = Arc::new(oauth);
+        }
+        // Inside everything else, so sessions see the original URLs
+        if let Some(SourceConfig::Wayback(wayback)) = &config.source {
+            transport = Arc::new(WaybackTransport::new(transport, wayback)?);
+        }
+        // Outside the signer, so session headers are part of what gets signed.
+        // A warmup needs sessions for the cookies it picks up to be kept
+        let sessions = match &config.sessions {
+            None if !config.warmup.is_empty() => Some(SessionConfig::default()),
+            sessions => sessions.clone(),
+        };
+        if let Some(sessions) = &sessions {
+            transport = Arc::new(SessionTransport::new(transport, sessions)?);
+        }
+        if !config.warmup.is_empty() {
+            transport = Arc::new(WarmupTransport::new(transport, &config.warmup)?);
+        }
+        spider = spider.with_transport(transport);
+        spider.redirects = config.redirects.clone().unwrap_or_default();
+        spider.head_check = config.head_check.clone();
+        spider.block_detection = config.block_detection.as_ref().map(BlockDetector::new);
+        if let Some(dedup) = &config.dedup {
+            spider = spider.with_dedup(dedup);
+        }
+        spider.compression = config.compression.clone().unwrap_or_default();
+        spider.bandwidth = config
+            .max_bandwidth_kbps
+            .as_ref()
+            .map(|bandwidth| Arc::new(BandwidthLimiter::new(bandwidth)));
+        spider.html_cleanup = config.html_cleanup.clone();
+        spider.pdf = config.pdf.as_ref().map(|pdf| Arc::new(PdfExtractor::new(pdf)));
+        spider.renderer = config.render.as_ref().map(Renderer::new).transpose()?;
+        if let Some(page_item) = &config.page_item {
+            let mut rules = page_item.rules.clone();
+            rules.entry("title".to_string()).or_insert_with(|| ExtractionRule {
+                selector: SelectorConfig::Simple("css:title".to_string()),
+                ..Default::default()
+            });
+            spider.page_extractor = Some(Arc::new(Extractor::new(None, rules)));
+        }
+        if !config.page_types.is_empty() {
+            let mut page_types = PageTypes::new(&config.page_types)?;
+            if let Some(rewrite) = url_rewrite {
+                page_types = page_types.with_url_rewrite(rewrite);
+            }
+            spider.page_types = Some(Arc::new(page_types));
+        }
+        if config.detect_language || !config.only_languages.is_empty() {
+            spider.language = Some(LanguageFilter::new(&config.only_languages));
+        }
+        spider.max_streams_per_origin = config.http.as_ref().and_then(|h| h.max_streams_per_origin);
+
+        if let Some(threads) = config.parser_threads {
+            spider = spider.with_parser_threads(threads);
+        }
+        spider.item_filter = config.item_filter.as_ref().map(ItemFilter::new).transpose()?;
+        spider.item_id = config.item_id.as_ref().map(ItemId::new);
+        spider.external_links = config
+            .external_links
+            .as_ref()
+            .map(|links| Arc::new(ExternalLinks::new(links, &config.start_urls)));
+        spider.relevance = config.relevance.as_ref().map(|relevance| Arc::new(RelevanceScorer::new(relevance)));
+        if let Some(secs) = config.extraction_timeout_secs {
+            spider.extraction_timeout = Duration::from_secs(secs);
+        }
+
+        Ok(spider.with_metrics(metrics))
+    }
+
+    /// Creates an engine with the crawl settings of `config`: delays, limits,
+    /// retries, deduplication, the crawl window and the frontier.
+    pub async fn create_engine(config: &SpiderConfig, metrics: Arc<MetricsCollector>) -> Result<CrawlerEngine> {
+        let (min_delay, max_delay) = config.delay_ms.bounds();
+        let mut engine = CrawlerEngine::new(Duration::from_millis(min_delay), config.concurrency, Some(metrics));
+        let jitter = max_delay - min_delay + config.delay_jitter_ms;
+        if jitter > 0 {
+            engine = engine.with_jitter(Duration::from_millis(jitter));
+        }
+        engine = engine.with_limits(config.limits.unwrap_or_default());
+        if let Some(ratio) = config.sample {
+            engine = engine.with_sample(ratio);
+        }
+        if let Some(grace) = config.shutdown_grace_secs {
+            engine = engine.with_shutdown_grace(Duration::from_secs(grace));
+        }
+        if let Some(limit_mb) = config.memory_limit_mb {
+            engine = engine.with_memory_limit(limit_mb);
+        }
+        if let Some(batch) = config.output_batch {
+            engine = engine.with_output_batch(batch);
+        }
+        if let Some(retry) = &config.retry {
+            engine = engine.with_retry(retry.clone());
+        }
+        if let Some(dedup) = &config.dedup {
+            engine = engine.with_dedup(dedup.clone());
+        }
+        if let Some(window) = &config.crawl_window {
+            engine = engine.with_crawl_window(CrawlWindow::new(window)?);
+        }
+        if let Some(alert) = config.coverage_alert {
+            engine = engine.with_coverage_alert(alert);
+        }
+        if let Some(frontier) = &config.frontier
+            && frontier.kind == FrontierKind::Disk
+        {
+            engine = engine.with_disk_frontier(DiskQueue::open(frontier).await?);
+        }
+        Ok(engine)
     }
 }
@@ -1,5 +1,9 @@
+pub mod env;
 pub mod loader;
+pub mod params;
+pub mod presets;
 pub mod schema;
 
+pub use env::ConfigSource;
 pub use loader::ConfigLoader;
-pub use schema::{OutputConfig, SpiderConfig};
+pub use schema::{OutputConfig, LimitsConfig, LoggingConfig, RedirectConfig, RetryConfig, SpiderConfig};
@@ -0,0 +1,210 @@
+//! Parameterized `start_urls`: every URL is expanded into one URL per
+//! combination of the `params` values, with the values URL-encoded, so faceted
+//! listings don't need hand-written URL lists.
+
+use crate::config::schema::SpiderConfig;
+use crate::error::{Error, Result};
+use regex::Regex;
+use serde_json::Value;
+use std::sync::LazyLock;
+use url::Url;
+
+/// Most URLs a config may expand into, so a typo like `1..50000000` fails
+/// instead of exhausting memory.
+const MAX_EXPANDED_URLS: usize = 1_000_000;
+
+static PLACEHOLDER: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"\{([A-Za-z_][A-Za-z0-9_]*)\}").unwrap());
+
+/// Replaces `config.start_urls` with their expansion over `config.params`,
+/// which is cleared, so expanding a config twice changes nothing.
+///
+/// Params used as `{name}` in a URL are nested in the order they appear, the
+/// first one varying slowest; the remaining params are added to the URL's
+/// query in alphabetical order.
+pub fn expand(config: &mut SpiderConfig) -> Result<()> {
+    if config.params.is_empty() {
+        return Ok(());
+    }
+    let params = config
+        .params
+        .iter()
+        .map(|(name, value)| Ok((name.clone(), values(name, value)?)))
+        .collect::<Result<Vec<(String, Vec<String>)>>>()?;
+
+    let mut urls = Vec::new();
+    for template in &config.start_urls {
+        let mut used: Vec<&str> = Vec::new();
+        for placeholder in PLACEHOLDER.captures_iter(template) {
+            let name = placeholder.get(1).map_or("", |name| name.as_str());
+            if !config.params.contains_key(name) {
+                return Err(Error::Config(format!(
+                    "Start URL '{}' uses {{{}}}, which isn't in `params`",
+                    template, name
+                )));
+            }
+            if !used.contains(&name) {
+                used.push(name);
+            }
+        }
+        // Placeholders first, in order of appearance, then the query params
+        let order: Vec<&(String, Vec<String>)> = used
+            .iter()
+            .filter_map(|name| params.iter().find(|(param, _)| param == name))
+            .chain(params.iter().filter(|(name, _)| !used.contains(&name.as_str())))
+            .collect();
+
+        let count = order
+            .iter()
+            .try_fold(1usize, |count, (_, values)| count.checked_mul(values.len()))
+            .filter(|&count| count <= MAX_EXPANDED_URLS - urls.len())
+            .ok_or_else(|| {
+                Error::Config(format!(
+                    "`params` expand start_urls into more than {} URLs",
+                    MAX_EXPANDED_URLS
+                ))
+            })?;
+        for combination in 0..count {
+            let mut url = template.clone();
+            let mut query = Vec::new();
+            // Mixed-radix digits of `combination`, the last param varying fastest
+            let mut rest = combination;
+            let mut picked = vec![""; order.len()];
+            for (i, (_, values)) in order.iter().enumerate().rev() {
+                picked[i] = &values[rest % values.len()];
+                rest /= values.len();
+            }
+            for ((name, _), value) in order.iter().zip(picked) {
+                if used.contains(&name.as_str()) {
+                    url = url.replace(&format!("{{{}}}", name), &encode(value));
+                } else {
+                    query.push((name, value));
+                }
+            }
+            if !query.is_empty() {
+                let mut parsed = Url::parse(&url)
+                    .map_err(|e| Error::Config(format!("Invalid start URL '{}': {}", url, e)))?;
+                parsed.query_pairs_mut().extend_pairs(query);
+                url = parsed.to_string();
+            }
+            urls.push(url);
+        }
+    }
+    log::debug!("Expanded {} start URLs into {}", config.start_urls.len(), urls.len());
+    config.start_urls = urls;
+    config.params.clear();
+    Ok(())
+}
+
+/// The values of param `name`: a list, a single value, or an inclusive range
+/// of integers like `1..50`.
+fn values(name: &str, value: &Value) -> Result<Vec<String>> {
+    let invalid = |reason: &str| Error::Config(format!("Invalid params.{}: {}", name, reason));
+    let text = |value: &Value| match value {
+        Value::String(text) => Ok(text.clone()),
+        Value::Number(number) => Ok(number.to_string()),
+        Value::Bool(flag) => Ok(flag.to_string()),
+        _ => Err(invalid("expected text, numbers or a range like 1..50")),
+    };
+    let values = match value {
+        Value::Array(values) => values.iter().map(text).collect::<Result<Vec<_>>>()?,
+        Value::String(range) if range.contains("..") => {
+            let (start, end) = range.split_once("..").unwrap_or_default();
+            let bound = |bound: &str| {
+                bound
+                    .trim()
+                    .parse::<i64>()
+                    .map_err(|_| invalid(&format!("'{}' is not a range like 1..50", range)))
+            };
+            let (start, end) = (bound(start)?, bound(end.trim_start_matches('='))?);
+            if start > end {
+                return Err(invalid(&format!("the range {} is empty", range)));
+            }
+            if end.abs_diff(start) >= MAX_EXPANDED_URLS as u64 {
+                return Err(invalid(&format!("the range {} is too large", range)));
+            }
+            (start..=end).map(|n| n.to_string()).collect()
+        }
+        value => vec![text(value)?],
+    };
+    if values.is_empty() {
+        return Err(invalid("no values"));
+    }
+    Ok(values)
+}
+
+/// `value` percent-encoded for use anywhere in a URL: everything but
+/// letters, digits and `-._~` is escaped.
+fn encode(value: &str) -> String {
+    value
+        .bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => (byte as char).to_string(),
+            byte => format!("%{:02X}", byte),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn expanded(start_urls: &[&str], params: Value) -> Result<Vec<String>> {
+        let mut config: SpiderConfig = serde_json::from_value(json!({
+            "name": "params",
+            "start_urls": start_urls,
+            "params": params,
+        }))
+        .unwrap();
+        expand(&mut config)?;
+        assert!(config.params.is_empty());
+        Ok(config.start_urls)
+    }
+
+    #[test]
+    fn nests_placeholders_in_order_and_queries_the_rest() {
+        let urls = expanded(
+            &["https://example.com/{category}/{page}"],
+            json!({"page": "1..2", "category": ["books", "games"], "sort": "price"}),
+        )
+        .unwrap();
+        assert_eq!(
+            urls,
+            [
+                "https://example.com/books/1?sort=price",
+                "https://example.com/books/2?sort=price",
+                "https://example.com/games/1?sort=price",
+                "https://example.com/games/2?sort=price",
+            ]
+        );
+    }
+
+    #[test]
+    fn encodes_placeholder_values() {
+        let urls = expanded(&["https://example.com/search/{q}"], json!({"q": ["a b/c&d", "é~x"]})).unwrap();
+        assert_eq!(
+            urls,
+            ["https://example.com/search/a%20b%2Fc%26d", "https://example.com/search/%C3%A9~x"]
+        );
+    }
+
+    #[test]
+    fn parses_ranges() {
+        assert_eq!(values("page", &json!("1..3")).unwrap(), ["1", "2", "3"]);
+        assert_eq!(values("page", &json!("-1..=1")).unwrap(), ["-1", "0", "1"]);
+        assert_eq!(values("page", &json!(" 5 .. 5 ")).unwrap(), ["5"]);
+        assert!(values("page", &json!("3..1")).is_err());
+        assert!(values("page", &json!("a..b")).is_err());
+        assert!(values("page", &json!("1..50000000")).is_err());
+        assert!(values("page", &json!([])).is_err());
+    }
+
+    #[test]
+    fn rejects_unknown_placeholders_and_huge_expansions() {
+        assert!(expanded(&["https://example.com/{missing}"], json!({"page": "1..2"})).is_err());
+        // Each range is allowed on its own, but their product overflows
+        let params: serde_json::Map<String, Value> =
+            (0..8).map(|i| (format!("p{}", i), json!("1..999999"))).collect();
+        assert!(expanded(&["https://example.com/"], Value::Object(params)).is_err());
+    }
+}
@@ -0,0 +1,69 @@
+//! Named extraction rule sets that expand into `extraction_rules` at load time.
+
+use crate::config::schema::SpiderConfig;
+use crate::error::{Error, Result};
+use crate::paths;
+use crate::spider::ExtractionRule;
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::Path;
+
+/// Field name to rule, the same shape as `extraction_rules`.
+pub type Preset = HashMap<String, ExtractionRule>;
+
+const BUILTIN: &str = include_str!("../../assets/presets/builtin.yaml");
+
+/// The presets shipped with the crawler, keyed by name.
+pub fn builtin() -> Result<BTreeMap<String, Preset>> {
+    Ok(serde_yaml::from_str(BUILTIN)?)
+}
+
+/// Reads a preset file: a map of preset names to extraction rules, in JSON, YAML or TOML.
+pub fn load_file(path: &Path) -> Result<BTreeMap<String, Preset>> {
+    let content = fs::read_to_string(path)
+        .map_err(|e| Error::Config(format!("{}: {}", path.display(), e)))?;
+
+    match paths::extension(path).as_deref() {
+        Some("json") => Ok(serde_json::from_str(&content)?),
+        Some("yaml") | Some("yml") => Ok(serde_yaml::from_str(&content)?),
+        Some("toml") => Ok(toml::from_str(&content)?),
+        _ => Err(Error::Config(format!(
+            "Unsupported preset file extension: {}",
+            path.display()
+        ))),
+    }
+}
+
+/// Adds the rules of every preset named in `config.preset` to its `extraction_rules`.
+///
+/// Presets from `use_presets` files shadow built-ins of the same name. Rules
+/// written out in the config win over preset rules for the same field, and
+/// earlier presets win over later ones.
+pub fn expand(config: &mut SpiderConfig) -> Result<()> {
+    if config.preset.is_empty() {
+        return Ok(());
+    }
+
+    let mut available = builtin()?;
+    for path in &config.use_presets {
+        available.extend(load_file(path)?);
+    }
+
+    for name in &config.preset {
+        let preset = available.get(name).ok_or_else(|| {
+            Error::Config(format!(
+                "Unknown preset '{}' (available: {})",
+                name,
+                available.keys().cloned().collect::<Vec<_>>().join(", ")
+            ))
+        })?;
+        for (field, rule) in preset {
+            config
+                .extraction_rules
+                .entry(field.clone())
+                .or_insert_with(|| rule.clone());
+        }
+    }
+
+    Ok(())
+}
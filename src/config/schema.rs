@@ -1,7 +1,9 @@
 use crate::selector::CssSelector;
 use crate::spider::ExtractionRule;
-use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use serde::{Deserialize, Deserializer, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::net::IpAddr;
+use std::path::PathBuf;
 use validator::Validate;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -36,17 +38,44 @@ pub struct SpiderConfig {
     #[validate(length(min = 1))]
     pub start_urls: Vec<String>,
 
+    /// Values that `start_urls` are expanded with, one URL per combination:
+    /// `{name}` in a URL is replaced, other params are added to its query,
+    /// e.g. `{category: [books, music], page: 1..50}`
+    #[serde(default)]
+    pub params: BTreeMap<String, serde_json::Value>,
+
     #[serde(default)]
     pub root_selector: Option<SelectorConfig>,
 
     #[serde(default)]
     pub extraction_rules: HashMap<String, ExtractionRule>,
 
+    /// Named rule sets added to `extraction_rules`, e.g. `open_graph` or `[article, open_graph]`
+    #[serde(default, deserialize_with = "one_or_many")]
+    pub preset: Vec<String>,
+
+    /// Files defining additional presets, relative to this config
+    #[serde(default)]
+    pub use_presets: Vec<PathBuf>,
+
     #[serde(default = "default_concurrency")]
     pub concurrency: usize,
 
+    /// Pause after each request, fixed (`500`) or picked at random from a range (`[300, 900]`)
     #[serde(default = "default_delay")]
-    pub delay_ms: u64,
+    pub delay_ms: Delay,
+
+    /// Random extra delay of up to this many milliseconds added to each pause
+    #[serde(default)]
+    pub delay_jitter_ms: u64,
+
+    /// Maximum number of pages parsed in parallel on the blocking pool
+    #[serde(default)]
+    pub parser_threads: Option<usize>,
+
+    /// Seconds a page's extraction may take before the page fails (default: 30)
+    #[serde(default)]
+    pub extraction_timeout_secs: Option<u64>,
 
     #[serde(default)]
     pub output: Option<OutputConfig>,
@@ -54,33 +83,1538 @@ pub struct SpiderConfig {
     /// Optional path to a parent configuration file to inherit from
     #[serde(default)]
     pub extends: Option<String>,
+
+    /// Redirect following and canonical URL handling
+    #[serde(default)]
+    pub redirects: Option<RedirectConfig>,
+
+    /// Send a HEAD request first and skip pages whose status, type or size rules them out
+    #[serde(default)]
+    pub head_check: Option<HeadCheckConfig>,
+
+    /// Responses to treat as block or captcha pages rather than successes
+    #[serde(default)]
+    pub block_detection: Option<BlockDetectionConfig>,
+
+    /// What counts as the same request when skipping duplicates
+    #[serde(default)]
+    pub dedup: Option<DedupConfig>,
+
+    /// Canonicalizes start and discovered URLs before they are deduplicated and queued
+    #[serde(default)]
+    pub url_rewrite: Option<UrlRewriteConfig>,
+
+    /// Where queued URLs are kept; `disk` for crawls too large for memory
+    #[serde(default)]
+    pub frontier: Option<FrontierConfig>,
+
+    /// Remembers fetched pages across runs and skips those still fresh per their cache headers
+    #[serde(default)]
+    pub incremental: Option<IncrementalConfig>,
+
+    /// SQLite cache of responses that several spiders can share, with a TTL
+    #[serde(default)]
+    pub page_cache: Option<PageCacheConfig>,
+
+    /// DNS caching, custom resolvers and static host overrides
+    #[serde(default)]
+    pub dns: Option<DnsConfig>,
+
+    /// Accepted content encodings and response size limits
+    #[serde(default)]
+    pub compression: Option<CompressionConfig>,
+
+    /// Throughput caps on response bodies, overall and per host
+    #[serde(default)]
+    pub max_bandwidth_kbps: Option<BandwidthConfig>,
+
+    /// HTTP/2 and connection pool tuning
+    #[serde(default)]
+    pub http: Option<HttpConfig>,
+
+    /// Private CAs, client certificates (mTLS) and certificate checks
+    #[serde(default)]
+    pub tls: Option<TlsConfig>,
+
+    /// HTTP and SOCKS5 proxies requests are spread over, with health checks
+    #[serde(default)]
+    pub proxy_pool: Option<ProxyPoolConfig>,
+
+    /// Cookie jars and headers kept separately per host, domain or group of hosts
+    #[serde(default)]
+    pub sessions: Option<SessionConfig>,
+
+    /// Requests sent once per host before its first page, e.g. to pick up the
+    /// cookies or anti-bot tokens a site sets on its landing page
+    #[serde(default)]
+    pub warmup: Vec<WarmupStep>,
+
+    /// Signs every request, e.g. with an HMAC header for authenticated APIs
+    #[serde(default)]
+    pub signing: Option<SigningConfig>,
+
+    /// Fetches pages from an archive instead of their live sites
+    #[serde(default)]
+    pub source: Option<SourceConfig>,
+
+    /// Bearer tokens for API crawls, fetched before the crawl and refreshed as needed
+    #[serde(default)]
+    pub auth: Option<AuthConfig>,
+
+    /// HTML cleanup applied before extraction
+    #[serde(default)]
+    pub html_cleanup: Option<HtmlCleanupConfig>,
+
+    /// Retries for transient failures (timeouts, DNS, 429/5xx)
+    #[serde(default)]
+    pub retry: Option<RetryConfig>,
+
+    /// Stop the crawl after a number of pages or items
+    #[serde(default)]
+    pub limits: Option<LimitsConfig>,
+
+    /// Caps the requests sent per month (or day, week) across runs, e.g. for
+    /// paid APIs; needs `incremental`, whose state database keeps the count
+    #[serde(default)]
+    pub request_budget: Option<RequestBudgetConfig>,
+
+    /// Randomly crawl only this fraction (0.0-1.0) of discovered URLs; start URLs are always crawled
+    #[serde(default)]
+    #[validate(range(min = 0.0, max = 1.0))]
+    pub sample: Option<f64>,
+
+    /// On Ctrl-C or SIGTERM, abort requests still in flight after this many seconds
+    #[serde(default)]
+    pub shutdown_grace_secs: Option<u64>,
+
+    /// Megabytes of page bodies and queued items to hold in memory before pausing fetches and spilling items to disk
+    #[serde(default)]
+    pub memory_limit_mb: Option<u64>,
+
+    /// Hand items to the output in batches, flushing it on a timer
+    #[serde(default)]
+    pub output_batch: Option<OutputBatchConfig>,
+
+    /// Instances of the output written to in parallel, for outputs that allow it
+    #[serde(default)]
+    pub output_concurrency: Option<usize>,
+
+    /// Only crawl between these times of day, pausing outside them
+    #[serde(default)]
+    pub crawl_window: Option<CrawlWindowConfig>,
+
+    /// Extract text from PDF responses and apply separate rules to it
+    #[serde(default)]
+    pub pdf: Option<PdfConfig>,
+
+    /// Rendering service used when rules marked `render_fallback` find nothing in the static HTML
+    #[serde(default)]
+    pub render: Option<RenderConfig>,
+
+    /// Also emit one item per page with page-level fields
+    #[serde(default)]
+    pub page_item: Option<PageItemConfig>,
+
+    /// Rule sets picked by URL pattern and crawl depth; other pages use the top-level rules
+    #[serde(default)]
+    pub page_types: Vec<PageTypeConfig>,
+
+    /// Add a `_lang` field with the detected language of each item
+    #[serde(default)]
+    pub detect_language: bool,
+
+    /// Drop items detected as any other language (ISO 639-1 codes); implies `detect_language`
+    #[serde(default)]
+    pub only_languages: Vec<String>,
+
+    /// Log levels per module and an optional rotating log file
+    #[serde(default)]
+    pub logging: Option<LoggingConfig>,
+
+    /// Webhook that receives a summary when the crawl finishes or is interrupted
+    #[serde(default)]
+    pub notify: Option<NotifyConfig>,
+
+    /// Alerts when a field's coverage fell since the previous run; needs `incremental`
+    #[serde(default)]
+    pub coverage_alert: Option<CoverageAlertConfig>,
+
+    /// Drops items failing these predicates right after extraction
+    #[serde(default)]
+    pub item_filter: Option<ItemFilterConfig>,
+
+    /// Fields identifying an item, hashed into its `_id`; duplicates are dropped
+    /// and SQLite outputs update rows by it
+    #[serde(default)]
+    pub item_id: Option<ItemIdConfig>,
+
+    /// Records links to other sites found on crawled pages in a CSV of their own
+    #[serde(default)]
+    pub external_links: Option<ExternalLinksConfig>,
+
+    /// Scores pages against keywords to crawl links on relevant pages first
+    /// and stop following links on irrelevant ones
+    #[serde(default)]
+    pub relevance: Option<RelevanceConfig>,
+
+    /// Another spider, started with the URLs in this spider's items once it
+    /// finishes, e.g. a listing spider feeding a detail spider
+    #[serde(default)]
+    pub feeds: Option<FeedsConfig>,
+
+    /// Saved pages and what extraction should find on them, run by `crawler check --run-tests`
+    #[serde(default)]
+    pub tests: Vec<ExtractionTestConfig>,
+
+    /// Conditions that make `crawler run` exit with status 4 when they hold
+    /// for the finished crawl, e.g. `items<100` or `error_rate>20`
+    #[serde(default)]
+    pub fail_on: Vec<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
-#[serde(tag = "type", rename_all = "lowercase")]
-pub enum OutputConfig {
-    Console,
-    Json {
-        path: String,
-    },
-    Csv {
-        path: String,
-    },
-    Sqlite {
-        path: String,
-        #[serde(default = "default_table_name")]
-        table: String,
-    },
+pub struct RenderConfig {
+    /// URL returning a page's rendered HTML, with `{url}` standing for the page,
+    /// e.g. `http://localhost:8050/render.html?url={url}&wait=1`
+    pub endpoint: String,
+
+    /// Seconds to wait for the rendered page
+    #[serde(default = "default_render_timeout")]
+    pub timeout_secs: u64,
 }
 
-fn default_concurrency() -> usize {
-    2
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PdfConfig {
+    /// Splits the text into several items, usually a `regex:` selector
+    #[serde(default)]
+    pub root_selector: Option<SelectorConfig>,
+
+    /// Rules applied to the PDF's text; use `regex:` selectors
+    #[serde(default)]
+    pub rules: HashMap<String, ExtractionRule>,
+
+    /// Add the full text as a `_text` field
+    #[serde(default)]
+    pub include_text: bool,
 }
 
-fn default_delay() -> u64 {
-    500
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PageItemConfig {
+    /// Page-level extraction rules; `title` defaults to the `<title>` text
+    #[serde(default)]
+    pub rules: HashMap<String, ExtractionRule>,
 }
 
-fn default_table_name() -> String {
-    "scraped_data".to_string()
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PageTypeConfig {
+    /// Added to the page's items as `_page_type`
+    #[serde(default)]
+    pub name: Option<String>,
+
+    /// Regex matched against the page URL; omitted matches every URL
+    #[serde(default, rename = "match")]
+    pub pattern: Option<String>,
+
+    /// Only match pages this many links away from a start URL (start URLs are 0)
+    #[serde(default)]
+    pub depth: Option<usize>,
+
+    #[serde(default)]
+    pub root_selector: Option<SelectorConfig>,
+
+    #[serde(default)]
+    pub rules: HashMap<String, ExtractionRule>,
+
+    /// Queue the page's links that match a page type
+    #[serde(default)]
+    pub follow: bool,
+
+    /// Follow the page's links without extracting anything from it
+    #[serde(default)]
+    pub follow_only: bool,
+
+    /// HEAD check for pages of this type, instead of the top-level `head_check`
+    #[serde(default)]
+    pub head_check: Option<HeadCheckConfig>,
+
+    /// Record the anchor text, nearest heading and `rel` of each followed link,
+    /// added as `_link` to the items of the page it leads to
+    #[serde(default)]
+    pub link_context: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NotifyConfig {
+    pub webhook: String,
+
+    #[serde(default)]
+    pub format: WebhookFormat,
+
+    /// Extra request headers, e.g. an `Authorization` token
+    #[serde(default)]
+    pub headers: BTreeMap<String, String>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum WebhookFormat {
+    /// The run summary as a JSON object
+    #[default]
+    Json,
+    /// A `{"text": ...}` message for Slack and compatible incoming webhooks
+    Slack,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct CoverageAlertConfig {
+    /// Percentage points of coverage a field may lose since the previous run
+    #[serde(default = "default_coverage_max_drop")]
+    pub max_drop: f64,
+
+    /// Fields seen on fewer pages than this in either run are not compared
+    #[serde(default = "default_coverage_min_pages")]
+    pub min_pages: u64,
+
+    /// Exit with status 3 when a field's coverage dropped, e.g. to fail a scheduled job
+    #[serde(default)]
+    pub fail: bool,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ItemFilterConfig {
+    /// Regexes the named fields must match; an item without the field is dropped
+    #[serde(default)]
+    pub must_contain: BTreeMap<String, String>,
+
+    /// Fields an item needs with a non-empty value, not counting `_` fields
+    #[serde(default)]
+    pub min_fields: Option<usize>,
+
+    /// Items kept per page, in page order, after the other predicates
+    #[serde(default)]
+    pub max_items_per_page: Option<usize>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ItemIdConfig {
+    /// Item fields making up the identity; `url` is the page URL unless items have a `url` field
+    pub fields: Vec<String>,
+
+    #[serde(default)]
+    pub hash: ItemIdHash,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ItemIdHash {
+    /// Hex SHA-256 of the field values
+    #[default]
+    Sha256,
+    /// The field values themselves, joined with `|`
+    None,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExternalLinksConfig {
+    /// CSV written when the crawl ends
+    #[serde(default = "default_external_links_path")]
+    pub path: PathBuf,
+
+    /// One row per external `link`, or per external `domain`
+    #[serde(default)]
+    pub group_by: ExternalLinksGroup,
+
+    /// Keep the anchor texts of each link or domain
+    #[serde(default)]
+    pub anchor_text: bool,
+
+    /// Also queue the external links, so their sites are crawled and harvested in turn
+    #[serde(default)]
+    pub follow: bool,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ExternalLinksGroup {
+    #[default]
+    Link,
+    Domain,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FeedsConfig {
+    /// Config of the next spider, relative to this one
+    pub config: PathBuf,
+
+    /// Item field holding the URL, or list of URLs, to start it with
+    #[serde(default = "default_feeds_url_field")]
+    pub url_field: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RelevanceConfig {
+    /// Words or phrases of the topic, matched case-insensitively as whole words
+    pub keywords: Vec<String>,
+
+    #[serde(default)]
+    pub scoring: RelevanceScoring,
+
+    /// Links on pages scoring below this aren't followed, except on start URLs
+    #[serde(default)]
+    pub min_score: f64,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RelevanceScoring {
+    /// The share of the keywords found on the page, from 0 to 1
+    #[default]
+    Keywords,
+    /// Keyword occurrences per word of the page's text
+    Tf,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExtractionTestConfig {
+    /// Saved HTML page, relative to this config
+    pub file: PathBuf,
+
+    /// URL the page is treated as coming from, for page types and relative
+    /// links; defaults to the first start URL
+    #[serde(default)]
+    pub url: Option<String>,
+
+    /// `items` is the number of items expected; any other key is a field and
+    /// the value expected in the first item, with `null` for a missing field
+    #[serde(default)]
+    pub expect: BTreeMap<String, serde_json::Value>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct LoggingConfig {
+    /// Default level (`error`, `warn`, `info`, `debug`, `trace` or `off`)
+    #[serde(default)]
+    pub level: Option<String>,
+
+    /// Levels for individual modules, e.g. `crawler::http: debug`
+    #[serde(default)]
+    pub modules: BTreeMap<String, String>,
+
+    /// Write logs to a file instead of stderr
+    #[serde(default)]
+    pub file: Option<LogFileConfig>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LogFileConfig {
+    pub path: String,
+
+    /// Size at which the file is rotated to `<path>.1`
+    #[serde(default = "default_log_max_size_mb")]
+    pub max_size_mb: u64,
+
+    /// Number of rotated files kept besides the current one
+    #[serde(default = "default_log_max_files")]
+    pub max_files: usize,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct OutputBatchConfig {
+    /// Items collected before they are written together
+    #[serde(default = "default_output_batch_size")]
+    pub size: usize,
+
+    /// Milliseconds after which a partial batch is written and the output flushed
+    #[serde(default = "default_output_flush_interval")]
+    pub flush_interval_ms: u64,
+}
+
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+pub struct LimitsConfig {
+    /// Maximum number of pages fetched
+    #[serde(default)]
+    pub max_pages: Option<u64>,
+
+    /// Maximum number of items emitted
+    #[serde(default)]
+    pub max_items: Option<u64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RequestBudgetConfig {
+    /// Requests allowed per period, including retries and redirects
+    pub max_requests: u64,
+
+    /// How often the budget starts over, on UTC calendar boundaries
+    #[serde(default)]
+    pub period: BudgetPeriod,
+
+    /// What happens once the budget is used up
+    #[serde(default)]
+    pub on_exhausted: OnExhausted,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BudgetPeriod {
+    Day,
+    /// Starting on Mondays
+    Week,
+    #[default]
+    Month,
+    /// Never starts over
+    Total,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum OnExhausted {
+    /// `stop` or `pause`
+    Action(BudgetAction),
+    /// Pause the crawl until this time, e.g. `2026-11-01` or
+    /// `2026-11-01T09:00:00Z`, and start a new budget then
+    PauseUntil { pause_until: String },
+}
+
+impl Default for OnExhausted {
+    fn default() -> Self {
+        OnExhausted::Action(BudgetAction::Stop)
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BudgetAction {
+    /// End the crawl; the remaining URLs are left for a resumed run
+    #[default]
+    Stop,
+    /// Pause the crawl until the next period starts
+    Pause,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum Delay {
+    Fixed(u64),
+    Range([u64; 2]),
+}
+
+impl Delay {
+    /// Shortest and longest delay in milliseconds.
+    pub fn bounds(&self) -> (u64, u64) {
+        match *self {
+            Delay::Fixed(ms) => (ms, ms),
+            Delay::Range([a, b]) => (a.min(b), a.max(b)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CrawlWindowConfig {
+    /// Time of day the window opens, `HH:MM`
+    pub start: String,
+
+    /// Time of day the window closes, `HH:MM`; before `start` for windows spanning midnight
+    pub end: String,
+
+    /// IANA time zone the times are in, e.g. `Europe/Berlin`
+    #[serde(default = "default_timezone")]
+    pub timezone: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DedupConfig {
+    /// `exact` remembers every fingerprint; `bloom` uses a fixed-rate probabilistic set
+    #[serde(default, rename = "type")]
+    pub kind: DedupKind,
+
+    /// Fingerprints the Bloom filter is sized for before it grows
+    #[serde(default = "default_expected_items")]
+    pub expected_items: usize,
+
+    /// Chance (0.0-1.0) that the Bloom filter treats a new URL as already seen
+    #[serde(default = "default_fp_rate")]
+    pub fp_rate: f64,
+
+    /// Query parameters left out of request fingerprints; `*` matches any characters, e.g. `utm_*`
+    #[serde(default)]
+    pub ignore_params: Vec<String>,
+
+    /// Pages whose text is nearly the same as an earlier page's, e.g. mirrors and print versions
+    #[serde(default)]
+    pub near_duplicates: Option<NearDuplicateConfig>,
+}
+
+impl Default for DedupConfig {
+    fn default() -> Self {
+        Self {
+            kind: DedupKind::default(),
+            expected_items: default_expected_items(),
+            fp_rate: default_fp_rate(),
+            ignore_params: Vec::new(),
+            near_duplicates: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NearDuplicateConfig {
+    /// Estimated share of shared word shingles (0.0-1.0) from which a page is a near duplicate
+    #[serde(default = "default_near_duplicate_threshold")]
+    pub threshold: f64,
+
+    #[serde(default)]
+    pub action: NearDuplicateAction,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum NearDuplicateAction {
+    /// Emit no items from the page and don't follow its links
+    #[default]
+    Skip,
+    /// Keep the page, naming the page it copies in `_near_duplicate_of`
+    Tag,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DedupKind {
+    #[default]
+    Exact,
+    Bloom,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct UrlRewriteConfig {
+    /// Query parameters removed from URLs; `*` matches any characters, e.g. `utm_*`
+    #[serde(default)]
+    pub strip_params: Vec<String>,
+
+    /// Regex replacements applied in order to the whole URL
+    #[serde(default)]
+    pub rewrites: Vec<UrlRewriteRule>,
+
+    /// Turn `http://` URLs into `https://`
+    #[serde(default)]
+    pub force_https: bool,
+
+    /// Lowercase the path, for sites with case-insensitive URLs
+    #[serde(default)]
+    pub lowercase_path: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UrlRewriteRule {
+    pub pattern: String,
+
+    /// Replacement, where `$1` or `${name}` refer to capture groups
+    pub replace: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FrontierConfig {
+    /// `memory` keeps every queued URL in RAM; `disk` keeps them in a SQLite file
+    #[serde(default, rename = "type")]
+    pub kind: FrontierKind,
+
+    /// SQLite file for the `disk` frontier; an unfinished crawl resumes from it
+    #[serde(default = "default_frontier_path")]
+    pub path: String,
+
+    /// Queued URLs the `disk` frontier holds in memory at once
+    #[serde(default = "default_frontier_window")]
+    pub window: usize,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FrontierKind {
+    #[default]
+    Memory,
+    Disk,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IncrementalConfig {
+    /// SQLite file recording when each page was fetched and until when it stays fresh
+    #[serde(default = "default_state_db")]
+    pub state_db: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PageCacheConfig {
+    /// SQLite file holding the cached responses; point several configs at the same one to share it
+    #[serde(default = "default_page_cache")]
+    pub path: String,
+
+    /// Seconds a cached response is served without contacting the server
+    #[serde(default = "default_page_cache_ttl_secs")]
+    pub ttl_secs: u64,
+
+    /// Request headers whose values are part of the cache key, e.g. `Accept-Language`
+    #[serde(default)]
+    pub vary_headers: Vec<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct HeadCheckConfig {
+    /// Content types to fetch, e.g. `text/html` or `text/*`; empty allows any
+    #[serde(default)]
+    pub content_types: Vec<String>,
+
+    /// Skip pages whose `Content-Length` is larger than this many bytes
+    #[serde(default)]
+    pub max_bytes: Option<u64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BlockDetectionConfig {
+    /// Statuses meaning the crawler was blocked rather than the page missing or broken
+    #[serde(default = "default_block_statuses")]
+    pub statuses: Vec<u16>,
+
+    /// Text meaning a block or captcha page was served, matched ignoring case
+    #[serde(default = "default_block_page_patterns")]
+    pub body_patterns: Vec<String>,
+
+    /// Recognize Cloudflare challenge pages by their headers and markup
+    #[serde(default = "default_true")]
+    pub cloudflare: bool,
+
+    /// Pages with fewer bytes than this are treated as blocked
+    #[serde(default)]
+    pub min_content_length: Option<usize>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RedirectConfig {
+    /// Maximum number of redirect hops followed for a single request
+    #[serde(default = "default_max_redirects")]
+    pub max_redirects: usize,
+
+    /// Use `<link rel="canonical">` instead of the final URL as the dedup key
+    #[serde(default = "default_true")]
+    pub honor_canonical: bool,
+
+    /// Attach the full redirect chain to emitted items as `_redirect_chain`
+    #[serde(default)]
+    pub record_chain: bool,
+}
+
+impl Default for RedirectConfig {
+    fn default() -> Self {
+        Self {
+            max_redirects: default_max_redirects(),
+            honor_canonical: true,
+            record_chain: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RetryConfig {
+    /// Additional attempts after the first one fails with a retryable error
+    #[serde(default = "default_max_retries")]
+    pub max_retries: u32,
+
+    /// Delay before the first retry; doubles on every further attempt
+    #[serde(default = "default_retry_backoff")]
+    pub backoff_ms: u64,
+}
+
+impl Default for RetryConfig {
+    fn default() -> Self {
+        Self {
+            max_retries: default_max_retries(),
+            backoff_ms: default_retry_backoff(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum OutputConfig {
+    Console(#[serde(default)] ConsoleConfig),
+    Json {
+        path: String,
+        /// Add to the array in an existing file instead of replacing it
+        #[serde(default)]
+        append: bool,
+    },
+    Csv {
+        path: String,
+        /// Add rows to an existing file, keeping its header
+        #[serde(default)]
+        append: bool,
+        #[serde(default)]
+        nested: NestedConfig,
+    },
+    Sqlite {
+        path: String,
+        #[serde(default = "default_table_name")]
+        table: String,
+        /// Columns the table is created with; items that don't fit are rejected
+        #[serde(default)]
+        columns: Vec<ColumnConfig>,
+        /// File receiving rejected items as NDJSON, `<path>.rejected.ndjson` by default
+        #[serde(default)]
+        dead_letter: Option<String>,
+        #[serde(default)]
+        pragmas: SqlitePragmas,
+        #[serde(default)]
+        nested: NestedConfig,
+    },
+    Parquet {
+        path: String,
+    },
+    Xlsx {
+        path: String,
+    },
+    /// NDJSON objects in S3, Google Cloud Storage or Azure Blob Storage
+    #[serde(rename = "object_store")]
+    ObjectStore {
+        /// `s3://bucket/prefix`, `gs://bucket/prefix` or `az://container/prefix`
+        url: String,
+        /// Store settings such as `region` or `endpoint`, on top of `AWS_*`, `GOOGLE_*` and `AZURE_*` variables
+        #[serde(default)]
+        options: HashMap<String, String>,
+        /// Items per uploaded object
+        #[serde(default = "default_items_per_object")]
+        items_per_object: usize,
+    },
+    /// Items only on stdout; logs, progress and the summary go to stderr
+    Stdout {
+        #[serde(default)]
+        format: StdoutFormat,
+    },
+}
+
+impl OutputConfig {
+    /// Replaces `{run_id}` in the output path, so each run can write its own file.
+    pub fn expand_run_id(&mut self, run_id: &str) {
+        match self {
+            OutputConfig::Json { path, .. }
+            | OutputConfig::Csv { path, .. }
+            | OutputConfig::Parquet { path }
+            | OutputConfig::Xlsx { path } => *path = path.replace("{run_id}", run_id),
+            OutputConfig::Sqlite { path, dead_letter, .. } => {
+                *path = path.replace("{run_id}", run_id);
+                if let Some(dead_letter) = dead_letter {
+                    *dead_letter = dead_letter.replace("{run_id}", run_id);
+                }
+            }
+            OutputConfig::ObjectStore { url, .. } => *url = url.replace("{run_id}", run_id),
+            OutputConfig::Console(_) | OutputConfig::Stdout { .. } => {}
+        }
+    }
+}
+
+/// A column of an output table declared up front.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ColumnConfig {
+    pub name: String,
+
+    #[serde(rename = "type", default)]
+    pub column_type: ColumnType,
+
+    /// Whether items may lack the field or have it `null`
+    #[serde(default = "default_true")]
+    pub nullable: bool,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ColumnType {
+    /// Any string, number or boolean, stored as text
+    #[default]
+    Text,
+    /// Whole JSON numbers
+    Integer,
+    /// Any JSON number
+    Real,
+    Boolean,
+    /// Any value, stored as JSON text
+    Json,
+}
+
+/// How a flat output stores nested objects and arrays.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NestedConfig {
+    #[serde(default)]
+    pub mode: NestedMode,
+
+    /// Joins the keys of nested objects, as in `author.name`; array elements get `[index]`
+    #[serde(default = "default_nested_separator")]
+    pub separator: String,
+}
+
+impl Default for NestedConfig {
+    fn default() -> Self {
+        Self {
+            mode: NestedMode::default(),
+            separator: default_nested_separator(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum NestedMode {
+    /// Nested values are written as JSON text in a single column
+    #[default]
+    Json,
+    /// Every leaf value gets its own column
+    Flatten,
+}
+
+/// Connection settings of a SQLite output.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct SqlitePragmas {
+    #[serde(default)]
+    pub journal_mode: SqliteJournal,
+
+    #[serde(default)]
+    pub synchronous: SqliteSync,
+
+    /// How long a write waits for a lock held by another connection
+    #[serde(default = "default_busy_timeout_ms")]
+    pub busy_timeout_ms: u64,
+
+    /// Page cache size; pages if positive, KiB if negative, like SQLite's own pragma
+    #[serde(default = "default_cache_size")]
+    pub cache_size: i64,
+}
+
+impl Default for SqlitePragmas {
+    fn default() -> Self {
+        Self {
+            journal_mode: SqliteJournal::default(),
+            synchronous: SqliteSync::default(),
+            busy_timeout_ms: default_busy_timeout_ms(),
+            cache_size: default_cache_size(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SqliteJournal {
+    Delete,
+    Truncate,
+    Persist,
+    Memory,
+    /// Readers don't block the writer, and writes only append to the log
+    #[default]
+    Wal,
+    Off,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SqliteSync {
+    Off,
+    /// Safe with `wal`; only a power loss may lose the last transactions
+    #[default]
+    Normal,
+    Full,
+    Extra,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StdoutFormat {
+    /// One JSON object per line
+    #[default]
+    Ndjson,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ConsoleConfig {
+    /// How items are rendered
+    pub format: ConsoleFormat,
+
+    /// Truncate values longer than this many characters (table and kv formats)
+    pub max_width: Option<usize>,
+
+    /// Highlight keys with color when the terminal supports it
+    pub color: bool,
+}
+
+impl Default for ConsoleConfig {
+    fn default() -> Self {
+        Self {
+            format: ConsoleFormat::default(),
+            max_width: None,
+            color: true,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConsoleFormat {
+    /// Indented JSON, one item spanning several lines
+    #[default]
+    PrettyJson,
+    /// One JSON object per line
+    CompactJson,
+    /// Aligned columns with a header built from the rule names
+    Table,
+    /// `key: value` lines, one block per item
+    Kv,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DnsConfig {
+    /// How long resolved addresses are reused before looking them up again
+    #[serde(default = "default_dns_ttl")]
+    pub cache_ttl_secs: u64,
+
+    /// Plain DNS nameservers to query instead of the system resolver
+    #[serde(default)]
+    pub nameservers: Vec<IpAddr>,
+
+    /// DNS-over-HTTPS upstream; takes precedence over `nameservers`
+    #[serde(default)]
+    pub doh: Option<DohProvider>,
+
+    /// Static host overrides, e.g. `{ "example.com": "10.0.0.5" }`
+    #[serde(default)]
+    pub hosts: HashMap<String, IpAddr>,
+}
+
+impl Default for DnsConfig {
+    fn default() -> Self {
+        Self {
+            cache_ttl_secs: default_dns_ttl(),
+            nameservers: Vec::new(),
+            doh: None,
+            hosts: HashMap::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DohProvider {
+    Cloudflare,
+    Google,
+    Custom { ips: Vec<IpAddr>, server_name: String },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CompressionConfig {
+    /// Encodings advertised in `Accept-Encoding`; empty means `identity`
+    #[serde(default = "default_encodings")]
+    pub encodings: Vec<ContentEncoding>,
+
+    /// Maximum body size in bytes, enforced both on the wire and after decompression
+    #[serde(default = "default_max_body_bytes")]
+    pub max_body_bytes: usize,
+}
+
+impl Default for CompressionConfig {
+    fn default() -> Self {
+        Self {
+            encodings: default_encodings(),
+            max_body_bytes: default_max_body_bytes(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct BandwidthConfig {
+    /// Kilobits per second for all responses together
+    #[serde(default)]
+    pub global: Option<u64>,
+
+    /// Kilobits per second for the responses of each host
+    #[serde(default)]
+    pub per_domain: Option<u64>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ContentEncoding {
+    Gzip,
+    Deflate,
+    Br,
+    Zstd,
+}
+
+impl ContentEncoding {
+    pub fn token(&self) -> &'static str {
+        match self {
+            ContentEncoding::Gzip => "gzip",
+            ContentEncoding::Deflate => "deflate",
+            ContentEncoding::Br => "br",
+            ContentEncoding::Zstd => "zstd",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct HttpConfig {
+    /// Speak HTTP/2 without negotiation; only use against servers known to support it
+    #[serde(default)]
+    pub http2_prior_knowledge: bool,
+
+    /// Let HTTP/2 flow-control windows grow with the measured bandwidth-delay product
+    #[serde(default)]
+    pub http2_adaptive_window: bool,
+
+    /// Interval for HTTP/2 keep-alive pings on busy connections
+    #[serde(default)]
+    pub http2_keep_alive_secs: Option<u64>,
+
+    /// Upper bound on in-flight requests to a single origin (scheme + host + port)
+    #[serde(default)]
+    pub max_streams_per_origin: Option<usize>,
+
+    /// Idle connections kept open per host for reuse
+    #[serde(default)]
+    pub pool_max_idle_per_host: Option<usize>,
+
+    /// How long an idle pooled connection is kept before closing
+    #[serde(default)]
+    pub pool_idle_timeout_secs: Option<u64>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct TlsConfig {
+    /// PEM file of CA certificates to trust in addition to the built-in roots
+    #[serde(default)]
+    pub ca_file: Option<String>,
+
+    /// PEM client certificate presented to servers requiring mTLS; needs `client_key`
+    #[serde(default)]
+    pub client_cert: Option<String>,
+
+    /// PEM private key for `client_cert`
+    #[serde(default)]
+    pub client_key: Option<String>,
+
+    /// Accept invalid, expired and self-signed certificates; only for testing
+    #[serde(default)]
+    pub insecure_skip_verify: bool,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SessionConfig {
+    /// Hosts outside any group get one session each (`host`) or one per domain (`domain`)
+    #[serde(default)]
+    pub scope: SessionScope,
+
+    /// Named sessions shared by several hosts, e.g. a shop and its login host;
+    /// `*.example.com` matches every subdomain
+    #[serde(default)]
+    pub groups: BTreeMap<String, Vec<String>>,
+
+    /// Headers sent with every request of a session, keyed by group name, or by
+    /// host or domain depending on `scope`
+    #[serde(default)]
+    pub headers: BTreeMap<String, BTreeMap<String, String>>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WarmupStep {
+    /// URL to request, or a path resolved against the host being warmed up, e.g. `/`
+    pub url: String,
+
+    #[serde(default = "default_warmup_method")]
+    pub method: String,
+
+    #[serde(default)]
+    pub headers: BTreeMap<String, String>,
+
+    #[serde(default)]
+    pub body: Option<String>,
+
+    /// Pause after the request, e.g. for a bot check to pass
+    #[serde(default)]
+    pub wait_ms: u64,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SessionScope {
+    #[default]
+    Host,
+    Domain,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum SourceConfig {
+    /// Snapshots from the Wayback Machine
+    Wayback(WaybackConfig),
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WaybackConfig {
+    /// Date the snapshots should be closest to, e.g. `2015-06-01` or `20150601120000`
+    pub date: String,
+
+    /// Base URL of the archive
+    #[serde(default = "default_wayback_endpoint")]
+    pub endpoint: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum SigningConfig {
+    HmacSha256(HmacSigningConfig),
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HmacSigningConfig {
+    /// Shared secret; prefer `secret_env` to keep it out of config files
+    #[serde(default)]
+    pub secret: Option<String>,
+
+    /// Environment variable holding the shared secret
+    #[serde(default)]
+    pub secret_env: Option<String>,
+
+    /// Text that is signed, with placeholders such as `{method}`, `{path}` and `{timestamp}`
+    #[serde(default = "default_string_to_sign")]
+    pub string_to_sign: String,
+
+    /// Header carrying the signature
+    #[serde(default = "default_signature_header")]
+    pub header: String,
+
+    /// Header value, where `{signature}` is the encoded signature, e.g. `HMAC {signature}`
+    #[serde(default = "default_signature_value")]
+    pub value: String,
+
+    #[serde(default)]
+    pub encoding: SignatureEncoding,
+
+    /// Also send the `{timestamp}` that was signed in this header
+    #[serde(default)]
+    pub timestamp_header: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SignatureEncoding {
+    #[default]
+    Hex,
+    Base64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum AuthConfig {
+    #[serde(rename = "oauth2_client_credentials")]
+    OAuth2ClientCredentials(OAuth2Config),
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OAuth2Config {
+    pub token_url: String,
+
+    pub client_id: String,
+
+    /// Client secret; prefer `client_secret_env` to keep it out of config files
+    #[serde(default)]
+    pub client_secret: Option<String>,
+
+    /// Environment variable holding the client secret
+    #[serde(default)]
+    pub client_secret_env: Option<String>,
+
+    #[serde(default)]
+    pub scopes: Vec<String>,
+
+    /// How the client credentials are sent to the token endpoint
+    #[serde(default)]
+    pub client_auth: OAuth2ClientAuth,
+
+    /// Hosts the token is sent to; defaults to those of the start URLs
+    #[serde(default)]
+    pub hosts: Vec<String>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OAuth2ClientAuth {
+    /// HTTP Basic authentication
+    #[default]
+    Basic,
+    /// `client_id` and `client_secret` form fields
+    Body,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProxyPoolConfig {
+    pub proxies: Vec<ProxyConfig>,
+
+    /// Consecutive failures before a proxy is taken out of rotation
+    #[serde(default = "default_proxy_max_failures")]
+    pub max_failures: u32,
+
+    /// Fetched through an evicted proxy to decide whether it can come back
+    #[serde(default = "default_proxy_check_url")]
+    pub check_url: String,
+
+    /// Seconds an evicted proxy waits between health checks
+    #[serde(default = "default_proxy_check_interval")]
+    pub check_interval_secs: u64,
+
+    /// `User-Agent` headers rotated along with the proxies
+    #[serde(default)]
+    pub user_agents: Vec<String>,
+
+    /// Retries responses that look blocked through another proxy and user agent
+    #[serde(default)]
+    pub block_retry: Option<BlockRetryConfig>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BlockRetryConfig {
+    /// Proxy and user agent combinations tried per request, the first one included
+    #[serde(default = "default_block_retry_attempts")]
+    pub max_attempts: u32,
+
+    /// Statuses meaning the request was blocked
+    #[serde(default = "default_block_statuses")]
+    pub statuses: Vec<u16>,
+
+    /// Text in an HTML body meaning a captcha or block page was served, matched ignoring case
+    #[serde(default = "default_block_markers")]
+    pub body_markers: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProxyConfig {
+    /// `http://`, `https://`, `socks5://` or `socks5h://` (DNS resolved by the proxy)
+    pub url: String,
+
+    #[serde(default)]
+    pub username: Option<String>,
+
+    #[serde(default)]
+    pub password: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct HtmlCleanupConfig {
+    /// Remove `<script>` elements and their contents
+    #[serde(default)]
+    pub strip_scripts: bool,
+
+    /// Remove `<style>` elements and their contents
+    #[serde(default)]
+    pub strip_styles: bool,
+
+    /// Remove `<!-- ... -->` comments
+    #[serde(default)]
+    pub strip_comments: bool,
+
+    /// Remove inline `on*="..."` event handler attributes
+    #[serde(default)]
+    pub strip_event_handlers: bool,
+
+    /// Only parse the bytes in `[start, end)` of the document
+    #[serde(default)]
+    pub byte_range: Option<(usize, usize)>,
+}
+
+fn default_concurrency() -> usize {
+    2
+}
+
+fn default_delay() -> Delay {
+    Delay::Fixed(500)
+}
+
+fn default_table_name() -> String {
+    "scraped_data".to_string()
+}
+
+fn default_nested_separator() -> String {
+    ".".to_string()
+}
+
+fn default_busy_timeout_ms() -> u64 {
+    5000
+}
+
+fn default_cache_size() -> i64 {
+    // 64 MiB
+    -65536
+}
+
+fn default_max_redirects() -> usize {
+    10
+}
+
+fn default_max_retries() -> u32 {
+    2
+}
+
+fn default_retry_backoff() -> u64 {
+    1000
+}
+
+fn default_log_max_size_mb() -> u64 {
+    10
+}
+
+fn default_log_max_files() -> usize {
+    5
+}
+
+fn default_dns_ttl() -> u64 {
+    300
+}
+
+fn default_encodings() -> Vec<ContentEncoding> {
+    vec![ContentEncoding::Gzip, ContentEncoding::Br, ContentEncoding::Zstd]
+}
+
+fn default_max_body_bytes() -> usize {
+    32 * 1024 * 1024
+}
+
+fn default_expected_items() -> usize {
+    1_000_000
+}
+
+fn default_fp_rate() -> f64 {
+    0.001
+}
+
+fn default_string_to_sign() -> String {
+    "{method}\n{path}\n{timestamp}".to_string()
+}
+
+fn default_signature_header() -> String {
+    "X-Signature".to_string()
+}
+
+fn default_signature_value() -> String {
+    "{signature}".to_string()
+}
+
+fn default_items_per_object() -> usize {
+    10_000
+}
+
+fn default_output_batch_size() -> usize {
+    100
+}
+
+fn default_output_flush_interval() -> u64 {
+    1000
+}
+
+fn default_proxy_max_failures() -> u32 {
+    3
+}
+
+fn default_render_timeout() -> u64 {
+    30
+}
+
+fn default_block_retry_attempts() -> u32 {
+    3
+}
+
+fn default_block_statuses() -> Vec<u16> {
+    vec![403, 429]
+}
+
+fn default_block_markers() -> Vec<String> {
+    vec!["captcha".to_string()]
+}
+
+fn default_block_page_patterns() -> Vec<String> {
+    ["unusual traffic", "are you a robot", "captcha"]
+        .iter()
+        .map(|pattern| pattern.to_string())
+        .collect()
+}
+
+fn default_proxy_check_url() -> String {
+    "https://www.google.com/generate_204".to_string()
+}
+
+fn default_proxy_check_interval() -> u64 {
+    60
+}
+
+fn default_timezone() -> String {
+    "UTC".to_string()
+}
+
+fn default_frontier_path() -> String {
+    "frontier.db".to_string()
+}
+
+fn default_near_duplicate_threshold() -> f64 {
+    0.9
+}
+
+fn default_external_links_path() -> PathBuf {
+    PathBuf::from("external_links.csv")
+}
+
+fn default_wayback_endpoint() -> String {
+    "https://web.archive.org".to_string()
+}
+
+fn default_warmup_method() -> String {
+    "GET".to_string()
+}
+
+fn default_feeds_url_field() -> String {
+    "url".to_string()
+}
+
+fn default_frontier_window() -> usize {
+    1000
+}
+
+fn default_coverage_max_drop() -> f64 {
+    20.0
+}
+
+fn default_coverage_min_pages() -> u64 {
+    10
+}
+
+fn default_state_db() -> String {
+    "crawl_state.db".to_string()
+}
+
+pub fn default_page_cache() -> String {
+    "page_cache.db".to_string()
+}
+
+fn default_page_cache_ttl_secs() -> u64 {
+    86400
+}
+
+fn default_true() -> bool {
+    true
+}
+
+/// Accepts either a single string or a list of strings.
+fn one_or_many<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<String>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum OneOrMany {
+        One(String),
+        Many(Vec<String>),
+    }
+
+    Ok(match OneOrMany::deserialize(deserializer)? {
+        OneOrMany::One(value) => vec![value],
+        OneOrMany::Many(values) => values,
+    })
 }
@@ -0,0 +1,71 @@
+//! Times of day a crawl may run, for sites that only allow off-peak crawling.
+
+use crate::config::schema::CrawlWindowConfig;
+use crate::error::{Error, Result};
+use chrono::{DateTime, NaiveTime, Utc};
+use chrono_tz::Tz;
+use std::fmt;
+use std::time::Duration;
+
+const MILLIS_PER_DAY: i64 = 24 * 60 * 60 * 1000;
+
+#[derive(Debug, Clone, Copy)]
+pub struct CrawlWindow {
+    start: NaiveTime,
+    end: NaiveTime,
+    timezone: Tz,
+}
+
+impl CrawlWindow {
+    pub fn new(config: &CrawlWindowConfig) -> Result<Self> {
+        let time = |value: &str| {
+            NaiveTime::parse_from_str(value, "%H:%M").map_err(|e| {
+                Error::Config(format!("Invalid crawl_window time '{}' (expected HH:MM): {}", value, e))
+            })
+        };
+        let timezone = config.timezone.parse::<Tz>().map_err(|e| {
+            Error::Config(format!("Invalid crawl_window timezone '{}': {}", config.timezone, e))
+        })?;
+        Ok(Self {
+            start: time(&config.start)?,
+            end: time(&config.end)?,
+            timezone,
+        })
+    }
+
+    /// Whether crawling is allowed at `now`. A window whose start equals its
+    /// end is always open.
+    pub fn is_open(&self, now: DateTime<Utc>) -> bool {
+        let time = self.local_time(now);
+        if self.start <= self.end {
+            self.start == self.end || (self.start <= time && time < self.end)
+        } else {
+            // Spans midnight, e.g. 22:00-06:00
+            time >= self.start || time < self.end
+        }
+    }
+
+    /// Time until the window next opens (if closed) or closes (if open).
+    pub fn until_change(&self, now: DateTime<Utc>) -> Duration {
+        let target = if self.is_open(now) { self.end } else { self.start };
+        let millis = (target - self.local_time(now)).num_milliseconds().rem_euclid(MILLIS_PER_DAY);
+        // An always-open window never changes; check again in a day
+        Duration::from_millis(if millis == 0 { MILLIS_PER_DAY } else { millis } as u64)
+    }
+
+    fn local_time(&self, now: DateTime<Utc>) -> NaiveTime {
+        now.with_timezone(&self.timezone).time()
+    }
+}
+
+impl fmt::Display for CrawlWindow {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}-{} {}",
+            self.start.format("%H:%M"),
+            self.end.format("%H:%M"),
+            self.timezone
+        )
+    }
+}
@@ -1,13 +1,40 @@
+use crate::compare::{self, FieldChange};
+use crate::config::schema::{CoverageAlertConfig, DedupConfig, LimitsConfig, OutputBatchConfig, RetryConfig};
+use crate::crawl_window::CrawlWindow;
+use crate::events::{CrawlEvent, EventBus, EventLog, EventRecord};
+use crate::error::{Error, Result};
+use crate::frontier::{DiskQueue, Frontier, FrontierSnapshot};
+use crate::memory_budget::MemoryBudget;
 use crate::spider::Spider;
 use crate::metrics::collector::MetricsCollector;
 use crate::metrics::snapshot::MetricsSnapshot;
+use crate::notify::WebhookNotifier;
+use crate::rate_limit::RateLimiter;
+use crate::recurring::RecurringCrawl;
+use crate::report::{self, CrawlReport, EndReason, FailedUrl, PhaseDurations};
+use crate::run_id::new_run_id;
+use crate::shard::Shard;
+use crate::state::StateDb;
+use crate::visited::VisitedSet;
 use futures::stream::StreamExt;
-use std::sync::{Arc, atomic::{AtomicUsize, Ordering}};
-use std::time::Duration;
-use tokio::sync::{mpsc, Barrier, watch, Mutex};
+use serde::Serialize;
+use std::path::PathBuf;
+use std::sync::{Arc, OnceLock, atomic::{AtomicU64, AtomicUsize, Ordering}};
+use std::time::{Duration, Instant};
+use tokio::sync::broadcast::error::RecvError;
+use tokio::sync::{broadcast, mpsc, watch, Mutex, Semaphore};
 use tokio::time::sleep;
+use url::Url;
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// Events buffered per subscriber before slow ones start missing events.
+const EVENT_CAPACITY: usize = 1024;
+/// Minimum time between snapshots sent by [`CrawlerEngine::watch_metrics`].
+const WATCH_INTERVAL: Duration = Duration::from_millis(100);
+/// Longest wait between crawl window checks, so clock and DST changes are noticed.
+const WINDOW_CHECK_INTERVAL: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
 pub enum CrawlerState {
     Idle,
     Running,
@@ -17,10 +44,37 @@ pub enum CrawlerState {
 
 pub struct CrawlerEngine {
     delay: Duration,
+    jitter: Duration,
     concurrency: usize,
     metrics: Arc<MetricsCollector>,
     state: Arc<Mutex<CrawlerState>>,
     state_watcher: watch::Sender<CrawlerState>,
+    events: EventBus,
+    shard: Option<Shard>,
+    seed_urls: Option<Vec<String>>,
+    event_log: Option<Arc<EventLog>>,
+    retry: Option<RetryConfig>,
+    failure_report: Option<PathBuf>,
+    failures: Arc<std::sync::Mutex<Vec<FailedUrl>>>,
+    limits: LimitsConfig,
+    sample: Option<f64>,
+    dedup: DedupConfig,
+    disk_frontier: Option<DiskQueue>,
+    crawl_window: Option<CrawlWindow>,
+    state_db: Option<StateDb>,
+    skip_fresh_pages: bool,
+    run_id: Option<String>,
+    notifier: Option<Arc<WebhookNotifier>>,
+    shutdown_grace: Option<Duration>,
+    memory_limit_mb: Option<u64>,
+    output_batch: Option<OutputBatchConfig>,
+    request_slots: Option<Arc<Semaphore>>,
+    rate_limiter: Option<Arc<RateLimiter>>,
+    coverage_alert: Option<CoverageAlertConfig>,
+    /// Fields whose coverage dropped in the last run, see [`CrawlerEngine::coverage_drift`]
+    coverage_drift: Arc<std::sync::Mutex<Vec<FieldChange>>>,
+    /// The frontier of the current or last run, see [`CrawlerEngine::frontier_snapshot`]
+    frontier: Arc<std::sync::Mutex<Option<Frontier>>>,
 }
 
 impl CrawlerEngine {
@@ -29,144 +83,1022 @@ impl CrawlerEngine {
 
         Self {
             delay,
+            jitter: Duration::ZERO,
             concurrency,
             metrics: metrics.unwrap_or_else(|| Arc::new(MetricsCollector::new())),
             state: Arc::new(Mutex::new(CrawlerState::Idle)),
             state_watcher: state_tx,
+            events: EventBus::new(EVENT_CAPACITY),
+            shard: None,
+            seed_urls: None,
+            event_log: None,
+            retry: None,
+            failure_report: None,
+            failures: Arc::new(std::sync::Mutex::new(Vec::new())),
+            limits: LimitsConfig::default(),
+            sample: None,
+            dedup: DedupConfig::default(),
+            disk_frontier: None,
+            crawl_window: None,
+            state_db: None,
+            skip_fresh_pages: false,
+            run_id: None,
+            notifier: None,
+            shutdown_grace: None,
+            memory_limit_mb: None,
+            output_batch: None,
+            request_slots: None,
+            rate_limiter: None,
+            coverage_alert: None,
+            coverage_drift: Arc::new(std::sync::Mutex::new(Vec::new())),
+            frontier: Arc::new(std::sync::Mutex::new(None)),
         }
     }
 
-    pub async fn run(&self, spider: Arc<dyn Spider>) {
+    /// Adds a random extra delay of up to `jitter` after each request, so requests
+    /// don't arrive at a regular cadence.
+    pub fn with_jitter(mut self, jitter: Duration) -> Self {
+        self.jitter = jitter;
+        self
+    }
+
+    /// Restricts this engine to the URLs owned by `shard`.
+    pub fn with_shard(mut self, shard: Shard) -> Self {
+        self.shard = Some(shard);
+        self
+    }
+
+    /// Seeds the crawl with `urls` instead of the spider's `start_urls`.
+    pub fn with_seed_urls(mut self, urls: Vec<String>) -> Self {
+        self.seed_urls = Some(urls);
+        self
+    }
+
+    /// Records the outcome of every request to an NDJSON event log.
+    pub fn with_event_log(mut self, event_log: EventLog) -> Self {
+        self.event_log = Some(Arc::new(event_log));
+        self
+    }
+
+    /// Retries requests that fail with a retryable error, with exponential backoff.
+    pub fn with_retry(mut self, retry: RetryConfig) -> Self {
+        self.retry = Some(retry);
+        self
+    }
+
+    /// Writes a CSV of the URLs that failed to `path` when the run ends.
+    pub fn with_failure_report(mut self, path: impl Into<PathBuf>) -> Self {
+        self.failure_report = Some(path.into());
+        self
+    }
+
+    /// Stops the crawl once `max_pages` pages were fetched or `max_items` items emitted.
+    pub fn with_limits(mut self, limits: LimitsConfig) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// Crawls each discovered URL with probability `ratio`, skipping the rest.
+    /// Start URLs are always crawled.
+    pub fn with_sample(mut self, ratio: f64) -> Self {
+        self.sample = Some(ratio.clamp(0.0, 1.0));
+        self
+    }
+
+    /// Chooses how queued URLs are remembered, e.g. a Bloom filter for very large crawls.
+    pub fn with_dedup(mut self, dedup: DedupConfig) -> Self {
+        self.dedup = dedup;
+        self
+    }
+
+    /// Keeps queued URLs in `queue` instead of memory, resuming the unfinished
+    /// crawl stored there if any.
+    pub fn with_disk_frontier(mut self, queue: DiskQueue) -> Self {
+        self.disk_frontier = Some(queue);
+        self
+    }
+
+    /// Pauses the crawl outside `window` and resumes it inside.
+    pub fn with_crawl_window(mut self, window: CrawlWindow) -> Self {
+        self.crawl_window = Some(window);
+        self
+    }
+
+    /// Records each run in `state`. With `skip_fresh_pages`, URLs fetched by an
+    /// earlier run whose copy is still fresh are skipped.
+    pub fn with_state(mut self, state: StateDb, skip_fresh_pages: bool) -> Self {
+        self.state_db = Some(state);
+        self.skip_fresh_pages = skip_fresh_pages;
+        self
+    }
+
+    /// Uses `run_id` for the next run instead of generating a new ULID, e.g. when
+    /// it was already used to name the output.
+    pub fn with_run_id(mut self, run_id: impl Into<String>) -> Self {
+        self.run_id = Some(run_id.into());
+        self
+    }
+
+    /// Posts a summary of each run to a webhook once it finished or was interrupted.
+    pub fn with_notifier(mut self, notifier: WebhookNotifier) -> Self {
+        self.notifier = Some(Arc::new(notifier));
+        self
+    }
+
+    /// Compares the field coverage of each run with the previous run in the
+    /// state database, warning (and notifying) when a field's dropped too far.
+    pub fn with_coverage_alert(mut self, alert: CoverageAlertConfig) -> Self {
+        self.coverage_alert = Some(alert);
+        self
+    }
+
+    /// On Ctrl-C or SIGTERM, aborts the requests still in flight after `grace`
+    /// instead of waiting for them, so the output is flushed before the process
+    /// gets killed.
+    pub fn with_shutdown_grace(mut self, grace: Duration) -> Self {
+        self.shutdown_grace = Some(grace);
+        self
+    }
+
+    /// Keeps page bodies in flight and items waiting for the output under
+    /// `limit_mb` megabytes, pausing fetches and spilling items to disk beyond it.
+    pub fn with_memory_limit(mut self, limit_mb: u64) -> Self {
+        self.memory_limit_mb = Some(limit_mb);
+        self
+    }
+
+    /// Hands items to the output in batches of `batch.size`, writing partial
+    /// batches and flushing the output every `batch.flush_interval_ms`.
+    pub fn with_output_batch(mut self, batch: OutputBatchConfig) -> Self {
+        self.output_batch = Some(batch);
+        self
+    }
+
+    /// Makes every request hold one of the permits of `slots`. Engines sharing
+    /// it stay within one limit of requests in flight, whatever their concurrency.
+    pub fn with_request_slots(mut self, slots: Arc<Semaphore>) -> Self {
+        self.request_slots = Some(slots);
+        self
+    }
+
+    /// Waits for `limiter` before every request, redirect hops included.
+    /// Engines sharing it stay within one rate per host, on top of each
+    /// engine's own delay.
+    pub fn with_rate_limiter(mut self, limiter: Arc<RateLimiter>) -> Self {
+        self.rate_limiter = Some(limiter);
+        self
+    }
+
+    /// Runs `spider` until it runs out of URLs, reaches a limit or is stopped,
+    /// and reports how the run went. Fails only if the frontier can't be opened.
+    pub async fn run(&self, spider: Arc<dyn Spider>) -> Result<CrawlReport> {
+        let run_id = self.run_id.clone().unwrap_or_else(new_run_id);
+        self.crawl(spider, run_id).await
+    }
+
+    /// Runs `spider` now and then again every `interval` from the start of the
+    /// previous run, plus a random delay of up to `jitter`, until the returned
+    /// handle stops it. A run taking longer than `interval` is followed by the
+    /// next one right away.
+    ///
+    /// Every run gets a new run ID and is reported through
+    /// [`RecurringCrawl::next_report`]. The spider is reused, so it is closed
+    /// after every run and should be ready to write again in `on_start`, as
+    /// [`GenericSpider`](crate::GenericSpider) is. The engine's metrics start
+    /// over with every run, so each report covers its own run only.
+    pub fn run_every(self, spider: Arc<dyn Spider>, interval: Duration, jitter: Duration) -> RecurringCrawl {
+        RecurringCrawl::start(self, spider, interval, jitter)
+    }
+
+    /// Runs `spider` to the end with the given run ID, returning its report.
+    pub(crate) async fn crawl(&self, spider: Arc<dyn Spider>, run_id: String) -> Result<CrawlReport> {
+        let started = Instant::now();
+        log::info!(run_id = run_id.as_str(); "Run {} of {}", run_id, spider.name());
+        self.metrics.set_run_id(&run_id);
+        if let Some(state) = &self.state_db
+            && let Err(e) = state.start_run(&run_id, &spider.name()).await
+        {
+            log::warn!("Failed to record the run: {}", e);
+        }
+
+        // The failures of an earlier run on this engine belong to its report
+        if let Ok(mut failures) = self.failures.lock() {
+            failures.clear();
+        }
+
+        let visited = VisitedSet::new(&self.dedup);
+        let (frontier, urls_stream, resumed) = match &self.disk_frontier {
+            Some(queue) => match Frontier::disk(queue.clone(), visited).await {
+                Ok(opened) => opened,
+                Err(e) => {
+                    log::error!("Failed to open the frontier: {}", e);
+                    return Err(e);
+                }
+            },
+            None => {
+                let (frontier, urls_stream) = Frontier::memory(visited);
+                (frontier, urls_stream, 0)
+            }
+        };
+
+        if let Ok(mut current) = self.frontier.lock() {
+            *current = Some(frontier.clone());
+        }
         self.set_state(CrawlerState::Running).await;
+        if let Some(limiter) = &self.rate_limiter {
+            spider.use_rate_limiter(limiter.clone());
+        }
+        spider.on_start().await;
+
+        // Outside the crawl window, start paused so no request goes out
+        let paused_by_window = match self.crawl_window {
+            Some(window) if !window.is_open(chrono::Utc::now()) => {
+                log::info!("Outside the crawl window {}, waiting for it to open", window);
+                self.set_state(CrawlerState::Paused).await;
+                true
+            }
+            _ => false,
+        };
 
-        let (urls_tx, urls_rx) = mpsc::channel(1000);
-        let (items_tx, items_rx) = mpsc::channel(100);
+        if let Some(event_log) = &self.event_log {
+            let started = EventRecord::RunStarted {
+                run_id: run_id.clone(),
+                spider: spider.name(),
+                timestamp: chrono::Utc::now().to_rfc3339(),
+            };
+            if let Err(e) = event_log.record(&started) {
+                log::warn!("Failed to write event log: {}", e);
+            }
+        }
+
+        let (items_tx, mut items_rx) = mpsc::channel(100);
+        let budget = self
+            .memory_limit_mb
+            .map(|limit_mb| Arc::new(MemoryBudget::new(limit_mb, self.metrics.clone(), &run_id)));
 
         let active_spiders = Arc::new(AtomicUsize::new(0));
-        let barrier = Arc::new(Barrier::new(3)); // Main + Processor + Scraper
 
-        // Seed initial URLs
-        let initial_urls = spider.start_urls();
-        let urls_tx_seed = urls_tx.clone();
+        // Seed initial URLs, unless an unfinished crawl is resumed
+        let initial_urls = if resumed > 0 {
+            if let Some(queue) = &self.disk_frontier {
+                log::info!("Resuming {} unfinished URLs from {}", resumed, queue.path());
+            }
+            for _ in 0..resumed {
+                self.metrics.increment_urls_queued();
+            }
+            Vec::new()
+        } else {
+            self.seed_urls.clone().unwrap_or_else(|| spider.start_urls())
+        };
+        let initial_urls: Vec<String> = match self.shard {
+            Some(shard) => {
+                let total = initial_urls.len();
+                let owned: Vec<String> = initial_urls.into_iter().filter(|url| shard.owns(url)).collect();
+                log::info!("Shard {} owns {} of {} start URLs", shard, owned.len(), total);
+                owned
+            }
+            None => initial_urls,
+        };
+        let setup_done = Instant::now();
+        let frontier_seed = frontier.clone();
         let metrics_seed = self.metrics.clone();
+        let spider_seed = spider.clone();
         tokio::spawn(async move {
             for url in initial_urls {
-                let _ = urls_tx_seed.send(url).await;
+                metrics_seed.record_domain(&url, |d| d.urls_queued += 1);
+                let fingerprint = spider_seed.fingerprint(&url);
+                frontier_seed.seed(url, fingerprint).await;
                 metrics_seed.increment_urls_queued();
             }
+            frontier_seed.seeded();
         });
 
         // Drop local senders in main thread
-        drop(urls_tx);
         let items_tx_scraper = items_tx.clone();
         drop(items_tx);
 
         // 1. Processor Task
         let spider_clone = spider.clone();
         let metrics_clone = self.metrics.clone();
-        let barrier_clone = barrier.clone();
-        tokio::spawn(async move {
-            tokio_stream::wrappers::ReceiverStream::new(items_rx)
-                .for_each(|item| async {
-                    metrics_clone.increment_items_processed();
-                    if let Err(e) = spider_clone.process(item).await {
-                        log::error!("Error processing item: {}", e);
-                        metrics_clone.increment_items_failed();
+        let budget_processor = budget.clone();
+        let batch_size = self.output_batch.map_or(1, |batch| batch.size.max(1));
+        let mut flush_timer = self
+            .output_batch
+            .map(|batch| tokio::time::interval(Duration::from_millis(batch.flush_interval_ms.max(1))));
+        let writer = BatchWriter::new(spider.clone(), self.metrics.clone(), self.events.clone());
+        let processor = tokio::spawn(async move {
+            let mut batch = Vec::with_capacity(batch_size);
+            loop {
+                let next = async {
+                    match &budget_processor {
+                        Some(budget) => budget.next(&mut items_rx).await,
+                        None => items_rx.recv().await,
                     }
-                }).await;
-            
+                };
+                let flush_due = async {
+                    match flush_timer.as_mut() {
+                        Some(timer) => {
+                            timer.tick().await;
+                        }
+                        None => std::future::pending().await,
+                    }
+                };
+                tokio::select! {
+                    item = next => {
+                        let Some(item) = item else { break };
+                        metrics_clone.increment_items_processed();
+                        batch.push(item);
+                        if batch.len() >= batch_size {
+                            writer.write(&mut batch).await;
+                        }
+                    }
+                    _ = flush_due => {
+                        writer.write(&mut batch).await;
+                        if let Err(e) = spider_clone.flush().await {
+                            log::error!("Error flushing the output: {}", e);
+                        }
+                    }
+                }
+            }
+            writer.write(&mut batch).await;
+            writer.finish().await;
+
             let _ = spider_clone.close().await;
-            barrier_clone.wait().await;
         });
 
         // 2. Scraper Task
         let spider_clone = spider.clone();
-        let barrier_clone = barrier.clone();
         let delay = self.delay;
+        let jitter = self.jitter;
         let concurrency = self.concurrency;
+        let request_slots = self.request_slots.clone();
+        let rate_limiter = self.rate_limiter.clone();
         let active_count = active_spiders.clone();
         let metrics_clone = self.metrics.clone();
+        let event_log = self.event_log.clone();
+        let failures = self.failures.clone();
+        let limits = self.limits;
+        let sample = self.sample;
+        let fresh_pages = self.state_db.clone().filter(|_| self.skip_fresh_pages);
+        let item_run_id = serde_json::Value::String(run_id.clone());
+        let pages_started = Arc::new(AtomicU64::new(0));
+        let items_sent = Arc::new(AtomicU64::new(0));
+        // The first limit reached ends the crawl
+        let limit_reached: Arc<OnceLock<EndReason>> = Arc::new(OnceLock::new());
+        let limit_ended = limit_reached.clone();
+        // When the request budget renews, while the crawl waits for it
+        let (budget_pause, _) = watch::channel(None);
+        let budget_pause = Arc::new(budget_pause);
+        let budget_followed = budget_pause.clone();
+        let shard = self.shard;
+        let bus = self.events.clone();
+        let state_rx = self.watch_state();
+        let retry = self.retry.clone().unwrap_or(RetryConfig {
+            max_retries: 0,
+            backoff_ms: 0,
+        });
 
-        tokio::spawn(async move {
-            let urls_stream = tokio_stream::wrappers::ReceiverStream::new(urls_rx);
-            urls_stream.for_each_concurrent(concurrency, |url| {
-                let spider = spider_clone.clone();
-                let items_tx = items_tx_scraper.clone();
-                let active = active_count.clone();
-                let metrics = metrics_clone.clone();
-
-                async move {
-                    active.fetch_add(1, Ordering::SeqCst);
-                    metrics.increment_active_workers();
-
-                    let start_time = std::time::Instant::now();
-                    let result = spider.scrape(url).await;
-                    let duration = start_time.elapsed();
-
-                    match result {
-                        Ok((items, _new_urls)) => {
-                            metrics.record_success(duration);
-                            metrics.increment_urls_processed();
-                            for item in items {
-                                metrics.increment_items_extracted();
-                                let _ = items_tx.send(item).await;
+        let scraper = tokio::spawn(async move {
+            let stop = limit_reached.clone();
+            let stop_state = state_rx.clone();
+            urls_stream
+                .take_while(move |_| {
+                    let stopped = *stop_state.borrow() == CrawlerState::Stopped;
+                    futures::future::ready(stop.get().is_none() && !stopped)
+                })
+                .for_each_concurrent(concurrency, |url| {
+                    let spider = spider_clone.clone();
+                    let items_tx = items_tx_scraper.clone();
+                    let active = active_count.clone();
+                    let metrics = metrics_clone.clone();
+                    let event_log = event_log.clone();
+                    let retry = retry.clone();
+                    let failures = failures.clone();
+                    let pages_started = pages_started.clone();
+                    let items_sent = items_sent.clone();
+                    let limit_reached = limit_reached.clone();
+                    let frontier = frontier.clone();
+                    let bus = bus.clone();
+                    let mut state = state_rx.clone();
+                    let fresh_pages = fresh_pages.clone();
+                    let run_id = item_run_id.clone();
+                    let budget = budget.clone();
+                    let request_slots = request_slots.clone();
+                    let rate_limiter = rate_limiter.clone();
+                    let budget_pause = budget_pause.clone();
+
+                    async move {
+                        if let Some(fresh_pages) = &fresh_pages {
+                            match fresh_pages.is_fresh(&url, chrono::Utc::now()).await {
+                                Ok(true) => {
+                                    log::debug!("Skipping {}, the last copy is still fresh", url);
+                                    metrics.increment_fresh_pages_skipped();
+                                    frontier.done(&url).await;
+                                    return;
+                                }
+                                Ok(false) => {}
+                                Err(e) => log::warn!("Failed to check the freshness of {}: {}", url, e),
                             }
-                            // In the future, we can send new_urls back to urls_tx here
                         }
-                        Err(e) => {
-                            metrics.record_failure(duration);
-                            log::error!("Failed to scrape: {}", e);
+                        if let Some(max) = limits.max_pages
+                            && pages_started.fetch_add(1, Ordering::SeqCst) >= max
+                        {
+                            if limit_reached.set(EndReason::PageLimit).is_ok() {
+                                log::info!("Reached page limit of {}", max);
+                            }
+                            frontier.abandon(&url);
+                            return;
+                        }
+                        if limit_reached.get().is_some() {
+                            frontier.abandon(&url);
+                            return;
                         }
-                    }
 
-                    sleep(delay).await;
-                    active.fetch_sub(1, Ordering::SeqCst);
-                    metrics.decrement_active_workers();
-                }
-            }).await;
+                        if let Some(budget) = &budget {
+                            budget.wait_for_room().await;
+                        }
+                        if !wait_until_runnable(&mut state).await {
+                            frontier.abandon(&url);
+                            return;
+                        }
+
+                        active.fetch_add(1, Ordering::SeqCst);
+                        metrics.increment_active_workers();
+
+                        let start_time = std::time::Instant::now();
+                        let mut attempt = 0;
+                        let result = loop {
+                            bus.emit(|| CrawlEvent::RequestStarted {
+                                url: url.clone(),
+                                attempt,
+                            });
+                            let scraped = {
+                                // Waiting for the host's turn doesn't hold a slot
+                                if let Some(limiter) = &rate_limiter
+                                    && let Ok(url) = Url::parse(&url)
+                                {
+                                    limiter.acquire(&url).await;
+                                }
+                                let _slot = match &request_slots {
+                                    Some(slots) => slots.acquire().await.ok(),
+                                    None => None,
+                                };
+                                spider.scrape(url.clone()).await
+                            };
+                            match scraped {
+                                Err(e) if e.is_retryable() && attempt < retry.max_retries => {
+                                    let backoff = Duration::from_millis(
+                                        retry.backoff_ms.saturating_mul(1 << attempt.min(16)),
+                                    );
+                                    attempt += 1;
+                                    log::warn!(
+                                        url = url.as_str(),
+                                        attempt = attempt,
+                                        error_kind = e.kind();
+                                        "Retrying {} in {:?} (attempt {} of {}): {}",
+                                        url,
+                                        backoff,
+                                        attempt,
+                                        retry.max_retries,
+                                        e
+                                    );
+                                    metrics.increment_retries();
+                                    sleep(backoff).await;
+                                    // A stopped crawl reports the URL as failed, so it can be resumed
+                                    if !wait_until_runnable(&mut state).await {
+                                        break Err(e);
+                                    }
+                                }
+                                Err(Error::RequestBudget { resumes_at: Some(resumes_at), .. }) => {
+                                    // The crawl pauses until the budget renews, then the request is sent
+                                    budget_pause.send_if_modified(|until| until.replace(resumes_at) != Some(resumes_at));
+                                    let mut renewed = budget_pause.subscribe();
+                                    let resumed = tokio::select! {
+                                        renewed = renewed.wait_for(Option::is_none) => renewed.is_ok(),
+                                        _ = state.wait_for(|state| *state == CrawlerState::Stopped) => false,
+                                    };
+                                    if !resumed || !wait_until_runnable(&mut state).await {
+                                        break Err(Error::Stopped);
+                                    }
+                                }
+                                result => break result,
+                            }
+                        };
+                        let duration = start_time.elapsed();
+
+                        // The request was never sent, so the URL is left for a later run rather than failed
+                        if let Err(e @ (Error::RequestBudget { .. } | Error::Stopped)) = &result {
+                            if let Error::RequestBudget { limit, .. } = e
+                                && limit_reached.set(EndReason::RequestBudget).is_ok()
+                            {
+                                log::warn!("Request budget of {} requests is used up, stopping", limit);
+                            }
+                            active.fetch_sub(1, Ordering::SeqCst);
+                            metrics.decrement_active_workers();
+                            frontier.abandon(&url);
+                            return;
+                        }
+
+                        if let Some(event_log) = &event_log {
+                            let timestamp = chrono::Utc::now().to_rfc3339();
+                            let duration_ms = duration.as_millis() as u64;
+                            let event = match &result {
+                                Ok((items, _)) => EventRecord::RequestSucceeded {
+                                    url: url.clone(),
+                                    items: items.len(),
+                                    duration_ms,
+                                    timestamp,
+                                },
+                                Err(e) => EventRecord::RequestFailed {
+                                    url: url.clone(),
+                                    error: e.to_string(),
+                                    error_kind: Some(e.kind().to_string()),
+                                    status: e.status(),
+                                    duration_ms,
+                                    timestamp,
+                                },
+                            };
+                            if let Err(e) = event_log.record(&event) {
+                                log::warn!("Failed to write event log: {}", e);
+                            }
+                        }
+
+                        match result {
+                            Ok((items, new_urls)) => {
+                                metrics.record_success(duration);
+                                metrics.increment_urls_processed();
+                                bus.emit(|| CrawlEvent::ResponseReceived {
+                                    url: url.clone(),
+                                    items: items.len(),
+                                    duration_ms: duration.as_millis() as u64,
+                                });
+                                let mut sent = 0;
+                                for mut item in items {
+                                    if let Some(max) = limits.max_items
+                                        && items_sent.fetch_add(1, Ordering::SeqCst) >= max
+                                    {
+                                        if limit_reached.set(EndReason::ItemLimit).is_ok() {
+                                            log::info!("Reached item limit of {}", max);
+                                        }
+                                        break;
+                                    }
+                                    if let serde_json::Value::Object(map) = &mut item {
+                                        map.insert("_run_id".to_string(), run_id.clone());
+                                    }
+                                    metrics.increment_items_extracted();
+                                    metrics.increment_items_awaiting_output();
+                                    sent += 1;
+                                    bus.emit(|| CrawlEvent::ItemExtracted {
+                                        url: url.clone(),
+                                        item: item.clone(),
+                                    });
+                                    match &budget {
+                                        Some(budget) => budget.send(&items_tx, item).await,
+                                        None => {
+                                            let _ = items_tx.send((item, std::time::Instant::now())).await;
+                                        }
+                                    }
+                                }
+                                metrics.record_domain(&url, |d| {
+                                    d.urls_processed += 1;
+                                    d.items_extracted += sent;
+                                });
+
+                                let links = new_urls
+                                    .into_iter()
+                                    .filter(|link| shard.is_none_or(|shard| shard.owns(link)))
+                                    .map(|link| {
+                                        let fingerprint = spider.fingerprint(&link);
+                                        (link, fingerprint)
+                                    })
+                                    .collect();
+                                let priority = spider.link_priority(&url);
+                                for link in frontier.discover(&url, links, priority, sample).await {
+                                    metrics.record_domain(&link, |d| d.urls_queued += 1);
+                                    metrics.increment_urls_queued();
+                                }
+                            }
+                            Err(e) => {
+                                metrics.record_failure(duration);
+                                metrics.record_domain(&url, |d| d.requests_failed += 1);
+                                log::error!(
+                                    url = url.as_str(), error_kind = e.kind(), status = e.status();
+                                    "Failed to scrape: {}", e
+                                );
+                                if let Ok(mut failures) = failures.lock() {
+                                    failures.push(FailedUrl::new(url.clone(), attempt + 1, &e));
+                                }
+                                bus.emit(|| CrawlEvent::Error {
+                                    url: Some(url.clone()),
+                                    kind: e.kind().to_string(),
+                                    message: e.to_string(),
+                                });
+                            }
+                        }
+
+                        sleep(delay + jitter.mul_f64(rand::random::<f64>())).await;
+                        active.fetch_sub(1, Ordering::SeqCst);
+                        metrics.decrement_active_workers();
+                        frontier.done(&url).await;
+                    }
+                }).await;
             
             // CRITICAL: Drop the scraper's item sender so the processor can finish
             drop(items_tx_scraper);
             log::debug!("Scraper task finished.");
-            barrier_clone.wait().await;
         });
 
         // 3. Main loop
-        tokio::select! {
-            _ = tokio::signal::ctrl_c() => {
-                log::info!("Shutting down...");
+        // Aborting the scraper drops its item senders, so the processor still
+        // writes the items already extracted and closes the output
+        let abort_scraper = scraper.abort_handle();
+        let scraped = OnceLock::new();
+        let finished = async {
+            let _ = scraper.await;
+            let _ = scraped.set(Instant::now());
+            let _ = processor.await;
+        };
+        tokio::pin!(finished);
+        let end_reason = tokio::select! {
+            signal = shutdown_signal() => {
+                log::info!("Received {}, shutting down, waiting for in-flight requests...", signal);
                 self.set_state(CrawlerState::Stopped).await;
+                let grace = async {
+                    match self.shutdown_grace {
+                        Some(grace) => sleep(grace).await,
+                        None => std::future::pending().await,
+                    }
+                };
+                tokio::select! {
+                    _ = &mut finished => {}
+                    _ = grace => {
+                        log::warn!(
+                            "Requests still in flight after the {}s grace period, aborting them",
+                            self.shutdown_grace.unwrap_or_default().as_secs()
+                        );
+                        abort_scraper.abort();
+                        (&mut finished).await;
+                    }
+                    signal = shutdown_signal() => {
+                        log::warn!("Received {} again, aborting requests in flight", signal);
+                        abort_scraper.abort();
+                        (&mut finished).await;
+                    }
+                }
+                EndReason::Interrupted
+            }
+            _ = &mut finished => {
+                let stopped = *self.state_watcher.borrow() == CrawlerState::Stopped;
+                log::info!("Crawl {}.", if stopped { "stopped" } else { "finished" });
+                match limit_ended.get() {
+                    _ if stopped => EndReason::Stopped,
+                    Some(limit) => *limit,
+                    None => EndReason::Finished,
+                }
             }
-            _ = barrier.wait() => {
-                log::info!("Crawl finished.");
+            _ = self.follow_crawl_window(paused_by_window) => unreachable!("the crawl window is followed until the crawl ends"),
+            _ = self.follow_request_budget(&budget_followed) => unreachable!("the request budget is followed until the crawl ends"),
+        };
+        let output_done = Instant::now();
+        let scraped = scraped.get().copied().unwrap_or(output_done);
+
+        if let Some(event_log) = &self.event_log {
+            let metrics = self.metrics.snapshot();
+            let finished = EventRecord::RunFinished {
+                run_id: run_id.clone(),
+                requests: metrics.requests_total,
+                failures: metrics.requests_failed,
+                items: metrics.items_extracted,
+                duration_ms: (metrics.elapsed_seconds * 1000.0) as u64,
+                timestamp: chrono::Utc::now().to_rfc3339(),
+            };
+            if let Err(e) = event_log.record(&finished).and_then(|_| event_log.flush()) {
+                log::warn!("Failed to write event log: {}", e);
             }
         }
-        
+
+        let failures = self.failed_urls();
+        if let Some(path) = &self.failure_report {
+            match report::write_failed_urls(path, &failures) {
+                Ok(()) if !failures.is_empty() => {
+                    log::warn!("{} URLs failed, see {}", failures.len(), path.display());
+                }
+                Ok(()) => {}
+                Err(e) => log::warn!("Failed to write failure report: {}", e),
+            }
+        }
+
+        let millis = |from: Instant, to: Instant| to.saturating_duration_since(from).as_millis() as u64;
+        let now = Instant::now();
+        let report = CrawlReport {
+            run_id,
+            spider: spider.name(),
+            interrupted: matches!(end_reason, EndReason::Stopped | EndReason::Interrupted),
+            end_reason,
+            metrics: self.metrics.snapshot(),
+            failures,
+            durations: PhaseDurations {
+                setup_ms: millis(started, setup_done),
+                crawl_ms: millis(setup_done, scraped),
+                output_ms: millis(scraped, output_done),
+                finish_ms: millis(output_done, now),
+                total_ms: millis(started, now),
+            },
+        };
+        if let Some(state) = &self.state_db
+            && let Err(e) = state.finish_run(&report).await
+        {
+            log::warn!("Failed to record the end of the run: {}", e);
+        }
+        if !report.interrupted {
+            self.check_coverage_drift(&report).await;
+        }
+        spider.on_finish(&report).await;
+        if let Some(notifier) = &self.notifier {
+            notifier.notify(&report).await;
+        }
+
         self.set_state(CrawlerState::Stopped).await;
+        Ok(report)
+    }
+
+    async fn check_coverage_drift(&self, report: &CrawlReport) {
+        let (Some(alert), Some(state)) = (&self.coverage_alert, &self.state_db) else {
+            return;
+        };
+        let (previous_run, previous) = match state.previous_run(&report.spider, &report.run_id).await {
+            Ok(Some(previous)) => previous,
+            Ok(None) => {
+                log::info!("No previous run of {} to compare field coverage with", report.spider);
+                return;
+            }
+            Err(e) => {
+                log::warn!("Failed to read the previous run: {}", e);
+                return;
+            }
+        };
+        let drift = compare::coverage_drift(&previous, &report.metrics, alert.max_drop, alert.min_pages);
+        for change in &drift {
+            log::warn!(
+                "Coverage of field `{}` fell from {:.1}% to {:.1}% since run {}",
+                change.field,
+                change.before.unwrap_or_default(),
+                change.after.unwrap_or_default(),
+                previous_run
+            );
+        }
+        if !drift.is_empty()
+            && let Some(notifier) = &self.notifier
+        {
+            notifier.notify_coverage_drift(report, &previous_run, &drift).await;
+        }
+        if let Ok(mut current) = self.coverage_drift.lock() {
+            *current = drift;
+        }
+    }
+
+    /// Fields whose coverage fell too far since the previous run, as of the
+    /// last run with a coverage alert.
+    pub fn coverage_drift(&self) -> Vec<FieldChange> {
+        self.coverage_drift.lock().map(|drift| drift.clone()).unwrap_or_default()
+    }
+
+    /// What is left in the frontier of the current run, with the `next` URLs
+    /// to be crawled; `None` before a run started.
+    pub async fn frontier_snapshot(&self, next: usize) -> Result<Option<FrontierSnapshot>> {
+        let frontier = self.frontier.lock().ok().and_then(|frontier| frontier.clone());
+        match frontier {
+            Some(frontier) => frontier.snapshot(next).await.map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// URLs that failed during the last run, after retries.
+    pub fn failed_urls(&self) -> Vec<FailedUrl> {
+        self.failures.lock().map(|f| f.clone()).unwrap_or_default()
+    }
+
+    /// Starts the metrics over for the next run of a recurring crawl.
+    pub(crate) fn reset_metrics(&self) {
+        self.metrics.reset();
     }
 
     pub fn get_metrics(&self) -> MetricsSnapshot {
         self.metrics.snapshot()
     }
 
+    /// Receives every [`CrawlEvent`] emitted from now on.
+    pub fn subscribe(&self) -> broadcast::Receiver<CrawlEvent> {
+        self.events.subscribe()
+    }
+
+    /// Metrics snapshots, refreshed as crawl events arrive (at most every
+    /// 100ms, and always on state changes).
     pub fn watch_metrics(&self) -> watch::Receiver<MetricsSnapshot> {
         let (tx, rx) = watch::channel(self.metrics.snapshot());
         let metrics = self.metrics.clone();
+        let mut events = self.subscribe();
         tokio::spawn(async move {
-            let mut interval = tokio::time::interval(Duration::from_millis(500));
+            let mut last_sent = std::time::Instant::now();
             loop {
-                interval.tick().await;
-                if tx.send(metrics.snapshot()).is_err() {
-                    break;
+                let force = match events.recv().await {
+                    Ok(event) => matches!(event, CrawlEvent::StateChanged { .. }),
+                    Err(RecvError::Lagged(_)) => false,
+                    Err(RecvError::Closed) => break,
+                };
+                if force || last_sent.elapsed() >= WATCH_INTERVAL {
+                    if tx.send(metrics.snapshot()).is_err() {
+                        break;
+                    }
+                    last_sent = std::time::Instant::now();
                 }
             }
         });
         rx
     }
 
+    /// Follows the engine's state. Workers wait while it is `Paused` and stop
+    /// taking URLs once it is `Stopped`.
+    pub fn watch_state(&self) -> watch::Receiver<CrawlerState> {
+        self.state_watcher.subscribe()
+    }
+
+    /// Pauses the crawl while outside the crawl window and resumes it when the
+    /// window opens. Never returns; a crawl paused by hand stays paused.
+    async fn follow_crawl_window(&self, mut paused_by_window: bool) {
+        let Some(window) = self.crawl_window else {
+            return std::future::pending().await;
+        };
+        loop {
+            let now = chrono::Utc::now();
+            let state = *self.state_watcher.borrow();
+            if window.is_open(now) {
+                if paused_by_window && state == CrawlerState::Paused {
+                    log::info!("Crawl window {} is open, resuming", window);
+                    self.set_state(CrawlerState::Running).await;
+                }
+                paused_by_window = false;
+            } else if state == CrawlerState::Running {
+                log::info!(
+                    "Outside the crawl window {}, pausing for {}m",
+                    window,
+                    window.until_change(now).as_secs().div_ceil(60)
+                );
+                self.set_state(CrawlerState::Paused).await;
+                paused_by_window = true;
+            }
+            sleep(window.until_change(now).min(WINDOW_CHECK_INTERVAL)).await;
+        }
+    }
+
+    /// Pauses the crawl once a request finds the request budget used up, and
+    /// resumes it when the budget renews. Never returns.
+    async fn follow_request_budget(&self, budget_pause: &watch::Sender<Option<chrono::DateTime<chrono::Utc>>>) {
+        let mut paused = budget_pause.subscribe();
+        loop {
+            let Some(until) = paused.wait_for(Option::is_some).await.ok().and_then(|until| *until) else {
+                return std::future::pending().await;
+            };
+            let wait = (until - chrono::Utc::now()).to_std().unwrap_or_default();
+            log::warn!(
+                "Request budget is used up, pausing until {} ({}m)",
+                until.format("%Y-%m-%d %H:%M UTC"),
+                wait.as_secs().div_ceil(60)
+            );
+            let paused_by_budget = *self.state_watcher.borrow() == CrawlerState::Running;
+            if paused_by_budget {
+                self.set_state(CrawlerState::Paused).await;
+            }
+            sleep(wait).await;
+            if paused_by_budget && *self.state_watcher.borrow() == CrawlerState::Paused {
+                log::info!("Request budget renewed, resuming");
+                self.set_state(CrawlerState::Running).await;
+            }
+            budget_pause.send_replace(None);
+        }
+    }
+
     pub async fn set_state(&self, state: CrawlerState) {
         let mut state_guard = self.state.lock().await;
         *state_guard = state;
-        let _ = self.state_watcher.send(state);
+        // Stored even without receivers, so later `watch_state` calls see it
+        self.state_watcher.send_replace(state);
+        self.events.emit(|| CrawlEvent::StateChanged { state });
+    }
+}
+
+/// Resolves on Ctrl-C, or on SIGTERM on Unix, with the name of the signal.
+pub(crate) async fn shutdown_signal() -> &'static str {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{SignalKind, signal};
+        match signal(SignalKind::terminate()) {
+            Ok(mut sigterm) => tokio::select! {
+                _ = tokio::signal::ctrl_c() => "Ctrl-C",
+                _ = sigterm.recv() => "SIGTERM",
+            },
+            Err(e) => {
+                log::warn!("Failed to listen for SIGTERM: {}", e);
+                let _ = tokio::signal::ctrl_c().await;
+                "Ctrl-C"
+            }
+        }
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = tokio::signal::ctrl_c().await;
+        "Ctrl-C"
+    }
+}
+
+/// Hands batches of items to the spider's output, as many at once as it
+/// accepts. With one at a time, batches are written in order.
+struct BatchWriter {
+    spider: Arc<dyn Spider>,
+    slots: Arc<Semaphore>,
+    concurrency: u32,
+    metrics: Arc<MetricsCollector>,
+    bus: EventBus,
+}
+
+impl BatchWriter {
+    fn new(spider: Arc<dyn Spider>, metrics: Arc<MetricsCollector>, bus: EventBus) -> Self {
+        let concurrency = spider.output_concurrency().max(1);
+        Self {
+            spider,
+            slots: Arc::new(Semaphore::new(concurrency)),
+            concurrency: concurrency as u32,
+            metrics,
+            bus,
+        }
+    }
+
+    /// Starts writing the items collected in `batch`, with the time each was
+    /// extracted, once an output is free.
+    async fn write(&self, batch: &mut Vec<(serde_json::Value, std::time::Instant)>) {
+        if batch.is_empty() {
+            return;
+        }
+        let Ok(slot) = self.slots.clone().acquire_owned().await else {
+            return;
+        };
+        let (items, extracted_at): (Vec<_>, Vec<_>) = std::mem::take(batch).into_iter().unzip();
+        let (spider, metrics, bus) = (self.spider.clone(), self.metrics.clone(), self.bus.clone());
+        tokio::spawn(async move {
+            let written = write_items(&*spider, items, &metrics, &bus).await;
+            for extracted_at in extracted_at {
+                metrics.record_item_output(extracted_at, written);
+            }
+            drop(slot);
+        });
+    }
+
+    /// Waits for the batches still being written.
+    async fn finish(&self) {
+        let _ = self.slots.acquire_many(self.concurrency).await;
+    }
+}
+
+/// Hands `items` to the spider's output, returning whether they were written.
+/// If that fails, every item counts as failed.
+async fn write_items(spider: &dyn Spider, items: Vec<serde_json::Value>, metrics: &MetricsCollector, bus: &EventBus) -> bool {
+    let count = items.len();
+    match spider.process_batch(items).await {
+        Ok(()) => {
+            for _ in 0..count {
+                bus.emit(|| CrawlEvent::ItemWritten);
+            }
+            true
+        }
+        Err(e) => {
+            if count == 1 {
+                log::error!("Error processing item: {}", e);
+            } else {
+                log::error!("Error processing a batch of {} items: {}", count, e);
+            }
+            for _ in 0..count {
+                metrics.increment_items_failed();
+            }
+            bus.emit(|| CrawlEvent::Error {
+                url: None,
+                kind: e.kind().to_string(),
+                message: e.to_string(),
+            });
+            false
+        }
+    }
+}
+
+/// Waits while the crawler is paused. Returns `false` once it is stopped.
+async fn wait_until_runnable(state: &mut watch::Receiver<CrawlerState>) -> bool {
+    loop {
+        match *state.borrow_and_update() {
+            CrawlerState::Stopped => return false,
+            CrawlerState::Paused => {}
+            CrawlerState::Idle | CrawlerState::Running => return true,
+        }
+        if state.changed().await.is_err() {
+            return false;
+        }
     }
 }
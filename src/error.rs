@@ -1,33 +1,104 @@
 use thiserror::Error;
 
+/// Every failure the crawler can report, grouped by where it happened.
+///
+/// Variants raised while fetching or processing a page carry the URL (or
+/// output sink) involved so log lines are actionable on their own. Use
+/// [`Error::is_retryable`] to decide whether another attempt can help.
 #[derive(Error, Debug)]
 pub enum Error {
-    #[error("IO error: {0}")]
-    Io(#[from] std::io::Error),
+    // Network
+    #[error("DNS lookup for {host} failed: {message}")]
+    Dns { host: String, message: String },
 
-    #[error("Request error: {0}")]
-    Request(#[from] reqwest::Error),
+    #[error("Failed to connect to {url}: {message}")]
+    Connect { url: String, message: String },
 
-    #[error("JSON error: {0}")]
-    Json(#[from] serde_json::Error),
+    #[error("Timed out connecting to {url}")]
+    ConnectTimeout { url: String },
 
-    #[error("YAML error: {0}")]
-    Yaml(#[from] serde_yaml::Error),
+    #[error("Timed out waiting for {url}")]
+    ReadTimeout { url: String },
 
-    #[error("TOML error: {0}")]
-    Toml(#[from] toml::de::Error),
+    #[error("TLS error for {url}: {message}")]
+    Tls { url: String, message: String },
+
+    #[error("Connection to {url} was interrupted: {message}")]
+    Transfer { url: String, message: String },
+
+    #[error("Request to {url} failed: {message}")]
+    Request { url: String, message: String },
+
+    #[error("Invalid URL {url}: {message}")]
+    InvalidUrl { url: String, message: String },
+
+    #[error("Redirect error for {url}: {message}")]
+    Redirect { url: String, message: String },
+
+    // Responses
+    #[error("HTTP {status} from {url}")]
+    HttpStatus { url: String, status: u16 },
+
+    #[error("Failed to decode response from {url}: {message}")]
+    Decode { url: String, message: String },
+
+    #[error("Blocked at {url}: {reason}")]
+    Blocked { url: String, reason: String },
+
+    #[error("Response body from {url} exceeds the {limit} byte limit")]
+    BodyTooLarge { url: String, limit: usize },
+
+    #[error("No recorded response for {url}: {message}")]
+    Replay { url: String, message: String },
+
+    #[error("Authentication with {url} failed: {message}")]
+    Auth { url: String, message: String },
+
+    #[error("Request budget of {limit} requests is used up, not sending {url}")]
+    RequestBudget {
+        url: String,
+        limit: u64,
+        /// When the budget starts over, or `None` if the crawl should stop
+        resumes_at: Option<chrono::DateTime<chrono::Utc>>,
+    },
+
+    // Pipeline
+    #[error("Extraction failed for {url}: {message}")]
+    Extraction { url: String, message: String },
+
+    #[error("Output error ({sink}): {message}")]
+    Output { sink: String, message: String },
+
+    #[error("Frontier error ({path}): {message}")]
+    Frontier { path: String, message: String },
+
+    #[error("State database error ({path}): {message}")]
+    State { path: String, message: String },
+
+    #[error("Page cache error ({path}): {message}")]
+    Cache { path: String, message: String },
 
+    #[error("Job queue error ({queue}): {message}")]
+    Queue { queue: String, message: String },
+
+    // Configuration & local files
     #[error("Config error: {0}")]
     Config(String),
 
-    #[error("Extraction error: {0}")]
-    Extraction(String),
-
     #[error("Validation error: {0}")]
     Validation(#[from] validator::ValidationErrors),
 
-    #[error("Database error: {0}")]
-    Database(#[from] sqlx::Error),
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("JSON error: {0}")]
+    Json(#[from] serde_json::Error),
+
+    #[error("YAML error: {0}")]
+    Yaml(#[from] serde_yaml::Error),
+
+    #[error("TOML error: {0}")]
+    Toml(#[from] toml::de::Error),
 
     #[error("Internal error: {0}")]
     Internal(String),
@@ -36,4 +107,151 @@ pub enum Error {
     Stopped,
 }
 
+impl Error {
+    /// Whether the same request might succeed if tried again: transient network
+    /// failures, timeouts, block pages and throttling or gateway status codes.
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            Error::Dns { .. }
+            | Error::Connect { .. }
+            | Error::ConnectTimeout { .. }
+            | Error::ReadTimeout { .. }
+            | Error::Transfer { .. }
+            | Error::Blocked { .. } => true,
+            Error::HttpStatus { status, .. } => {
+                matches!(status, 408 | 425 | 429 | 500 | 502 | 503 | 504)
+            }
+            _ => false,
+        }
+    }
+
+    /// Short, stable name of the error's category, e.g. `read_timeout`.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Error::Dns { .. } => "dns",
+            Error::Connect { .. } => "connect",
+            Error::ConnectTimeout { .. } => "connect_timeout",
+            Error::ReadTimeout { .. } => "read_timeout",
+            Error::Tls { .. } => "tls",
+            Error::Transfer { .. } => "transfer",
+            Error::Request { .. } => "request",
+            Error::InvalidUrl { .. } => "invalid_url",
+            Error::Redirect { .. } => "redirect",
+            Error::HttpStatus { .. } => "http_status",
+            Error::Decode { .. } => "decode",
+            Error::Blocked { .. } => "blocked",
+            Error::BodyTooLarge { .. } => "body_too_large",
+            Error::Replay { .. } => "replay",
+            Error::Auth { .. } => "auth",
+            Error::RequestBudget { .. } => "request_budget",
+            Error::Extraction { .. } => "extraction",
+            Error::Output { .. } => "output",
+            Error::Frontier { .. } => "frontier",
+            Error::State { .. } => "state",
+            Error::Cache { .. } => "cache",
+            Error::Queue { .. } => "queue",
+            Error::Config(_) => "config",
+            Error::Validation(_) => "validation",
+            Error::Io(_) => "io",
+            Error::Json(_) => "json",
+            Error::Yaml(_) => "yaml",
+            Error::Toml(_) => "toml",
+            Error::Internal(_) => "internal",
+            Error::Stopped => "stopped",
+        }
+    }
+
+    /// The URL the error relates to, if any.
+    pub fn url(&self) -> Option<&str> {
+        match self {
+            Error::Connect { url, .. }
+            | Error::ConnectTimeout { url }
+            | Error::ReadTimeout { url }
+            | Error::Tls { url, .. }
+            | Error::Transfer { url, .. }
+            | Error::Request { url, .. }
+            | Error::InvalidUrl { url, .. }
+            | Error::Redirect { url, .. }
+            | Error::HttpStatus { url, .. }
+            | Error::Decode { url, .. }
+            | Error::Blocked { url, .. }
+            | Error::BodyTooLarge { url, .. }
+            | Error::Replay { url, .. }
+            | Error::Auth { url, .. }
+            | Error::RequestBudget { url, .. }
+            | Error::Extraction { url, .. } => Some(url),
+            _ => None,
+        }
+    }
+
+    /// The HTTP status code, for [`Error::HttpStatus`].
+    pub fn status(&self) -> Option<u16> {
+        match self {
+            Error::HttpStatus { status, .. } => Some(*status),
+            _ => None,
+        }
+    }
+}
+
+impl From<reqwest::Error> for Error {
+    fn from(e: reqwest::Error) -> Self {
+        let url = e.url().map(|u| u.to_string()).unwrap_or_default();
+
+        // Errors from our own resolver come back wrapped in a connect error
+        let mut source = std::error::Error::source(&e);
+        while let Some(inner) = source {
+            if let Some(Error::Dns { host, message }) = inner.downcast_ref::<Error>() {
+                return Error::Dns {
+                    host: host.clone(),
+                    message: message.clone(),
+                };
+            }
+            source = inner.source();
+        }
+
+        let message = root_cause(&e);
+        let lower = message.to_ascii_lowercase();
+        if e.is_timeout() {
+            if e.is_connect() {
+                Error::ConnectTimeout { url }
+            } else {
+                Error::ReadTimeout { url }
+            }
+        } else if e.is_connect() {
+            if lower.contains("certificate") || lower.contains("tls") || lower.contains("handshake") {
+                Error::Tls { url, message }
+            } else if lower.contains("dns") {
+                Error::Dns {
+                    host: e.url().and_then(|u| u.host_str()).unwrap_or_default().to_string(),
+                    message,
+                }
+            } else {
+                Error::Connect { url, message }
+            }
+        } else if let Some(status) = e.status() {
+            Error::HttpStatus {
+                url,
+                status: status.as_u16(),
+            }
+        } else if e.is_body() {
+            Error::Transfer { url, message }
+        } else if e.is_decode() {
+            Error::Decode { url, message }
+        } else if e.is_redirect() {
+            Error::Redirect { url, message }
+        } else {
+            Error::Request { url, message }
+        }
+    }
+}
+
+/// The innermost error message, which is usually the most specific one.
+fn root_cause(e: &(dyn std::error::Error + 'static)) -> String {
+    let mut current = e;
+    while let Some(source) = current.source() {
+        current = source;
+    }
+    current.to_string()
+}
+
 pub type Result<T> = std::result::Result<T, Error>;
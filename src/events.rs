@@ -0,0 +1,195 @@
+use crate::crawler::CrawlerState;
+use crate::error::{Error, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{HashMap, HashSet};
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::Path;
+use std::sync::Mutex;
+use tokio::sync::broadcast;
+
+/// One line of the NDJSON event log.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum EventRecord {
+    RunStarted {
+        run_id: String,
+        spider: String,
+        timestamp: String,
+    },
+    RunFinished {
+        run_id: String,
+        requests: u64,
+        failures: u64,
+        items: u64,
+        duration_ms: u64,
+        timestamp: String,
+    },
+    RequestSucceeded {
+        url: String,
+        items: usize,
+        duration_ms: u64,
+        timestamp: String,
+    },
+    RequestFailed {
+        url: String,
+        error: String,
+        /// Category of the error, see [`crate::error::Error::kind`]
+        #[serde(default)]
+        error_kind: Option<String>,
+        #[serde(default)]
+        status: Option<u16>,
+        duration_ms: u64,
+        timestamp: String,
+    },
+}
+
+impl EventRecord {
+    /// The requested URL, for request events.
+    pub fn url(&self) -> Option<&str> {
+        match self {
+            EventRecord::RequestSucceeded { url, .. } | EventRecord::RequestFailed { url, .. } => {
+                Some(url)
+            }
+            _ => None,
+        }
+    }
+
+    pub fn is_failure(&self) -> bool {
+        matches!(self, EventRecord::RequestFailed { .. })
+    }
+}
+
+/// Appends structured events to an NDJSON file, one record per line.
+pub struct EventLog {
+    writer: Mutex<BufWriter<File>>,
+}
+
+impl EventLog {
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)?;
+        Ok(Self {
+            writer: Mutex::new(BufWriter::new(file)),
+        })
+    }
+
+    pub fn record(&self, event: &EventRecord) -> Result<()> {
+        let mut writer = self
+            .writer
+            .lock()
+            .map_err(|e| Error::Internal(e.to_string()))?;
+        serde_json::to_writer(&mut *writer, event)?;
+        writeln!(writer)?;
+        Ok(())
+    }
+
+    pub fn flush(&self) -> Result<()> {
+        let mut writer = self
+            .writer
+            .lock()
+            .map_err(|e| Error::Internal(e.to_string()))?;
+        writer.flush()?;
+        Ok(())
+    }
+}
+
+/// Something that happened during a crawl, broadcast live to [`EventBus`] subscribers.
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum CrawlEvent {
+    /// A request is about to be sent; `attempt` is 0 unless it's a retry
+    RequestStarted { url: String, attempt: u32 },
+    /// A page was fetched and parsed
+    ResponseReceived {
+        url: String,
+        items: usize,
+        duration_ms: u64,
+    },
+    /// An item was extracted and queued for output
+    ItemExtracted { url: String, item: Value },
+    /// An item was written by the output handler
+    ItemWritten,
+    /// A request failed after all retries, or an item couldn't be written (no `url`)
+    Error {
+        url: Option<String>,
+        /// See [`crate::error::Error::kind`]
+        kind: String,
+        message: String,
+    },
+    StateChanged { state: CrawlerState },
+}
+
+/// Broadcasts [`CrawlEvent`]s to any number of subscribers.
+///
+/// Subscribers that fall more than the channel capacity behind miss the oldest
+/// events (`RecvError::Lagged`) rather than slowing the crawl down.
+#[derive(Debug, Clone)]
+pub struct EventBus {
+    tx: broadcast::Sender<CrawlEvent>,
+}
+
+impl EventBus {
+    pub fn new(capacity: usize) -> Self {
+        let (tx, _) = broadcast::channel(capacity.max(1));
+        Self { tx }
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<CrawlEvent> {
+        self.tx.subscribe()
+    }
+
+    /// Sends the event built by `event`, which is only called if anyone is subscribed.
+    pub fn emit(&self, event: impl FnOnce() -> CrawlEvent) {
+        if self.tx.receiver_count() > 0 {
+            let _ = self.tx.send(event());
+        }
+    }
+}
+
+/// Reads every event in a log, skipping (with a warning) lines that don't parse.
+pub fn read_events<P: AsRef<Path>>(path: P) -> Result<Vec<EventRecord>> {
+    let path = path.as_ref();
+    let file = File::open(path).map_err(|e| Error::Config(format!("{}: {}", path.display(), e)))?;
+
+    let mut events = Vec::new();
+    for (line_no, line) in BufReader::new(file).lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        match serde_json::from_str(&line) {
+            Ok(event) => events.push(event),
+            Err(e) => log::warn!("Skipping {}:{}: {}", path.display(), line_no + 1, e),
+        }
+    }
+    Ok(events)
+}
+
+/// Reads an event log and returns the URLs it mentions, in first-seen order.
+///
+/// With `only_failed`, a URL is kept only if its most recent event was a failure,
+/// so a URL that failed once and later succeeded is not crawled again.
+pub fn read_seed_urls<P: AsRef<Path>>(path: P, only_failed: bool) -> Result<Vec<String>> {
+    let mut order = Vec::new();
+    let mut seen = HashSet::new();
+    let mut last_failed = HashMap::new();
+
+    for event in read_events(path)? {
+        let Some(url) = event.url() else {
+            continue;
+        };
+        if seen.insert(url.to_string()) {
+            order.push(url.to_string());
+        }
+        last_failed.insert(url.to_string(), event.is_failure());
+    }
+
+    Ok(order
+        .into_iter()
+        .filter(|url| !only_failed || last_failed.get(url).copied().unwrap_or(false))
+        .collect())
+}
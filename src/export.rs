@@ -0,0 +1,140 @@
+//! Converts an existing crawl output into another format, reusing the output handlers.
+
+use crate::config::schema::OutputConfig;
+use crate::config::ConfigLoader;
+use crate::error::{Error, Result};
+use crate::paths;
+use serde_json::{Map, Value};
+use sqlx::sqlite::{SqliteConnectOptions, SqlitePool};
+use sqlx::{Column, Row};
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+
+/// Reads the items stored at `path`: a JSON array, JSON Lines (`.jsonl`,
+/// `.ndjson`) or a SQLite database (`.db`, `.sqlite`, `.sqlite3`), in which
+/// case rows are read from `table`.
+pub async fn read_items(path: &Path, table: &str) -> Result<Vec<Value>> {
+    match extension(path).as_str() {
+        "jsonl" | "ndjson" => read_json_lines(path),
+        "json" => {
+            let content = std::fs::read_to_string(path)?;
+            match serde_json::from_str(&content)? {
+                Value::Array(items) => Ok(items),
+                _ => Err(Error::Config(format!(
+                    "{}: expected a JSON array of items",
+                    path.display()
+                ))),
+            }
+        }
+        "db" | "sqlite" | "sqlite3" => read_sqlite(path, table).await,
+        other => Err(Error::Config(format!(
+            "{}: unsupported input format '{}' (expected json, jsonl, ndjson or db)",
+            path.display(),
+            other
+        ))),
+    }
+}
+
+/// Picks the output handler for `path` from its extension, or from `format`
+/// when given (`csv`, `json`, `sqlite`, `parquet` or `xlsx`).
+pub fn output_config(path: &Path, format: Option<&str>, table: &str) -> Result<OutputConfig> {
+    let format = format
+        .map(str::to_ascii_lowercase)
+        .unwrap_or_else(|| extension(path));
+    // Output paths are config strings, and `display` would quietly mangle a non-UTF-8 one
+    let path = path
+        .to_str()
+        .ok_or_else(|| Error::Config(format!("{}: output paths must be valid UTF-8", path.display())))?
+        .to_string();
+    match format.as_str() {
+        "csv" => Ok(OutputConfig::Csv {
+            path,
+            append: false,
+            nested: Default::default(),
+        }),
+        "json" => Ok(OutputConfig::Json { path, append: false }),
+        "sqlite" | "db" | "sqlite3" => Ok(OutputConfig::Sqlite {
+            path,
+            table: table.to_string(),
+            columns: Vec::new(),
+            dead_letter: None,
+            pragmas: Default::default(),
+            nested: Default::default(),
+        }),
+        "parquet" => Ok(OutputConfig::Parquet { path }),
+        "xlsx" => Ok(OutputConfig::Xlsx { path }),
+        other => Err(Error::Config(format!(
+            "Unsupported output format '{}' (expected csv, json, sqlite, parquet or xlsx)",
+            other
+        ))),
+    }
+}
+
+/// Copies every item from `input` to `output`, returning how many were written.
+pub async fn export(input: &Path, output: &OutputConfig, table: &str) -> Result<usize> {
+    let items = read_items(input, table).await?;
+    let mut handler = ConfigLoader::create_output(Some(output), None, &[]).await?;
+    let count = items.len();
+    for item in items {
+        handler.write(item).await?;
+    }
+    handler.close().await?;
+    Ok(count)
+}
+
+fn extension(path: &Path) -> String {
+    paths::extension(path).unwrap_or_default()
+}
+
+fn read_json_lines(path: &Path) -> Result<Vec<Value>> {
+    let file = std::fs::File::open(path)?;
+    let mut items = Vec::new();
+    for line in BufReader::new(file).lines() {
+        let line = line?;
+        if !line.trim().is_empty() {
+            items.push(serde_json::from_str(&line)?);
+        }
+    }
+    Ok(items)
+}
+
+async fn read_sqlite(path: &Path, table: &str) -> Result<Vec<Value>> {
+    let sink = format!("sqlite {}", path.display());
+    let output_error = |e: sqlx::Error| Error::Output {
+        sink: sink.clone(),
+        message: e.to_string(),
+    };
+
+    let options = SqliteConnectOptions::new().filename(path).read_only(true);
+    let pool = SqlitePool::connect_with(options)
+        .await
+        .map_err(output_error)?;
+    let rows = sqlx::query(&format!("SELECT * FROM \"{}\"", table.replace('"', "\"\"")))
+        .fetch_all(&pool)
+        .await
+        .map_err(output_error)?;
+
+    let mut items = Vec::with_capacity(rows.len());
+    for row in rows {
+        let mut item = Map::new();
+        for (i, column) in row.columns().iter().enumerate() {
+            // The row id added by the SQLite output isn't part of the item
+            if column.name() == "id" {
+                continue;
+            }
+            let value = if let Ok(v) = row.try_get::<Option<String>, _>(i) {
+                v.map(Value::String)
+            } else if let Ok(v) = row.try_get::<Option<i64>, _>(i) {
+                v.map(Value::from)
+            } else if let Ok(v) = row.try_get::<Option<f64>, _>(i) {
+                v.map(Value::from)
+            } else {
+                None
+            };
+            item.insert(column.name().to_string(), value.unwrap_or(Value::Null));
+        }
+        items.push(Value::Object(item));
+    }
+    pool.close().await;
+    Ok(items)
+}
@@ -0,0 +1,189 @@
+//! Links to other sites found on crawled pages, counted per link or per
+//! domain and written to their own CSV when the crawl ends, e.g. for backlink
+//! analysis or as seed lists for later crawls.
+
+use crate::config::schema::{ExternalLinksConfig, ExternalLinksGroup};
+use crate::error::{Error, Result};
+use select::document::Document;
+use select::predicate::Name;
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use std::path::PathBuf;
+use std::sync::Mutex;
+use url::Url;
+
+/// Distinct anchor texts kept per link or domain.
+const MAX_ANCHOR_TEXTS: usize = 20;
+
+/// Separates anchor texts in the CSV column.
+const ANCHOR_TEXT_SEPARATOR: &str = " | ";
+
+#[derive(Debug, Default)]
+struct Stats {
+    domain: String,
+    /// Times the link (or a link to the domain) was found
+    count: u64,
+    /// Crawled pages linking to it
+    pages: u64,
+    first_seen_on: String,
+    anchor_texts: BTreeSet<String>,
+}
+
+/// One row of the CSV.
+#[derive(Debug, Serialize)]
+struct Row<'a> {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    url: Option<&'a str>,
+    domain: &'a str,
+    count: u64,
+    pages: u64,
+    first_seen_on: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    anchor_text: Option<String>,
+}
+
+#[derive(Debug)]
+pub struct ExternalLinks {
+    path: PathBuf,
+    group_by: ExternalLinksGroup,
+    anchor_text: bool,
+    /// Whether harvested links are queued too
+    pub follow: bool,
+    /// Hosts of the start URLs, without `www.`; links elsewhere are external
+    internal_hosts: HashSet<String>,
+    found: Mutex<BTreeMap<String, Stats>>,
+}
+
+impl ExternalLinks {
+    pub fn new(config: &ExternalLinksConfig, start_urls: &[String]) -> Self {
+        let internal_hosts = start_urls
+            .iter()
+            .filter_map(|url| Url::parse(url).ok())
+            .filter_map(|url| url.host_str().map(site_host))
+            .collect();
+        Self {
+            path: config.path.clone(),
+            group_by: config.group_by,
+            anchor_text: config.anchor_text,
+            follow: config.follow,
+            internal_hosts,
+            found: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    /// Records the external links of the page at `page_url`. Returns the
+    /// distinct external URLs on the page, for queueing them when `follow` is
+    /// set, and how many links (or domains) were found for the first time.
+    pub fn harvest(&self, html: &str, base: &Url, page_url: &str) -> (Vec<String>, usize) {
+        let document = Document::from(html);
+        let mut urls = BTreeSet::new();
+        let mut on_page: BTreeMap<String, (String, Vec<String>)> = BTreeMap::new();
+        for node in document.find(Name("a")) {
+            let Some(mut url) = node.attr("href").and_then(|href| base.join(href.trim()).ok()) else {
+                continue;
+            };
+            if !matches!(url.scheme(), "http" | "https") {
+                continue;
+            }
+            let Some(host) = url.host_str().map(site_host) else {
+                continue;
+            };
+            if self.internal_hosts.contains(&host) {
+                continue;
+            }
+            url.set_fragment(None);
+            urls.insert(url.to_string());
+            let key = match self.group_by {
+                ExternalLinksGroup::Link => url.to_string(),
+                ExternalLinksGroup::Domain => host.clone(),
+            };
+            let entry = on_page.entry(key).or_insert_with(|| (host, Vec::new()));
+            let text = node.text().split_whitespace().collect::<Vec<_>>().join(" ");
+            entry.1.push(text);
+        }
+
+        let mut new_count = 0;
+        if let Ok(mut found) = self.found.lock() {
+            for (key, (domain, texts)) in on_page {
+                let stats = found.entry(key).or_default();
+                if stats.pages == 0 {
+                    stats.domain = domain;
+                    stats.first_seen_on = page_url.to_string();
+                    new_count += 1;
+                }
+                stats.pages += 1;
+                stats.count += texts.len() as u64;
+                if self.anchor_text {
+                    for text in texts.into_iter().filter(|text| !text.is_empty()) {
+                        if stats.anchor_texts.len() >= MAX_ANCHOR_TEXTS {
+                            break;
+                        }
+                        stats.anchor_texts.insert(text);
+                    }
+                }
+            }
+        }
+        (urls.into_iter().collect(), new_count)
+    }
+
+    /// Writes everything found so far, most linked first, replacing any previous file.
+    pub fn write(&self) -> Result<()> {
+        let found = self
+            .found
+            .lock()
+            .map_err(|_| Error::Internal("external links lock poisoned".to_string()))?;
+        let mut rows: Vec<(&String, &Stats)> = found.iter().collect();
+        rows.sort_by_key(|(_, stats)| std::cmp::Reverse(stats.count));
+
+        let output_error = |e: csv::Error| Error::Output {
+            sink: format!("csv {}", self.path.display()),
+            message: e.to_string(),
+        };
+        let mut writer = csv::Writer::from_path(&self.path).map_err(output_error)?;
+        if rows.is_empty() {
+            let mut header = vec!["domain", "count", "pages", "first_seen_on"];
+            if self.group_by == ExternalLinksGroup::Link {
+                header.insert(0, "url");
+            }
+            if self.anchor_text {
+                header.push("anchor_text");
+            }
+            writer.write_record(header).map_err(output_error)?;
+        }
+        for (key, stats) in rows {
+            writer
+                .serialize(Row {
+                    url: (self.group_by == ExternalLinksGroup::Link).then_some(key.as_str()),
+                    domain: &stats.domain,
+                    count: stats.count,
+                    pages: stats.pages,
+                    first_seen_on: &stats.first_seen_on,
+                    anchor_text: self.anchor_text.then(|| {
+                        stats
+                            .anchor_texts
+                            .iter()
+                            .map(String::as_str)
+                            .collect::<Vec<_>>()
+                            .join(ANCHOR_TEXT_SEPARATOR)
+                    }),
+                })
+                .map_err(output_error)?;
+        }
+        writer.flush()?;
+        let noun = match self.group_by {
+            ExternalLinksGroup::Link => "links",
+            ExternalLinksGroup::Domain => "domains",
+        };
+        log::info!("Wrote {} external {} to {}", found.len(), noun, self.path.display());
+        Ok(())
+    }
+}
+
+/// `host` without a leading `www.`, so both count as the same site.
+fn site_host(host: &str) -> String {
+    let host = host.to_ascii_lowercase();
+    match host.strip_prefix("www.") {
+        Some(rest) => rest.to_string(),
+        None => host,
+    }
+}
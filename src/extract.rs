@@ -0,0 +1,405 @@
+use crate::config::schema::SelectorConfig;
+use crate::error::{Error, Result};
+use crate::spider::{Aggregate, ExtractionRule, RuleSource};
+use chadselect::ChadSelect;
+use reqwest::header::HeaderMap;
+use scraper::{Html, Selector};
+use serde_json::{json, Map, Value};
+use std::collections::HashMap;
+
+/// Field holding the matched HTML of rules with `keep_evidence`.
+pub const EVIDENCE_FIELD: &str = "_evidence";
+
+/// Longest evidence kept per field; longer HTML is cut at a character boundary.
+const MAX_EVIDENCE_BYTES: usize = 4096;
+
+/// The response a page came with, read by rules with `source: header` or
+/// `source: status`.
+#[derive(Debug, Clone)]
+pub struct PageResponse {
+    /// Final status code, after redirects
+    pub status: u16,
+    pub headers: HeaderMap,
+}
+
+impl PageResponse {
+    /// The value `rule` reads from the response, if it reads one and it's there.
+    /// Repeated headers are joined with `, `, or with the rule's `join`.
+    fn value(&self, rule: &ExtractionRule) -> Option<Value> {
+        match rule.source {
+            RuleSource::Html => None,
+            RuleSource::Status => Some(match &rule.transform {
+                Some(_) => rule.typed_value(&self.status.to_string()),
+                None => json!(self.status),
+            }),
+            RuleSource::Header => {
+                let values: Vec<&str> = self
+                    .headers
+                    .get_all(rule.name.as_deref()?.trim())
+                    .iter()
+                    .filter_map(|value| value.to_str().ok())
+                    .collect();
+                (!values.is_empty()).then(|| rule.typed_value(&values.join(rule.join.as_deref().unwrap_or(", "))))
+            }
+        }
+    }
+}
+
+/// The extraction half of a spider: selectors and rules applied to a fetched page.
+///
+/// Extraction is synchronous and CPU-bound, so spiders run it on the blocking pool.
+#[derive(Debug, Clone, Default)]
+pub struct Extractor {
+    pub root_selector: Option<SelectorConfig>,
+    pub extraction_rules: HashMap<String, ExtractionRule>,
+}
+
+impl Extractor {
+    pub fn new(
+        root_selector: Option<SelectorConfig>,
+        extraction_rules: HashMap<String, ExtractionRule>,
+    ) -> Self {
+        Self {
+            root_selector,
+            extraction_rules,
+        }
+    }
+
+    /// Parses `html` and returns the items found on the page at `url`. Rules
+    /// reading the response find nothing; see [`Extractor::extract_with_response`].
+    pub fn extract(&self, html: String, url: &str) -> Vec<Value> {
+        self.extract_page(html, url, None)
+    }
+
+    /// Like [`Extractor::extract`], with the response the page came with.
+    pub fn extract_with_response(&self, html: String, url: &str, response: &PageResponse) -> Vec<Value> {
+        self.extract_page(html, url, Some(response))
+    }
+
+    fn extract_page(&self, html: String, url: &str, response: Option<&PageResponse>) -> Vec<Value> {
+        // Parsed a second time only when there's evidence to keep or roots to split
+        let document = (self.css_root().is_some() || self.extraction_rules.values().any(|rule| rule.keep_evidence))
+            .then(|| Html::parse_document(&html));
+        let mut cs = ChadSelect::new();
+        cs.add_html(html);
+        self.extract_from(&cs, document.as_ref(), url, response)
+    }
+
+    /// Whether a rule with `render_fallback` found nothing in `items`, so the
+    /// rendered page should be tried.
+    pub fn needs_render(&self, items: &[Value]) -> bool {
+        let fields: Vec<&String> = self
+            .extraction_rules
+            .iter()
+            .filter(|(_, rule)| rule.render_fallback && rule.reads_page())
+            .map(|(field, _)| field)
+            .collect();
+        !fields.is_empty()
+            && (items.is_empty()
+                || items
+                    .iter()
+                    .any(|item| fields.iter().any(|field| item.get(field.as_str()).is_none_or(Value::is_null))))
+    }
+
+    /// Like [`Extractor::extract`], for plain text such as a PDF's contents.
+    /// Only `regex:` selectors are meaningful on text.
+    pub fn extract_text(&self, text: String, url: &str, response: Option<&PageResponse>) -> Vec<Value> {
+        let mut cs = ChadSelect::new();
+        cs.add_text(text);
+        self.extract_from(&cs, None, url, response)
+    }
+
+    /// The root selector as a query, `css:` unless it names another engine.
+    fn root_query(&self) -> Option<String> {
+        let root = self.root_selector.as_ref()?.to_query_string();
+        Some(if root.contains(':') { root } else { format!("css:{}", root) })
+    }
+
+    /// The CSS selector of a `css:` root, whose elements can be taken apart.
+    fn css_root(&self) -> Option<Selector> {
+        let root = self.root_query()?;
+        Selector::parse(root.strip_prefix("css:")?.trim()).ok()
+    }
+
+    /// One item per element the CSS root matches, with every rule, its
+    /// fallbacks and `index` applied within that element alone.
+    fn extract_roots(
+        &self,
+        document: &Html,
+        root: &Selector,
+        url: &str,
+        response_fields: &[(String, Value)],
+    ) -> Vec<Value> {
+        let keep_evidence = self.extraction_rules.values().any(|rule| rule.keep_evidence);
+        let items: Vec<Value> = document
+            .select(root)
+            .filter_map(|element| {
+                let html = standalone_html(element.value().name(), element.html());
+                let fragment = keep_evidence.then(|| Html::parse_fragment(&html));
+                let mut cs = ChadSelect::new();
+                cs.add_html(html);
+                match self.extract_data(&cs, fragment.as_ref(), 0, url, response_fields.to_vec()) {
+                    Ok(item) => Some(item),
+                    Err(e) => {
+                        log::debug!("{}", e);
+                        None
+                    }
+                }
+            })
+            .collect();
+        log::info!("Extracted {} items from {}", items.len(), url);
+        items
+    }
+
+    /// The fields of rules reading the response.
+    fn response_fields(&self, response: Option<&PageResponse>) -> Vec<(String, Value)> {
+        let Some(response) = response else {
+            return Vec::new();
+        };
+        self.extraction_rules
+            .iter()
+            .filter(|(_, rule)| rule.condition.is_none())
+            .filter_map(|(field_name, rule)| Some((field_name.clone(), response.value(rule)?)))
+            .collect()
+    }
+
+    fn extract_from(
+        &self,
+        cs: &ChadSelect,
+        document: Option<&Html>,
+        url: &str,
+        response: Option<&PageResponse>,
+    ) -> Vec<Value> {
+        let mut items = Vec::new();
+        let response_fields = self.response_fields(response);
+
+        if let (Some(document), Some(root)) = (document, self.css_root()) {
+            return self.extract_roots(document, &root, url, &response_fields);
+        }
+        // Other roots, e.g. `regex:` on a PDF's text, are matched field by field
+        if let Some(root_query) = self.root_query() {
+            
+            log::debug!("Processing with root selector: {}", root_query);
+            
+            let roots = cs.query(-1, &root_query);
+            log::debug!("Root selector '{}' found {} matches", root_query, roots.len());
+
+            let mut field_results = HashMap::new();
+            let mut field_evidence = HashMap::new();
+            let mut max_len = 0;
+
+            for (field_name, rule) in &self.extraction_rules {
+                if !rule.reads_page() {
+                    continue;
+                }
+                let mut results = Vec::new();
+                for selector in rule.selectors() {
+                    let raw_rule = selector.to_query_string();
+                    let rule_selector = raw_rule.split_once(':').map(|s| s.1).unwrap_or(&raw_rule);
+
+                    // Try combined selector first: root + space + rule
+                    let full_query = format!("{} {}", root_query, rule_selector);
+                    results = cs.query(-1, &full_query);
+
+                    log::debug!("Field '{}' with query '{}' found {} results", field_name, full_query, results.len());
+                    if !results.is_empty() {
+                        if let Some(document) = document.filter(|_| rule.keep_evidence) {
+                            field_evidence.insert(field_name.clone(), matched_html(document, &full_query));
+                        }
+                        break;
+                    }
+                }
+
+                max_len = max_len.max(results.len());
+                field_results.insert(field_name.clone(), results);
+            }
+            
+            if max_len == 0 && !roots.is_empty() {
+                log::warn!("Found {} roots but 0 items. Checking if rules should be absolute...", roots.len());
+            }
+            
+            log::info!("Extracted {} items from {}", max_len, url);
+
+            for i in 0..max_len {
+                let mut item = serde_json::Map::new();
+                for (field_name, results) in &field_results {
+                    if let Some(val) = results.get(i) {
+                        let rule = &self.extraction_rules[field_name];
+                        item.insert(field_name.clone(), rule.typed_value(val));
+                    }
+                }
+                let evidence: Map<String, Value> = field_evidence
+                    .iter()
+                    .filter_map(|(field, matches)| Some((field.clone(), json!(matches.get(i)?))))
+                    .collect();
+                if !item.is_empty() {
+                    item.extend(response_fields.iter().cloned());
+                    self.apply_conditions(&mut item);
+                    if !evidence.is_empty() {
+                        item.insert(EVIDENCE_FIELD.to_string(), Value::Object(evidence));
+                    }
+                    items.push(Value::Object(item));
+                }
+            }
+        } else {
+            // Single item mode
+            match self.extract_data(cs, document, 0, url, response_fields) {
+                Ok(item) => items.push(item),
+                Err(e) => log::debug!("{}", e),
+            }
+        }
+
+        items
+    }
+
+    /// Fills in `if`/`then`/`else` fields from the other fields already in `item`.
+    fn apply_conditions(&self, item: &mut serde_json::Map<String, Value>) {
+        let derived: Vec<(String, Value)> = self
+            .extraction_rules
+            .iter()
+            .filter_map(|(field_name, rule)| {
+                let condition = rule.condition.as_ref()?;
+                let value = if condition.holds(item) {
+                    rule.then.clone()
+                } else {
+                    rule.otherwise.clone()
+                };
+                Some((field_name.clone(), value?))
+            })
+            .collect();
+        item.extend(derived);
+    }
+
+    /// Applies one selector of `rule`, returning `None` if it yields nothing.
+    fn rule_value(cs: &ChadSelect, doc_index: i32, rule: &ExtractionRule, query: &str) -> Option<Value> {
+        if let Some(aggregate) = &rule.aggregate {
+            return aggregate.apply(&cs.query(-1, query), rule.locale.as_deref());
+        }
+        if let Some(separator) = &rule.join {
+            let matches = cs.query(-1, query);
+            return (!matches.is_empty()).then(|| rule.typed_value(&matches.join(separator)));
+        }
+
+        let val = match &rule.index {
+            Some(index) => index.pick(&cs.query(-1, query)).cloned().unwrap_or_default(),
+            None => cs.select(doc_index, query),
+        };
+        (!val.is_empty()).then(|| rule.typed_value(&val))
+    }
+
+    /// The page's single item. Fields read from the response are added to it,
+    /// but only make an item on their own when no rule reads the page.
+    fn extract_data(
+        &self,
+        cs: &ChadSelect,
+        document: Option<&Html>,
+        doc_index: i32,
+        url: &str,
+        response_fields: Vec<(String, Value)>,
+    ) -> Result<Value> {
+        let mut item = serde_json::Map::new();
+        let mut evidence = Map::new();
+        let mut found_data = false;
+
+        for (field_name, rule) in &self.extraction_rules {
+            // ChadSelect select returns a String
+            // We might need to handle different extraction types if ChadSelect supports them directly,
+            // but for now we follow the rule.extract.
+            
+            // NOTE: ChadSelect's select(index, query) might need the prefix (css:, xpath:, regex:)
+            // We assume rule.selector already has it or we could add a default.
+            if !rule.reads_page() {
+                continue;
+            }
+            let found = rule.selectors().find_map(|selector| {
+                let raw_selector = selector.to_query_string();
+                let query = if raw_selector.contains(':') {
+                    raw_selector
+                } else {
+                    format!("css:{}", raw_selector)
+                };
+                Self::rule_value(cs, doc_index, rule, &query).map(|val| (query, val))
+            });
+            if let (Some(document), Some((query, _))) = (document.filter(|_| rule.keep_evidence), &found) {
+                let matches = matched_html(document, query);
+                let matched = match &rule.index {
+                    Some(index) => index.pick(&matches),
+                    None => matches.first(),
+                };
+                if let Some(html) = matched {
+                    evidence.insert(field_name.clone(), json!(html));
+                }
+            }
+            let val = found.map(|(_, val)| val);
+
+            if let Some(exists_as) = &rule.exists_as {
+                let value = if val.is_some() {
+                    &exists_as.when_present
+                } else {
+                    &exists_as.when_absent
+                };
+                item.insert(field_name.clone(), value.clone());
+                found_data = true;
+            } else if let Some(val) = val {
+                item.insert(field_name.clone(), val);
+                found_data = true;
+            } else if rule.aggregate == Some(Aggregate::Count) {
+                item.insert(field_name.clone(), json!(0));
+            }
+        }
+
+        if !found_data && !self.extraction_rules.values().any(ExtractionRule::reads_page) {
+            found_data = !response_fields.is_empty();
+        }
+        if found_data {
+            item.extend(response_fields);
+            self.apply_conditions(&mut item);
+            if !evidence.is_empty() {
+                item.insert(EVIDENCE_FIELD.to_string(), Value::Object(evidence));
+            }
+            Ok(Value::Object(item))
+        } else {
+            Err(Error::Extraction {
+                url: url.to_string(),
+                message: "no rule matched any data".to_string(),
+            })
+        }
+    }
+}
+
+/// `html` of an element named `name` wrapped in the parents it needs to
+/// survive being parsed on its own, e.g. a `<table>` around a `<tr>`.
+fn standalone_html(name: &str, html: String) -> String {
+    match name {
+        "tr" | "thead" | "tbody" | "tfoot" | "caption" | "colgroup" => format!("<table>{}</table>", html),
+        "td" | "th" => format!("<table><tr>{}</tr></table>", html),
+        "option" | "optgroup" => format!("<select>{}</select>", html),
+        _ => html,
+    }
+}
+
+/// The outer HTML of each element a `css:` query matches, up to
+/// [`MAX_EVIDENCE_BYTES`] each. Other queries have no elements to show.
+fn matched_html(document: &Html, query: &str) -> Vec<String> {
+    let Some(css) = query.strip_prefix("css:") else {
+        return Vec::new();
+    };
+    let Ok(selector) = Selector::parse(css.trim()) else {
+        return Vec::new();
+    };
+    document
+        .select(&selector)
+        .map(|element| {
+            let mut html = element.html();
+            if html.len() > MAX_EVIDENCE_BYTES {
+                let mut end = MAX_EVIDENCE_BYTES;
+                while !html.is_char_boundary(end) {
+                    end -= 1;
+                }
+                html.truncate(end);
+                html.push('…');
+            }
+            html
+        })
+        .collect()
+}
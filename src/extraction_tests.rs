@@ -0,0 +1,111 @@
+//! The `tests` of a config: saved pages run through the spider's extraction
+//! and checked against the expected items, for `crawler check --run-tests`.
+
+use crate::cleanup::clean_html;
+use crate::config::schema::{ExtractionTestConfig, SpiderConfig};
+use crate::error::{Error, Result};
+use crate::extract::Extractor;
+use crate::item_filter::ItemFilter;
+use crate::page_types::PageTypes;
+use serde_json::Value;
+use std::path::PathBuf;
+
+/// Page URL of tests in a config without start URLs.
+const DEFAULT_URL: &str = "http://localhost/";
+
+/// The outcome of one test.
+#[derive(Debug, Clone)]
+pub struct TestResult {
+    pub file: PathBuf,
+    pub items: usize,
+    /// What didn't match the expectations; empty when the test passed
+    pub failures: Vec<String>,
+}
+
+impl TestResult {
+    pub fn passed(&self) -> bool {
+        self.failures.is_empty()
+    }
+}
+
+/// Runs every test of `config`, failing only if a page can't be read.
+pub fn run(config: &SpiderConfig) -> Result<Vec<TestResult>> {
+    let page_types = (!config.page_types.is_empty())
+        .then(|| PageTypes::new(&config.page_types))
+        .transpose()?;
+    let extractor = Extractor::new(config.root_selector.clone(), config.extraction_rules.clone());
+    let item_filter = config.item_filter.as_ref().map(ItemFilter::new).transpose()?;
+    config
+        .tests
+        .iter()
+        .map(|test| run_test(config, test, &extractor, page_types.as_ref(), item_filter.as_ref()))
+        .collect()
+}
+
+fn run_test(
+    config: &SpiderConfig,
+    test: &ExtractionTestConfig,
+    extractor: &Extractor,
+    page_types: Option<&PageTypes>,
+    item_filter: Option<&ItemFilter>,
+) -> Result<TestResult> {
+    let html = std::fs::read_to_string(&test.file)
+        .map_err(|e| Error::Config(format!("{}: {}", test.file.display(), e)))?;
+    let url = test
+        .url
+        .as_deref()
+        .or(config.start_urls.first().map(String::as_str))
+        .unwrap_or(DEFAULT_URL);
+    let html = match &config.html_cleanup {
+        Some(options) => clean_html(html, options),
+        None => html,
+    };
+
+    // As in a crawl, a page matching a page type gets that type's rules
+    let mut items = match page_types.and_then(|types| types.find(url, 0)) {
+        Some(page_type) => page_type
+            .extractor
+            .as_ref()
+            .map(|extractor| extractor.extract(html, url))
+            .unwrap_or_default(),
+        None => extractor.extract(html, url),
+    };
+    if let Some(filter) = item_filter {
+        filter.apply(&mut items);
+    }
+
+    let mut failures = Vec::new();
+    for (key, expected) in &test.expect {
+        if key == "items" {
+            if expected.as_u64() != Some(items.len() as u64) {
+                failures.push(format!("expected {} items, found {}", expected, items.len()));
+            }
+            continue;
+        }
+        let actual = items
+            .first()
+            .and_then(|item| item.get(key))
+            .cloned()
+            .unwrap_or(Value::Null);
+        if !same_value(expected, &actual) {
+            failures.push(format!("`{}`: expected {}, found {}", key, expected, actual));
+        }
+    }
+
+    Ok(TestResult {
+        file: test.file.clone(),
+        items: items.len(),
+        failures,
+    })
+}
+
+/// Whether `actual` is `expected`, with a string expectation also matching
+/// the same number or boolean, since YAML configs often quote them.
+fn same_value(expected: &Value, actual: &Value) -> bool {
+    match (expected, actual) {
+        (Value::String(expected), Value::Number(_) | Value::Bool(_)) => {
+            serde_json::from_str::<Value>(expected).is_ok_and(|expected| expected == *actual)
+        }
+        _ => expected == actual,
+    }
+}
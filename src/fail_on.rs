@@ -0,0 +1,247 @@
+//! Expectations a finished crawl must meet, written as conditions like
+//! `items<100` that fail the run when they hold, so schedulers and CI notice
+//! crawls that end without errors but without the data either.
+
+use crate::config::schema::SpiderConfig;
+use crate::error::{Error, Result};
+use crate::report::CrawlReport;
+use std::fmt;
+use std::str::FromStr;
+
+/// Operators, longest first so `<=` isn't read as `<`.
+const OPERATORS: [(&str, Operator); 7] = [
+    ("<=", Operator::LessOrEqual),
+    (">=", Operator::GreaterOrEqual),
+    ("==", Operator::Equal),
+    ("!=", Operator::NotEqual),
+    ("<", Operator::Less),
+    (">", Operator::Greater),
+    ("=", Operator::Equal),
+];
+
+#[derive(Debug, Clone, PartialEq)]
+enum Metric {
+    /// Items extracted
+    Items,
+    /// Pages processed
+    Pages,
+    Requests,
+    /// Failed requests
+    Errors,
+    /// Percentage of requests that failed
+    ErrorRate,
+    /// URLs that still failed after their retries
+    FailedUrls,
+    /// Responses detected as blocked
+    Blocked,
+    /// Seconds since the crawl started
+    Duration,
+    /// Percentage of pages on which the field was found
+    Coverage(String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Operator {
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual,
+    Equal,
+    NotEqual,
+}
+
+/// A condition on a crawl's report, e.g. `items<100` or `error_rate>20`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FailCondition {
+    metric: Metric,
+    operator: Operator,
+    threshold: f64,
+    text: String,
+}
+
+impl FailCondition {
+    /// The value of the metric if the condition holds for `report`, i.e. the
+    /// run failed it.
+    pub fn check(&self, report: &CrawlReport) -> Option<f64> {
+        let metrics = &report.metrics;
+        let value = match &self.metric {
+            Metric::Items => metrics.items_extracted as f64,
+            Metric::Pages => metrics.urls_processed as f64,
+            Metric::Requests => metrics.requests_total as f64,
+            Metric::Errors => metrics.requests_failed as f64,
+            Metric::ErrorRate if metrics.requests_total == 0 => 0.0,
+            Metric::ErrorRate => metrics.requests_failed as f64 / metrics.requests_total as f64 * 100.0,
+            Metric::FailedUrls => report.failures.len() as f64,
+            Metric::Blocked => metrics.requests_blocked as f64,
+            Metric::Duration => metrics.elapsed_seconds,
+            Metric::Coverage(field) => metrics
+                .field_coverage
+                .get(field)
+                .map_or(0.0, |coverage| coverage.ratio() * 100.0),
+        };
+        let holds = match self.operator {
+            Operator::Less => value < self.threshold,
+            Operator::LessOrEqual => value <= self.threshold,
+            Operator::Greater => value > self.threshold,
+            Operator::GreaterOrEqual => value >= self.threshold,
+            Operator::Equal => value == self.threshold,
+            Operator::NotEqual => value != self.threshold,
+        };
+        holds.then_some(value)
+    }
+
+    /// Checks that a `coverage.<field>` condition names a field `config`
+    /// extracts, as any other field's coverage is always 0%.
+    pub fn validate(&self, config: &SpiderConfig) -> Result<()> {
+        let Metric::Coverage(field) = &self.metric else {
+            return Ok(());
+        };
+        let extracted = config.extraction_rules.contains_key(field)
+            || config.page_types.iter().any(|page_type| page_type.rules.contains_key(field));
+        if !extracted {
+            return Err(Error::Config(format!(
+                "Invalid fail-on condition '{}': no extraction rule is named '{}'",
+                self.text, field
+            )));
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for FailCondition {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let invalid = |reason: &str| Error::Config(format!("Invalid fail-on condition '{}': {}", s, reason));
+        let start = s
+            .find(['<', '>', '=', '!'])
+            .ok_or_else(|| invalid("expected a comparison like items<100"))?;
+        let (name, rest) = s.split_at(start);
+        let (symbol, operator) = OPERATORS
+            .iter()
+            .find(|(symbol, _)| rest.starts_with(symbol))
+            .ok_or_else(|| invalid("unknown operator"))?;
+        let metric = match name.trim() {
+            "items" => Metric::Items,
+            "pages" => Metric::Pages,
+            "requests" => Metric::Requests,
+            "errors" => Metric::Errors,
+            "error_rate" => Metric::ErrorRate,
+            "failed_urls" => Metric::FailedUrls,
+            "blocked" => Metric::Blocked,
+            "duration" => Metric::Duration,
+            name => match name.strip_prefix("coverage.") {
+                Some(field) if !field.is_empty() => Metric::Coverage(field.to_string()),
+                _ => return Err(invalid(&format!("unknown metric '{}'", name))),
+            },
+        };
+        let value = rest[symbol.len()..].trim();
+        let threshold = value
+            .strip_suffix('%')
+            .unwrap_or(value)
+            .trim()
+            .parse::<f64>()
+            .map_err(|_| invalid(&format!("'{}' is not a number", value)))?;
+        Ok(Self {
+            metric,
+            operator: *operator,
+            threshold,
+            text: s.trim().to_string(),
+        })
+    }
+}
+
+impl fmt::Display for FailCondition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.text)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metrics::snapshot::{FieldCoverage, MetricsSnapshot};
+    use crate::report::{EndReason, PhaseDurations};
+    use serde_json::json;
+
+    fn report(metrics: MetricsSnapshot) -> CrawlReport {
+        CrawlReport {
+            run_id: "run".to_string(),
+            spider: "spider".to_string(),
+            interrupted: false,
+            end_reason: EndReason::Finished,
+            metrics,
+            failures: Vec::new(),
+            durations: PhaseDurations::default(),
+        }
+    }
+
+    fn fails(condition: &str, report: &CrawlReport) -> bool {
+        condition.parse::<FailCondition>().unwrap().check(report).is_some()
+    }
+
+    #[test]
+    fn compares_inclusively_only_with_or_equal() {
+        let report = report(MetricsSnapshot {
+            items_extracted: 100,
+            ..Default::default()
+        });
+        assert!(fails("items<=100", &report));
+        assert!(!fails("items<100", &report));
+        assert!(fails("items>=100", &report));
+        assert!(!fails("items>100", &report));
+        assert!(fails("items=100", &report));
+        assert!(fails("items==100", &report));
+        assert!(!fails("items!=100", &report));
+    }
+
+    #[test]
+    fn reads_percentages() {
+        let mut metrics = MetricsSnapshot {
+            requests_total: 10,
+            requests_failed: 3,
+            ..Default::default()
+        };
+        metrics.field_coverage.insert(
+            "price".to_string(),
+            FieldCoverage {
+                pages_matched: 9,
+                pages_missed: 1,
+            },
+        );
+        let report = report(metrics);
+        assert!(fails("error_rate>20%", &report));
+        assert!(!fails("error_rate > 30 %", &report));
+        assert!(fails("coverage.price<95%", &report));
+        assert!(!fails("coverage.price<90", &report));
+    }
+
+    #[test]
+    fn counts_no_requests_as_no_errors() {
+        let report = report(MetricsSnapshot::default());
+        assert!(!fails("error_rate>0", &report));
+        assert!(fails("error_rate<=0", &report));
+    }
+
+    #[test]
+    fn rejects_bad_conditions() {
+        assert!("items".parse::<FailCondition>().is_err());
+        assert!("itemz<100".parse::<FailCondition>().is_err());
+        assert!("items<lots".parse::<FailCondition>().is_err());
+        assert!("coverage.<50".parse::<FailCondition>().is_err());
+    }
+
+    #[test]
+    fn rejects_coverage_of_fields_not_extracted() {
+        let config: SpiderConfig = serde_json::from_value(json!({
+            "name": "spider",
+            "start_urls": ["https://example.com"],
+            "extraction_rules": {"price": {"selector": ".price"}},
+        }))
+        .unwrap();
+        let condition = |text: &str| text.parse::<FailCondition>().unwrap();
+        assert!(condition("coverage.price<90").validate(&config).is_ok());
+        assert!(condition("coverage.prize<90").validate(&config).is_err());
+        assert!(condition("items<1").validate(&config).is_ok());
+    }
+}
@@ -0,0 +1,556 @@
+//! The URL queue. Spiders can add the links they find, so the queue closes once
+//! no URL is waiting or being scraped rather than when seeding ends.
+//!
+//! The memory frontier holds every queued URL. The disk frontier keeps them in a
+//! SQLite file with only a window in memory, and lets an interrupted crawl resume.
+//! Both hand out URLs with a higher priority first, then in the order they were queued.
+//!
+//! Either can be inspected while the crawl runs with [`Frontier::snapshot`], and
+//! a disk frontier from another process with [`DiskQueue::snapshot`].
+
+use crate::config::schema::FrontierConfig;
+use crate::error::{Error, Result};
+use crate::visited::VisitedSet;
+use futures::stream::{BoxStream, StreamExt, TryStreamExt};
+use serde::Serialize;
+use sqlx::sqlite::{
+    SqliteConnectOptions, SqliteJournalMode, SqlitePool, SqlitePoolOptions, SqliteSynchronous,
+};
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use tokio::sync::{mpsc, Notify};
+use tokio_stream::wrappers::{ReceiverStream, UnboundedReceiverStream};
+
+// Row states in the `frontier` table
+const QUEUED: i64 = 0;
+const IN_FLIGHT: i64 = 1;
+const DONE: i64 = 2;
+
+/// A URL waiting in the frontier, `depth` links away from a start URL.
+#[derive(Debug, Clone, Serialize)]
+pub struct QueuedUrl {
+    pub url: String,
+    pub depth: usize,
+    /// Given by the page the URL was found on, see [`Spider::link_priority`](crate::Spider::link_priority)
+    pub priority: i64,
+}
+
+/// What is left in a frontier.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct FrontierSnapshot {
+    /// URLs waiting to be crawled
+    pub queued: usize,
+    /// URLs taken from the queue and not finished yet
+    pub in_flight: usize,
+    /// Queued URLs per host
+    pub by_domain: BTreeMap<String, usize>,
+    /// Queued URLs per depth
+    pub by_depth: BTreeMap<usize, usize>,
+    /// The next queued URLs, in the order they will be crawled
+    pub next: Vec<QueuedUrl>,
+    /// Some of the in-flight URLs, which hold the crawl open until they finish
+    pub in_flight_urls: Vec<QueuedUrl>,
+}
+
+impl FrontierSnapshot {
+    /// Counts a queued URL, keeping it if fewer than `next` are kept.
+    fn add_queued(&mut self, entry: QueuedUrl, next: usize) {
+        self.queued += 1;
+        *self.by_domain.entry(host(&entry.url)).or_default() += 1;
+        *self.by_depth.entry(entry.depth).or_default() += 1;
+        if self.next.len() < next {
+            self.next.push(entry);
+        }
+    }
+}
+
+fn host(url: &str) -> String {
+    url::Url::parse(url)
+        .ok()
+        .and_then(|u| u.host_str().map(str::to_string))
+        .unwrap_or_else(|| "(unknown)".to_string())
+}
+
+/// An open disk frontier file.
+#[derive(Debug, Clone)]
+pub struct DiskQueue {
+    pool: SqlitePool,
+    path: String,
+    window: usize,
+}
+
+impl DiskQueue {
+    pub async fn open(config: &FrontierConfig) -> Result<Self> {
+        Self::connect(&config.path, config.window, true).await
+    }
+
+    /// Opens the frontier file of another crawl, e.g. to inspect it while the crawl runs.
+    pub async fn open_existing(path: &str) -> Result<Self> {
+        Self::connect(path, 1, false).await
+    }
+
+    async fn connect(path: &str, window: usize, create: bool) -> Result<Self> {
+        let error = |e: sqlx::Error| Error::Frontier {
+            path: path.to_string(),
+            message: e.to_string(),
+        };
+        let options = SqliteConnectOptions::new()
+            .filename(path)
+            .create_if_missing(create)
+            .journal_mode(SqliteJournalMode::Wal)
+            .synchronous(SqliteSynchronous::Normal);
+        // A single connection serializes writes from concurrent workers
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect_with(options)
+            .await
+            .map_err(error)?;
+
+        for statement in [
+            "CREATE TABLE IF NOT EXISTS frontier (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                url TEXT NOT NULL,
+                fingerprint TEXT NOT NULL,
+                status INTEGER NOT NULL DEFAULT 0
+            )",
+            "CREATE INDEX IF NOT EXISTS frontier_url ON frontier (url, status)",
+        ] {
+            sqlx::query(statement).execute(&pool).await.map_err(error)?;
+        }
+        // Files from before depths and priorities were recorded
+        let columns: Vec<String> = sqlx::query_scalar("SELECT name FROM pragma_table_info('frontier')")
+            .fetch_all(&pool)
+            .await
+            .map_err(error)?;
+        for column in ["depth", "priority"] {
+            if !columns.iter().any(|existing| existing == column) {
+                sqlx::query(&format!("ALTER TABLE frontier ADD COLUMN {} INTEGER NOT NULL DEFAULT 0", column))
+                    .execute(&pool)
+                    .await
+                    .map_err(error)?;
+            }
+        }
+        sqlx::query("CREATE INDEX IF NOT EXISTS frontier_next ON frontier (status, priority DESC, id)")
+            .execute(&pool)
+            .await
+            .map_err(error)?;
+
+        Ok(Self {
+            pool,
+            path: path.to_string(),
+            window: window.max(1),
+        })
+    }
+
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    fn error(&self, e: sqlx::Error) -> Error {
+        Error::Frontier {
+            path: self.path.clone(),
+            message: e.to_string(),
+        }
+    }
+
+    /// Prepares the file for a run and returns the number of unfinished URLs in it.
+    ///
+    /// URLs that were in flight when the last run stopped are queued again and
+    /// every stored fingerprint is added to `seen`. The file of a finished crawl
+    /// is cleared instead, so the next run starts over.
+    async fn resume(&self, seen: &mut VisitedSet) -> Result<usize> {
+        let unfinished: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM frontier WHERE status != ?")
+            .bind(DONE)
+            .fetch_one(&self.pool)
+            .await
+            .map_err(|e| self.error(e))?;
+        if unfinished == 0 {
+            sqlx::query("DELETE FROM frontier")
+                .execute(&self.pool)
+                .await
+                .map_err(|e| self.error(e))?;
+            return Ok(0);
+        }
+
+        sqlx::query("UPDATE frontier SET status = ? WHERE status = ?")
+            .bind(QUEUED)
+            .bind(IN_FLIGHT)
+            .execute(&self.pool)
+            .await
+            .map_err(|e| self.error(e))?;
+        let mut fingerprints =
+            sqlx::query_scalar::<_, String>("SELECT fingerprint FROM frontier").fetch(&self.pool);
+        while let Some(fingerprint) = fingerprints.try_next().await.map_err(|e| self.error(e))? {
+            seen.insert(&fingerprint);
+        }
+        Ok(unfinished as usize)
+    }
+
+    async fn push(&self, entries: &[(String, String)], depth: usize, priority: i64) -> Result<()> {
+        let mut tx = self.pool.begin().await.map_err(|e| self.error(e))?;
+        for (url, fingerprint) in entries {
+            sqlx::query("INSERT INTO frontier (url, fingerprint, status, depth, priority) VALUES (?, ?, ?, ?, ?)")
+                .bind(url)
+                .bind(fingerprint)
+                .bind(QUEUED)
+                .bind(depth as i64)
+                .bind(priority)
+                .execute(&mut *tx)
+                .await
+                .map_err(|e| self.error(e))?;
+        }
+        tx.commit().await.map_err(|e| self.error(e))
+    }
+
+    /// Marks up to `limit` queued URLs as in flight, the highest priority and
+    /// then the oldest first, and returns them with their depth and priority.
+    async fn take(&self, limit: usize) -> Result<Vec<(String, i64, i64)>> {
+        sqlx::query_as(
+            "UPDATE frontier SET status = ? WHERE id IN (
+                SELECT id FROM frontier WHERE status = ? ORDER BY priority DESC, id LIMIT ?
+            ) RETURNING url, depth, priority",
+        )
+        .bind(IN_FLIGHT)
+        .bind(QUEUED)
+        .bind(limit as i64)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| self.error(e))
+    }
+
+    async fn finish(&self, url: &str) -> Result<()> {
+        sqlx::query(
+            "UPDATE frontier SET status = ? WHERE id = (
+                SELECT id FROM frontier WHERE url = ? AND status = ? LIMIT 1
+            )",
+        )
+        .bind(DONE)
+        .bind(url)
+        .bind(IN_FLIGHT)
+        .execute(&self.pool)
+        .await
+        .map_err(|e| self.error(e))?;
+        Ok(())
+    }
+
+    /// What is left in the file, with the `next` URLs to be crawled. Queued
+    /// URLs are read one by one, so this takes a while for millions of them.
+    pub async fn snapshot(&self, next: usize) -> Result<FrontierSnapshot> {
+        let mut snapshot = FrontierSnapshot::default();
+        let mut rows = sqlx::query_as::<_, (String, i64, i64, i64)>(
+            "SELECT url, depth, priority, status FROM frontier WHERE status != ? ORDER BY priority DESC, id",
+        )
+        .bind(DONE)
+        .fetch(&self.pool);
+        while let Some((url, depth, priority, status)) = rows.try_next().await.map_err(|e| self.error(e))? {
+            let entry = QueuedUrl {
+                url,
+                depth: depth.max(0) as usize,
+                priority,
+            };
+            if status == QUEUED {
+                snapshot.add_queued(entry, next);
+            } else {
+                snapshot.in_flight += 1;
+                if snapshot.in_flight_urls.len() < next {
+                    snapshot.in_flight_urls.push(entry);
+                }
+            }
+        }
+        Ok(snapshot)
+    }
+}
+
+#[derive(Clone)]
+pub struct Frontier {
+    inner: Arc<Inner>,
+}
+
+struct Inner {
+    store: Store,
+    /// Queued and in-flight URLs, plus one while start URLs are being seeded
+    pending: AtomicUsize,
+    seen: Mutex<VisitedSet>,
+    /// Depths and priorities of the URLs taken from the queue and not finished yet
+    in_flight: Mutex<HashMap<String, (usize, i64)>>,
+}
+
+enum Store {
+    /// Sends one token per URL in `queued`, which keeps them in the order
+    /// they're taken in until then: by priority, then by when they were queued
+    Memory {
+        tx: Mutex<Option<mpsc::UnboundedSender<()>>>,
+        queued: Mutex<BTreeMap<(Reverse<i64>, u64), QueuedUrl>>,
+        next_key: AtomicU64,
+    },
+    Disk {
+        queue: DiskQueue,
+        /// Wakes the feeder when URLs are queued or the frontier closes
+        wake: Notify,
+        closed: AtomicBool,
+    },
+}
+
+impl Frontier {
+    /// A frontier holding every queued URL in memory, and the stream of URLs to crawl.
+    pub fn memory(seen: VisitedSet) -> (Self, BoxStream<'static, String>) {
+        let (tx, rx) = mpsc::unbounded_channel();
+        let store = Store::Memory {
+            tx: Mutex::new(Some(tx)),
+            queued: Mutex::new(BTreeMap::new()),
+            next_key: AtomicU64::new(0),
+        };
+        let frontier = Self::new(store, seen, 0);
+        let taker = frontier.clone();
+        let urls = UnboundedReceiverStream::new(rx)
+            .filter_map(move |()| std::future::ready(taker.take_queued()))
+            .boxed();
+        (frontier, urls)
+    }
+
+    /// Moves the next URL from the memory queue to the in-flight URLs.
+    fn take_queued(&self) -> Option<String> {
+        let Store::Memory { queued, .. } = &self.inner.store else {
+            return None;
+        };
+        let (_, entry) = queued.lock().ok()?.pop_first()?;
+        if let Ok(mut in_flight) = self.inner.in_flight.lock() {
+            in_flight.insert(entry.url.clone(), (entry.depth, entry.priority));
+        }
+        Some(entry.url)
+    }
+
+    /// A frontier stored in `queue`, the stream of URLs to crawl and the number of
+    /// unfinished URLs resumed from an earlier run.
+    pub async fn disk(
+        queue: DiskQueue,
+        mut seen: VisitedSet,
+    ) -> Result<(Self, BoxStream<'static, String>, usize)> {
+        let resumed = queue.resume(&mut seen).await?;
+        let (tx, rx) = mpsc::channel(queue.window);
+        let store = Store::Disk {
+            queue,
+            wake: Notify::new(),
+            closed: AtomicBool::new(false),
+        };
+        let frontier = Self::new(store, seen, resumed);
+        tokio::spawn(frontier.clone().feed(tx));
+        Ok((frontier, ReceiverStream::new(rx).boxed(), resumed))
+    }
+
+    fn new(store: Store, seen: VisitedSet, pending: usize) -> Self {
+        Self {
+            inner: Arc::new(Inner {
+                store,
+                pending: AtomicUsize::new(pending + 1),
+                seen: Mutex::new(seen),
+                in_flight: Mutex::new(HashMap::new()),
+            }),
+        }
+    }
+
+    /// Queues a start URL. Duplicate start URLs are kept, the spider skips them.
+    pub async fn seed(&self, url: String, fingerprint: String) {
+        if let Ok(mut seen) = self.inner.seen.lock() {
+            seen.insert(&fingerprint);
+        }
+        self.push(vec![(url, fingerprint)], 0, 0).await;
+    }
+
+    /// Marks the start URLs as seeded.
+    pub fn seeded(&self) {
+        self.release(1);
+    }
+
+    /// Queues the `(url, fingerprint)` links found on the page at `from` whose
+    /// fingerprint wasn't queued before, returning their URLs. Links with a
+    /// higher `priority` are crawled first. With a `sample` ratio each new link
+    /// is queued with that probability; the others still count as seen, so a
+    /// link found again isn't drawn again.
+    pub async fn discover(
+        &self,
+        from: &str,
+        links: Vec<(String, String)>,
+        priority: i64,
+        sample: Option<f64>,
+    ) -> Vec<String> {
+        let depth = self
+            .inner
+            .in_flight
+            .lock()
+            .ok()
+            .and_then(|in_flight| in_flight.get(from).map(|(depth, _)| *depth))
+            .unwrap_or_default()
+            + 1;
+        let new = match self.inner.seen.lock() {
+            Ok(mut seen) => links
+                .into_iter()
+                .filter(|(_, fingerprint)| seen.insert(fingerprint))
+                .filter(|(url, _)| {
+                    let keep = sample.is_none_or(|ratio| rand::random::<f64>() < ratio);
+                    if !keep {
+                        log::debug!("Sampled out: {}", url);
+                    }
+                    keep
+                })
+                .collect(),
+            Err(_) => Vec::new(),
+        };
+        self.push(new, depth, priority).await
+    }
+
+    async fn push(&self, entries: Vec<(String, String)>, depth: usize, priority: i64) -> Vec<String> {
+        if entries.is_empty() {
+            return Vec::new();
+        }
+        let total = entries.len();
+        // Counted first, so the queue can't close while they are on their way
+        self.inner.pending.fetch_add(total, Ordering::SeqCst);
+
+        let mut queued = Vec::with_capacity(total);
+        match &self.inner.store {
+            Store::Memory { tx, queued: keys, next_key } => {
+                if let Ok(tx) = tx.lock()
+                    && let Some(tx) = tx.as_ref()
+                    && let Ok(mut keys) = keys.lock()
+                {
+                    for (url, _) in entries {
+                        let key = (Reverse(priority), next_key.fetch_add(1, Ordering::SeqCst));
+                        keys.insert(key, QueuedUrl { url: url.clone(), depth, priority });
+                        if tx.send(()).is_ok() {
+                            queued.push(url);
+                        } else {
+                            keys.remove(&key);
+                        }
+                    }
+                }
+            }
+            Store::Disk { queue, wake, .. } => match queue.push(&entries, depth, priority).await {
+                Ok(()) => {
+                    queued.extend(entries.into_iter().map(|(url, _)| url));
+                    wake.notify_one();
+                }
+                Err(e) => log::error!("Failed to queue {} URLs: {}", total, e),
+            },
+        }
+
+        if queued.len() < total {
+            self.release(total - queued.len());
+        }
+        queued
+    }
+
+    /// Marks `url` as finished, closing the queue if nothing is left.
+    pub async fn done(&self, url: &str) {
+        self.finish_in_flight(url);
+        if let Store::Disk { queue, .. } = &self.inner.store
+            && let Err(e) = queue.finish(url).await
+        {
+            log::warn!("Failed to mark {} as done: {}", url, e);
+        }
+        self.release(1);
+    }
+
+    /// Gives up on `url` without crawling it. A disk frontier queues it again
+    /// when the crawl resumes.
+    pub fn abandon(&self, url: &str) {
+        self.finish_in_flight(url);
+        self.release(1);
+    }
+
+    fn finish_in_flight(&self, url: &str) {
+        if let Ok(mut in_flight) = self.inner.in_flight.lock() {
+            in_flight.remove(url);
+        }
+    }
+
+    /// What is left to crawl, with the `next` URLs to be crawled.
+    pub async fn snapshot(&self, next: usize) -> Result<FrontierSnapshot> {
+        let mut snapshot = match &self.inner.store {
+            Store::Memory { queued, .. } => {
+                let mut snapshot = FrontierSnapshot::default();
+                if let Ok(queued) = queued.lock() {
+                    for entry in queued.values() {
+                        snapshot.add_queued(entry.clone(), next);
+                    }
+                }
+                snapshot
+            }
+            // The file also has the URLs waiting in the window, as in flight
+            Store::Disk { queue, .. } => return queue.snapshot(next).await,
+        };
+        if let Ok(in_flight) = self.inner.in_flight.lock() {
+            snapshot.in_flight = in_flight.len();
+            let mut urls: Vec<QueuedUrl> = in_flight
+                .iter()
+                .map(|(url, (depth, priority))| QueuedUrl {
+                    url: url.clone(),
+                    depth: *depth,
+                    priority: *priority,
+                })
+                .collect();
+            urls.sort_by(|a, b| a.url.cmp(&b.url));
+            urls.truncate(next);
+            snapshot.in_flight_urls = urls;
+        }
+        Ok(snapshot)
+    }
+
+    fn release(&self, n: usize) {
+        if self.inner.pending.fetch_sub(n, Ordering::SeqCst) == n {
+            match &self.inner.store {
+                Store::Memory { tx, .. } => {
+                    if let Ok(mut tx) = tx.lock() {
+                        tx.take();
+                    }
+                }
+                Store::Disk { wake, closed, .. } => {
+                    closed.store(true, Ordering::SeqCst);
+                    wake.notify_one();
+                }
+            }
+        }
+    }
+
+    /// Moves queued URLs from disk into `tx` as room frees up, until the frontier
+    /// closes or the crawl stops reading.
+    async fn feed(self, tx: mpsc::Sender<String>) {
+        let Store::Disk { queue, wake, closed } = &self.inner.store else {
+            return;
+        };
+        loop {
+            if closed.load(Ordering::SeqCst) {
+                break;
+            }
+            // Wait for room first, so URLs aren't marked in flight long before they are crawled
+            match tx.reserve().await {
+                Ok(permit) => drop(permit),
+                Err(_) => break,
+            }
+            match queue.take(tx.capacity()).await {
+                Ok(urls) if urls.is_empty() => {
+                    tokio::select! {
+                        _ = wake.notified() => {}
+                        _ = tx.closed() => break,
+                    }
+                }
+                Ok(urls) => {
+                    for (url, depth, priority) in urls {
+                        if let Ok(mut in_flight) = self.inner.in_flight.lock() {
+                            in_flight.insert(url.clone(), (depth.max(0) as usize, priority));
+                        }
+                        if tx.send(url).await.is_err() {
+                            return;
+                        }
+                    }
+                }
+                Err(e) => {
+                    log::error!("Failed to read queued URLs: {}", e);
+                    break;
+                }
+            }
+        }
+    }
+}
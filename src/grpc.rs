@@ -0,0 +1,181 @@
+//! The gRPC API of `crawler serve`, for callers living in a gRPC service mesh.
+//! It mirrors the REST API over the same jobs: `StartCrawl`, `GetStatus` and
+//! `Cancel` work like `POST /jobs`, `GET /jobs/{id}` and `DELETE /jobs/{id}`,
+//! and `StreamItems` streams the items of a running job as they are
+//! extracted. The service is defined in `proto/crawler.proto`. Calls need
+//! the same bearer token as the REST API, in `authorization` metadata.
+
+use crate::crawler::{CrawlerState, shutdown_signal};
+use crate::error::{Error, Result};
+use crate::events::CrawlEvent;
+use crate::serve::{JobError, JobInfo, JobSpec, JobStatus, Server};
+use serde_json::{Map, Value};
+use tokio::net::TcpListener;
+use tokio::sync::broadcast::error::RecvError;
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::ReceiverStream;
+use tonic::transport::server::TcpIncoming;
+use tonic::{Request, Response, Status};
+
+/// Types generated from `proto/crawler.proto`.
+pub mod proto {
+    tonic::include_proto!("crawler.v1");
+}
+
+use proto::crawler_server::{Crawler, CrawlerServer};
+use proto::start_crawl_request::Spider;
+
+/// Items buffered for a slow `StreamItems` caller. The job doesn't wait for
+/// the caller: once it falls further behind than the event bus holds, items
+/// are lost and the stream ends with `DATA_LOSS`.
+const ITEM_STREAM_BUFFER: usize = 256;
+
+/// Serves the gRPC API on `listener` until Ctrl-C or SIGTERM.
+pub(crate) async fn serve(listener: TcpListener, server: Server) -> Result<()> {
+    tonic::transport::Server::builder()
+        .add_service(CrawlerServer::with_interceptor(
+            CrawlerService { server: server.clone() },
+            move |request: Request<()>| {
+                let authorization = request.metadata().get("authorization").and_then(|value| value.to_str().ok());
+                if server.authorized(authorization) {
+                    Ok(request)
+                } else {
+                    Err(Status::unauthenticated("missing or wrong bearer token"))
+                }
+            },
+        ))
+        .serve_with_incoming_shutdown(TcpIncoming::from(listener), async {
+            let signal = shutdown_signal().await;
+            log::info!("Received {}, stopping the gRPC API", signal);
+        })
+        .await
+        .map_err(|e| Error::Internal(format!("gRPC server failed: {}", e)))
+}
+
+struct CrawlerService {
+    server: Server,
+}
+
+#[tonic::async_trait]
+impl Crawler for CrawlerService {
+    async fn start_crawl(
+        &self,
+        request: Request<proto::StartCrawlRequest>,
+    ) -> std::result::Result<Response<proto::Job>, Status> {
+        let spec = job_spec(request.into_inner())?;
+        let info = self.server.submit(spec).await.map_err(status)?;
+        Ok(Response::new(job(info)))
+    }
+
+    async fn get_status(
+        &self,
+        request: Request<proto::GetStatusRequest>,
+    ) -> std::result::Result<Response<proto::Job>, Status> {
+        let info = self.server.job(&request.into_inner().id).map_err(status)?;
+        Ok(Response::new(job(info)))
+    }
+
+    type StreamItemsStream = ReceiverStream<std::result::Result<proto::Item, Status>>;
+
+    async fn stream_items(
+        &self,
+        request: Request<proto::StreamItemsRequest>,
+    ) -> std::result::Result<Response<Self::StreamItemsStream>, Status> {
+        let id = request.into_inner().id;
+        let engine = self.server.running_engine(&id).map_err(status)?;
+        let mut events = engine.subscribe();
+        if *engine.watch_state().borrow() == CrawlerState::Stopped {
+            return Err(status(JobError::NotRunning(id, JobStatus::Finished)));
+        }
+        let (tx, rx) = mpsc::channel(ITEM_STREAM_BUFFER);
+        tokio::spawn(async move {
+            loop {
+                let item = match events.recv().await {
+                    Ok(CrawlEvent::ItemExtracted { url, item }) => proto::Item {
+                        url,
+                        json: item.to_string(),
+                    },
+                    Ok(CrawlEvent::StateChanged {
+                        state: CrawlerState::Stopped,
+                    })
+                    | Err(RecvError::Closed) => break,
+                    Ok(_) => continue,
+                    Err(RecvError::Lagged(skipped)) => {
+                        log::warn!("Item stream of job {} fell behind, skipped {} events", id, skipped);
+                        let lost = Status::data_loss(format!("Fell behind the job, {} events were skipped", skipped));
+                        let _ = tx.send(Err(lost)).await;
+                        break;
+                    }
+                };
+                // The caller hung up
+                if tx.send(Ok(item)).await.is_err() {
+                    break;
+                }
+            }
+        });
+        Ok(Response::new(ReceiverStream::new(rx)))
+    }
+
+    async fn cancel(
+        &self,
+        request: Request<proto::CancelRequest>,
+    ) -> std::result::Result<Response<proto::Job>, Status> {
+        let info = self.server.cancel(&request.into_inner().id).await.map_err(status)?;
+        Ok(Response::new(job(info)))
+    }
+}
+
+/// The [`JobSpec`] of a `StartCrawl` request, whose configs and params are JSON.
+fn job_spec(request: proto::StartCrawlRequest) -> std::result::Result<JobSpec, Status> {
+    let json = |name: &str, text: &str| {
+        serde_json::from_str::<Value>(text)
+            .map_err(|e| Status::invalid_argument(format!("Invalid {}: {}", name, e)))
+    };
+    let (config, spider) = match request.spider {
+        Some(Spider::Config(path)) => (Some(path.into()), None),
+        Some(Spider::SpiderJson(text)) => (None, Some(json("spider_json", &text)?)),
+        None => (None, None),
+    };
+    let params = match request.params_json.trim() {
+        "" => Map::new(),
+        text => match json("params_json", text)? {
+            Value::Object(params) => params,
+            _ => return Err(Status::invalid_argument("Invalid params_json: expected a JSON object")),
+        },
+    };
+    Ok(JobSpec {
+        config,
+        spider,
+        params,
+        concurrency: request.concurrency.map(|concurrency| concurrency as usize),
+    })
+}
+
+fn job(info: JobInfo) -> proto::Job {
+    let status = match info.status {
+        JobStatus::Running => proto::JobStatus::Running,
+        JobStatus::Finished => proto::JobStatus::Finished,
+        JobStatus::Cancelled => proto::JobStatus::Cancelled,
+    };
+    proto::Job {
+        metrics_json: serde_json::to_string(&info.metrics).unwrap_or_default(),
+        id: info.id,
+        spider: info.spider,
+        status: status.into(),
+        concurrency: info.concurrency as u32,
+        started_at: info.started_at,
+        finished_at: info.finished_at,
+    }
+}
+
+/// The gRPC status of a [`JobError`], matching the HTTP status of the REST API.
+fn status(e: JobError) -> Status {
+    let message = e.to_string();
+    match e {
+        JobError::Busy(_) => Status::resource_exhausted(message),
+        JobError::Invalid(_) => Status::invalid_argument(message),
+        JobError::NotFound(_) => Status::not_found(message),
+        JobError::NotRunning(..) => Status::failed_precondition(message),
+        JobError::Unavailable => Status::internal(message),
+    }
+}
@@ -0,0 +1,132 @@
+//! Health endpoints for crawls run as a service, so an orchestrator can restart
+//! a wedged crawler:
+//!
+//! - `/healthz` fails once a running crawl has shown no activity for the stall timeout
+//! - `/readyz` succeeds while the crawl is running or paused
+//! - `/state` reports the engine state, the last activity and the current metrics
+
+use crate::crawler::{CrawlerEngine, CrawlerState};
+use crate::error::Result;
+use crate::metrics::snapshot::MetricsSnapshot;
+use axum::Json;
+use axum::Router;
+use axum::extract::State;
+use axum::http::StatusCode;
+use axum::routing::get;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::broadcast::error::RecvError;
+use tokio::sync::watch;
+
+#[derive(Clone)]
+struct Health {
+    state: watch::Receiver<CrawlerState>,
+    metrics: watch::Receiver<MetricsSnapshot>,
+    /// When the engine last emitted an event
+    last_activity: Arc<Mutex<DateTime<Utc>>>,
+    stall_timeout: Duration,
+}
+
+#[derive(Debug, Serialize)]
+struct StateReport {
+    state: CrawlerState,
+    last_activity: String,
+    seconds_since_activity: i64,
+    stalled: bool,
+    metrics: MetricsSnapshot,
+}
+
+impl Health {
+    fn seconds_since_activity(&self) -> i64 {
+        let last = self.last_activity.lock().map(|last| *last).unwrap_or_else(|_| Utc::now());
+        (Utc::now() - last).num_seconds().max(0)
+    }
+
+    /// Whether the crawl is running but nothing happened for the stall timeout.
+    fn is_stalled(&self) -> bool {
+        *self.state.borrow() == CrawlerState::Running
+            && self.seconds_since_activity() as u64 > self.stall_timeout.as_secs()
+    }
+}
+
+/// Serves the health endpoints of `engine` on `addr` until the process exits,
+/// returning the address actually bound.
+pub async fn serve(addr: SocketAddr, engine: &CrawlerEngine, stall_timeout: Duration) -> Result<SocketAddr> {
+    let health = Health {
+        state: engine.watch_state(),
+        metrics: engine.watch_metrics(),
+        last_activity: Arc::new(Mutex::new(Utc::now())),
+        stall_timeout,
+    };
+
+    // Every request, response, item and state change counts as activity
+    let mut events = engine.subscribe();
+    let last_activity = health.last_activity.clone();
+    tokio::spawn(async move {
+        while let Ok(_) | Err(RecvError::Lagged(_)) = events.recv().await {
+            if let Ok(mut last) = last_activity.lock() {
+                *last = Utc::now();
+            }
+        }
+    });
+
+    let app = Router::new()
+        .route("/healthz", get(healthz))
+        .route("/readyz", get(readyz))
+        .route("/state", get(state))
+        .with_state(health);
+    let listener = tokio::net::TcpListener::bind(addr).await?;
+    let addr = listener.local_addr()?;
+    tokio::spawn(async move {
+        if let Err(e) = axum::serve(listener, app).await {
+            log::error!("Health server stopped: {}", e);
+        }
+    });
+    log::info!("Serving health endpoints on http://{}", addr);
+    Ok(addr)
+}
+
+async fn healthz(State(health): State<Health>) -> (StatusCode, String) {
+    if health.is_stalled() {
+        (
+            StatusCode::SERVICE_UNAVAILABLE,
+            format!("stalled: no activity for {}s\n", health.seconds_since_activity()),
+        )
+    } else {
+        (StatusCode::OK, "ok\n".to_string())
+    }
+}
+
+async fn readyz(State(health): State<Health>) -> (StatusCode, String) {
+    let state = *health.state.borrow();
+    match state {
+        CrawlerState::Running | CrawlerState::Paused => (StatusCode::OK, "ready\n".to_string()),
+        CrawlerState::Idle | CrawlerState::Stopped => (
+            StatusCode::SERVICE_UNAVAILABLE,
+            format!("not ready: {}\n", state_name(state)),
+        ),
+    }
+}
+
+async fn state(State(health): State<Health>) -> Json<StateReport> {
+    let last_activity = health.last_activity.lock().map(|last| *last).unwrap_or_else(|_| Utc::now());
+    Json(StateReport {
+        state: *health.state.borrow(),
+        last_activity: last_activity.to_rfc3339(),
+        seconds_since_activity: health.seconds_since_activity(),
+        stalled: health.is_stalled(),
+        metrics: health.metrics.borrow().clone(),
+    })
+}
+
+fn state_name(state: CrawlerState) -> &'static str {
+    match state {
+        CrawlerState::Idle => "idle",
+        CrawlerState::Running => "running",
+        CrawlerState::Paused => "paused",
+        CrawlerState::Stopped => "stopped",
+    }
+}
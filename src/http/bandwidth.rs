@@ -0,0 +1,82 @@
+//! Bandwidth caps on response bodies, as token buckets refilled at the configured
+//! rate. Bodies are read chunk by chunk, and reading pauses while a bucket is empty.
+
+use crate::config::schema::BandwidthConfig;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::Mutex;
+use tokio::time::sleep;
+
+/// Limits throughput overall and per host.
+pub struct BandwidthLimiter {
+    global: Option<TokenBucket>,
+    /// Bytes per second allowed for each host
+    per_domain: Option<f64>,
+    domains: std::sync::Mutex<HashMap<String, Arc<TokenBucket>>>,
+}
+
+impl BandwidthLimiter {
+    pub fn new(config: &BandwidthConfig) -> Self {
+        Self {
+            global: config.global.map(|kbps| TokenBucket::new(bytes_per_sec(kbps))),
+            per_domain: config.per_domain.map(bytes_per_sec),
+            domains: std::sync::Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Waits until `bytes` more bytes from `host` fit within the limits.
+    pub async fn consume(&self, host: &str, bytes: usize) {
+        if let Some(bucket) = self.domain_bucket(host) {
+            bucket.consume(bytes).await;
+        }
+        if let Some(bucket) = &self.global {
+            bucket.consume(bytes).await;
+        }
+    }
+
+    fn domain_bucket(&self, host: &str) -> Option<Arc<TokenBucket>> {
+        let rate = self.per_domain?;
+        let mut domains = self.domains.lock().ok()?;
+        Some(
+            domains
+                .entry(host.to_string())
+                .or_insert_with(|| Arc::new(TokenBucket::new(rate)))
+                .clone(),
+        )
+    }
+}
+
+/// Kilobits per second to bytes per second.
+fn bytes_per_sec(kbps: u64) -> f64 {
+    (kbps.max(1) * 1000) as f64 / 8.0
+}
+
+struct TokenBucket {
+    rate: f64,
+    /// Available bytes, negative while a consumer waits off its debt, and when they were counted
+    state: Mutex<(f64, Instant)>,
+}
+
+impl TokenBucket {
+    /// A bucket refilled at `rate` bytes per second, holding up to one second's worth.
+    fn new(rate: f64) -> Self {
+        Self {
+            rate,
+            state: Mutex::new((rate, Instant::now())),
+        }
+    }
+
+    async fn consume(&self, bytes: usize) {
+        // Held while sleeping, so concurrent readers are served one after another
+        let mut state = self.state.lock().await;
+        let (tokens, counted_at) = &mut *state;
+        let now = Instant::now();
+        *tokens = (*tokens + now.duration_since(*counted_at).as_secs_f64() * self.rate).min(self.rate);
+        *counted_at = now;
+        *tokens -= bytes as f64;
+        if *tokens < 0.0 {
+            sleep(Duration::from_secs_f64(-*tokens / self.rate)).await;
+        }
+    }
+}
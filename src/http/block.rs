@@ -0,0 +1,72 @@
+//! Recognizes block and captcha pages, so they count as failures rather than
+//! as pages scraped successfully.
+
+use crate::config::schema::BlockDetectionConfig;
+use reqwest::StatusCode;
+use reqwest::header::{HeaderMap, SERVER};
+
+/// Text only found on Cloudflare challenge and block pages, lowercased.
+const CLOUDFLARE_MARKERS: &[&str] = &[
+    "cf-browser-verification",
+    "challenges.cloudflare.com",
+    "cf_chl_opt",
+    "<title>just a moment...</title>",
+    "attention required! | cloudflare",
+];
+
+pub struct BlockDetector {
+    statuses: Vec<u16>,
+    /// Lowercased
+    body_patterns: Vec<String>,
+    cloudflare: bool,
+    min_content_length: Option<usize>,
+}
+
+impl BlockDetector {
+    pub fn new(config: &BlockDetectionConfig) -> Self {
+        Self {
+            statuses: config.statuses.clone(),
+            body_patterns: config.body_patterns.iter().map(|p| p.to_lowercase()).collect(),
+            cloudflare: config.cloudflare,
+            min_content_length: config.min_content_length,
+        }
+    }
+
+    /// Why a response looks blocked judging by its status and headers, if it does.
+    pub fn check_response(&self, status: StatusCode, headers: &HeaderMap) -> Option<String> {
+        if self.statuses.contains(&status.as_u16()) {
+            return Some(format!("HTTP {}", status.as_u16()));
+        }
+        if self.cloudflare {
+            let header = |name: &str| headers.get(name).and_then(|v| v.to_str().ok()).unwrap_or_default();
+            if header("cf-mitigated").eq_ignore_ascii_case("challenge") {
+                return Some("Cloudflare challenge".to_string());
+            }
+            let from_cloudflare = headers
+                .get(SERVER)
+                .and_then(|v| v.to_str().ok())
+                .is_some_and(|server| server.eq_ignore_ascii_case("cloudflare"));
+            if from_cloudflare && matches!(status.as_u16(), 403 | 503) {
+                return Some(format!("HTTP {} from Cloudflare", status.as_u16()));
+            }
+        }
+        None
+    }
+
+    /// Why a page body looks like a block or captcha page, if it does.
+    pub fn check_body(&self, body: &str) -> Option<String> {
+        if let Some(min) = self.min_content_length
+            && body.len() < min
+        {
+            return Some(format!("{} bytes, fewer than {}", body.len(), min));
+        }
+        let body = body.to_lowercase();
+        if let Some(pattern) = self.body_patterns.iter().find(|p| body.contains(p.as_str())) {
+            return Some(format!("page contains '{}'", pattern));
+        }
+        if self.cloudflare && CLOUDFLARE_MARKERS.iter().any(|marker| body.contains(marker)) {
+            return Some("Cloudflare challenge page".to_string());
+        }
+        None
+    }
+}
@@ -0,0 +1,158 @@
+use crate::config::schema::ContentEncoding;
+use crate::http::bandwidth::BandwidthLimiter;
+use crate::error::{Error, Result};
+use bytes::{Bytes, BytesMut};
+use reqwest::{Response, header};
+use std::io::Read;
+
+const UTF8_BOM: &[u8] = b"\xEF\xBB\xBF";
+
+/// A fully read response body, decoded according to its `Content-Encoding`.
+#[derive(Debug)]
+pub struct Body {
+    /// Decompressed bytes
+    pub bytes: Bytes,
+    /// Bytes received on the wire, before decompression
+    pub wire_bytes: usize,
+    /// Value of the `Content-Type` header, if any
+    pub content_type: Option<String>,
+}
+
+impl Body {
+    /// Consumes the body and decodes it as text, honoring the charset declared in
+    /// `Content-Type`. Valid UTF-8 bodies are converted in place without copying.
+    pub fn into_text(self) -> String {
+        let encoding = self
+            .content_type
+            .as_deref()
+            .and_then(|ct| {
+                ct.split(';')
+                    .filter_map(|param| param.trim().strip_prefix("charset="))
+                    .next()
+            })
+            .and_then(|label| encoding_rs::Encoding::for_label(label.trim_matches('"').as_bytes()))
+            .unwrap_or(encoding_rs::UTF_8);
+
+        if encoding == encoding_rs::UTF_8 && !self.bytes.starts_with(UTF8_BOM) {
+            return match String::from_utf8(Vec::from(self.bytes)) {
+                Ok(text) => text,
+                Err(e) => String::from_utf8_lossy(e.as_bytes()).into_owned(),
+            };
+        }
+
+        let (text, _, _) = encoding.decode(&self.bytes);
+        text.into_owned()
+    }
+}
+
+/// Builds the `Accept-Encoding` header value for the configured encodings.
+pub fn accept_encoding(encodings: &[ContentEncoding]) -> String {
+    if encodings.is_empty() {
+        return "identity".to_string();
+    }
+    encodings
+        .iter()
+        .map(ContentEncoding::token)
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Reads a response body, refusing to buffer more than `limit` bytes either
+/// on the wire or after decompression, and no faster than `bandwidth` allows.
+pub async fn read_body(mut res: Response, limit: usize, bandwidth: Option<&BandwidthLimiter>) -> Result<Body> {
+    let url = res.url().to_string();
+    let content_type = res
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .map(str::to_string);
+    let encoding = res
+        .headers()
+        .get(header::CONTENT_ENCODING)
+        .and_then(|v| v.to_str().ok())
+        .map(|v| v.trim().to_ascii_lowercase());
+
+    let raw = read_raw(&mut res, limit, bandwidth).await?;
+    let wire_bytes = raw.len();
+    let bytes = decode_content(raw, encoding.as_deref(), limit, &url)?;
+
+    Ok(Body {
+        bytes,
+        wire_bytes,
+        content_type,
+    })
+}
+
+/// Reads a response body as received on the wire, still compressed, refusing
+/// to buffer more than `limit` bytes.
+pub async fn read_raw(res: &mut Response, limit: usize, bandwidth: Option<&BandwidthLimiter>) -> Result<Bytes> {
+    let url = res.url().to_string();
+    let host = res.url().host_str().unwrap_or_default().to_string();
+    let capacity = res
+        .content_length()
+        .map_or(0, |len| (len as usize).min(limit));
+    let mut raw = BytesMut::with_capacity(capacity);
+    while let Some(chunk) = res.chunk().await? {
+        if raw.len() + chunk.len() > limit {
+            return Err(oversized(&url, limit));
+        }
+        raw.extend_from_slice(&chunk);
+        if let Some(bandwidth) = bandwidth {
+            bandwidth.consume(&host, chunk.len()).await;
+        }
+    }
+    Ok(raw.freeze())
+}
+
+/// Decompresses `raw` according to a lowercase `Content-Encoding` token.
+pub fn decode_content(raw: Bytes, encoding: Option<&str>, limit: usize, url: &str) -> Result<Bytes> {
+    let bytes = match encoding {
+        None | Some("") | Some("identity") => raw,
+        Some("gzip") | Some("x-gzip") => decode(flate2::read::GzDecoder::new(&raw[..]), limit, url)?,
+        Some("deflate") => decode(flate2::read::ZlibDecoder::new(&raw[..]), limit, url)?,
+        Some("br") => decode(
+            brotli_decompressor::Decompressor::new(&raw[..], 4096),
+            limit,
+            url,
+        )?,
+        Some("zstd") => {
+            let decoder = zstd::stream::read::Decoder::new(&raw[..])
+                .map_err(|e| decode_error(url, e))?;
+            decode(decoder, limit, url)?
+        }
+        Some(other) => {
+            return Err(decode_error(
+                url,
+                format!("unsupported Content-Encoding '{}'", other),
+            ));
+        }
+    };
+    Ok(bytes)
+}
+
+fn decode<R: Read>(reader: R, limit: usize, url: &str) -> Result<Bytes> {
+    let mut out = Vec::new();
+    // Read one byte past the limit so an exactly-full body is still accepted
+    reader
+        .take(limit as u64 + 1)
+        .read_to_end(&mut out)
+        .map_err(|e| decode_error(url, e))?;
+    if out.len() > limit {
+        return Err(oversized(url, limit));
+    }
+    Ok(Bytes::from(out))
+}
+
+fn decode_error(url: &str, message: impl std::fmt::Display) -> Error {
+    Error::Decode {
+        url: url.to_string(),
+        message: message.to_string(),
+    }
+}
+
+fn oversized(url: &str, limit: usize) -> Error {
+    Error::BodyTooLarge {
+        url: url.to_string(),
+        limit,
+    }
+}
@@ -0,0 +1,180 @@
+//! Request budgets for crawls of paid or rate-limited APIs. Every request a
+//! spider sends is counted in the state database, per calendar period and
+//! across runs, and none is sent once the period's budget is used up, so a
+//! runaway crawl can't blow a monthly quota.
+
+use crate::config::schema::{BudgetAction, BudgetPeriod, OnExhausted, RequestBudgetConfig};
+use crate::error::{Error, Result};
+use crate::http::transport::Transport;
+use crate::state::StateDb;
+use async_trait::async_trait;
+use chrono::{DateTime, Datelike, Months, NaiveDate, TimeDelta, Utc};
+use reqwest::{Request, Response};
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use url::Url;
+
+/// The requests of one spider, counted against its budget.
+pub struct RequestBudget {
+    state: StateDb,
+    spider: String,
+    config: RequestBudgetConfig,
+    /// Parsed `on_exhausted: pause_until`
+    pause_until: Option<DateTime<Utc>>,
+    window: Mutex<Window>,
+}
+
+/// The span of time a budget is counted over: a calendar period, or what is
+/// left of it after a `pause_until`.
+#[derive(Debug, Clone, Copy)]
+struct Window {
+    start: DateTime<Utc>,
+    /// When the next window starts; `None` if the budget never renews
+    ends: Option<DateTime<Utc>>,
+    used: u64,
+}
+
+impl RequestBudget {
+    pub async fn open(config: &RequestBudgetConfig, state: StateDb, spider: &str) -> Result<Self> {
+        let pause_until = parse(config)?;
+        let window = load(&state, spider, config.period, Utc::now()).await?;
+        log::info!(
+            "Request budget: {} of {} requests used since {}",
+            window.used,
+            config.max_requests,
+            window.start.format("%Y-%m-%d %H:%M UTC")
+        );
+        Ok(Self {
+            state,
+            spider: spider.to_string(),
+            config: config.clone(),
+            pause_until,
+            window: Mutex::new(window),
+        })
+    }
+
+    /// Checks `config` without opening a budget.
+    pub fn validate(config: &RequestBudgetConfig) -> Result<()> {
+        parse(config).map(|_| ())
+    }
+
+    /// Counts a request for `url`, or fails with [`Error::RequestBudget`] if
+    /// the budget is used up.
+    pub async fn take(&self, url: &Url) -> Result<()> {
+        let now = Utc::now();
+        let mut window = self.window.lock().await;
+        if window.ends.is_some_and(|ends| now >= ends) {
+            *window = load(&self.state, &self.spider, self.config.period, now).await?;
+            log::info!("Request budget renewed, {} requests used so far", window.used);
+        }
+        if window.used >= self.config.max_requests {
+            let resumes_at = match self.config.on_exhausted {
+                OnExhausted::Action(BudgetAction::Stop) => None,
+                OnExhausted::Action(BudgetAction::Pause) => window.ends,
+                OnExhausted::PauseUntil { .. } => match self.pause_until.filter(|until| *until > now) {
+                    Some(until) => {
+                        // Recorded, so later runs count from then on as well
+                        self.state.add_budget_requests(&self.spider, until, 0).await?;
+                        window.ends = Some(until);
+                        Some(until)
+                    }
+                    // Once it has passed, the budget renews with the period
+                    None => window.ends,
+                },
+            };
+            return Err(Error::RequestBudget {
+                url: url.to_string(),
+                limit: self.config.max_requests,
+                resumes_at,
+            });
+        }
+        self.state.add_budget_requests(&self.spider, window.start, 1).await?;
+        window.used += 1;
+        Ok(())
+    }
+}
+
+/// Wraps a transport, counting every request it sends against a budget.
+pub struct BudgetTransport {
+    inner: Arc<dyn Transport>,
+    budget: Arc<RequestBudget>,
+}
+
+impl BudgetTransport {
+    pub fn new(inner: Arc<dyn Transport>, budget: Arc<RequestBudget>) -> Self {
+        Self { inner, budget }
+    }
+}
+
+#[async_trait]
+impl Transport for BudgetTransport {
+    async fn execute(&self, request: Request) -> Result<Response> {
+        self.budget.take(request.url()).await?;
+        self.inner.execute(request).await
+    }
+}
+
+/// The current window of `spider` at `now`: its calendar period, unless a
+/// pause started a later window within it.
+async fn load(state: &StateDb, spider: &str, period: BudgetPeriod, now: DateTime<Utc>) -> Result<Window> {
+    let period_start = start_of(period, now);
+    let ends = period_start.and_then(|start| end_of(period, start));
+    let (start, used) = match state.budget_window(spider, now).await? {
+        Some((start, used)) if period_start.is_none_or(|period_start| start >= period_start) => (start, used),
+        _ => (period_start.unwrap_or(now), 0),
+    };
+    Ok(Window { start, ends, used })
+}
+
+/// The start of the period containing `now`, or `None` for `total`.
+fn start_of(period: BudgetPeriod, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+    let today = now.date_naive();
+    let day = match period {
+        BudgetPeriod::Day => today,
+        BudgetPeriod::Week => today - TimeDelta::days(today.weekday().num_days_from_monday() as i64),
+        BudgetPeriod::Month => today.with_day(1)?,
+        BudgetPeriod::Total => return None,
+    };
+    Some(day.and_hms_opt(0, 0, 0)?.and_utc())
+}
+
+/// The start of the period after the one starting at `start`.
+fn end_of(period: BudgetPeriod, start: DateTime<Utc>) -> Option<DateTime<Utc>> {
+    match period {
+        BudgetPeriod::Day => Some(start + TimeDelta::days(1)),
+        BudgetPeriod::Week => Some(start + TimeDelta::weeks(1)),
+        BudgetPeriod::Month => start.checked_add_months(Months::new(1)),
+        BudgetPeriod::Total => None,
+    }
+}
+
+/// Checks `config`, returning the time of `pause_until` if set.
+fn parse(config: &RequestBudgetConfig) -> Result<Option<DateTime<Utc>>> {
+    if config.max_requests == 0 {
+        return Err(Error::Config("request_budget.max_requests must be at least 1".to_string()));
+    }
+    match &config.on_exhausted {
+        OnExhausted::Action(BudgetAction::Stop) => Ok(None),
+        OnExhausted::Action(BudgetAction::Pause) if config.period == BudgetPeriod::Total => Err(Error::Config(
+            "request_budget.on_exhausted: pause waits for the next period, but period: total never renews; \
+             use stop or pause_until"
+                .to_string(),
+        )),
+        OnExhausted::Action(BudgetAction::Pause) => Ok(None),
+        OnExhausted::PauseUntil { pause_until: value } => DateTime::parse_from_rfc3339(value)
+            .map(|time| time.with_timezone(&Utc))
+            .ok()
+            .or_else(|| {
+                let day = NaiveDate::parse_from_str(value, "%Y-%m-%d").ok()?;
+                Some(day.and_hms_opt(0, 0, 0)?.and_utc())
+            })
+            .map(Some)
+            .ok_or_else(|| {
+                Error::Config(format!(
+                    "Invalid request_budget.on_exhausted.pause_until '{}': expected a date like 2026-11-01 \
+                     or a time like 2026-11-01T09:00:00Z",
+                    value
+                ))
+            }),
+    }
+}
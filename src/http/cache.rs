@@ -0,0 +1,410 @@
+//! A page cache in SQLite that several spiders can share. Responses are keyed by
+//! URL and the configured `Vary` headers. Within the TTL they are served without
+//! touching the network; after it they are revalidated with their `ETag` or
+//! `Last-Modified` if they have one. Requests carrying credentials bypass the
+//! cache unless those are among the `Vary` headers, so one spider's logged-in
+//! pages are never served to another.
+
+use crate::config::schema::PageCacheConfig;
+use crate::error::{Error, Result};
+use crate::http::transport::Transport;
+use crate::metrics::collector::MetricsCollector;
+use async_trait::async_trait;
+use reqwest::header::{
+    AUTHORIZATION, CACHE_CONTROL, COOKIE, ETAG, HeaderName, HeaderValue, IF_MODIFIED_SINCE, IF_NONE_MATCH,
+    LAST_MODIFIED,
+};
+use reqwest::{Method, Request, Response, ResponseBuilderExt, StatusCode};
+use sha2::{Digest, Sha256};
+use sqlx::sqlite::{
+    SqliteConnectOptions, SqliteJournalMode, SqlitePool, SqlitePoolOptions, SqliteSynchronous,
+};
+use sqlx::Row;
+use std::sync::Arc;
+use std::time::Duration;
+use url::Url;
+
+/// How long a process waits for another one writing to the shared cache.
+const BUSY_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// An open page cache database.
+#[derive(Debug, Clone)]
+pub struct PageCache {
+    pool: SqlitePool,
+    path: String,
+}
+
+/// What `crawler cache stats` reports.
+#[derive(Debug, Clone)]
+pub struct CacheStats {
+    pub entries: u64,
+    /// Entries stored less than `ttl_secs` ago, if a TTL was given
+    pub fresh: Option<u64>,
+    pub body_bytes: u64,
+    pub hosts: u64,
+    pub oldest: Option<chrono::DateTime<chrono::Utc>>,
+    pub newest: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+/// A cached response.
+struct Entry {
+    status: u16,
+    headers: Vec<(String, String)>,
+    body: Vec<u8>,
+    etag: Option<String>,
+    last_modified: Option<String>,
+    stored_at: i64,
+}
+
+impl PageCache {
+    pub async fn open(path: &str) -> Result<Self> {
+        let error = |e: sqlx::Error| Error::Cache {
+            path: path.to_string(),
+            message: e.to_string(),
+        };
+        let options = SqliteConnectOptions::new()
+            .filename(path)
+            .create_if_missing(true)
+            .journal_mode(SqliteJournalMode::Wal)
+            .synchronous(SqliteSynchronous::Normal)
+            .busy_timeout(BUSY_TIMEOUT);
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect_with(options)
+            .await
+            .map_err(error)?;
+        sqlx::query(
+            "CREATE TABLE IF NOT EXISTS pages (
+                key TEXT PRIMARY KEY,
+                url TEXT NOT NULL,
+                status INTEGER NOT NULL,
+                headers TEXT NOT NULL,
+                body BLOB NOT NULL,
+                etag TEXT,
+                last_modified TEXT,
+                stored_at INTEGER NOT NULL
+            )",
+        )
+        .execute(&pool)
+        .await
+        .map_err(error)?;
+
+        Ok(Self {
+            pool,
+            path: path.to_string(),
+        })
+    }
+
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    fn error(&self, e: impl std::fmt::Display) -> Error {
+        Error::Cache {
+            path: self.path.clone(),
+            message: e.to_string(),
+        }
+    }
+
+    /// Counts the cached pages; with `ttl_secs`, also those still fresh.
+    pub async fn stats(&self, ttl_secs: Option<u64>) -> Result<CacheStats> {
+        let row = sqlx::query(
+            "SELECT COUNT(*), COALESCE(SUM(LENGTH(body)), 0), MIN(stored_at), MAX(stored_at) FROM pages",
+        )
+        .fetch_one(&self.pool)
+        .await
+        .map_err(|e| self.error(e))?;
+        let timestamp = |secs: Option<i64>| secs.and_then(|secs| chrono::DateTime::from_timestamp(secs, 0));
+
+        let urls: Vec<String> = sqlx::query_scalar("SELECT url FROM pages")
+            .fetch_all(&self.pool)
+            .await
+            .map_err(|e| self.error(e))?;
+        let hosts: std::collections::HashSet<String> = urls
+            .iter()
+            .filter_map(|url| Url::parse(url).ok()?.host_str().map(str::to_string))
+            .collect();
+
+        let fresh = match ttl_secs {
+            Some(ttl) => {
+                let since = chrono::Utc::now().timestamp() - ttl as i64;
+                let fresh: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM pages WHERE stored_at > ?")
+                    .bind(since)
+                    .fetch_one(&self.pool)
+                    .await
+                    .map_err(|e| self.error(e))?;
+                Some(fresh as u64)
+            }
+            None => None,
+        };
+
+        Ok(CacheStats {
+            entries: row.get::<i64, _>(0) as u64,
+            fresh,
+            body_bytes: row.get::<i64, _>(1) as u64,
+            hosts: hosts.len() as u64,
+            oldest: timestamp(row.get(2)),
+            newest: timestamp(row.get(3)),
+        })
+    }
+
+    /// Deletes cached pages, only those older than `older_than_secs` if given.
+    /// Returns how many were deleted.
+    pub async fn clear(&self, older_than_secs: Option<u64>) -> Result<u64> {
+        let before = match older_than_secs {
+            Some(secs) => chrono::Utc::now().timestamp() - secs as i64,
+            None => i64::MAX,
+        };
+        let deleted = sqlx::query("DELETE FROM pages WHERE stored_at < ?")
+            .bind(before)
+            .execute(&self.pool)
+            .await
+            .map_err(|e| self.error(e))?
+            .rows_affected();
+        sqlx::query("VACUUM")
+            .execute(&self.pool)
+            .await
+            .map_err(|e| self.error(e))?;
+        Ok(deleted)
+    }
+
+    async fn get(&self, key: &str) -> Result<Option<Entry>> {
+        let row = sqlx::query(
+            "SELECT status, headers, body, etag, last_modified, stored_at FROM pages WHERE key = ?",
+        )
+        .bind(key)
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|e| self.error(e))?;
+        let Some(row) = row else {
+            return Ok(None);
+        };
+        let headers: String = row.get(1);
+        Ok(Some(Entry {
+            status: row.get::<i64, _>(0) as u16,
+            headers: serde_json::from_str(&headers)?,
+            body: row.get(2),
+            etag: row.get(3),
+            last_modified: row.get(4),
+            stored_at: row.get(5),
+        }))
+    }
+
+    async fn put(&self, key: &str, url: &str, entry: &Entry) -> Result<()> {
+        sqlx::query(
+            "INSERT OR REPLACE INTO pages (key, url, status, headers, body, etag, last_modified, stored_at)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?)",
+        )
+        .bind(key)
+        .bind(url)
+        .bind(entry.status as i64)
+        .bind(serde_json::to_string(&entry.headers)?)
+        .bind(&entry.body)
+        .bind(&entry.etag)
+        .bind(&entry.last_modified)
+        .bind(entry.stored_at)
+        .execute(&self.pool)
+        .await
+        .map_err(|e| self.error(e))?;
+        Ok(())
+    }
+
+    /// Marks a revalidated entry as fresh again.
+    async fn touch(&self, key: &str, stored_at: i64) -> Result<()> {
+        sqlx::query("UPDATE pages SET stored_at = ? WHERE key = ?")
+            .bind(stored_at)
+            .bind(key)
+            .execute(&self.pool)
+            .await
+            .map_err(|e| self.error(e))?;
+        Ok(())
+    }
+}
+
+/// Wraps a transport, serving GET requests from a [`PageCache`] when possible.
+///
+/// Bodies are stored as received on the wire, so cached pages go through the
+/// same decoding as fresh ones. Cache errors are logged and the request goes
+/// to the network.
+pub struct CachingTransport {
+    inner: Arc<dyn Transport>,
+    cache: PageCache,
+    ttl_secs: i64,
+    vary: Vec<HeaderName>,
+    /// Headers that make a request credentialed
+    credentials: Vec<HeaderName>,
+    max_body_bytes: usize,
+    metrics: Arc<MetricsCollector>,
+}
+
+impl CachingTransport {
+    pub fn new(
+        inner: Arc<dyn Transport>,
+        cache: PageCache,
+        config: &PageCacheConfig,
+        max_body_bytes: usize,
+        metrics: Arc<MetricsCollector>,
+    ) -> Result<Self> {
+        let vary = config
+            .vary_headers
+            .iter()
+            .map(|name| {
+                HeaderName::from_bytes(name.as_bytes())
+                    .map_err(|e| Error::Config(format!("Invalid page_cache vary header '{}': {}", name, e)))
+            })
+            .collect::<Result<_>>()?;
+        Ok(Self {
+            inner,
+            cache,
+            ttl_secs: config.ttl_secs as i64,
+            vary,
+            credentials: vec![AUTHORIZATION, COOKIE],
+            max_body_bytes,
+            metrics,
+        })
+    }
+
+    /// Also counts requests carrying `name`, e.g. a signature, as credentialed.
+    pub fn with_credential_header(mut self, name: &str) -> Result<Self> {
+        let name = HeaderName::from_bytes(name.as_bytes())
+            .map_err(|e| Error::Config(format!("Invalid credential header '{}': {}", name, e)))?;
+        self.credentials.push(name);
+        Ok(self)
+    }
+
+    /// Whether `request` carries credentials the cache key doesn't vary on.
+    fn is_credentialed(&self, request: &Request) -> bool {
+        self.credentials
+            .iter()
+            .any(|name| request.headers().contains_key(name) && !self.vary.contains(name))
+    }
+
+    /// The URL and the request's values of the vary headers, hashed.
+    fn key(&self, request: &Request) -> String {
+        let mut hasher = Sha256::new();
+        hasher.update(request.url().as_str());
+        for name in &self.vary {
+            hasher.update(b"\n");
+            hasher.update(name.as_str());
+            hasher.update(b":");
+            if let Some(value) = request.headers().get(name) {
+                hasher.update(value.as_bytes());
+            }
+        }
+        hasher.finalize().iter().map(|b| format!("{:02x}", b)).collect()
+    }
+
+    async fn cached(&self, key: &str) -> Option<Entry> {
+        match self.cache.get(key).await {
+            Ok(entry) => entry,
+            Err(e) => {
+                log::warn!("Failed to read the page cache: {}", e);
+                None
+            }
+        }
+    }
+
+    /// Buffers `res` and stores it unless it asks not to be.
+    async fn store(&self, key: &str, url: Url, res: Response) -> Result<Response> {
+        let status = res.status().as_u16();
+        let no_store = res
+            .headers()
+            .get(CACHE_CONTROL)
+            .and_then(|v| v.to_str().ok())
+            .is_some_and(|v| v.to_ascii_lowercase().contains("no-store"));
+        if status != 200 || no_store {
+            return Ok(res);
+        }
+        let header = |name| {
+            res.headers()
+                .get(name)
+                .and_then(|v: &HeaderValue| v.to_str().ok())
+                .map(str::to_string)
+        };
+        let etag = header(ETAG);
+        let last_modified = header(LAST_MODIFIED);
+        let headers: Vec<(String, String)> = res
+            .headers()
+            .iter()
+            .filter_map(|(k, v)| Some((k.to_string(), v.to_str().ok()?.to_string())))
+            .collect();
+
+        let mut res = res;
+        let mut body = Vec::new();
+        while let Some(chunk) = res.chunk().await? {
+            if body.len() + chunk.len() > self.max_body_bytes {
+                return Err(Error::BodyTooLarge {
+                    url: url.to_string(),
+                    limit: self.max_body_bytes,
+                });
+            }
+            body.extend_from_slice(&chunk);
+        }
+        let entry = Entry {
+            status,
+            headers,
+            body,
+            etag,
+            last_modified,
+            stored_at: chrono::Utc::now().timestamp(),
+        };
+        if let Err(e) = self.cache.put(key, url.as_str(), &entry).await {
+            log::warn!("Failed to store {} in the page cache: {}", url, e);
+        }
+        entry.into_response(url)
+    }
+}
+
+impl Entry {
+    fn into_response(self, url: Url) -> Result<Response> {
+        let mut builder = http::Response::builder().status(self.status).url(url.clone());
+        for (name, value) in &self.headers {
+            builder = builder.header(name.as_str(), value.as_str());
+        }
+        let response = builder
+            .body(self.body)
+            .map_err(|e| Error::Internal(format!("Invalid cached response for {}: {}", url, e)))?;
+        Ok(Response::from(response))
+    }
+}
+
+#[async_trait]
+impl Transport for CachingTransport {
+    async fn execute(&self, mut request: Request) -> Result<Response> {
+        if request.method() != Method::GET || self.is_credentialed(&request) {
+            return self.inner.execute(request).await;
+        }
+        let url = request.url().clone();
+        let key = self.key(&request);
+        let now = chrono::Utc::now().timestamp();
+
+        let Some(entry) = self.cached(&key).await else {
+            let res = self.inner.execute(request).await?;
+            return self.store(&key, url, res).await;
+        };
+        if now - entry.stored_at < self.ttl_secs {
+            log::debug!("Serving {} from the page cache", url);
+            self.metrics.increment_cache_hits();
+            return entry.into_response(url);
+        }
+
+        // Stale: ask the server whether the cached copy still holds
+        let headers = request.headers_mut();
+        if let Some(value) = entry.etag.as_deref().and_then(|v| HeaderValue::from_str(v).ok()) {
+            headers.insert(IF_NONE_MATCH, value);
+        }
+        if let Some(value) = entry.last_modified.as_deref().and_then(|v| HeaderValue::from_str(v).ok()) {
+            headers.insert(IF_MODIFIED_SINCE, value);
+        }
+        let res = self.inner.execute(request).await?;
+        if res.status() == StatusCode::NOT_MODIFIED {
+            log::debug!("{} not modified, serving it from the page cache", url);
+            self.metrics.increment_cache_revalidations();
+            if let Err(e) = self.cache.touch(&key, now).await {
+                log::warn!("Failed to refresh {} in the page cache: {}", url, e);
+            }
+            return entry.into_response(url);
+        }
+        self.store(&key, url, res).await
+    }
+}
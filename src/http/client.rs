@@ -0,0 +1,131 @@
+use crate::config::schema::{CompressionConfig, HttpConfig, SpiderConfig, TlsConfig};
+use crate::error::{Error, Result};
+use crate::http::body::accept_encoding;
+use crate::http::dns::CachingResolver;
+use crate::metrics::collector::MetricsCollector;
+use reqwest::header::{ACCEPT_ENCODING, HeaderMap, HeaderValue};
+use reqwest::{Certificate, Client, ClientBuilder, Identity, Proxy, redirect};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// `User-Agent` of requests unless a proxy pool rotates its own.
+pub const USER_AGENT: &str = "Unified-Crawler/1.0";
+
+/// Base client settings shared by every spider.
+pub fn client_builder() -> ClientBuilder {
+    Client::builder()
+        .timeout(Duration::from_secs(10))
+        .user_agent(USER_AGENT)
+        // Redirects are followed manually so the chain can be recorded
+        .redirect(redirect::Policy::none())
+        .default_headers(encoding_headers(&CompressionConfig::default()))
+}
+
+/// Bodies are decompressed by the crawler itself (see `http::body`) so that
+/// wire and decoded sizes can be measured and capped.
+fn encoding_headers(compression: &CompressionConfig) -> HeaderMap {
+    let mut headers = HeaderMap::new();
+    if let Ok(value) = HeaderValue::from_str(&accept_encoding(&compression.encodings)) {
+        headers.insert(ACCEPT_ENCODING, value);
+    }
+    headers
+}
+
+/// Builds the HTTP client for a spider, applying the network sections of its config.
+pub fn build_client(config: &SpiderConfig, metrics: &Arc<MetricsCollector>) -> Result<Client> {
+    spider_client_builder(config, metrics)?
+        .build()
+        .map_err(|e| Error::Config(format!("Failed to build HTTP client: {}", e)))
+}
+
+/// Like [`build_client`], but sending every request through `proxy`.
+pub fn build_proxied_client(
+    config: &SpiderConfig,
+    metrics: &Arc<MetricsCollector>,
+    proxy: Proxy,
+) -> Result<Client> {
+    spider_client_builder(config, metrics)?
+        .proxy(proxy)
+        .build()
+        .map_err(|e| Error::Config(format!("Failed to build HTTP client: {}", e)))
+}
+
+fn spider_client_builder(config: &SpiderConfig, metrics: &Arc<MetricsCollector>) -> Result<ClientBuilder> {
+    let mut builder = client_builder();
+
+    if let Some(http) = &config.http {
+        builder = apply_http(builder, http);
+    }
+
+    if let Some(tls) = &config.tls {
+        builder = apply_tls(builder, tls)?;
+    }
+
+    if let Some(compression) = &config.compression {
+        builder = builder.default_headers(encoding_headers(compression));
+    }
+
+    let resolver = CachingResolver::new(&config.dns.clone().unwrap_or_default())?
+        .with_metrics(metrics.clone());
+    Ok(builder.dns_resolver(Arc::new(resolver)))
+}
+
+fn apply_tls(mut builder: ClientBuilder, tls: &TlsConfig) -> Result<ClientBuilder> {
+    let read = |path: &str, what: &str| {
+        std::fs::read(path).map_err(|e| Error::Config(format!("Failed to read {} {}: {}", what, path, e)))
+    };
+
+    if let Some(path) = &tls.ca_file {
+        let certs = Certificate::from_pem_bundle(&read(path, "CA file")?)
+            .map_err(|e| Error::Config(format!("Invalid CA file {}: {}", path, e)))?;
+        if certs.is_empty() {
+            return Err(Error::Config(format!("No certificates found in CA file {}", path)));
+        }
+        for cert in certs {
+            builder = builder.add_root_certificate(cert);
+        }
+    }
+
+    match (&tls.client_cert, &tls.client_key) {
+        (Some(cert), Some(key)) => {
+            let mut pem = read(cert, "client certificate")?;
+            pem.push(b'\n');
+            pem.extend(read(key, "client key")?);
+            let identity = Identity::from_pem(&pem)
+                .map_err(|e| Error::Config(format!("Invalid client certificate or key: {}", e)))?;
+            // PEM identities are only supported by the rustls backend
+            builder = builder.use_rustls_tls().identity(identity);
+        }
+        (None, None) => {}
+        _ => {
+            return Err(Error::Config(
+                "tls.client_cert and tls.client_key must be set together".to_string(),
+            ));
+        }
+    }
+
+    if tls.insecure_skip_verify {
+        log::warn!("TLS certificate verification is disabled");
+        builder = builder.danger_accept_invalid_certs(true);
+    }
+    Ok(builder)
+}
+
+fn apply_http(mut builder: ClientBuilder, http: &HttpConfig) -> ClientBuilder {
+    if http.http2_prior_knowledge {
+        builder = builder.http2_prior_knowledge();
+    }
+    if http.http2_adaptive_window {
+        builder = builder.http2_adaptive_window(true);
+    }
+    if let Some(secs) = http.http2_keep_alive_secs {
+        builder = builder.http2_keep_alive_interval(Duration::from_secs(secs));
+    }
+    if let Some(max) = http.pool_max_idle_per_host {
+        builder = builder.pool_max_idle_per_host(max);
+    }
+    if let Some(secs) = http.pool_idle_timeout_secs {
+        builder = builder.pool_idle_timeout(Duration::from_secs(secs));
+    }
+    builder
+}
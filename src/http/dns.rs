@@ -0,0 +1,156 @@
+use crate::config::schema::{DnsConfig, DohProvider};
+use crate::error::{Error, Result};
+use crate::metrics::collector::MetricsCollector;
+use hickory_resolver::config::{NameServerConfigGroup, ResolverConfig};
+use hickory_resolver::name_server::TokioConnectionProvider;
+use hickory_resolver::TokioResolver;
+use reqwest::dns::{Addrs, Name, Resolve, Resolving};
+use std::collections::HashMap;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Where cache misses are resolved.
+enum Upstream {
+    /// The operating system resolver (`getaddrinfo`)
+    System,
+    /// Explicit nameservers or DNS-over-HTTPS
+    Hickory(Box<TokioResolver>),
+}
+
+struct Inner {
+    upstream: Upstream,
+    hosts: HashMap<String, IpAddr>,
+    ttl: Duration,
+    cache: Mutex<HashMap<String, (Instant, Vec<IpAddr>)>>,
+}
+
+/// A reqwest DNS resolver with static host overrides and a TTL-bounded cache.
+///
+/// hyper resolves once per new connection, so the resolver also counts
+/// connections opened, from which connection reuse is derived.
+#[derive(Clone)]
+pub struct CachingResolver {
+    inner: Arc<Inner>,
+    metrics: Option<Arc<MetricsCollector>>,
+}
+
+impl CachingResolver {
+    pub fn new(config: &DnsConfig) -> Result<Self> {
+        let upstream = match (&config.doh, config.nameservers.is_empty()) {
+            (Some(doh), _) => {
+                let group = match doh {
+                    DohProvider::Cloudflare => NameServerConfigGroup::cloudflare_https(),
+                    DohProvider::Google => NameServerConfigGroup::google_https(),
+                    DohProvider::Custom { ips, .. } if ips.is_empty() => {
+                        return Err(Error::Config(
+                            "dns.doh.custom requires at least one IP".to_string(),
+                        ));
+                    }
+                    DohProvider::Custom { ips, server_name } => {
+                        NameServerConfigGroup::from_ips_https(ips, 443, server_name.clone(), true)
+                    }
+                };
+                Upstream::Hickory(Box::new(Self::hickory(group)))
+            }
+            (None, false) => Upstream::Hickory(Box::new(Self::hickory(
+                NameServerConfigGroup::from_ips_clear(&config.nameservers, 53, true),
+            ))),
+            (None, true) => Upstream::System,
+        };
+
+        let hosts = config
+            .hosts
+            .iter()
+            .map(|(host, ip)| (host.to_ascii_lowercase(), *ip))
+            .collect();
+
+        Ok(Self {
+            inner: Arc::new(Inner {
+                upstream,
+                hosts,
+                ttl: Duration::from_secs(config.cache_ttl_secs),
+                cache: Mutex::new(HashMap::new()),
+            }),
+            metrics: None,
+        })
+    }
+
+    pub fn with_metrics(mut self, metrics: Arc<MetricsCollector>) -> Self {
+        self.metrics = Some(metrics);
+        self
+    }
+
+    fn hickory(group: NameServerConfigGroup) -> TokioResolver {
+        let config = ResolverConfig::from_parts(None, vec![], group);
+        TokioResolver::builder_with_config(config, TokioConnectionProvider::default()).build()
+    }
+
+    fn cached(&self, host: &str) -> Option<Vec<IpAddr>> {
+        let cache = self.inner.cache.lock().ok()?;
+        cache
+            .get(host)
+            .filter(|(expires, _)| *expires > Instant::now())
+            .map(|(_, ips)| ips.clone())
+    }
+
+    async fn lookup(&self, host: &str) -> Result<Vec<IpAddr>> {
+        if let Some(ip) = self.inner.hosts.get(host) {
+            return Ok(vec![*ip]);
+        }
+        if let Some(ips) = self.cached(host) {
+            log::trace!("DNS cache hit for {}", host);
+            return Ok(ips);
+        }
+
+        let mut expires = Instant::now() + self.inner.ttl;
+        let ips: Vec<IpAddr> = match &self.inner.upstream {
+            Upstream::System => tokio::net::lookup_host((host, 0))
+                .await
+                .map_err(|e| dns_error(host, e))?
+                .map(|addr| addr.ip())
+                .collect(),
+            Upstream::Hickory(resolver) => {
+                let lookup = resolver
+                    .lookup_ip(host)
+                    .await
+                    .map_err(|e| dns_error(host, e))?;
+                expires = expires.min(lookup.valid_until());
+                lookup.iter().collect()
+            }
+        };
+
+        if ips.is_empty() {
+            return Err(dns_error(host, "no addresses found"));
+        }
+
+        log::debug!("Resolved {} -> {:?}", host, ips);
+        if let Ok(mut cache) = self.inner.cache.lock() {
+            cache.insert(host.to_string(), (expires, ips.clone()));
+        }
+        Ok(ips)
+    }
+}
+
+impl Resolve for CachingResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        let resolver = self.clone();
+        let host = name.as_str().to_ascii_lowercase();
+        if let Some(metrics) = &self.metrics {
+            metrics.increment_connections_opened();
+        }
+        Box::pin(async move {
+            let ips = resolver.lookup(&host).await?;
+            // Port 0 is replaced by reqwest with the port of the request URL
+            let addrs: Addrs = Box::new(ips.into_iter().map(|ip| SocketAddr::new(ip, 0)));
+            Ok(addrs)
+        })
+    }
+}
+
+fn dns_error(host: &str, message: impl std::fmt::Display) -> Error {
+    Error::Dns {
+        host: host.to_string(),
+        message: message.to_string(),
+    }
+}
@@ -0,0 +1,86 @@
+use crate::config::schema::DedupConfig;
+use reqwest::{Method, Request};
+use sha2::{Digest, Sha256};
+use url::Url;
+
+/// Identifies requests that would fetch the same resource, for deduplication.
+///
+/// A fingerprint covers the method, the URL with its query parameters sorted and
+/// ignored ones removed, and a hash of the body. The fragment never counts.
+#[derive(Debug, Clone, Default)]
+pub struct Fingerprinter {
+    ignore_params: Vec<String>,
+}
+
+impl Fingerprinter {
+    pub fn new(config: &DedupConfig) -> Self {
+        Self {
+            ignore_params: config.ignore_params.clone(),
+        }
+    }
+
+    /// Hex SHA-256 fingerprint of `request`.
+    pub fn fingerprint(&self, request: &Request) -> String {
+        let mut hasher = Sha256::new();
+        hasher.update(request.method().as_str());
+        hasher.update(b"\n");
+        hasher.update(self.normalize_url(request.url()));
+        hasher.update(b"\n");
+        let body = request.body().and_then(|body| body.as_bytes()).unwrap_or_default();
+        hasher.update(Sha256::digest(body));
+        hasher
+            .finalize()
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect()
+    }
+
+    /// Fingerprint of a plain GET of `url`; unparseable URLs are returned as is.
+    pub fn fingerprint_url(&self, url: &str) -> String {
+        match Url::parse(url) {
+            Ok(url) => self.fingerprint(&Request::new(Method::GET, url)),
+            Err(_) => url.to_string(),
+        }
+    }
+
+    /// `url` without its fragment or ignored query parameters, with the rest sorted.
+    pub fn normalize_url(&self, url: &Url) -> String {
+        let mut url = url.clone();
+        url.set_fragment(None);
+        let mut params: Vec<(String, String)> = url
+            .query_pairs()
+            .filter(|(key, _)| !self.ignore_params.iter().any(|pattern| wildcard_match(pattern, key)))
+            .map(|(key, value)| (key.into_owned(), value.into_owned()))
+            .collect();
+        if params.is_empty() {
+            url.set_query(None);
+        } else {
+            params.sort();
+            url.query_pairs_mut().clear().extend_pairs(params);
+        }
+        url.to_string()
+    }
+}
+
+/// Case-insensitive match where `*` stands for any run of characters.
+pub(crate) fn wildcard_match(pattern: &str, text: &str) -> bool {
+    let pattern = pattern.to_ascii_lowercase();
+    let text = text.to_ascii_lowercase();
+    let mut parts = pattern.split('*');
+    let first = parts.next().unwrap_or_default();
+    let Some(mut rest) = text.strip_prefix(first) else {
+        return false;
+    };
+    let parts: Vec<&str> = parts.collect();
+    let Some((last, middle)) = parts.split_last() else {
+        // No `*`: the pattern must match exactly
+        return rest.is_empty();
+    };
+    for part in middle {
+        match rest.find(part) {
+            Some(i) => rest = &rest[i + part.len()..],
+            None => return false,
+        }
+    }
+    rest.ends_with(last)
+}
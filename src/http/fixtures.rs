@@ -0,0 +1,148 @@
+use crate::config::schema::CompressionConfig;
+use crate::error::{Error, Result};
+use crate::http::body::read_raw;
+use crate::http::transport::Transport;
+use async_trait::async_trait;
+use reqwest::{Request, Response, ResponseBuilderExt};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use url::Url;
+
+/// Metadata stored next to each recorded body.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Fixture {
+    pub method: String,
+    pub url: String,
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    pub recorded_at: String,
+}
+
+/// Returns the file stem used for a request, stable across runs.
+pub fn fixture_key(method: &str, url: &str) -> String {
+    let digest = Sha256::digest(format!("{} {}", method, url).as_bytes());
+    digest[..16].iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Wraps another transport and saves every response it returns to `dir`.
+///
+/// Bodies are stored exactly as received on the wire (still compressed), so a
+/// replay exercises the same decoding path as the original run.
+pub struct RecordingTransport {
+    inner: Arc<dyn Transport>,
+    dir: PathBuf,
+    max_body_bytes: usize,
+}
+
+impl RecordingTransport {
+    pub fn new(inner: Arc<dyn Transport>, dir: impl Into<PathBuf>) -> Result<Self> {
+        let dir = dir.into();
+        fs::create_dir_all(&dir)?;
+        Ok(Self {
+            inner,
+            dir,
+            max_body_bytes: CompressionConfig::default().max_body_bytes,
+        })
+    }
+
+    /// Fails responses whose body exceeds `limit` bytes on the wire instead of
+    /// recording them.
+    pub fn with_max_body_bytes(mut self, limit: usize) -> Self {
+        self.max_body_bytes = limit;
+        self
+    }
+}
+
+#[async_trait]
+impl Transport for RecordingTransport {
+    async fn execute(&self, request: Request) -> Result<Response> {
+        let method = request.method().to_string();
+        let url = request.url().clone();
+        let mut res = self.inner.execute(request).await?;
+
+        let status = res.status().as_u16();
+        let headers: Vec<(String, String)> = res
+            .headers()
+            .iter()
+            .filter_map(|(k, v)| Some((k.to_string(), v.to_str().ok()?.to_string())))
+            .collect();
+        let body = read_raw(&mut res, self.max_body_bytes, None).await?;
+
+        let fixture = Fixture {
+            method: method.clone(),
+            url: url.to_string(),
+            status,
+            headers,
+            recorded_at: chrono::Utc::now().to_rfc3339(),
+        };
+        let key = fixture_key(&method, url.as_str());
+        fs::write(self.dir.join(format!("{}.json", key)), serde_json::to_vec_pretty(&fixture)?)?;
+        fs::write(self.dir.join(format!("{}.body", key)), &body)?;
+        log::debug!("Recorded {} {} as {}", method, url, key);
+
+        build_response(&fixture, url, body.to_vec())
+    }
+}
+
+/// Serves responses exclusively from a directory written by [`RecordingTransport`].
+///
+/// Requests without a recording fail rather than touching the network, so a
+/// replayed run is fully deterministic.
+pub struct ReplayTransport {
+    dir: PathBuf,
+}
+
+impl ReplayTransport {
+    pub fn new(dir: impl Into<PathBuf>) -> Result<Self> {
+        let dir = dir.into();
+        if !dir.is_dir() {
+            return Err(Error::Config(format!(
+                "Fixture directory {} does not exist",
+                dir.display()
+            )));
+        }
+        Ok(Self { dir })
+    }
+
+    fn load(&self, key: &str) -> Result<(Fixture, Vec<u8>)> {
+        let meta = read(&self.dir.join(format!("{}.json", key)))?;
+        let body = read(&self.dir.join(format!("{}.body", key)))?;
+        Ok((serde_json::from_slice(&meta)?, body))
+    }
+}
+
+#[async_trait]
+impl Transport for ReplayTransport {
+    async fn execute(&self, request: Request) -> Result<Response> {
+        let method = request.method().to_string();
+        let url = request.url().clone();
+        let key = fixture_key(&method, url.as_str());
+
+        let (fixture, body) = self.load(&key).map_err(|e| Error::Replay {
+            url: url.to_string(),
+            message: format!("{} fixture {} unreadable: {}", method, key, e),
+        })?;
+        build_response(&fixture, url, body)
+    }
+}
+
+fn read(path: &Path) -> Result<Vec<u8>> {
+    Ok(fs::read(path)?)
+}
+
+fn build_response(fixture: &Fixture, url: Url, body: Vec<u8>) -> Result<Response> {
+    let mut builder = http::Response::builder().status(fixture.status).url(url);
+    for (name, value) in &fixture.headers {
+        builder = builder.header(name.as_str(), value.as_str());
+    }
+    let response = builder
+        .body(body)
+        .map_err(|e| Error::Replay {
+            url: fixture.url.clone(),
+            message: format!("invalid fixture: {}", e),
+        })?;
+    Ok(Response::from(response))
+}
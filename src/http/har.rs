@@ -0,0 +1,372 @@
+//! HTTP Archive (HAR 1.2) import and export.
+//!
+//! Exported archives open in browser devtools; imported archives can seed a
+//! crawl or replace the network entirely via [`HarTransport`].
+
+use crate::config::schema::CompressionConfig;
+use crate::error::{Error, Result};
+use crate::http::body::{decode_content, read_raw};
+use crate::http::transport::Transport;
+use async_trait::async_trait;
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use bytes::Bytes;
+use reqwest::{Request, Response, ResponseBuilderExt, header};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Har {
+    pub log: HarLog,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HarLog {
+    pub version: String,
+    pub creator: HarCreator,
+    pub entries: Vec<HarEntry>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HarCreator {
+    pub name: String,
+    pub version: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HarEntry {
+    pub started_date_time: String,
+    pub time: f64,
+    pub request: HarRequest,
+    pub response: HarResponse,
+    #[serde(default)]
+    pub cache: serde_json::Value,
+    #[serde(default)]
+    pub timings: HarTimings,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HarRequest {
+    pub method: String,
+    pub url: String,
+    pub http_version: String,
+    #[serde(default)]
+    pub headers: Vec<HarHeader>,
+    #[serde(default)]
+    pub query_string: Vec<HarHeader>,
+    #[serde(default)]
+    pub cookies: Vec<serde_json::Value>,
+    #[serde(default = "unknown_size")]
+    pub headers_size: i64,
+    #[serde(default = "unknown_size")]
+    pub body_size: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HarResponse {
+    pub status: u16,
+    #[serde(default)]
+    pub status_text: String,
+    pub http_version: String,
+    #[serde(default)]
+    pub headers: Vec<HarHeader>,
+    #[serde(default)]
+    pub cookies: Vec<serde_json::Value>,
+    pub content: HarContent,
+    #[serde(default, rename = "redirectURL")]
+    pub redirect_url: String,
+    #[serde(default = "unknown_size")]
+    pub headers_size: i64,
+    #[serde(default = "unknown_size")]
+    pub body_size: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HarContent {
+    pub size: i64,
+    #[serde(default)]
+    pub mime_type: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub text: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub encoding: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HarHeader {
+    pub name: String,
+    pub value: String,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct HarTimings {
+    pub send: f64,
+    pub wait: f64,
+    pub receive: f64,
+}
+
+fn unknown_size() -> i64 {
+    -1
+}
+
+impl Har {
+    pub fn new(entries: Vec<HarEntry>) -> Self {
+        Self {
+            log: HarLog {
+                version: "1.2".to_string(),
+                creator: HarCreator {
+                    name: env!("CARGO_PKG_NAME").to_string(),
+                    version: env!("CARGO_PKG_VERSION").to_string(),
+                },
+                entries,
+            },
+        }
+    }
+
+    pub fn load(path: &Path) -> Result<Self> {
+        let content = std::fs::read_to_string(path)?;
+        Ok(serde_json::from_str(&content)?)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        std::fs::write(path, serde_json::to_vec_pretty(self)?)?;
+        Ok(())
+    }
+
+    /// URLs of successful HTML documents, in first-seen order. Assets such as
+    /// scripts and images in browser-captured archives are skipped.
+    pub fn page_urls(&self) -> Vec<String> {
+        let mut seen = std::collections::HashSet::new();
+        self.log
+            .entries
+            .iter()
+            .filter(|e| e.request.method == "GET")
+            .filter(|e| (200..300).contains(&e.response.status))
+            .filter(|e| e.response.content.mime_type.contains("html"))
+            .filter(|e| seen.insert(e.request.url.clone()))
+            .map(|e| e.request.url.clone())
+            .collect()
+    }
+}
+
+impl HarContent {
+    /// Returns the decoded body bytes, undoing base64 if needed.
+    pub fn bytes(&self) -> Result<Vec<u8>> {
+        let text = self.text.as_deref().unwrap_or_default();
+        if self.encoding.as_deref() == Some("base64") {
+            BASE64
+                .decode(text)
+                .map_err(|e| Error::Internal(format!("Invalid base64 HAR content: {}", e)))
+        } else {
+            Ok(text.as_bytes().to_vec())
+        }
+    }
+
+    fn from_bytes(bytes: &[u8], mime_type: String) -> Self {
+        let (text, encoding) = match std::str::from_utf8(bytes) {
+            Ok(text) => (text.to_string(), None),
+            Err(_) => (BASE64.encode(bytes), Some("base64".to_string())),
+        };
+        Self {
+            size: bytes.len() as i64,
+            mime_type,
+            text: Some(text),
+            encoding,
+        }
+    }
+}
+
+/// Wraps another transport and collects every exchange as a HAR entry.
+///
+/// Bodies are stored decompressed, as browsers do, so the archive is readable
+/// in devtools. Call [`HarRecorder::save`] once the crawl has finished.
+pub struct HarRecorder {
+    inner: Arc<dyn Transport>,
+    entries: Mutex<Vec<HarEntry>>,
+    max_body_bytes: usize,
+}
+
+impl HarRecorder {
+    pub fn new(inner: Arc<dyn Transport>) -> Self {
+        Self {
+            inner,
+            entries: Mutex::new(Vec::new()),
+            max_body_bytes: CompressionConfig::default().max_body_bytes,
+        }
+    }
+
+    /// Fails responses whose body exceeds `limit` bytes on the wire instead of
+    /// recording them.
+    pub fn with_max_body_bytes(mut self, limit: usize) -> Self {
+        self.max_body_bytes = limit;
+        self
+    }
+
+    pub fn save(&self, path: &Path) -> Result<usize> {
+        let entries = self
+            .entries
+            .lock()
+            .map_err(|_| Error::Internal("HAR entries lock poisoned".to_string()))?
+            .clone();
+        let count = entries.len();
+        Har::new(entries).save(path)?;
+        Ok(count)
+    }
+}
+
+#[async_trait]
+impl Transport for HarRecorder {
+    async fn execute(&self, request: Request) -> Result<Response> {
+        let started = chrono::Utc::now();
+        let start = Instant::now();
+        let method = request.method().to_string();
+        let url = request.url().clone();
+        let request_headers = har_headers(request.headers());
+
+        let mut res = self.inner.execute(request).await?;
+        let wait = start.elapsed().as_secs_f64() * 1000.0;
+        let status = res.status();
+        let version = format!("{:?}", res.version());
+        let headers = res.headers().clone();
+        let raw = read_raw(&mut res, self.max_body_bytes, None).await?;
+        let total = start.elapsed().as_secs_f64() * 1000.0;
+
+        let encoding = headers
+            .get(header::CONTENT_ENCODING)
+            .and_then(|v| v.to_str().ok())
+            .map(|v| v.trim().to_ascii_lowercase());
+        let decoded = decode_content(raw.clone(), encoding.as_deref(), self.max_body_bytes, url.as_str())
+            .unwrap_or_else(|_| raw.clone());
+        let mime_type = headers
+            .get(header::CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .unwrap_or_default()
+            .to_string();
+        let redirect_url = headers
+            .get(header::LOCATION)
+            .and_then(|v| v.to_str().ok())
+            .unwrap_or_default()
+            .to_string();
+
+        let entry = HarEntry {
+            started_date_time: started.to_rfc3339(),
+            time: total,
+            request: HarRequest {
+                method,
+                url: url.to_string(),
+                http_version: version.clone(),
+                headers: request_headers,
+                query_string: url
+                    .query_pairs()
+                    .map(|(name, value)| HarHeader {
+                        name: name.into_owned(),
+                        value: value.into_owned(),
+                    })
+                    .collect(),
+                cookies: Vec::new(),
+                headers_size: -1,
+                body_size: 0,
+            },
+            response: HarResponse {
+                status: status.as_u16(),
+                status_text: status.canonical_reason().unwrap_or_default().to_string(),
+                http_version: version,
+                headers: har_headers(&headers),
+                cookies: Vec::new(),
+                content: HarContent::from_bytes(&decoded, mime_type),
+                redirect_url,
+                headers_size: -1,
+                body_size: raw.len() as i64,
+            },
+            cache: serde_json::json!({}),
+            timings: HarTimings {
+                send: 0.0,
+                wait,
+                receive: total - wait,
+            },
+        };
+        if let Ok(mut entries) = self.entries.lock() {
+            entries.push(entry);
+        }
+
+        let mut builder = http::Response::builder().status(status).url(url);
+        if let Some(h) = builder.headers_mut() {
+            *h = headers;
+        }
+        let response = builder
+            .body(raw)
+            .map_err(|e| Error::Internal(format!("Failed to rebuild response: {}", e)))?;
+        Ok(Response::from(response))
+    }
+}
+
+/// Serves responses from the entries of a HAR archive instead of the network.
+///
+/// Content in a HAR is already decompressed, so `Content-Encoding` and
+/// `Content-Length` headers are dropped when rebuilding responses. Requests
+/// without a matching entry fail.
+pub struct HarTransport {
+    entries: HashMap<(String, String), HarEntry>,
+}
+
+impl HarTransport {
+    pub fn new(har: &Har) -> Self {
+        let mut entries = HashMap::new();
+        for entry in &har.log.entries {
+            // Keep the first exchange for a URL, matching what a crawl would see first
+            entries
+                .entry((entry.request.method.clone(), entry.request.url.clone()))
+                .or_insert_with(|| entry.clone());
+        }
+        Self { entries }
+    }
+}
+
+#[async_trait]
+impl Transport for HarTransport {
+    async fn execute(&self, request: Request) -> Result<Response> {
+        let url = request.url().clone();
+        let key = (request.method().to_string(), url.to_string());
+        let entry = self.entries.get(&key).ok_or_else(|| Error::Replay {
+            url: key.1.clone(),
+            message: format!("no {} entry in the HAR archive", key.0),
+        })?;
+
+        let mut builder = http::Response::builder()
+            .status(entry.response.status)
+            .url(url);
+        for h in &entry.response.headers {
+            let name = h.name.to_ascii_lowercase();
+            if name == "content-encoding" || name == "content-length" || name.starts_with(':') {
+                continue;
+            }
+            builder = builder.header(h.name.as_str(), h.value.as_str());
+        }
+        let response = builder
+            .body(Bytes::from(entry.response.content.bytes()?))
+            .map_err(|e| Error::Replay {
+                url: key.1.clone(),
+                message: format!("invalid HAR entry: {}", e),
+            })?;
+        Ok(Response::from(response))
+    }
+}
+
+fn har_headers(headers: &header::HeaderMap) -> Vec<HarHeader> {
+    headers
+        .iter()
+        .filter_map(|(name, value)| {
+            Some(HarHeader {
+                name: name.to_string(),
+                value: value.to_str().ok()?.to_string(),
+            })
+        })
+        .collect()
+}
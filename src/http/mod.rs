@@ -0,0 +1,32 @@
+pub mod bandwidth;
+pub mod block;
+pub mod budget;
+pub mod body;
+pub mod cache;
+pub mod client;
+pub mod dns;
+pub mod fingerprint;
+pub mod fixtures;
+pub mod har;
+pub mod oauth;
+pub mod proxy;
+pub mod session;
+pub mod signing;
+pub mod transport;
+pub mod warmup;
+pub mod wayback;
+
+pub use bandwidth::BandwidthLimiter;
+pub use block::BlockDetector;
+pub use budget::{BudgetTransport, RequestBudget};
+pub use cache::{CachingTransport, PageCache};
+pub use client::build_client;
+pub use dns::CachingResolver;
+pub use fixtures::{RecordingTransport, ReplayTransport};
+pub use oauth::OAuth2Transport;
+pub use proxy::ProxyTransport;
+pub use session::SessionTransport;
+pub use signing::{RequestSigner, SigningTransport};
+pub use transport::{HttpTransport, MockResponse, MockTransport, Transport};
+pub use warmup::WarmupTransport;
+pub use wayback::WaybackTransport;
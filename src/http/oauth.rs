@@ -0,0 +1,235 @@
+//! OAuth2 client-credentials tokens for API crawls. A token is fetched before the
+//! crawl and replaced shortly before it expires or when the API answers `401`.
+//! It is only sent to the hosts it is meant for, never to followed links
+//! elsewhere.
+
+use crate::config::schema::{AuthConfig, OAuth2ClientAuth, OAuth2Config};
+use crate::error::{Error, Result};
+use crate::http::transport::Transport;
+use async_trait::async_trait;
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use reqwest::header::{ACCEPT, AUTHORIZATION, CONTENT_TYPE, HeaderValue};
+use reqwest::{Method, Request, Response, StatusCode};
+use serde::Deserialize;
+use std::collections::HashSet;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::Mutex;
+use url::Url;
+
+/// Tokens are replaced this long before they expire, so none expires in flight.
+const EXPIRY_MARGIN: Duration = Duration::from_secs(30);
+
+/// Wraps a transport, adding a bearer token to the requests to `hosts`.
+pub struct OAuth2Transport {
+    inner: Arc<dyn Transport>,
+    /// Sends token requests, bypassing signing and sessions
+    token_transport: Arc<dyn Transport>,
+    token_url: Url,
+    client_id: String,
+    client_secret: String,
+    scopes: Vec<String>,
+    client_auth: OAuth2ClientAuth,
+    /// Lowercase hosts that get the token
+    hosts: HashSet<String>,
+    token: Mutex<Option<Token>>,
+}
+
+#[derive(Clone)]
+struct Token {
+    value: HeaderValue,
+    expires_at: Option<Instant>,
+}
+
+impl Token {
+    fn is_fresh(&self) -> bool {
+        self.expires_at
+            .is_none_or(|expires_at| Instant::now() + EXPIRY_MARGIN < expires_at)
+    }
+}
+
+#[derive(Deserialize)]
+struct TokenResponse {
+    access_token: String,
+    #[serde(default)]
+    expires_in: Option<u64>,
+}
+
+impl OAuth2Transport {
+    /// The token goes to the hosts in `config`, or else to those of `start_urls`.
+    pub fn new(
+        inner: Arc<dyn Transport>,
+        token_transport: Arc<dyn Transport>,
+        config: &AuthConfig,
+        start_urls: &[String],
+    ) -> Result<Self> {
+        let AuthConfig::OAuth2ClientCredentials(config) = config;
+        let OAuth2Config {
+            token_url,
+            client_id,
+            client_secret,
+            client_secret_env,
+            scopes,
+            client_auth,
+            hosts,
+        } = config;
+
+        let client_secret = match (client_secret, client_secret_env) {
+            (Some(secret), _) => secret.clone(),
+            (None, Some(var)) => std::env::var(var).map_err(|_| {
+                Error::Config(format!("Environment variable {} with the client secret is not set", var))
+            })?,
+            (None, None) => {
+                return Err(Error::Config(
+                    "oauth2_client_credentials needs `client_secret` or `client_secret_env`".to_string(),
+                ));
+            }
+        };
+        let token_url = Url::parse(token_url)
+            .map_err(|e| Error::Config(format!("Invalid token_url '{}': {}", token_url, e)))?;
+        let hosts: HashSet<String> = if hosts.is_empty() {
+            start_urls
+                .iter()
+                .filter_map(|url| Url::parse(url).ok()?.host_str().map(str::to_ascii_lowercase))
+                .collect()
+        } else {
+            hosts.iter().map(|host| host.trim().to_ascii_lowercase()).collect()
+        };
+        if hosts.is_empty() {
+            return Err(Error::Config(
+                "oauth2_client_credentials needs `hosts` when no start URL has a host".to_string(),
+            ));
+        }
+
+        Ok(Self {
+            inner,
+            token_transport,
+            token_url,
+            client_id: client_id.clone(),
+            client_secret,
+            scopes: scopes.clone(),
+            client_auth: *client_auth,
+            hosts,
+            token: Mutex::new(None),
+        })
+    }
+
+    /// Fetches the first token, so bad credentials stop the crawl before it starts.
+    pub async fn authenticate(&self) -> Result<()> {
+        self.token(None).await.map(|_| ())
+    }
+
+    /// A fresh token. `rejected` is a token the API refused, which is replaced
+    /// unless another request already did so.
+    async fn token(&self, rejected: Option<&HeaderValue>) -> Result<HeaderValue> {
+        // Held while fetching, so concurrent requests wait for a single refresh
+        let mut token = self.token.lock().await;
+        if let Some(current) = token.as_ref()
+            && current.is_fresh()
+            && rejected.is_none_or(|rejected| *rejected != current.value)
+        {
+            return Ok(current.value.clone());
+        }
+
+        let fetched = self.fetch().await?;
+        let value = fetched.value.clone();
+        *token = Some(fetched);
+        Ok(value)
+    }
+
+    fn is_authorized(&self, url: &Url) -> bool {
+        url.host_str()
+            .is_some_and(|host| self.hosts.contains(&host.to_ascii_lowercase()))
+    }
+
+    fn error(&self, message: String) -> Error {
+        Error::Auth {
+            url: self.token_url.to_string(),
+            message,
+        }
+    }
+
+    fn token_request(&self) -> Result<Request> {
+        let mut form = url::form_urlencoded::Serializer::new(String::new());
+        form.append_pair("grant_type", "client_credentials");
+        if !self.scopes.is_empty() {
+            form.append_pair("scope", &self.scopes.join(" "));
+        }
+        let mut request = Request::new(Method::POST, self.token_url.clone());
+        match self.client_auth {
+            OAuth2ClientAuth::Basic => {
+                let credentials = BASE64.encode(format!("{}:{}", self.client_id, self.client_secret));
+                let value = HeaderValue::from_str(&format!("Basic {}", credentials))
+                    .map_err(|e| self.error(e.to_string()))?;
+                request.headers_mut().insert(AUTHORIZATION, value);
+            }
+            OAuth2ClientAuth::Body => {
+                form.append_pair("client_id", &self.client_id);
+                form.append_pair("client_secret", &self.client_secret);
+            }
+        }
+        request.headers_mut().insert(
+            CONTENT_TYPE,
+            HeaderValue::from_static("application/x-www-form-urlencoded"),
+        );
+        request
+            .headers_mut()
+            .insert(ACCEPT, HeaderValue::from_static("application/json"));
+        *request.body_mut() = Some(form.finish().into());
+        Ok(request)
+    }
+
+    async fn fetch(&self) -> Result<Token> {
+        let response = self.token_transport.execute(self.token_request()?).await?;
+        let status = response.status();
+        if !status.is_success() {
+            let body = response.text().await.unwrap_or_default();
+            let body: String = body.chars().take(200).collect();
+            return Err(self.error(format!("HTTP {}: {}", status.as_u16(), body.trim())));
+        }
+        let response: TokenResponse = response.json().await.map_err(|e| self.error(e.to_string()))?;
+
+        let value = HeaderValue::from_str(&format!("Bearer {}", response.access_token))
+            .map_err(|e| self.error(format!("Invalid access token: {}", e)))?;
+        log::debug!(
+            "Fetched an OAuth2 token from {} (expires in {})",
+            self.token_url,
+            response
+                .expires_in
+                .map_or_else(|| "an unknown time".to_string(), |secs| format!("{}s", secs))
+        );
+        Ok(Token {
+            value,
+            expires_at: response
+                .expires_in
+                .map(|secs| Instant::now() + Duration::from_secs(secs)),
+        })
+    }
+}
+
+#[async_trait]
+impl Transport for OAuth2Transport {
+    async fn execute(&self, mut request: Request) -> Result<Response> {
+        if !self.is_authorized(request.url()) {
+            return self.inner.execute(request).await;
+        }
+        let token = self.token(None).await?;
+        // Kept to send again with a new token if this one is rejected
+        let retry = request.try_clone();
+        request.headers_mut().insert(AUTHORIZATION, token.clone());
+
+        let response = self.inner.execute(request).await?;
+        if response.status() != StatusCode::UNAUTHORIZED {
+            return Ok(response);
+        }
+        let Some(mut retry) = retry else {
+            return Ok(response);
+        };
+
+        log::debug!("{} rejected the OAuth2 token, fetching a new one", retry.url());
+        let token = self.token(Some(&token)).await?;
+        retry.headers_mut().insert(AUTHORIZATION, token);
+        self.inner.execute(retry).await
+    }
+}
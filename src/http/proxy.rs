@@ -0,0 +1,408 @@
+//! Spreads requests over a pool of HTTP and SOCKS5 proxies. Proxies that keep
+//! failing are taken out of rotation until a health check through them succeeds.
+//! Responses that look blocked can be retried through another proxy and user
+//! agent, and the combination that got through is used first for that host.
+
+use crate::config::schema::{BlockRetryConfig, ProxyConfig, ProxyPoolConfig, SpiderConfig};
+use crate::error::{Error, Result};
+use crate::http::body::decode_content;
+use crate::http::budget::RequestBudget;
+use crate::http::client::build_proxied_client;
+use crate::http::transport::Transport;
+use crate::metrics::collector::MetricsCollector;
+use async_trait::async_trait;
+use reqwest::header::{CONTENT_ENCODING, CONTENT_TYPE, HeaderValue, USER_AGENT};
+use reqwest::{Client, Proxy, Request, Response, ResponseBuilderExt, StatusCode};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use url::Url;
+
+const SCHEMES: &[&str] = &["http", "https", "socks5", "socks5h"];
+
+/// Transport sending each request through the next healthy proxy of the pool.
+pub struct ProxyTransport {
+    proxies: Vec<Arc<PooledProxy>>,
+    next: AtomicUsize,
+    max_failures: u32,
+    check_url: String,
+    check_interval: Duration,
+    user_agents: Vec<HeaderValue>,
+    next_agent: AtomicUsize,
+    block_retry: Option<BlockRetry>,
+    /// Combination that got past a block, per host
+    unblocked: Mutex<HashMap<String, Combination>>,
+    /// Budget the retries of blocked responses count against; the first
+    /// attempt is counted by the [`BudgetTransport`](crate::http::BudgetTransport) around this
+    budget: Option<Arc<RequestBudget>>,
+    metrics: Arc<MetricsCollector>,
+}
+
+/// A proxy and the user agent sent through it, as indexes into the pool.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Combination {
+    proxy: usize,
+    agent: Option<usize>,
+}
+
+struct BlockRetry {
+    max_attempts: usize,
+    statuses: Vec<u16>,
+    /// Lowercased
+    body_markers: Vec<String>,
+    max_body_bytes: usize,
+}
+
+struct PooledProxy {
+    /// Proxy URL without credentials, for logs and metrics
+    label: String,
+    client: Client,
+    health: Mutex<Health>,
+}
+
+#[derive(Default)]
+struct Health {
+    consecutive_failures: u32,
+    evicted_at: Option<Instant>,
+    checking: bool,
+}
+
+impl ProxyTransport {
+    pub fn new(
+        config: &SpiderConfig,
+        pool: &ProxyPoolConfig,
+        metrics: Arc<MetricsCollector>,
+    ) -> Result<Self> {
+        if pool.proxies.is_empty() {
+            return Err(Error::Config("proxy_pool.proxies must not be empty".to_string()));
+        }
+        let proxies = pool
+            .proxies
+            .iter()
+            .map(|proxy| {
+                let (label, proxy) = parse_proxy(proxy)?;
+                Ok(Arc::new(PooledProxy {
+                    label,
+                    client: build_proxied_client(config, &metrics, proxy)?,
+                    health: Mutex::new(Health::default()),
+                }))
+            })
+            .collect::<Result<_>>()?;
+        let user_agents = pool
+            .user_agents
+            .iter()
+            .map(|agent| {
+                HeaderValue::from_str(agent)
+                    .map_err(|e| Error::Config(format!("Invalid user agent '{}': {}", agent, e)))
+            })
+            .collect::<Result<_>>()?;
+        let max_body_bytes = config.compression.clone().unwrap_or_default().max_body_bytes;
+
+        Ok(Self {
+            proxies,
+            next: AtomicUsize::new(0),
+            max_failures: pool.max_failures.max(1),
+            check_url: pool.check_url.clone(),
+            check_interval: Duration::from_secs(pool.check_interval_secs),
+            user_agents,
+            next_agent: AtomicUsize::new(0),
+            block_retry: pool
+                .block_retry
+                .as_ref()
+                .map(|retry| BlockRetry::new(retry, max_body_bytes)),
+            unblocked: Mutex::new(HashMap::new()),
+            budget: None,
+            metrics,
+        })
+    }
+
+    /// Counts the retries of blocked responses against `budget` too.
+    pub fn with_budget(mut self, budget: Arc<RequestBudget>) -> Self {
+        self.budget = Some(budget);
+        self
+    }
+
+    /// Index of the next proxy in rotation. When every proxy is evicted they are
+    /// still used in turn, rather than failing every request.
+    fn pick(&self) -> usize {
+        let start = self.next.fetch_add(1, Ordering::Relaxed) % self.proxies.len();
+        (0..self.proxies.len())
+            .map(|i| (start + i) % self.proxies.len())
+            .find(|&i| self.in_rotation(&self.proxies[i]))
+            .unwrap_or(start)
+    }
+
+    fn pick_agent(&self) -> Option<usize> {
+        match self.user_agents.len() {
+            0 => None,
+            len => Some(self.next_agent.fetch_add(1, Ordering::Relaxed) % len),
+        }
+    }
+
+    /// Number of distinct proxy and user agent combinations.
+    fn combinations(&self) -> usize {
+        self.proxies.len() * self.user_agents.len().max(1)
+    }
+
+    /// The combination for the next attempt at a request to `host`: the one that
+    /// last got past a block there, then ones not `tried` yet.
+    fn choose(&self, host: &str, tried: &[Combination]) -> Combination {
+        if tried.is_empty()
+            && let Some(known) = self.unblocked.lock().ok().and_then(|unblocked| unblocked.get(host).copied())
+            && self.in_rotation(&self.proxies[known.proxy])
+        {
+            return known;
+        }
+        let mut combination = Combination {
+            proxy: self.pick(),
+            agent: self.pick_agent(),
+        };
+        for _ in 0..self.combinations() {
+            if !tried.contains(&combination) {
+                break;
+            }
+            combination = Combination {
+                proxy: self.pick(),
+                agent: self.pick_agent(),
+            };
+        }
+        combination
+    }
+
+    fn describe(&self, combination: Combination) -> String {
+        let proxy = &self.proxies[combination.proxy].label;
+        match combination.agent {
+            Some(agent) => format!("{} with user agent {}", proxy, agent + 1),
+            None => proxy.clone(),
+        }
+    }
+
+    async fn send(&self, combination: Combination, mut request: Request) -> Result<Response> {
+        if let Some(agent) = combination.agent {
+            request.headers_mut().insert(USER_AGENT, self.user_agents[agent].clone());
+        }
+        let proxy = &self.proxies[combination.proxy];
+        let started = Instant::now();
+        let result = proxy.client.execute(request).await;
+        let ok = match &result {
+            Ok(response) => response.status() != StatusCode::PROXY_AUTHENTICATION_REQUIRED,
+            Err(_) => false,
+        };
+        self.record(proxy, ok, started.elapsed());
+        Ok(result?)
+    }
+
+    /// Whether `proxy` is healthy. Starts a health check of evicted proxies that are due one.
+    fn in_rotation(&self, proxy: &Arc<PooledProxy>) -> bool {
+        let Ok(mut health) = proxy.health.lock() else {
+            return false;
+        };
+        match health.evicted_at {
+            None => true,
+            Some(evicted_at) => {
+                if !health.checking && evicted_at.elapsed() >= self.check_interval {
+                    health.checking = true;
+                    tokio::spawn(health_check(proxy.clone(), self.check_url.clone()));
+                }
+                false
+            }
+        }
+    }
+
+    fn record(&self, proxy: &PooledProxy, ok: bool, latency: Duration) {
+        let mut evicted = false;
+        if let Ok(mut health) = proxy.health.lock() {
+            if ok {
+                health.consecutive_failures = 0;
+            } else {
+                health.consecutive_failures += 1;
+                if health.consecutive_failures >= self.max_failures && health.evicted_at.is_none() {
+                    health.evicted_at = Some(Instant::now());
+                    evicted = true;
+                }
+            }
+        }
+        if evicted {
+            log::warn!(
+                "Proxy {} failed {} times in a row, taking it out of rotation",
+                proxy.label,
+                self.max_failures
+            );
+        }
+        self.metrics.record_proxy(&proxy.label, |m| {
+            m.requests += 1;
+            m.failures += u64::from(!ok);
+            m.total_latency_ms += latency.as_millis() as u64;
+            m.evictions += u64::from(evicted);
+        });
+    }
+}
+
+#[async_trait]
+impl Transport for ProxyTransport {
+    async fn execute(&self, request: Request) -> Result<Response> {
+        let host = request.url().host_str().unwrap_or_default().to_string();
+        let Some(block_retry) = &self.block_retry else {
+            return self.send(self.choose(&host, &[]), request).await;
+        };
+        let max_attempts = block_retry.max_attempts.min(self.combinations());
+        let mut tried = Vec::new();
+        let mut request = request;
+        loop {
+            let combination = self.choose(&host, &tried);
+            tried.push(combination);
+            // Requests with streamed bodies can't be cloned and are sent only once
+            let retry = if tried.len() < max_attempts {
+                request.try_clone()
+            } else {
+                None
+            };
+            if tried.len() > 1
+                && let Some(budget) = &self.budget
+            {
+                budget.take(request.url()).await?;
+            }
+            let url = request.url().to_string();
+            let (response, blocked) = block_retry.check(self.send(combination, request).await?).await?;
+            if !blocked {
+                if tried.len() > 1 {
+                    let description = self.describe(combination);
+                    log::info!("Got past the block on {} through {}", host, description);
+                    if let Ok(mut unblocked) = self.unblocked.lock() {
+                        unblocked.insert(host.clone(), combination);
+                    }
+                    self.metrics.record_unblocked(&host, &description);
+                }
+                return Ok(response);
+            }
+
+            self.metrics
+                .record_proxy(&self.proxies[combination.proxy].label, |m| m.blocked += 1);
+            if let Ok(mut unblocked) = self.unblocked.lock()
+                && unblocked.get(&host) == Some(&combination)
+            {
+                unblocked.remove(&host);
+            }
+            match retry {
+                Some(next) => {
+                    log::info!(
+                        "{} looks blocked through {}, retrying with another proxy or user agent",
+                        url,
+                        self.describe(combination)
+                    );
+                    request = next;
+                }
+                None => {
+                    log::warn!("{} still looks blocked after {} attempts", url, tried.len());
+                    return Ok(response);
+                }
+            }
+        }
+    }
+}
+
+impl BlockRetry {
+    fn new(config: &BlockRetryConfig, max_body_bytes: usize) -> Self {
+        Self {
+            max_attempts: config.max_attempts.max(1) as usize,
+            statuses: config.statuses.clone(),
+            body_markers: config.body_markers.iter().map(|m| m.to_lowercase()).collect(),
+            max_body_bytes,
+        }
+    }
+
+    /// Whether `res` looks blocked, along with the response itself, rebuilt if
+    /// its body had to be read to tell.
+    async fn check(&self, res: Response) -> Result<(Response, bool)> {
+        if self.statuses.contains(&res.status().as_u16()) {
+            return Ok((res, true));
+        }
+        let is_html = res
+            .headers()
+            .get(CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .is_some_and(|v| v.to_ascii_lowercase().contains("html"));
+        if self.body_markers.is_empty() || !is_html {
+            return Ok((res, false));
+        }
+
+        let url = res.url().clone();
+        let mut builder = http::Response::builder()
+            .status(res.status())
+            .version(res.version())
+            .url(url.clone());
+        if let Some(headers) = builder.headers_mut() {
+            *headers = res.headers().clone();
+        }
+        let encoding = res
+            .headers()
+            .get(CONTENT_ENCODING)
+            .and_then(|v| v.to_str().ok())
+            .map(str::to_string);
+        let raw = res.bytes().await?;
+        let blocked = match decode_content(raw.clone(), encoding.as_deref(), self.max_body_bytes, url.as_str()) {
+            Ok(body) => {
+                let text = String::from_utf8_lossy(&body).to_lowercase();
+                self.body_markers.iter().any(|marker| text.contains(marker.as_str()))
+            }
+            // Left for the spider to report
+            Err(_) => false,
+        };
+        let response = builder
+            .body(raw)
+            .map_err(|e| Error::Internal(format!("Failed to rebuild the response of {}: {}", url, e)))?;
+        Ok((Response::from(response), blocked))
+    }
+}
+
+/// Puts `proxy` back into rotation if `url` can be fetched through it.
+async fn health_check(proxy: Arc<PooledProxy>, url: String) {
+    let healthy = match proxy.client.get(&url).send().await {
+        Ok(response) => {
+            let status = response.status();
+            status != StatusCode::PROXY_AUTHENTICATION_REQUIRED && !status.is_server_error()
+        }
+        Err(_) => false,
+    };
+    if let Ok(mut health) = proxy.health.lock() {
+        health.checking = false;
+        if healthy {
+            health.evicted_at = None;
+            health.consecutive_failures = 0;
+        } else {
+            health.evicted_at = Some(Instant::now());
+        }
+    }
+    if healthy {
+        log::info!("Proxy {} passed its health check, back in rotation", proxy.label);
+    } else {
+        log::debug!("Proxy {} failed its health check", proxy.label);
+    }
+}
+
+/// The proxy's label (URL without credentials) and its reqwest configuration.
+fn parse_proxy(config: &ProxyConfig) -> Result<(String, Proxy)> {
+    let invalid = |message: String| Error::Config(format!("Invalid proxy '{}': {}", config.url, message));
+    let mut url = Url::parse(&config.url).map_err(|e| invalid(e.to_string()))?;
+    if !SCHEMES.contains(&url.scheme()) {
+        return Err(invalid(format!("scheme must be one of {}", SCHEMES.join(", "))));
+    }
+    let host = url.host_str().ok_or_else(|| invalid("missing host".to_string()))?;
+    let label = match url.port_or_known_default() {
+        Some(port) => format!("{}://{}:{}", url.scheme(), host, port),
+        None => format!("{}://{}", url.scheme(), host),
+    };
+
+    // Credentials go into the URL, which both HTTP and SOCKS5 proxies understand
+    if let Some(username) = &config.username {
+        url.set_username(username)
+            .map_err(|_| invalid("cannot set username".to_string()))?;
+    }
+    if let Some(password) = &config.password {
+        url.set_password(Some(password))
+            .map_err(|_| invalid("cannot set password".to_string()))?;
+    }
+    let proxy = Proxy::all(url.as_str()).map_err(|e| invalid(e.to_string()))?;
+    Ok((label, proxy))
+}
@@ -0,0 +1,163 @@
+//! Per-session cookie jars and headers, so cookies set by one site are never sent
+//! to another and logins stay scoped to the hosts they belong to.
+
+use crate::config::schema::{SessionConfig, SessionScope};
+use crate::error::{Error, Result};
+use crate::http::transport::Transport;
+use async_trait::async_trait;
+use reqwest::cookie::{CookieStore, Jar};
+use reqwest::header::{COOKIE, HeaderMap, HeaderName, HeaderValue, SET_COOKIE};
+use reqwest::{Request, Response};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use url::Url;
+
+/// Second-level labels that, under a two-letter country code, are registries
+/// rather than sites: `co.uk`, `com.au`, `ne.jp` and the like.
+const COUNTRY_REGISTRIES: &[&str] = &[
+    "ac", "co", "com", "edu", "go", "gob", "gov", "gv", "ltd", "me", "mil", "ne", "net", "nic", "nom", "or", "org", "plc",
+    "sch",
+];
+
+/// Wraps a transport, keeping cookies and configured headers per session.
+pub struct SessionTransport {
+    inner: Arc<dyn Transport>,
+    scope: SessionScope,
+    /// Session name and the host patterns that belong to it
+    groups: Vec<(String, Vec<String>)>,
+    headers: HashMap<String, HeaderMap>,
+    jars: Mutex<HashMap<String, Arc<Jar>>>,
+}
+
+impl SessionTransport {
+    pub fn new(inner: Arc<dyn Transport>, config: &SessionConfig) -> Result<Self> {
+        let headers = config
+            .headers
+            .iter()
+            .map(|(session, headers)| {
+                let mut map = HeaderMap::new();
+                for (name, value) in headers {
+                    let invalid = |e: &dyn std::fmt::Display| {
+                        Error::Config(format!("Invalid header '{}' for session '{}': {}", name, session, e))
+                    };
+                    let name = HeaderName::from_bytes(name.as_bytes()).map_err(|e| invalid(&e))?;
+                    let value = HeaderValue::from_str(value).map_err(|e| invalid(&e))?;
+                    map.insert(name, value);
+                }
+                Ok((session.clone(), map))
+            })
+            .collect::<Result<_>>()?;
+
+        Ok(Self {
+            inner,
+            scope: config.scope,
+            groups: config
+                .groups
+                .iter()
+                .map(|(name, hosts)| (name.clone(), hosts.clone()))
+                .collect(),
+            headers,
+            jars: Mutex::new(HashMap::new()),
+        })
+    }
+
+    /// The session `url` belongs to: its group, else its host or domain.
+    pub fn session_name(&self, url: &Url) -> String {
+        let host = url.host_str().unwrap_or_default().to_ascii_lowercase();
+        if let Some((name, _)) = self
+            .groups
+            .iter()
+            .find(|(_, patterns)| patterns.iter().any(|pattern| host_matches(pattern, &host)))
+        {
+            return name.clone();
+        }
+        match self.scope {
+            SessionScope::Host => host,
+            SessionScope::Domain => domain(url),
+        }
+    }
+
+    fn jar(&self, session: &str) -> Arc<Jar> {
+        match self.jars.lock() {
+            Ok(mut jars) => jars.entry(session.to_string()).or_default().clone(),
+            Err(_) => Arc::default(),
+        }
+    }
+}
+
+#[async_trait]
+impl Transport for SessionTransport {
+    async fn execute(&self, mut request: Request) -> Result<Response> {
+        let url = request.url().clone();
+        let session = self.session_name(&url);
+        let jar = self.jar(&session);
+
+        if let Some(headers) = self.headers.get(&session) {
+            for (name, value) in headers {
+                request.headers_mut().insert(name, value.clone());
+            }
+        }
+        if let Some(cookies) = jar.cookies(&url) {
+            request.headers_mut().insert(COOKIE, cookies);
+        }
+
+        let response = self.inner.execute(request).await?;
+        jar.set_cookies(&mut response.headers().get_all(SET_COOKIE).iter(), &url);
+        Ok(response)
+    }
+}
+
+/// `pattern` is a host, or `*.example.com` for any subdomain of `example.com`.
+fn host_matches(pattern: &str, host: &str) -> bool {
+    let pattern = pattern.to_ascii_lowercase();
+    match pattern.strip_prefix("*.") {
+        Some(suffix) => host.strip_suffix(suffix).is_some_and(|rest| rest.ends_with('.')),
+        None => pattern == host,
+    }
+}
+
+/// The registered domain of the host: its last two labels (`shop.example.com`
+/// -> `example.com`), or three under a country registry (`shop.example.co.uk`
+/// -> `example.co.uk`). This isn't the full public suffix list, so other
+/// multi-label suffixes, such as hosting platforms', still share a session.
+/// IP addresses are kept whole.
+fn domain(url: &Url) -> String {
+    match url.host() {
+        Some(url::Host::Domain(host)) => {
+            let host = host.to_ascii_lowercase();
+            let labels: Vec<&str> = host.split('.').collect();
+            let registry = match labels.as_slice() {
+                [.., second, tld] => tld.len() == 2 && COUNTRY_REGISTRIES.contains(second),
+                _ => false,
+            };
+            let kept = if registry { 3 } else { 2 };
+            labels[labels.len().saturating_sub(kept)..].join(".")
+        }
+        Some(host) => host.to_string(),
+        None => String::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn domain_of(url: &str) -> String {
+        domain(&Url::parse(url).unwrap())
+    }
+
+    #[test]
+    fn keeps_the_registered_domain() {
+        assert_eq!(domain_of("https://shop.example.com/"), "example.com");
+        assert_eq!(domain_of("https://example.com/"), "example.com");
+        assert_eq!(domain_of("https://shop.Example.co.uk/"), "example.co.uk");
+        assert_eq!(domain_of("https://www.example.com.au/"), "example.com.au");
+        assert_eq!(domain_of("https://www.example.de/"), "example.de");
+        assert_eq!(domain_of("https://127.0.0.1:8080/"), "127.0.0.1");
+    }
+
+    #[test]
+    fn keeps_sites_under_a_country_registry_apart() {
+        assert_ne!(domain_of("https://www.one.co.uk/"), domain_of("https://www.two.co.uk/"));
+    }
+}
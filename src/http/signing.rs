@@ -0,0 +1,198 @@
+//! Request signing for APIs that authenticate each request with a signature,
+//! e.g. an HMAC over the method, path and a timestamp.
+
+use crate::config::schema::{HmacSigningConfig, SignatureEncoding, SigningConfig};
+use crate::error::{Error, Result};
+use crate::http::transport::Transport;
+use async_trait::async_trait;
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac};
+use reqwest::header::{HeaderName, HeaderValue};
+use reqwest::{Request, Response};
+use sha2::{Digest, Sha256};
+use std::sync::Arc;
+
+/// Adds authentication to a request just before it is sent.
+pub trait RequestSigner: Send + Sync {
+    fn sign(&self, request: &mut Request) -> Result<()>;
+}
+
+/// The signer described by `config`.
+pub fn signer(config: &SigningConfig) -> Result<Arc<dyn RequestSigner>> {
+    match config {
+        SigningConfig::HmacSha256(hmac) => Ok(Arc::new(HmacSigner::new(hmac)?)),
+    }
+}
+
+/// Wraps a transport, signing every request (including redirect hops) first.
+pub struct SigningTransport {
+    inner: Arc<dyn Transport>,
+    signer: Arc<dyn RequestSigner>,
+}
+
+impl SigningTransport {
+    pub fn new(inner: Arc<dyn Transport>, signer: Arc<dyn RequestSigner>) -> Self {
+        Self { inner, signer }
+    }
+}
+
+#[async_trait]
+impl Transport for SigningTransport {
+    async fn execute(&self, mut request: Request) -> Result<Response> {
+        self.signer.sign(&mut request)?;
+        self.inner.execute(request).await
+    }
+}
+
+/// HMAC-SHA256 over a templated string-to-sign, sent in a header.
+///
+/// Placeholders: `{method}`, `{url}`, `{scheme}`, `{host}`, `{path}`, `{query}`,
+/// `{timestamp}` (Unix seconds), `{datetime}` (`20240131T120000Z`),
+/// `{body_sha256}` (hex) and `{header:Name}`.
+pub struct HmacSigner {
+    secret: Vec<u8>,
+    string_to_sign: Vec<Part>,
+    header: HeaderName,
+    value: String,
+    encoding: SignatureEncoding,
+    timestamp_header: Option<HeaderName>,
+}
+
+enum Part {
+    Literal(String),
+    Method,
+    Url,
+    Scheme,
+    Host,
+    Path,
+    Query,
+    Timestamp,
+    DateTime,
+    BodySha256,
+    Header(HeaderName),
+}
+
+impl HmacSigner {
+    pub fn new(config: &HmacSigningConfig) -> Result<Self> {
+        let secret = match (&config.secret, &config.secret_env) {
+            (Some(secret), _) => secret.clone(),
+            (None, Some(var)) => std::env::var(var).map_err(|_| {
+                Error::Config(format!("Environment variable {} with the signing secret is not set", var))
+            })?,
+            (None, None) => {
+                return Err(Error::Config("signing needs `secret` or `secret_env`".to_string()));
+            }
+        };
+
+        Ok(Self {
+            secret: secret.into_bytes(),
+            string_to_sign: parse_template(&config.string_to_sign)?,
+            header: header_name(&config.header)?,
+            value: config.value.clone(),
+            encoding: config.encoding,
+            timestamp_header: config.timestamp_header.as_deref().map(header_name).transpose()?,
+        })
+    }
+
+    fn string_to_sign(&self, request: &Request, now: DateTime<Utc>) -> String {
+        let url = request.url();
+        let mut out = String::new();
+        for part in &self.string_to_sign {
+            match part {
+                Part::Literal(text) => out.push_str(text),
+                Part::Method => out.push_str(request.method().as_str()),
+                Part::Url => out.push_str(url.as_str()),
+                Part::Scheme => out.push_str(url.scheme()),
+                Part::Host => out.push_str(url.host_str().unwrap_or_default()),
+                Part::Path => out.push_str(url.path()),
+                Part::Query => out.push_str(url.query().unwrap_or_default()),
+                Part::Timestamp => out.push_str(&now.timestamp().to_string()),
+                Part::DateTime => out.push_str(&now.format("%Y%m%dT%H%M%SZ").to_string()),
+                Part::BodySha256 => {
+                    let body = request.body().and_then(|body| body.as_bytes()).unwrap_or_default();
+                    out.push_str(&hex(&Sha256::digest(body)));
+                }
+                Part::Header(name) => {
+                    if let Some(value) = request.headers().get(name).and_then(|v| v.to_str().ok()) {
+                        out.push_str(value);
+                    }
+                }
+            }
+        }
+        out
+    }
+}
+
+impl RequestSigner for HmacSigner {
+    fn sign(&self, request: &mut Request) -> Result<()> {
+        let now = Utc::now();
+        let mut mac = Hmac::<Sha256>::new_from_slice(&self.secret)
+            .map_err(|e| Error::Internal(format!("Invalid HMAC key: {}", e)))?;
+        mac.update(self.string_to_sign(request, now).as_bytes());
+        let signature = mac.finalize().into_bytes();
+        let signature = match self.encoding {
+            SignatureEncoding::Hex => hex(&signature),
+            SignatureEncoding::Base64 => BASE64.encode(signature),
+        };
+
+        let value = HeaderValue::from_str(&self.value.replace("{signature}", &signature))
+            .map_err(|e| Error::Config(format!("Invalid signature header value: {}", e)))?;
+        request.headers_mut().insert(self.header.clone(), value);
+        if let Some(name) = &self.timestamp_header {
+            request
+                .headers_mut()
+                .insert(name.clone(), HeaderValue::from(now.timestamp()));
+        }
+        Ok(())
+    }
+}
+
+fn parse_template(template: &str) -> Result<Vec<Part>> {
+    let mut parts = Vec::new();
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        let Some(len) = rest[start..].find('}') else {
+            break;
+        };
+        if start > 0 {
+            parts.push(Part::Literal(rest[..start].to_string()));
+        }
+        let placeholder = &rest[start + 1..start + len];
+        parts.push(match placeholder {
+            "method" => Part::Method,
+            "url" => Part::Url,
+            "scheme" => Part::Scheme,
+            "host" => Part::Host,
+            "path" => Part::Path,
+            "query" => Part::Query,
+            "timestamp" => Part::Timestamp,
+            "datetime" => Part::DateTime,
+            "body_sha256" => Part::BodySha256,
+            other => match other.strip_prefix("header:") {
+                Some(name) => Part::Header(header_name(name)?),
+                None => {
+                    return Err(Error::Config(format!(
+                        "Unknown placeholder {{{}}} in string_to_sign",
+                        other
+                    )));
+                }
+            },
+        });
+        rest = &rest[start + len + 1..];
+    }
+    if !rest.is_empty() {
+        parts.push(Part::Literal(rest.to_string()));
+    }
+    Ok(parts)
+}
+
+fn header_name(name: &str) -> Result<HeaderName> {
+    HeaderName::from_bytes(name.as_bytes())
+        .map_err(|e| Error::Config(format!("Invalid header name '{}': {}", name, e)))
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
@@ -0,0 +1,243 @@
+use crate::error::{Error, Result};
+use async_trait::async_trait;
+use bytes::Bytes;
+use reqwest::{Client, Request, Response, ResponseBuilderExt};
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+use std::time::Duration;
+use url::Url;
+
+/// Sends requests on behalf of a spider.
+///
+/// The default [`HttpTransport`] goes to the network; [`MockTransport`] serves
+/// canned responses so spiders and pipelines can be exercised without it.
+#[async_trait]
+pub trait Transport: Send + Sync {
+    async fn execute(&self, request: Request) -> Result<Response>;
+}
+
+/// Transport backed by a reqwest [`Client`].
+pub struct HttpTransport {
+    client: Client,
+}
+
+impl HttpTransport {
+    pub fn new(client: Client) -> Self {
+        Self { client }
+    }
+}
+
+#[async_trait]
+impl Transport for HttpTransport {
+    async fn execute(&self, request: Request) -> Result<Response> {
+        Ok(self.client.execute(request).await?)
+    }
+}
+
+/// A canned response served by [`MockTransport`].
+#[derive(Debug, Clone)]
+pub struct MockResponse {
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    pub body: Bytes,
+    pub delay: Option<Duration>,
+    /// When set, the request fails with this message instead of responding
+    pub failure: Option<String>,
+}
+
+impl MockResponse {
+    pub fn new(status: u16, body: impl Into<Bytes>) -> Self {
+        Self {
+            status,
+            headers: Vec::new(),
+            body: body.into(),
+            delay: None,
+            failure: None,
+        }
+    }
+
+    /// A `200 OK` HTML page.
+    pub fn html(body: impl Into<Bytes>) -> Self {
+        Self::new(200, body).header("content-type", "text/html; charset=utf-8")
+    }
+
+    /// An empty response with the given status.
+    pub fn status(status: u16) -> Self {
+        Self::new(status, Bytes::new())
+    }
+
+    /// A redirect to `location`.
+    pub fn redirect(status: u16, location: &str) -> Self {
+        Self::status(status).header("location", location)
+    }
+
+    /// A transport-level failure, as if the connection could not be made.
+    pub fn failure(message: &str) -> Self {
+        Self {
+            failure: Some(message.to_string()),
+            ..Self::status(0)
+        }
+    }
+
+    pub fn header(mut self, name: &str, value: &str) -> Self {
+        self.headers.push((name.to_string(), value.to_string()));
+        self
+    }
+
+    pub fn delay(mut self, delay: Duration) -> Self {
+        self.delay = Some(delay);
+        self
+    }
+
+    fn into_response(self, url: Url) -> Result<Response> {
+        let mut builder = http::Response::builder().status(self.status).url(url);
+        for (name, value) in &self.headers {
+            builder = builder.header(name.as_str(), value.as_str());
+        }
+        let response = builder
+            .body(self.body)
+            .map_err(|e| Error::Internal(format!("Invalid mock response: {}", e)))?;
+        Ok(Response::from(response))
+    }
+}
+
+/// In-memory transport mapping URLs to scripted responses.
+///
+/// Each URL holds a queue of responses: every request pops the next one, and the
+/// last response repeats once the queue is down to it. This makes it easy to
+/// script "fail twice, then succeed". Unknown URLs get a `404`.
+#[derive(Default)]
+pub struct MockTransport {
+    routes: Mutex<HashMap<String, VecDeque<MockResponse>>>,
+    requests: Mutex<Vec<String>>,
+}
+
+impl MockTransport {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Appends a response to the queue for `url`.
+    pub fn with_response(self, url: &str, response: MockResponse) -> Self {
+        self.push_response(url, response);
+        self
+    }
+
+    /// Appends a response to the queue for `url` on a shared transport.
+    pub fn push_response(&self, url: &str, response: MockResponse) {
+        if let Ok(mut routes) = self.routes.lock() {
+            routes
+                .entry(normalize(url))
+                .or_default()
+                .push_back(response);
+        }
+    }
+
+    /// URLs requested so far, in order.
+    pub fn requests(&self) -> Vec<String> {
+        self.requests
+            .lock()
+            .map(|requests| requests.clone())
+            .unwrap_or_default()
+    }
+
+    fn next_response(&self, url: &str) -> MockResponse {
+        let mut routes = match self.routes.lock() {
+            Ok(routes) => routes,
+            Err(_) => return MockResponse::status(404),
+        };
+        let next = match routes.get_mut(url) {
+            Some(queue) if queue.len() > 1 => queue.pop_front(),
+            Some(queue) => queue.front().cloned(),
+            None => None,
+        };
+        next.unwrap_or_else(|| MockResponse::status(404))
+    }
+}
+
+#[async_trait]
+impl Transport for MockTransport {
+    async fn execute(&self, request: Request) -> Result<Response> {
+        let url = request.url().clone();
+        if let Ok(mut requests) = self.requests.lock() {
+            requests.push(url.to_string());
+        }
+
+        let response = self.next_response(&normalize(url.as_str()));
+        if let Some(delay) = response.delay {
+            tokio::time::sleep(delay).await;
+        }
+        if let Some(message) = &response.failure {
+            return Err(Error::Connect {
+                url: url.to_string(),
+                message: format!("mock transport failure: {}", message),
+            });
+        }
+        response.into_response(url)
+    }
+}
+
+/// Keys routes by their parsed form so `http://a.com` and `http://a.com/` match.
+fn normalize(url: &str) -> String {
+    Url::parse(url)
+        .map(|u| u.to_string())
+        .unwrap_or_else(|_| url.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::ConfigLoader;
+    use crate::metrics::collector::MetricsCollector;
+    use serde_json::{Value, json};
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn drives_a_spider_through_redirects_and_retries() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("items.json");
+        let config = ConfigLoader::from_json(json!({
+            "name": "mock",
+            "start_urls": ["http://example.com/old", "http://example.com/flaky"],
+            "delay_ms": 0,
+            "retry": {"max_retries": 2, "backoff_ms": 0},
+            "redirects": {"record_chain": true},
+            "extraction_rules": {
+                "status": {"source": "status"},
+                "page": {"source": "header", "name": "x-page"},
+            },
+            "output": {"type": "json", "path": path},
+        }))
+        .unwrap();
+        let transport = Arc::new(
+            MockTransport::new()
+                .with_response("http://example.com/old", MockResponse::redirect(301, "/new"))
+                .with_response("http://example.com/new", MockResponse::html("<html></html>").header("x-page", "new"))
+                .with_response("http://example.com/flaky", MockResponse::failure("connection reset"))
+                .with_response("http://example.com/flaky", MockResponse::status(503))
+                .with_response("http://example.com/flaky", MockResponse::html("<html></html>").header("x-page", "flaky")),
+        );
+        let metrics = Arc::new(MetricsCollector::new());
+        let spider = ConfigLoader::create_spider(&config, None, metrics.clone())
+            .await
+            .unwrap()
+            .with_transport(transport.clone());
+        let engine = ConfigLoader::create_engine(&config, metrics).await.unwrap();
+
+        let report = engine.run(Arc::new(spider)).await.unwrap();
+        assert_eq!(report.metrics.items_extracted, 2);
+        assert_eq!(report.metrics.retries, 2);
+
+        let requests = transport.requests();
+        assert_eq!(requests.iter().filter(|url| url.ends_with("/flaky")).count(), 3);
+        assert!(requests.contains(&"http://example.com/new".to_string()));
+
+        let items: Vec<Value> = serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+        let item = |page: &str| items.iter().find(|item| item["page"] == page).unwrap().clone();
+        let moved = item("new");
+        assert_eq!(moved["status"], 200);
+        assert_eq!(moved["_final_url"], "http://example.com/new");
+        assert_eq!(moved["_redirect_chain"], json!(["http://example.com/old"]));
+        assert_eq!(item("flaky")["status"], 200);
+    }
+}
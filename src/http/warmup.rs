@@ -0,0 +1,146 @@
+//! Warmup requests sent once per host before its first page. Many sites set
+//! the cookies or anti-bot tokens later requests need on their landing page,
+//! or only once a consent form was posted.
+
+use crate::config::schema::WarmupStep;
+use crate::error::{Error, Result};
+use crate::http::transport::Transport;
+use async_trait::async_trait;
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue, LOCATION};
+use reqwest::{Method, Request, Response, StatusCode};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::OnceCell;
+use tokio::time::sleep;
+use url::Url;
+
+/// Redirects followed per warmup request.
+const MAX_WARMUP_REDIRECTS: usize = 10;
+
+/// A [`WarmupStep`] ready to send.
+struct Step {
+    url: String,
+    method: Method,
+    headers: HeaderMap,
+    body: Option<String>,
+    wait: Duration,
+}
+
+/// Wraps a transport, sending the warmup steps to each host before the first
+/// request to it. Requests to a host wait while it is warmed up.
+pub struct WarmupTransport {
+    inner: Arc<dyn Transport>,
+    steps: Vec<Step>,
+    /// Warmups by origin, each run by whichever request comes first
+    origins: Mutex<HashMap<String, Arc<OnceCell<()>>>>,
+}
+
+impl WarmupTransport {
+    pub fn new(inner: Arc<dyn Transport>, steps: &[WarmupStep]) -> Result<Self> {
+        Ok(Self {
+            inner,
+            steps: steps.iter().map(parse).collect::<Result<_>>()?,
+            origins: Mutex::new(HashMap::new()),
+        })
+    }
+
+    /// Checks `steps` without building a transport.
+    pub fn validate(steps: &[WarmupStep]) -> Result<()> {
+        steps.iter().try_for_each(|step| parse(step).map(|_| ()))
+    }
+
+    /// Sends every step to `origin`. Failed steps are logged and skipped, so a
+    /// broken warmup degrades the crawl rather than stopping it.
+    async fn warm_up(&self, origin: &Url) {
+        log::info!("Warming up {}", origin);
+        for step in &self.steps {
+            let url = match origin.join(&step.url) {
+                Ok(url) => url,
+                Err(e) => {
+                    log::warn!("Skipping warmup request {} for {}: {}", step.url, origin, e);
+                    continue;
+                }
+            };
+            match self.send(step, url.clone()).await {
+                Ok(status) if status.is_success() => log::debug!("Warmup {} {}: HTTP {}", step.method, url, status),
+                Ok(status) => log::warn!("Warmup {} {} returned HTTP {}", step.method, url, status.as_u16()),
+                Err(e) => log::warn!("Warmup {} {} failed: {}", step.method, url, e),
+            }
+            if !step.wait.is_zero() {
+                sleep(step.wait).await;
+            }
+        }
+    }
+
+    /// Sends `step` to `url`, following redirects with GET, and returns the
+    /// final status.
+    async fn send(&self, step: &Step, url: Url) -> Result<StatusCode> {
+        let mut request = Request::new(step.method.clone(), url.clone());
+        *request.headers_mut() = step.headers.clone();
+        if let Some(body) = &step.body {
+            *request.body_mut() = Some(body.clone().into());
+        }
+        for _ in 0..=MAX_WARMUP_REDIRECTS {
+            let current = request.url().clone();
+            let response = self.inner.execute(request).await?;
+            let next = response
+                .status()
+                .is_redirection()
+                .then(|| response.headers().get(LOCATION)?.to_str().ok())
+                .flatten()
+                .and_then(|location| current.join(location).ok());
+            let Some(next) = next else {
+                return Ok(response.status());
+            };
+            log::debug!("Warmup redirect {} -> {}", current, next);
+            request = Request::new(Method::GET, next);
+        }
+        Err(Error::Redirect {
+            url: url.to_string(),
+            message: format!("too many redirects ({})", MAX_WARMUP_REDIRECTS),
+        })
+    }
+}
+
+#[async_trait]
+impl Transport for WarmupTransport {
+    async fn execute(&self, request: Request) -> Result<Response> {
+        let origin = request.url().origin();
+        if origin.is_tuple() {
+            let key = origin.ascii_serialization();
+            let warmup = self
+                .origins
+                .lock()
+                .map(|mut origins| origins.entry(key.clone()).or_default().clone())
+                .unwrap_or_default();
+            if let Ok(origin) = Url::parse(&key) {
+                warmup.get_or_init(|| self.warm_up(&origin)).await;
+            }
+        }
+        self.inner.execute(request).await
+    }
+}
+
+fn parse(step: &WarmupStep) -> Result<Step> {
+    let invalid = |reason: String| Error::Config(format!("Invalid warmup step '{}': {}", step.url, reason));
+    Url::parse("http://example.com/")
+        .and_then(|base| base.join(&step.url))
+        .map_err(|e| invalid(e.to_string()))?;
+    let method = Method::from_bytes(step.method.trim().to_ascii_uppercase().as_bytes())
+        .map_err(|_| invalid(format!("unknown method '{}'", step.method)))?;
+    let mut headers = HeaderMap::new();
+    for (name, value) in &step.headers {
+        let header = |e: &dyn std::fmt::Display| invalid(format!("header '{}': {}", name, e));
+        let name = HeaderName::from_bytes(name.as_bytes()).map_err(|e| header(&e))?;
+        let value = HeaderValue::from_str(value).map_err(|e| header(&e))?;
+        headers.insert(name, value);
+    }
+    Ok(Step {
+        url: step.url.clone(),
+        method,
+        headers,
+        body: step.body.clone(),
+        wait: Duration::from_millis(step.wait_ms),
+    })
+}
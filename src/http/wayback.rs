@@ -0,0 +1,155 @@
+//! Historical versions of a site, fetched from the Wayback Machine. Requests
+//! are rewritten to the snapshot closest to the configured date, and archive
+//! URLs in responses are unwrapped back to the pages they archive, so rules,
+//! link following and outputs see the original site.
+
+use crate::config::schema::WaybackConfig;
+use crate::error::{Error, Result};
+use crate::http::transport::Transport;
+use async_trait::async_trait;
+use reqwest::header::{HeaderValue, LOCATION};
+use reqwest::{Request, Response, ResponseBuilderExt};
+use std::sync::Arc;
+use url::Url;
+
+/// Redirects between snapshots of the same page followed before giving up.
+const MAX_SNAPSHOT_HOPS: usize = 10;
+
+/// Wraps a transport, sending every request to the archive instead.
+pub struct WaybackTransport {
+    inner: Arc<dyn Transport>,
+    endpoint: Url,
+    /// `YYYYMMDDhhmmss`, or a prefix of it
+    timestamp: String,
+}
+
+impl WaybackTransport {
+    pub fn new(inner: Arc<dyn Transport>, config: &WaybackConfig) -> Result<Self> {
+        let (endpoint, timestamp) = parse(config)?;
+        Ok(Self {
+            inner,
+            endpoint,
+            timestamp,
+        })
+    }
+
+    /// Checks `config` without building a transport.
+    pub fn validate(config: &WaybackConfig) -> Result<()> {
+        parse(config).map(|_| ())
+    }
+
+    /// The archive URL of `url`'s snapshot closest to the target date. `id_`
+    /// asks for the page as it was captured, without the archive's banner or
+    /// rewritten links.
+    pub fn archive_url(&self, url: &Url) -> Result<Url> {
+        let path = format!("web/{}id_/{}", self.timestamp, url);
+        self.endpoint
+            .join(&path)
+            .map_err(|e| Error::Internal(format!("Invalid archive URL for {}: {}", url, e)))
+    }
+
+    /// The page archived at `url`, if it is an archive URL such as
+    /// `https://web.archive.org/web/20150601000000id_/http://example.com/`.
+    pub fn unwrap_url(&self, url: &Url) -> Option<Url> {
+        if url.host_str() != self.endpoint.host_str() {
+            return None;
+        }
+        let rest = url.path().strip_prefix("/web/")?;
+        let (snapshot, original) = rest.split_once('/')?;
+        // A timestamp, possibly followed by flags like `id_` or `im_`
+        if !snapshot.starts_with(|c: char| c.is_ascii_digit()) {
+            return None;
+        }
+        // The archive collapses `http://` into `http:/` in some URLs
+        let original = match original.split_once(":/") {
+            Some((scheme, rest)) if !rest.starts_with('/') => format!("{}://{}", scheme, rest),
+            Some(_) => original.to_string(),
+            None => format!("http://{}", original),
+        };
+        let mut unwrapped = Url::parse(&original).ok()?;
+        unwrapped.set_query(url.query());
+        Some(unwrapped)
+    }
+
+    /// `response` as if it came from `original`: its URL and any redirect
+    /// target are unwrapped.
+    fn unwrapped(&self, response: Response, original: Url) -> Result<Response> {
+        let url = self.unwrap_url(response.url()).unwrap_or(original);
+        let location = response
+            .headers()
+            .get(LOCATION)
+            .and_then(|location| location.to_str().ok())
+            .and_then(|location| response.url().join(location).ok())
+            .and_then(|location| self.unwrap_url(&location));
+        let (mut parts, body) = http::Response::from(response).into_parts();
+        if let Some(location) = location.and_then(|location| HeaderValue::from_str(location.as_str()).ok()) {
+            parts.headers.insert(LOCATION, location);
+        }
+        let mut builder = http::Response::builder()
+            .status(parts.status)
+            .version(parts.version)
+            .url(url.clone());
+        if let Some(headers) = builder.headers_mut() {
+            *headers = parts.headers;
+        }
+        let response = builder
+            .body(body)
+            .map_err(|e| Error::Internal(format!("Invalid archived response for {}: {}", url, e)))?;
+        Ok(Response::from(response))
+    }
+}
+
+#[async_trait]
+impl Transport for WaybackTransport {
+    async fn execute(&self, mut request: Request) -> Result<Response> {
+        let original = self.unwrap_url(request.url()).unwrap_or_else(|| request.url().clone());
+        *request.url_mut() = self.archive_url(&original)?;
+        for _ in 0..MAX_SNAPSHOT_HOPS {
+            let next = request.try_clone();
+            let response = self.inner.execute(request).await?;
+            // The archive redirects to the closest snapshot it has; only
+            // redirects the site itself made are left to the spider
+            let snapshot = response
+                .status()
+                .is_redirection()
+                .then(|| response.headers().get(LOCATION)?.to_str().ok())
+                .flatten()
+                .and_then(|location| response.url().join(location).ok())
+                .filter(|location| self.unwrap_url(location).as_ref() == Some(&original));
+            match (snapshot, next) {
+                (Some(snapshot), Some(mut next)) => {
+                    log::debug!("Following {} to snapshot {}", original, snapshot);
+                    *next.url_mut() = snapshot;
+                    request = next;
+                }
+                _ => return self.unwrapped(response, original),
+            }
+        }
+        Err(Error::Internal(format!(
+            "The archive redirected {} more than {} times",
+            original, MAX_SNAPSHOT_HOPS
+        )))
+    }
+}
+
+/// The endpoint and the timestamp of the target date.
+fn parse(config: &WaybackConfig) -> Result<(Url, String)> {
+    let endpoint = Url::parse(&config.endpoint)
+        .map_err(|e| Error::Config(format!("Invalid source.endpoint '{}': {}", config.endpoint, e)))?;
+    Ok((endpoint, timestamp(&config.date)?))
+}
+
+/// `date` as a Wayback timestamp: its digits, e.g. `20150601` for `2015-06-01`.
+fn timestamp(date: &str) -> Result<String> {
+    let digits: String = date
+        .chars()
+        .filter(|c| !matches!(c, '-' | ':' | 'T' | ' '))
+        .collect();
+    if !(4..=14).contains(&digits.len()) || !digits.chars().all(|c| c.is_ascii_digit()) {
+        return Err(Error::Config(format!(
+            "Invalid source.date '{}': expected a date like 2015-06-01 or 20150601120000",
+            date
+        )));
+    }
+    Ok(digits)
+}
@@ -0,0 +1,87 @@
+//! Predicates applied to items right after extraction, so junk rows from
+//! navigation or footers that match the root selector are dropped before
+//! they reach the output.
+
+use crate::config::schema::ItemFilterConfig;
+use crate::error::{Error, Result};
+use regex::Regex;
+use serde_json::{Map, Value};
+
+#[derive(Debug)]
+pub struct ItemFilter {
+    must_contain: Vec<(String, Regex)>,
+    min_fields: Option<usize>,
+    max_items_per_page: Option<usize>,
+}
+
+impl ItemFilter {
+    pub fn new(config: &ItemFilterConfig) -> Result<Self> {
+        let must_contain = config
+            .must_contain
+            .iter()
+            .map(|(field, pattern)| {
+                Regex::new(pattern)
+                    .map(|regex| (field.clone(), regex))
+                    .map_err(|e| {
+                        Error::Config(format!(
+                            "Invalid item_filter.must_contain pattern for '{}': {}",
+                            field, e
+                        ))
+                    })
+            })
+            .collect::<Result<_>>()?;
+        Ok(Self {
+            must_contain,
+            min_fields: config.min_fields,
+            max_items_per_page: config.max_items_per_page,
+        })
+    }
+
+    /// Drops the items of one page that fail the predicates, then the ones
+    /// beyond `max_items_per_page`, returning how many were dropped. Page
+    /// items (`_type: page`) are always kept.
+    pub fn apply(&self, items: &mut Vec<Value>) -> usize {
+        let before = items.len();
+        let mut kept = 0;
+        items.retain(|item| {
+            let Value::Object(map) = item else {
+                return true;
+            };
+            if map.get("_type").and_then(Value::as_str) == Some("page") {
+                return true;
+            }
+            if !self.accepts(map) || self.max_items_per_page.is_some_and(|max| kept >= max) {
+                return false;
+            }
+            kept += 1;
+            true
+        });
+        before - items.len()
+    }
+
+    fn accepts(&self, item: &Map<String, Value>) -> bool {
+        let matches = self.must_contain.iter().all(|(field, regex)| {
+            item.get(field)
+                .and_then(text)
+                .is_some_and(|value| regex.is_match(&value))
+        });
+        matches
+            && self.min_fields.is_none_or(|min| {
+                // Fields added by the crawler don't count
+                item.iter()
+                    .filter(|(name, value)| !name.starts_with('_') && text(value).is_some_and(|v| !v.is_empty()))
+                    .count()
+                    >= min
+            })
+    }
+}
+
+/// The value as text, or `None` for `null` and empty lists.
+fn text(value: &Value) -> Option<String> {
+    match value {
+        Value::Null => None,
+        Value::Array(values) if values.is_empty() => None,
+        Value::String(s) => Some(s.trim().to_string()),
+        other => Some(other.to_string()),
+    }
+}
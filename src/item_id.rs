@@ -0,0 +1,68 @@
+//! The identity of an item, computed once from the fields named by `item_id`
+//! and attached as `_id`, so that duplicate detection and upserting outputs
+//! agree on which items are the same.
+
+use crate::config::schema::{ItemIdConfig, ItemIdHash};
+use serde_json::{Map, Value};
+use sha2::{Digest, Sha256};
+
+/// Field the identity is stored in.
+pub const ITEM_ID_FIELD: &str = "_id";
+
+#[derive(Debug, Clone)]
+pub struct ItemId {
+    fields: Vec<String>,
+    hash: ItemIdHash,
+}
+
+impl ItemId {
+    pub fn new(config: &ItemIdConfig) -> Self {
+        Self {
+            fields: config.fields.clone(),
+            hash: config.hash,
+        }
+    }
+
+    /// The identity of `item`, or `None` if it has none of the fields.
+    pub fn compute(&self, item: &Map<String, Value>) -> Option<String> {
+        let values: Vec<&Value> = self
+            .fields
+            .iter()
+            .map(|field| match item.get(field) {
+                None if field == "url" => item.get("_final_url"),
+                value => value,
+            })
+            .map(|value| value.unwrap_or(&Value::Null))
+            .collect();
+        if values.iter().all(|value| value.is_null()) {
+            return None;
+        }
+
+        match self.hash {
+            ItemIdHash::Sha256 => {
+                // Hashed as a JSON array, so `["a|b", "c"]` and `["a", "b|c"]` differ
+                let canonical = serde_json::to_string(&values).unwrap_or_default();
+                Some(Sha256::digest(canonical.as_bytes()).iter().map(|b| format!("{:02x}", b)).collect())
+            }
+            ItemIdHash::None => Some(
+                values
+                    .iter()
+                    .map(|value| match value {
+                        Value::String(s) => s.clone(),
+                        Value::Null => String::new(),
+                        value => value.to_string(),
+                    })
+                    .collect::<Vec<_>>()
+                    .join("|"),
+            ),
+        }
+    }
+
+    /// Sets the `_id` of `item`, returning it.
+    pub fn attach(&self, item: &mut Value) -> Option<String> {
+        let Value::Object(map) = item else { return None };
+        let id = self.compute(map)?;
+        map.insert(ITEM_ID_FIELD.to_string(), Value::String(id.clone()));
+        Some(id)
+    }
+}
@@ -0,0 +1,80 @@
+//! Language detection for extracted items, backed by whatlang.
+
+use serde_json::{json, Value};
+
+/// whatlang's ISO 639-3 codes mapped to the ISO 639-1 codes users usually write.
+const ISO_639_1: &[(&str, &str)] = &[
+    ("afr", "af"), ("aka", "ak"), ("amh", "am"), ("ara", "ar"), ("aze", "az"), ("bel", "be"),
+    ("ben", "bn"), ("bul", "bg"), ("cat", "ca"), ("ces", "cs"), ("cmn", "zh"), ("dan", "da"),
+    ("deu", "de"), ("ell", "el"), ("eng", "en"), ("epo", "eo"), ("est", "et"), ("fin", "fi"),
+    ("fra", "fr"), ("guj", "gu"), ("heb", "he"), ("hin", "hi"), ("hrv", "hr"), ("hun", "hu"),
+    ("hye", "hy"), ("ind", "id"), ("ita", "it"), ("jav", "jv"), ("jpn", "ja"), ("kan", "kn"),
+    ("kat", "ka"), ("khm", "km"), ("kor", "ko"), ("lat", "la"), ("lav", "lv"), ("lit", "lt"),
+    ("mal", "ml"), ("mar", "mr"), ("mkd", "mk"), ("mya", "my"), ("nep", "ne"), ("nld", "nl"),
+    ("nob", "nb"), ("ori", "or"), ("pan", "pa"), ("pes", "fa"), ("pol", "pl"), ("por", "pt"),
+    ("ron", "ro"), ("rus", "ru"), ("sin", "si"), ("slk", "sk"), ("slv", "sl"), ("sna", "sn"),
+    ("spa", "es"), ("srp", "sr"), ("swe", "sv"), ("tam", "ta"), ("tel", "te"), ("tgl", "tl"),
+    ("tha", "th"), ("tuk", "tk"), ("tur", "tr"), ("ukr", "uk"), ("urd", "ur"), ("uzb", "uz"),
+    ("vie", "vi"), ("yid", "yi"), ("zul", "zu"),
+];
+
+/// Short ISO 639-1 code where one exists, otherwise the ISO 639-3 code.
+fn short_code(code: &'static str) -> &'static str {
+    ISO_639_1
+        .iter()
+        .find(|(long, _)| *long == code)
+        .map_or(code, |(_, short)| short)
+}
+
+/// Detects the language of `text`, or `None` if it can't be told reliably.
+pub fn detect(text: &str) -> Option<&'static str> {
+    let info = whatlang::detect(text)?;
+    info.is_reliable().then(|| short_code(info.lang().code()))
+}
+
+/// Tags items with a `_lang` field and optionally keeps only some languages.
+#[derive(Debug, Clone, Default)]
+pub struct LanguageFilter {
+    /// Allowed languages as lowercase ISO 639-1 or 639-3 codes; empty allows all
+    pub only: Vec<String>,
+}
+
+impl LanguageFilter {
+    pub fn new(only: &[String]) -> Self {
+        Self {
+            only: only.iter().map(|code| code.to_ascii_lowercase()).collect(),
+        }
+    }
+
+    /// Adds `_lang` to `item`, returning `false` if the item should be dropped.
+    ///
+    /// Items whose language can't be detected reliably are always kept.
+    pub fn tag(&self, item: &mut Value) -> bool {
+        let Value::Object(map) = item else {
+            return true;
+        };
+        let text = map
+            .iter()
+            .filter(|(key, _)| !key.starts_with('_'))
+            .filter_map(|(_, value)| value.as_str())
+            .collect::<Vec<_>>()
+            .join(" ");
+        let lang = detect(&text);
+        map.insert("_lang".to_string(), json!(lang));
+
+        match lang {
+            Some(lang) if !self.only.is_empty() => self.allows(lang),
+            _ => true,
+        }
+    }
+
+    fn allows(&self, short: &str) -> bool {
+        let long = ISO_639_1
+            .iter()
+            .find(|(_, code)| *code == short)
+            .map(|(long, _)| *long);
+        self.only
+            .iter()
+            .any(|code| code == short || Some(code.as_str()) == long)
+    }
+}
@@ -1,13 +1,58 @@
+pub mod bench;
+pub mod chain;
+pub mod cleanup;
+pub mod compare;
 pub mod config;
+pub mod crawl_window;
 pub mod crawler;
 pub mod error;
+pub mod events;
+pub mod export;
+pub mod external_links;
+pub mod extract;
+pub mod extraction_tests;
+pub mod fail_on;
+pub mod frontier;
+#[cfg(feature = "grpc")]
+pub mod grpc;
+pub mod health;
+pub mod http;
+pub mod item_filter;
+pub mod item_id;
+pub mod language;
+pub mod logging;
+pub mod memory_budget;
 pub mod metrics;
+pub mod near_duplicates;
+pub mod notify;
 pub mod output;
+pub mod page_types;
+pub mod paths;
+pub mod pdf;
+pub mod rate_limit;
+pub mod recurring;
+pub mod relevance;
+pub mod render;
+pub mod report;
+pub mod robots;
+pub mod run_id;
+pub mod run_report;
 pub mod selector;
+pub mod serve;
+pub mod shard;
 pub mod spider;
+pub mod state;
+pub mod stats;
+pub mod suggest;
+pub mod transform;
+pub mod url_rewrite;
+pub mod visited;
+pub mod worker;
 
 pub use crawler::{CrawlerEngine, CrawlerState};
 pub use error::{Error, Result};
 pub use metrics::collector::MetricsCollector;
 pub use metrics::snapshot::MetricsSnapshot;
+pub use rate_limit::RateLimiter;
+pub use recurring::RecurringCrawl;
 pub use spider::{GenericSpider, Spider};
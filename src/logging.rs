@@ -0,0 +1,260 @@
+//! Logger setup from CLI verbosity flags and the config's `logging` block.
+
+use crate::config::schema::{LogFileConfig, LoggingConfig};
+use crate::error::{Error, Result};
+use env_logger::fmt::Formatter;
+use env_logger::{Builder, Env, Logger, Target, WriteStyle};
+use log::kv::{self, VisitSource, VisitValue};
+use log::{LevelFilter, Record};
+use serde_json::{Map, Value, json};
+use std::fmt;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::str::FromStr;
+
+/// How log records are written.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum LogFormat {
+    /// env_logger's human-readable lines
+    #[default]
+    Text,
+    /// One JSON object per record, for log collectors such as Loki or ELK
+    Json,
+}
+
+impl FromStr for LogFormat {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "text" => Ok(LogFormat::Text),
+            "json" => Ok(LogFormat::Json),
+            _ => Err(Error::Config(format!(
+                "Invalid log format '{}': expected text or json",
+                s
+            ))),
+        }
+    }
+}
+
+impl fmt::Display for LogFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LogFormat::Text => write!(f, "text"),
+            LogFormat::Json => write!(f, "json"),
+        }
+    }
+}
+
+impl LogFormat {
+    /// `json` inside a container, where logs go to a collector, `text` otherwise.
+    pub fn detect() -> Self {
+        if in_container() { LogFormat::Json } else { LogFormat::Text }
+    }
+}
+
+/// Whether the process runs in a container: a Kubernetes pod, or a Docker or
+/// Podman container.
+pub fn in_container() -> bool {
+    std::env::var_os("KUBERNETES_SERVICE_HOST").is_some()
+        || std::path::Path::new("/.dockerenv").exists()
+        || std::path::Path::new("/run/.containerenv").exists()
+}
+
+/// Filter directives for the `-q`/`-v`/`-vv` flags, or `None` when neither was given.
+pub fn verbosity_preset(quiet: bool, verbose: u8) -> Option<&'static str> {
+    match (quiet, verbose) {
+        (true, _) => Some("warn"),
+        (false, 0) => None,
+        (false, 1) => Some("info,crawler=debug"),
+        (false, _) => Some("debug,crawler=trace"),
+    }
+}
+
+/// Builds the logger for a run.
+///
+/// Filters are applied in increasing order of precedence: the config's `logging`
+/// block, then the CLI verbosity `preset`, then `RUST_LOG`. JSON records carry
+/// `run_id`, if given.
+pub fn build_logger(
+    config: Option<&LoggingConfig>,
+    preset: Option<&str>,
+    format: LogFormat,
+    run_id: Option<&str>,
+) -> Result<Logger> {
+    let mut builder = Builder::new();
+    builder.filter_level(LevelFilter::Info);
+    if format == LogFormat::Json {
+        let run_id = run_id.map(str::to_string);
+        builder
+            .format(move |buf, record| write_json(buf, record, run_id.as_deref()))
+            .write_style(WriteStyle::Never);
+    }
+
+    if let Some(config) = config {
+        if let Some(level) = &config.level {
+            builder.filter_level(parse_level(level)?);
+        }
+        for (module, level) in &config.modules {
+            builder.filter_module(module, parse_level(level)?);
+        }
+        if let Some(file) = &config.file {
+            builder
+                .target(Target::Pipe(Box::new(RotatingFile::open(file)?)))
+                .write_style(WriteStyle::Never);
+        }
+    }
+
+    if let Some(preset) = preset {
+        builder.parse_filters(preset);
+    }
+    builder.parse_env(Env::default());
+
+    Ok(builder.build())
+}
+
+/// Writes `record` as a single JSON line.
+///
+/// A `url` key-value becomes a top-level field; any other key-values go under `fields`.
+fn write_json(buf: &mut Formatter, record: &Record, run_id: Option<&str>) -> io::Result<()> {
+    let mut fields = JsonFields(Map::new());
+    let _ = record.key_values().visit(&mut fields);
+    let mut fields = fields.0;
+
+    let mut line = json!({
+        "timestamp": chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Millis, true),
+        "level": record.level().as_str(),
+        "target": record.target(),
+        "message": record.args().to_string(),
+    });
+    if let Some(run_id) = run_id {
+        line["run_id"] = json!(run_id);
+    }
+    if let Some(url) = fields.remove("url") {
+        line["url"] = url;
+    }
+    if !fields.is_empty() {
+        line["fields"] = Value::Object(fields);
+    }
+    writeln!(buf, "{}", line)
+}
+
+struct JsonFields(Map<String, Value>);
+
+impl<'kvs> VisitSource<'kvs> for JsonFields {
+    fn visit_pair(&mut self, key: kv::Key<'kvs>, value: kv::Value<'kvs>) -> std::result::Result<(), kv::Error> {
+        let mut json = JsonValue(Value::Null);
+        value.visit(&mut json)?;
+        self.0.insert(key.as_str().to_string(), json.0);
+        Ok(())
+    }
+}
+
+/// Keeps numbers, booleans and `None` typed instead of stringifying every value.
+struct JsonValue(Value);
+
+impl<'v> VisitValue<'v> for JsonValue {
+    fn visit_any(&mut self, value: kv::Value) -> std::result::Result<(), kv::Error> {
+        self.0 = json!(value.to_string());
+        Ok(())
+    }
+
+    fn visit_null(&mut self) -> std::result::Result<(), kv::Error> {
+        self.0 = Value::Null;
+        Ok(())
+    }
+
+    fn visit_u64(&mut self, value: u64) -> std::result::Result<(), kv::Error> {
+        self.0 = json!(value);
+        Ok(())
+    }
+
+    fn visit_i64(&mut self, value: i64) -> std::result::Result<(), kv::Error> {
+        self.0 = json!(value);
+        Ok(())
+    }
+
+    fn visit_f64(&mut self, value: f64) -> std::result::Result<(), kv::Error> {
+        self.0 = json!(value);
+        Ok(())
+    }
+
+    fn visit_bool(&mut self, value: bool) -> std::result::Result<(), kv::Error> {
+        self.0 = json!(value);
+        Ok(())
+    }
+}
+
+fn parse_level(level: &str) -> Result<LevelFilter> {
+    level
+        .parse()
+        .map_err(|_| Error::Config(format!("invalid log level '{}'", level)))
+}
+
+/// Log file that moves to `<path>.1` once it reaches `max_size_mb`, shifting
+/// older files up and deleting the one past `max_files`.
+pub struct RotatingFile {
+    path: PathBuf,
+    max_bytes: u64,
+    max_files: usize,
+    file: File,
+    written: u64,
+}
+
+impl RotatingFile {
+    pub fn open(config: &LogFileConfig) -> Result<Self> {
+        let path = PathBuf::from(&config.path);
+        if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            fs::create_dir_all(parent)?;
+        }
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+        let written = file.metadata()?.len();
+
+        Ok(Self {
+            path,
+            max_bytes: config.max_size_mb.saturating_mul(1024 * 1024).max(1),
+            max_files: config.max_files,
+            file,
+            written,
+        })
+    }
+
+    fn rotated_path(&self, n: usize) -> PathBuf {
+        let mut path = self.path.clone().into_os_string();
+        path.push(format!(".{}", n));
+        path.into()
+    }
+
+    fn rotate(&mut self) -> io::Result<()> {
+        self.file.flush()?;
+        if self.max_files > 0 {
+            let _ = fs::remove_file(self.rotated_path(self.max_files));
+            for n in (1..self.max_files).rev() {
+                let from = self.rotated_path(n);
+                if from.exists() {
+                    fs::rename(&from, self.rotated_path(n + 1))?;
+                }
+            }
+            fs::rename(&self.path, self.rotated_path(1))?;
+        }
+        self.file = File::create(&self.path)?;
+        self.written = 0;
+        Ok(())
+    }
+}
+
+impl Write for RotatingFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.written > 0 && self.written + buf.len() as u64 > self.max_bytes {
+            self.rotate()?;
+        }
+        let written = self.file.write(buf)?;
+        self.written += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
@@ -1,12 +1,52 @@
-use clap::{Parser, Subcommand};
-use crawler::config::ConfigLoader;
-use crawler::crawler::CrawlerEngine;
+use clap::{Args, CommandFactory, Parser, Subcommand};
+use clap_complete::Shell;
+use crawler::bench::{self, BenchOptions};
+use crawler::chain;
+use crawler::compare::{self, Change, Comparison, RunRecord};
+use crawler::config::schema::{FrontierKind, IncrementalConfig, default_page_cache};
+use crawler::config::{ConfigLoader, ConfigSource, OutputConfig, SpiderConfig};
+use crawler::events::{self, EventLog};
+use crawler::export;
+use crawler::extraction_tests;
+use crawler::fail_on::FailCondition;
+use crawler::frontier::{DiskQueue, FrontierSnapshot};
+use crawler::health;
+use crawler::http::har::{Har, HarRecorder, HarTransport};
+use crawler::http::client::USER_AGENT;
+use crawler::http::{PageCache, RecordingTransport, ReplayTransport};
+use crawler::logging::{self, LogFormat};
+use crawler::metrics::collector::MetricsCollector;
 use crawler::metrics::snapshot::MetricsSnapshot;
-use indicatif::{ProgressBar, ProgressStyle};
+use crawler::notify::WebhookNotifier;
+use crawler::output::preview::PreviewOutput;
+use crawler::robots::{self, RobotsFile};
+use crawler::run_id::new_run_id;
+use crawler::run_report::{ReportFormat, ReportRecorder};
+use crawler::serve::{self, ServeOptions};
+use crawler::shard::Shard;
+use crawler::state::StateDb;
+use crawler::stats;
+use crawler::suggest;
+use crawler::worker;
+use indicatif::{MultiProgress, ProgressBar, ProgressStyle};
+use std::collections::BTreeMap;
+use std::io::IsTerminal;
 use std::path::PathBuf;
 use std::sync::Arc;
 use std::time::Duration;
 
+/// Prints a line of the run summary, to stderr when stdout is reserved for items
+/// or piped.
+macro_rules! summary {
+    ($to_stderr:expr, $($arg:tt)*) => {
+        if $to_stderr {
+            eprintln!($($arg)*);
+        } else {
+            println!($($arg)*);
+        }
+    };
+}
+
 #[derive(Parser)]
 #[command(name = "crawler")]
 #[command(version = "0.1.0")]
@@ -14,39 +54,387 @@ use std::time::Duration;
 struct Cli {
     #[command(subcommand)]
     command: Commands,
+
+    /// Only log warnings and errors
+    #[arg(short, long, global = true, conflicts_with = "verbose")]
+    quiet: bool,
+
+    /// Log more detail: `-v` for debug, `-vv` for trace (`RUST_LOG` still takes precedence)
+    #[arg(short, long, global = true, action = clap::ArgAction::Count)]
+    verbose: u8,
+
+    /// Log line format: `text`, or `json` for one structured record per line.
+    /// Defaults to `json` in a container and `text` otherwise
+    #[arg(long, global = true)]
+    log_format: Option<LogFormat>,
 }
 
 #[derive(Subcommand)]
 enum Commands {
     /// Run a spider from a config file
-    Run {
-        /// Path to the configuration file (JSON/YAML/TOML)
-        #[arg(short, long)]
-        config: PathBuf,
+    Run(Box<RunArgs>),
+    /// Convert an existing output (JSON, JSONL or SQLite) into another format
+    Export {
+        /// Output file to read: .json, .jsonl, .ndjson or a SQLite .db
+        input: PathBuf,
+
+        /// File to write; the format is taken from the extension unless --to is given
+        output: PathBuf,
 
-        /// Show progress bars (stderr)
-        #[arg(short, long, default_value_t = true)]
-        progress: bool,
+        /// Output format: csv, json, sqlite, parquet or xlsx
+        #[arg(long)]
+        to: Option<String>,
+
+        /// SQLite table to read from and/or write to
+        #[arg(long, default_value = "scraped_data")]
+        table: String,
+    },
+    /// Summarize previous runs recorded in an event log
+    Stats {
+        /// Event log written by `run --events`
+        events: PathBuf,
+
+        /// Only show the most recent N runs
+        #[arg(long, default_value_t = 10)]
+        last: usize,
+
+        /// Number of entries listed per failure hot-spot
+        #[arg(long, default_value_t = 5)]
+        top: usize,
     },
+    /// Compare two runs: items, new failures, latency and per-field coverage,
+    /// with warnings for likely markup changes or blocking
+    Compare {
+        /// First run: a `run --metrics-out` file, or a run ID with `--state`
+        before: Option<String>,
+
+        /// Second run, in the same forms. Without either run, the last two
+        /// finished runs in `--state` are compared
+        after: Option<String>,
+
+        /// State database of an incremental crawl, where finished runs are recorded
+        #[arg(long)]
+        state: Option<String>,
+
+        /// Event log written by `run --events`, to compare the failed URLs
+        #[arg(long)]
+        events: Option<PathBuf>,
+
+        /// Number of new failures listed
+        #[arg(long, default_value_t = 10)]
+        top: usize,
+    },
+    /// Print a shell completion script, e.g. `crawler completions zsh > _crawler`
+    Completions {
+        /// Shell to generate completions for
+        shell: Shell,
+    },
+    /// Print the man page in roff format, e.g. `crawler manpage > crawler.1`
+    Manpage,
     /// Validate a configuration file
     Check {
-        /// Path to the configuration file
+        /// Path to the configuration file. Defaults to the config in
+        /// `CRAWLER_CONFIG_JSON`, or the file named by `CRAWLER_CONFIG`
+        #[arg(short, long)]
+        config: Option<PathBuf>,
+
+        /// Also run the config's `tests` against their saved pages, exiting
+        /// with status 1 if any fails
+        #[arg(long)]
+        run_tests: bool,
+    },
+    /// Propose a root selector and fields from a page's most repeated structure
+    /// and print a starter config, e.g. `crawler suggest --url <url> > spider.yaml`
+    Suggest {
+        /// Page to analyze, ideally a listing with several items
+        #[arg(long)]
+        url: String,
+    },
+    /// Explain what a host's robots.txt allows: the verdict and deciding rule
+    /// for each path, with any crawl-delay and sitemaps
+    Robots {
+        /// Page or host to check, e.g. `https://example.com/products/`
+        url: String,
+
+        /// Also check the config's start URLs on the same host, as its user agents
         #[arg(short, long)]
-        config: PathBuf,
+        config: Option<PathBuf>,
+
+        /// User agent to check for, instead of the crawler's
+        #[arg(long)]
+        user_agent: Option<String>,
+
+        /// Paths to check, e.g. `/search?q=x`; defaults to the URL's own path
+        #[arg(long = "path")]
+        paths: Vec<String>,
+    },
+    /// Show what is left in a disk frontier, e.g. of a running crawl: queued URLs
+    /// by host and depth, the next ones to be crawled and those in flight
+    Queue {
+        /// Config whose `frontier` file to read
+        #[arg(short, long, conflicts_with = "frontier", required_unless_present = "frontier")]
+        config: Option<PathBuf>,
+
+        /// Disk frontier file to read, instead of a config's
+        #[arg(long)]
+        frontier: Option<String>,
+
+        /// Number of queued URLs listed
+        #[arg(long, default_value_t = 20)]
+        next: usize,
+
+        /// Print the snapshot as JSON
+        #[arg(long)]
+        json: bool,
+    },
+    /// Inspect or empty a page cache shared by spiders through `page_cache`
+    Cache {
+        #[command(subcommand)]
+        action: CacheAction,
+    },
+    /// Benchmark the engine against a local server serving generated pages
+    Bench {
+        /// Pages crawled at each concurrency level
+        #[arg(long, default_value_t = 200)]
+        pages: usize,
+
+        /// Approximate size of each generated page in KB
+        #[arg(long, default_value_t = 20)]
+        page_kb: usize,
+
+        /// Artificial server latency per response in milliseconds
+        #[arg(long, default_value_t = 0)]
+        latency_ms: u64,
+
+        /// Comma-separated concurrency levels to measure
+        #[arg(long, value_delimiter = ',', default_value = "1,4,16,64")]
+        concurrency: Vec<usize>,
+    },
+    /// Run crawl jobs submitted over HTTP, several at once
+    Serve {
+        /// Address to listen on; any but a loopback address needs
+        /// `CRAWLER_SERVE_TOKEN` set
+        #[arg(long, default_value = "127.0.0.1:8080")]
+        addr: std::net::SocketAddr,
+
+        /// Jobs allowed to run at once; further submissions get HTTP 429
+        #[arg(long, default_value_t = 4)]
+        max_jobs: usize,
+
+        /// Highest concurrency a single job may use
+        #[arg(long, default_value_t = 8)]
+        job_concurrency: usize,
+
+        /// Requests in flight across all jobs
+        #[arg(long, default_value_t = 32)]
+        max_requests_in_flight: usize,
+
+        /// Also serve the jobs over gRPC on this address, e.g. `127.0.0.1:50051`
+        #[cfg(feature = "grpc")]
+        #[arg(long)]
+        grpc_addr: Option<std::net::SocketAddr>,
+    },
+    /// Run crawl jobs taken from a queue, e.g. `crawler worker --queue redis://localhost`
+    Worker {
+        /// Queue to take jobs from; `redis://` or `rediss://`
+        #[arg(long)]
+        queue: String,
+
+        /// List holding the jobs
+        #[arg(long, default_value = "crawler:jobs")]
+        jobs_key: String,
+
+        /// List receiving job results
+        #[arg(long, default_value = "crawler:results")]
+        results_key: String,
+
+        /// Highest concurrency a job may use
+        #[arg(long, default_value_t = 8)]
+        max_concurrency: usize,
+    },
+}
+
+/// Options of `crawler run`
+#[derive(Args)]
+struct RunArgs {
+    /// Path to the configuration file (JSON/YAML/TOML). Defaults to the
+    /// config in `CRAWLER_CONFIG_JSON`, or the file named by `CRAWLER_CONFIG`
+    #[arg(short, long)]
+    config: Option<PathBuf>,
+
+    /// Show progress bars (stderr). By default they are shown only when
+    /// stderr is a terminal, outside of a container
+    #[arg(short, long, overrides_with = "no_progress")]
+    progress: bool,
+
+    /// Never show progress bars
+    #[arg(long, overrides_with = "progress")]
+    no_progress: bool,
+
+    /// Only crawl the slice of URLs owned by this shard, e.g. `2/8`
+    #[arg(long)]
+    shard: Option<Shard>,
+
+    /// Append an NDJSON log of request outcomes to this file
+    #[arg(long = "events")]
+    event_log: Option<PathBuf>,
+
+    /// Seed the crawl from a previous run's event log, or from the HTML
+    /// pages in a `.har` archive
+    #[arg(long)]
+    seed_from: Option<PathBuf>,
+
+    /// With --seed-from, only re-crawl URLs whose last attempt failed
+    #[arg(long, requires = "seed_from")]
+    only_failed: bool,
+
+    /// Save every response into this directory for later replay
+    #[arg(long, conflicts_with = "replay")]
+    record: Option<PathBuf>,
+
+    /// Serve all responses from a directory written by --record
+    #[arg(long)]
+    replay: Option<PathBuf>,
+
+    /// Scrape and extract, but print a preview instead of writing to the
+    /// configured output
+    #[arg(long)]
+    dry_run: bool,
+
+    /// Number of items printed by --dry-run
+    #[arg(long, default_value_t = 5)]
+    preview_items: usize,
+
+    /// Stop after emitting this many items (overrides `limits.max_items`)
+    #[arg(long)]
+    limit_items: Option<u64>,
+
+    /// Stop after fetching this many pages (overrides `limits.max_pages`)
+    #[arg(long)]
+    limit_pages: Option<u64>,
+
+    /// Where to write the CSV of URLs that failed after all retries
+    #[arg(long, default_value = "failed_urls.csv")]
+    failed_urls: PathBuf,
+
+    /// Write the final metrics snapshot as JSON to this file
+    #[arg(long)]
+    metrics_out: Option<PathBuf>,
+
+    /// Write a report with charts, per-domain tables, top errors, slowest
+    /// URLs and field fill rates to this file (`.html` or `.md`)
+    #[arg(long)]
+    report: Option<PathBuf>,
+
+    /// Warn about fields whose selector matched on fewer than this
+    /// percentage of pages
+    #[arg(long, default_value_t = 10.0)]
+    min_field_coverage: f64,
+
+    /// Write every request/response to a HAR file when the crawl ends
+    #[arg(long)]
+    export_har: Option<PathBuf>,
+
+    /// Serve all responses from a HAR archive; seeds from its pages unless
+    /// --seed-from is given
+    #[arg(long, conflicts_with_all = ["record", "replay"])]
+    replay_har: Option<PathBuf>,
+
+    /// With `incremental`, fetch every page again even if its last copy is
+    /// still fresh (freshness is still recorded)
+    #[arg(long)]
+    ignore_freshness: bool,
+
+    /// Serve /healthz, /readyz and /state on this address, e.g. `0.0.0.0:8080`
+    #[arg(long)]
+    health_addr: Option<std::net::SocketAddr>,
+
+    /// Seconds without any activity after which /healthz reports a running
+    /// crawl as stalled
+    #[arg(long, default_value_t = 300)]
+    stall_timeout: u64,
+
+    /// Exit with status 4 if this condition holds when the crawl ends,
+    /// e.g. `items<100` or `error_rate>20`; may be repeated, adds to `fail_on`
+    #[arg(long = "fail-on")]
+    fail_on: Vec<FailCondition>,
+}
+
+#[derive(Subcommand)]
+enum CacheAction {
+    /// Count the cached pages, their hosts and size
+    Stats {
+        /// Page cache database
+        #[arg(long, default_value_t = default_page_cache())]
+        db: String,
+
+        /// Also count the pages still fresh under this TTL
+        #[arg(long)]
+        ttl_secs: Option<u64>,
+    },
+    /// Delete cached pages
+    Clear {
+        /// Page cache database
+        #[arg(long, default_value_t = default_page_cache())]
+        db: String,
+
+        /// Only delete pages cached more than this many seconds ago
+        #[arg(long)]
+        older_than_secs: Option<u64>,
     },
 }
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
-    if std::env::var("RUST_LOG").is_err() {
-        unsafe { std::env::set_var("RUST_LOG", "info"); }
-    }
     let cli = Cli::parse();
-    let logger = env_logger::Builder::from_default_env().build();
+    let verbosity = logging::verbosity_preset(cli.quiet, cli.verbose);
+    let log_format = cli.log_format.unwrap_or_else(LogFormat::detect);
     let multi = Arc::new(indicatif::MultiProgress::new());
 
     match cli.command {
-        Commands::Run { config, progress } => {
+        Commands::Run(args) => {
+            let RunArgs {
+                config,
+                progress,
+                no_progress,
+                shard,
+                event_log,
+                seed_from,
+                only_failed,
+                record,
+                replay,
+                dry_run,
+                preview_items,
+                limit_items,
+                limit_pages,
+                failed_urls,
+                metrics_out,
+                report,
+                min_field_coverage,
+                export_har,
+                replay_har,
+                ignore_freshness,
+                health_addr,
+                stall_timeout,
+                fail_on,
+            } = *args;
+            // Loaded before the logger, which is configured by the `logging` block
+            let config = ConfigSource::resolve(config)?;
+            let mut config_data = ConfigLoader::load_source(&config)?;
+            let run_id = new_run_id();
+            let logger = logging::build_logger(
+                config_data.logging.as_ref(),
+                verbosity,
+                log_format,
+                Some(&run_id),
+            )?;
+            let max_level = logger.filter();
+            // Redrawn bars would garble logs captured from a pipe, a CI job or a container
+            let progress = match (progress, no_progress) {
+                (_, true) => false,
+                (true, _) => true,
+                _ => std::io::stderr().is_terminal() && !logging::in_container(),
+            };
             if progress {
                 let multi_clone = multi.clone();
                 indicatif_log_bridge::LogWrapper::new((*multi_clone).clone(), logger)
@@ -54,82 +442,621 @@ async fn main() -> anyhow::Result<()> {
                     .unwrap();
             } else {
                 log::set_boxed_logger(Box::new(logger)).unwrap();
-                log::set_max_level(log::LevelFilter::Info);
             }
+            log::set_max_level(max_level);
 
-            log::info!("Loading config from {:?}", config);
-            let config_data = ConfigLoader::load(&config)?;
+            log::info!("Loaded config from {}", config);
+            for condition in &fail_on {
+                condition.validate(&config_data)?;
+            }
+            if dry_run {
+                // Never open the configured output, not even to create an empty file
+                config_data.output = None;
+            }
+            if let Some(output) = &mut config_data.output {
+                output.expand_run_id(&run_id);
+            }
             log::info!("Loaded spider: {}", config_data.name);
 
-            let spider = Arc::new(ConfigLoader::create_spider(&config_data, Some(multi.clone())).await?);
-            let engine = CrawlerEngine::new(
-                Duration::from_millis(config_data.delay_ms),
-                config_data.concurrency,
-                None,
-            );
+            let metrics = Arc::new(MetricsCollector::new());
+            let mut spider =
+                ConfigLoader::create_spider(&config_data, Some(multi.clone()), metrics.clone())
+                    .await?;
+            let state = match &config_data.incremental {
+                Some(incremental) => Some(StateDb::open(incremental).await?),
+                None => None,
+            };
+            // A dry run mustn't make the next real run skip the pages it previewed
+            if let Some(state) = state.as_ref().filter(|_| !dry_run) {
+                spider = spider.with_state(state.clone());
+            }
+            if dry_run {
+                log::info!("Dry run: previewing {} items, output disabled", preview_items);
+                spider = spider.with_output(Box::new(PreviewOutput::new(
+                    preview_items,
+                    Some(multi.clone()),
+                )));
+            }
+            if let Some(dir) = &record {
+                log::info!("Recording responses to {:?}", dir);
+                let recorder = RecordingTransport::new(spider.transport.clone(), dir)?
+                    .with_max_body_bytes(config_data.compression.clone().unwrap_or_default().max_body_bytes);
+                spider = spider.with_transport(Arc::new(recorder));
+            }
+            if let Some(dir) = &replay {
+                log::info!("Replaying responses from {:?}", dir);
+                spider = spider.with_transport(Arc::new(ReplayTransport::new(dir)?));
+            }
+            let mut har_seeds = None;
+            if let Some(path) = &replay_har {
+                let har = Har::load(path)?;
+                log::info!("Replaying {} HAR entries from {:?}", har.log.entries.len(), path);
+                spider = spider.with_transport(Arc::new(HarTransport::new(&har)));
+                har_seeds = Some(har.page_urls());
+            }
+            let mut har_recorder = None;
+            if export_har.is_some() {
+                let recorder = Arc::new(
+                    HarRecorder::new(spider.transport.clone())
+                        .with_max_body_bytes(config_data.compression.clone().unwrap_or_default().max_body_bytes),
+                );
+                spider = spider.with_transport(recorder.clone());
+                har_recorder = Some(recorder);
+            }
+            let (spider, feed) = chain::feeding(Arc::new(spider), &config_data);
+            let mut engine = ConfigLoader::create_engine(&config_data, metrics.clone())
+                .await?
+                .with_run_id(run_id.clone())
+                .with_failure_report(failed_urls);
+            if limit_items.is_some() || limit_pages.is_some() {
+                let mut limits = config_data.limits.unwrap_or_default();
+                limits.max_items = limit_items.or(limits.max_items);
+                limits.max_pages = limit_pages.or(limits.max_pages);
+                engine = engine.with_limits(limits);
+            }
+            if let Some(shard) = shard {
+                engine = engine.with_shard(shard);
+            }
+            // Nor record the preview as a run, the baseline of the next one's coverage drift
+            if let Some(state) = state.filter(|_| !dry_run) {
+                if ignore_freshness {
+                    log::info!("Ignoring freshness recorded in {}", state.path());
+                }
+                engine = engine.with_state(state, !ignore_freshness);
+            }
+            if let Some(path) = &seed_from {
+                let urls = if path.extension().is_some_and(|ext| ext == "har") {
+                    if only_failed {
+                        log::warn!("--only-failed has no effect when seeding from a HAR file");
+                    }
+                    Har::load(path)?.page_urls()
+                } else {
+                    events::read_seed_urls(path, only_failed)?
+                };
+                log::info!("Seeding {} URLs from {:?}", urls.len(), path);
+                engine = engine.with_seed_urls(urls);
+            } else if let Some(urls) = har_seeds {
+                engine = engine.with_seed_urls(urls);
+            }
+            if let Some(path) = &event_log {
+                engine = engine.with_event_log(EventLog::open(path)?);
+            }
+            if let Some(notify) = &config_data.notify
+                && !dry_run
+            {
+                engine = engine.with_notifier(WebhookNotifier::new(notify)?);
+            }
 
-            let mut progress_bar: Option<ProgressBar> = None;
+            let mut domain_bars = None;
             let mut _progress_task = None;
             if progress {
-                let pb = multi.add(ProgressBar::new(0));
-                pb.set_style(ProgressStyle::default_bar()
-                    .template("{spinner:.green} [{elapsed_precise}] [{bar:40.cyan/blue}] {pos}/{len} ({eta}) {msg}")?
-                    .progress_chars("#>-"));
-                
+                let bars = Arc::new(std::sync::Mutex::new(DomainBars::new(multi.clone())?));
                 let mut metrics_rx = engine.watch_metrics();
-                let pb_clone = pb.clone();
-                progress_bar = Some(pb);
+                let bars_clone = bars.clone();
+                domain_bars = Some(bars);
                 _progress_task = Some(tokio::spawn(async move {
                     while metrics_rx.changed().await.is_ok() {
                         let snapshot: MetricsSnapshot = metrics_rx.borrow().clone();
-                        pb_clone.set_length(snapshot.urls_queued);
-                        pb_clone.set_position(snapshot.urls_processed);
-                        pb_clone.set_message(format!(
-                            "Items: {} | Success: {:.1}% | RPS: {:.2}",
-                            snapshot.items_extracted,
-                            snapshot.success_rate,
-                            snapshot.requests_per_second
-                        ));
+                        if let Ok(mut bars) = bars_clone.lock() {
+                            bars.update(&snapshot);
+                        }
                     }
                 }));
             }
 
+            if let Some(addr) = health_addr {
+                health::serve(addr, &engine, Duration::from_secs(stall_timeout)).await?;
+            }
+
+            let report_recorder = match &report {
+                Some(path) => {
+                    ReportFormat::from_path(path)?;
+                    Some(ReportRecorder::start(&engine))
+                }
+                None => None,
+            };
+
             log::info!("Starting crawl...");
-            engine.run(spider).await;
+            let mut crawl_report = engine.run(spider).await?;
+            if let Some(feed) = feed {
+                if dry_run {
+                    log::info!("Dry run: not starting {}", feed.config.display());
+                } else if let Some(last) =
+                    chain::run_stages(feed, &config_data, &run_id, metrics, Some(multi.clone())).await?
+                {
+                    // The summary covers every stage of the chain
+                    crawl_report.metrics = last.metrics;
+                }
+            }
+
+            if let (Some(recorder), Some(path)) = (&har_recorder, &export_har) {
+                let count = recorder.save(path)?;
+                log::info!("Wrote {} HAR entries to {:?}", count, path);
+            }
 
             if progress {
                 if let Some(task) = _progress_task {
                     task.abort();
                 }
-                if let Some(pb) = progress_bar {
-                    let final_metrics = engine.get_metrics();
-                    pb.set_style(ProgressStyle::default_bar()
-                        .template("✅ [{elapsed_precise}] [{bar:40.green/blue}] {pos}/{len} {msg}")?
-                        .progress_chars("#>-"));
-                    pb.finish_with_message(format!(
-                        "Items: {} | Success: {:.1}% | RPS: {:.2} - Completed",
-                        final_metrics.items_extracted,
-                        final_metrics.success_rate,
-                        final_metrics.requests_per_second
-                    ));
-                }
-            }
-
-            let final_metrics = engine.get_metrics();
-            println!("\n✅ Crawl Completed:");
-            println!("   URLs Processed: {}", final_metrics.urls_processed);
-            println!("   Items Extracted: {}", final_metrics.items_extracted);
-            println!("   Success Rate: {:.1}%", final_metrics.success_rate);
-            println!("   Average Duration: {}ms", final_metrics.avg_response_time_ms);
-            println!("   Total Time: {:.1}s", final_metrics.elapsed_seconds);
-        }
-        Commands::Check { config } => {
-            match ConfigLoader::load(&config) {
+                if let Some(Ok(mut bars)) = domain_bars.as_ref().map(|bars| bars.lock()) {
+                    bars.finish(&crawl_report.metrics);
+                }
+            }
+
+            let final_metrics = &crawl_report.metrics;
+            // Piped stdout belongs to whatever reads it, so the summary goes to stderr
+            let to_stderr = matches!(config_data.output, Some(OutputConfig::Stdout { .. }))
+                || !std::io::stdout().is_terminal();
+            summary!(to_stderr, "\n✅ Crawl Completed:");
+            summary!(to_stderr, "   Run ID: {}", run_id);
+            summary!(to_stderr, "   Ended: {}", crawl_report.end_reason);
+            summary!(to_stderr, "   URLs Processed: {}", final_metrics.urls_processed);
+            summary!(to_stderr, "   Items Extracted: {}", final_metrics.items_extracted);
+            summary!(to_stderr, "   Success Rate: {:.1}%", final_metrics.success_rate);
+            if final_metrics.requests_redirected > 0 {
+                summary!(
+                    to_stderr,
+                    "   Redirects: {} ({} requests)",
+                    final_metrics.redirects_followed, final_metrics.requests_redirected
+                );
+            }
+            summary!(
+                to_stderr,
+                "   Connections: {} opened, {:.1}% reuse, {} HTTP/2 responses",
+                final_metrics.connections_opened,
+                final_metrics.connection_reuse_rate,
+                final_metrics.http2_responses
+            );
+            summary!(
+                to_stderr,
+                "   Bytes: {} received, {} decoded",
+                final_metrics.bytes_received, final_metrics.bytes_decoded
+            );
+            if final_metrics.peak_rss_bytes > 0 {
+                summary!(
+                    to_stderr,
+                    "   Memory: peak RSS {:.1} MB, peak in-flight bodies {:.1} MB",
+                    final_metrics.peak_rss_bytes as f64 / 1_048_576.0,
+                    final_metrics.peak_body_bytes_in_flight as f64 / 1_048_576.0
+                );
+            }
+            if final_metrics.items_spilled > 0 {
+                summary!(
+                    to_stderr,
+                    "   Items Spilled to Disk: {} (memory budget {} MB)",
+                    final_metrics.items_spilled,
+                    final_metrics.memory_limit_bytes / 1_048_576
+                );
+            }
+            if final_metrics.oversized_bodies > 0 {
+                summary!(to_stderr, "   Oversized Bodies: {}", final_metrics.oversized_bodies);
+            }
+            if final_metrics.extraction_errors > 0 {
+                summary!(to_stderr, "   Extraction Errors: {}", final_metrics.extraction_errors);
+            }
+            if final_metrics.retries > 0 {
+                summary!(to_stderr, "   Retries: {}", final_metrics.retries);
+            }
+            if final_metrics.duplicate_pages > 0 {
+                summary!(to_stderr, "   Duplicate Pages Skipped: {}", final_metrics.duplicate_pages);
+            }
+            if final_metrics.duplicate_items > 0 {
+                summary!(to_stderr, "   Duplicate Items Dropped: {}", final_metrics.duplicate_items);
+            }
+            if final_metrics.near_duplicate_pages > 0 {
+                summary!(to_stderr, "   Near-Duplicate Pages: {}", final_metrics.near_duplicate_pages);
+            }
+            if final_metrics.external_links > 0 {
+                summary!(to_stderr, "   External Links Found: {}", final_metrics.external_links);
+            }
+            if final_metrics.irrelevant_pages > 0 {
+                summary!(to_stderr, "   Irrelevant Pages (links not followed): {}", final_metrics.irrelevant_pages);
+            }
+            if final_metrics.cache_hits + final_metrics.cache_revalidations > 0 {
+                summary!(
+                    to_stderr,
+                    "   Page Cache: {} hits, {} revalidated",
+                    final_metrics.cache_hits, final_metrics.cache_revalidations
+                );
+            }
+            if final_metrics.requests_blocked > 0 {
+                summary!(to_stderr, "   Blocked Responses: {}", final_metrics.requests_blocked);
+            }
+            if final_metrics.pages_rendered > 0 {
+                summary!(to_stderr, "   Pages Rendered: {}", final_metrics.pages_rendered);
+            }
+            if final_metrics.head_skipped > 0 {
+                summary!(to_stderr, "   Skipped After HEAD: {}", final_metrics.head_skipped);
+            }
+            if final_metrics.fresh_pages_skipped > 0 {
+                summary!(to_stderr, "   Fresh Pages Skipped: {}", final_metrics.fresh_pages_skipped);
+            }
+            if final_metrics.items_dropped_language > 0 {
+                summary!(
+                    to_stderr,
+                    "   Items Dropped (language): {}",
+                    final_metrics.items_dropped_language
+                );
+            }
+            if final_metrics.items_filtered > 0 {
+                summary!(to_stderr, "   Items Dropped (item_filter): {}", final_metrics.items_filtered);
+            }
+            summary!(
+                to_stderr,
+                "   Average Duration: {}ms (fetch {}ms, parse {}ms)",
+                final_metrics.avg_response_time_ms,
+                final_metrics.avg_fetch_time_ms,
+                final_metrics.avg_parse_time_ms
+            );
+            if final_metrics.items_processed > 0 {
+                summary!(
+                    to_stderr,
+                    "   Pipeline Latency: p50 {}ms, p99 {}ms from extraction to output",
+                    final_metrics.p50_pipeline_latency_ms,
+                    final_metrics.p99_pipeline_latency_ms
+                );
+            }
+            summary!(to_stderr, "   Total Time: {:.1}s", final_metrics.elapsed_seconds);
+            let durations = &crawl_report.durations;
+            summary!(
+                to_stderr,
+                "   Phases: setup {}ms, crawl {}ms, output {}ms, finish {}ms",
+                durations.setup_ms, durations.crawl_ms, durations.output_ms, durations.finish_ms
+            );
+
+            if !final_metrics.field_coverage.is_empty() {
+                summary!(to_stderr, "\n📋 Field Coverage:");
+                for (field, coverage) in &final_metrics.field_coverage {
+                    summary!(
+                        to_stderr,
+                        "   {}: {:.1}% ({} of {} pages)",
+                        field,
+                        coverage.ratio() * 100.0,
+                        coverage.pages_matched,
+                        coverage.pages()
+                    );
+                }
+                for (field, coverage) in &final_metrics.field_coverage {
+                    if coverage.pages() > 0 && coverage.ratio() * 100.0 < min_field_coverage {
+                        let selector = config_data
+                            .extraction_rules
+                            .get(field)
+                            .map(|rule| rule.selector.to_query_string())
+                            .unwrap_or_default();
+                        summary!(
+                            to_stderr,
+                            "   ⚠️  Selector `{}` ({}) matched on {:.0}% of {} pages — likely broken",
+                            selector,
+                            field,
+                            coverage.ratio() * 100.0,
+                            coverage.pages()
+                        );
+                    }
+                }
+            }
+
+            if !final_metrics.proxies.is_empty() {
+                summary!(to_stderr, "\n🧭 Proxies:");
+                for (proxy, metrics) in &final_metrics.proxies {
+                    summary!(
+                        to_stderr,
+                        "   {}: {} requests, {:.1}% success, {}ms average, {} evictions, {} blocked",
+                        proxy,
+                        metrics.requests,
+                        metrics.success_rate() * 100.0,
+                        metrics.avg_latency_ms(),
+                        metrics.evictions,
+                        metrics.blocked
+                    );
+                }
+                for (host, combination) in &final_metrics.unblocked_hosts {
+                    summary!(to_stderr, "   {} got through via {}", host, combination);
+                }
+            }
+
+            if let Some(path) = &metrics_out {
+                std::fs::write(path, serde_json::to_string_pretty(final_metrics)?)?;
+                log::info!("Wrote metrics to {:?}", path);
+            }
+            if let (Some(recorder), Some(path)) = (report_recorder, &report) {
+                recorder
+                    .write(path, &config_data.name, &run_id, final_metrics, &crawl_report.failures)
+                    .await?;
+                log::info!("Wrote report to {:?}", path);
+            }
+
+            let drift = engine.coverage_drift();
+            if !drift.is_empty() {
+                summary!(to_stderr, "\n📉 Coverage Drift Since the Previous Run:");
+                for change in &drift {
+                    summary!(
+                        to_stderr,
+                        "   {}: {:.1}% → {:.1}%",
+                        change.field,
+                        change.before.unwrap_or_default(),
+                        change.after.unwrap_or_default()
+                    );
+                }
+                if config_data.coverage_alert.is_some_and(|alert| alert.fail) {
+                    std::process::exit(3);
+                }
+            }
+
+            // A dry run only previews a few items, which says nothing about the crawl
+            if !dry_run {
+                let mut conditions = config_data
+                    .fail_on
+                    .iter()
+                    .map(|condition| condition.parse())
+                    .collect::<Result<Vec<FailCondition>, _>>()?;
+                conditions.extend(fail_on);
+                let failed: Vec<(FailCondition, f64)> = conditions
+                    .into_iter()
+                    .filter_map(|condition| condition.check(&crawl_report).map(|value| (condition, value)))
+                    .collect();
+                if !failed.is_empty() {
+                    summary!(to_stderr, "\n❌ Failed Expectations:");
+                    for (condition, value) in &failed {
+                        summary!(to_stderr, "   {} (got {})", condition, (value * 10.0).round() / 10.0);
+                    }
+                    std::process::exit(4);
+                }
+            }
+        }
+        Commands::Bench {
+            pages,
+            page_kb,
+            latency_ms,
+            concurrency,
+        } => {
+            let options = BenchOptions {
+                pages,
+                page_kb,
+                latency_ms,
+                concurrency_levels: concurrency,
+            };
+            println!(
+                "Benchmarking {} pages of ~{} KB ({}ms server latency)\n",
+                pages, page_kb, latency_ms
+            );
+            println!(
+                "{:>11} {:>8} {:>8} {:>10} {:>8} {:>8} {:>12}",
+                "concurrency", "pages", "items", "RPS", "p50 ms", "p99 ms", "peak RSS MB"
+            );
+            for result in bench::run(&options).await? {
+                println!(
+                    "{:>11} {:>8} {:>8} {:>10.1} {:>8} {:>8} {:>12.1}",
+                    result.concurrency,
+                    result.pages,
+                    result.items,
+                    result.requests_per_second,
+                    result.p50_ms,
+                    result.p99_ms,
+                    result.peak_rss_bytes as f64 / 1_048_576.0
+                );
+            }
+        }
+        Commands::Serve {
+            addr,
+            max_jobs,
+            job_concurrency,
+            max_requests_in_flight,
+            #[cfg(feature = "grpc")]
+            grpc_addr,
+        } => {
+            let logger = logging::build_logger(None, verbosity, log_format, None)?;
+            let max_level = logger.filter();
+            log::set_boxed_logger(Box::new(logger)).unwrap();
+            log::set_max_level(max_level);
+            let options = ServeOptions {
+                max_jobs,
+                job_concurrency,
+                max_requests_in_flight,
+                #[cfg(feature = "grpc")]
+                grpc_addr,
+            };
+            serve::serve(addr, options).await?;
+        }
+        Commands::Worker {
+            queue,
+            jobs_key,
+            results_key,
+            max_concurrency,
+        } => {
+            let logger = logging::build_logger(None, verbosity, log_format, None)?;
+            let max_level = logger.filter();
+            log::set_boxed_logger(Box::new(logger)).unwrap();
+            log::set_max_level(max_level);
+            let job_queue = worker::open_queue(&queue, &jobs_key, &results_key).await?;
+            log::info!("Taking jobs from {} on {}", jobs_key, queue);
+            worker::run(job_queue, max_concurrency).await?;
+        }
+        Commands::Export {
+            input,
+            output,
+            to,
+            table,
+        } => {
+            let output_config = export::output_config(&output, to.as_deref(), &table)?;
+            let count = export::export(&input, &output_config, &table).await?;
+            println!("✅ Exported {} items from {:?} to {:?}", count, input, output);
+        }
+        Commands::Stats { events, last, top } => {
+            let all_runs = stats::summarize_runs(&events::read_events(&events)?);
+            let runs = &all_runs[all_runs.len().saturating_sub(last)..];
+            println!(
+                "📊 Crawl history for {:?}: showing {} of {} runs\n",
+                events,
+                runs.len(),
+                all_runs.len()
+            );
+            println!(
+                "{:<22} {:<16} {:>9} {:>8} {:>8} {:>9} {:>10}",
+                "run", "spider", "requests", "success", "items", "Δ items", "duration"
+            );
+            let mut previous_items: Option<u64> = None;
+            for run in runs {
+                let trend = match previous_items {
+                    Some(0) | None => "-".to_string(),
+                    Some(prev) => format!(
+                        "{:+.0}%",
+                        (run.items as f64 - prev as f64) / prev as f64 * 100.0
+                    ),
+                };
+                let duration = run
+                    .duration_ms
+                    .map(|ms| format!("{:.1}s", ms as f64 / 1000.0))
+                    .unwrap_or_else(|| "unfinished".to_string());
+                println!(
+                    "{:<22} {:<16} {:>9} {:>7.1}% {:>8} {:>9} {:>10}",
+                    run.run_id,
+                    run.spider.as_deref().unwrap_or("-"),
+                    run.requests,
+                    run.success_rate(),
+                    run.items,
+                    trend,
+                    duration
+                );
+                previous_items = Some(run.items);
+            }
+
+            let hotspots = stats::failure_hotspots(runs, top);
+            if !hotspots.by_kind.is_empty() {
+                let list = |entries: &[(String, u64)]| {
+                    entries
+                        .iter()
+                        .map(|(key, count)| format!("{} ({})", key, count))
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                };
+                println!("\n🔥 Failure hot-spots:");
+                println!("   Hosts:  {}", list(&hotspots.by_host));
+                println!("   Errors: {}", list(&hotspots.by_kind));
+                println!("   URLs:   {}", list(&hotspots.by_url));
+            }
+        }
+        Commands::Compare {
+            before,
+            after,
+            state,
+            events,
+            top,
+        } => {
+            let state = match &state {
+                Some(path) if !std::path::Path::new(path).exists() => {
+                    anyhow::bail!("No state database at {}", path)
+                }
+                Some(path) => Some(StateDb::open(&IncrementalConfig { state_db: path.clone() }).await?),
+                None => None,
+            };
+            let (before, after) = match (before, after, &state) {
+                (Some(before), Some(after), _) => (before, after),
+                (None, None, Some(state)) => {
+                    let mut runs = state.finished_runs(2).await?;
+                    if runs.len() < 2 {
+                        anyhow::bail!("{} has fewer than two finished runs", state.path());
+                    }
+                    (runs.remove(1), runs.remove(0))
+                }
+                _ => anyhow::bail!("Give two runs to compare, or --state to compare its last two"),
+            };
+            let mut before = load_run(&before, state.as_ref()).await?;
+            let mut after = load_run(&after, state.as_ref()).await?;
+            if let Some(path) = &events {
+                let runs = stats::summarize_runs(&events::read_events(path)?);
+                before = before.with_failures_from(&runs);
+                after = after.with_failures_from(&runs);
+            }
+            print_comparison(&before, &after, &compare::compare(&before, &after), top);
+        }
+        Commands::Completions { shell } => {
+            clap_complete::generate(shell, &mut Cli::command(), "crawler", &mut std::io::stdout());
+        }
+        Commands::Manpage => {
+            clap_mangen::Man::new(Cli::command()).render(&mut std::io::stdout())?;
+        }
+        Commands::Queue {
+            config,
+            frontier,
+            next,
+            json,
+        } => {
+            let path = match (frontier, config) {
+                (Some(path), _) => path,
+                (None, Some(config)) => {
+                    let config = ConfigLoader::load(&config)?;
+                    match config.frontier {
+                        Some(frontier) if frontier.kind == FrontierKind::Disk => frontier.path,
+                        _ => anyhow::bail!(
+                            "{} keeps its frontier in memory; ask `crawler serve` for a job's queue at GET /jobs/{{id}}/queue",
+                            config.name
+                        ),
+                    }
+                }
+                (None, None) => unreachable!("clap requires --config or --frontier"),
+            };
+            let snapshot = DiskQueue::open_existing(&path).await?.snapshot(next).await?;
+            if json {
+                println!("{}", serde_json::to_string_pretty(&snapshot)?);
+            } else {
+                print_frontier(&path, &snapshot);
+            }
+        }
+        Commands::Cache { action } => match action {
+            CacheAction::Stats { db, ttl_secs } => {
+                let cache = PageCache::open(&db).await?;
+                let stats = cache.stats(ttl_secs).await?;
+                let date = |date: Option<chrono::DateTime<chrono::Utc>>| {
+                    date.map_or_else(|| "-".to_string(), |date| date.to_rfc3339())
+                };
+                println!("🗄️  Page cache {}:", cache.path());
+                match (stats.fresh, ttl_secs) {
+                    (Some(fresh), Some(ttl)) => {
+                        println!("   Pages: {} ({} fresh within {}s)", stats.entries, fresh, ttl)
+                    }
+                    _ => println!("   Pages: {}", stats.entries),
+                }
+                println!("   Hosts: {}", stats.hosts);
+                println!("   Bodies: {:.1} MB", stats.body_bytes as f64 / 1_048_576.0);
+                println!("   Oldest: {}", date(stats.oldest));
+                println!("   Newest: {}", date(stats.newest));
+            }
+            CacheAction::Clear { db, older_than_secs } => {
+                let cache = PageCache::open(&db).await?;
+                let deleted = cache.clear(older_than_secs).await?;
+                println!("🧹 Deleted {} pages from {}", deleted, cache.path());
+            }
+        },
+        Commands::Check { config, run_tests } => {
+            match ConfigSource::resolve(config).and_then(|source| ConfigLoader::load_source(&source)) {
                 Ok(cfg) => {
                     println!("✅ Config is valid:");
                     println!("   Name: {}", cfg.name);
                     println!("   Start URLs: {:?}", cfg.start_urls);
                     println!("   Rules: {}", cfg.extraction_rules.len());
+                    if run_tests && !run_extraction_tests(&cfg)? {
+                        std::process::exit(1);
+                    }
                 }
                 Err(e) => {
                     eprintln!("❌ Config error: {}", e);
@@ -137,7 +1064,328 @@ async fn main() -> anyhow::Result<()> {
                 }
             }
         }
+        Commands::Robots {
+            url,
+            config,
+            user_agent,
+            paths,
+        } => {
+            let url = url::Url::parse(&url)?;
+            let config = config.map(|path| ConfigLoader::load(&path)).transpose()?;
+            let mut paths = paths;
+            let mut user_agents = Vec::new();
+            if let Some(config) = &config {
+                for start_url in &config.start_urls {
+                    if let Ok(start_url) = url::Url::parse(start_url)
+                        && start_url.origin() == url.origin()
+                    {
+                        paths.push(path_and_query(&start_url));
+                    }
+                }
+                if let Some(pool) = &config.proxy_pool {
+                    user_agents.extend(pool.user_agents.iter().cloned());
+                }
+            }
+            if paths.is_empty() {
+                paths.push(path_and_query(&url));
+            }
+            if let Some(agent) = user_agent {
+                user_agents = vec![agent];
+            }
+            if user_agents.is_empty() {
+                user_agents.push(USER_AGENT.to_string());
+            }
+
+            let robots_url = robots::robots_url(&url);
+            println!("🤖 {}\n", robots_url);
+            let robots = match robots::fetch(&robots_url, &user_agents[0]).await? {
+                RobotsFile::Found(robots) => robots,
+                RobotsFile::Missing(status) => {
+                    println!("   HTTP {}: no robots.txt, so every path is allowed", status);
+                    return Ok(());
+                }
+                RobotsFile::Unreachable(status) => {
+                    println!("   HTTP {}: robots.txt is unreachable, so crawlers must treat every path as disallowed", status);
+                    return Ok(());
+                }
+            };
+
+            for agent in &user_agents {
+                let rules = robots.for_agent(agent);
+                let groups = if rules.matched_agents.is_empty() {
+                    "no group applies".to_string()
+                } else {
+                    format!(
+                        "rules of `User-agent: {}` ({} rules)",
+                        rules.matched_agents.join("`, `"),
+                        rules.rules.len()
+                    )
+                };
+                let delay = rules
+                    .crawl_delay
+                    .map(|delay| format!(", crawl-delay {}s", delay))
+                    .unwrap_or_default();
+                println!("User agent {}: {}{}", agent, groups, delay);
+                for path in &paths {
+                    let verdict = rules.check(path);
+                    let icon = if verdict.allowed { "✅" } else { "❌" };
+                    match verdict.rule {
+                        Some(rule) => println!(
+                            "   {} {}  ({} by `{}: {}` on line {})",
+                            icon,
+                            path,
+                            if verdict.allowed { "allowed" } else { "disallowed" },
+                            if rule.allow { "Allow" } else { "Disallow" },
+                            rule.pattern,
+                            rule.line
+                        ),
+                        None => println!("   {} {}  (no rule matches)", icon, path),
+                    }
+                }
+            }
+            if !robots.sitemaps.is_empty() {
+                println!("\n🗺️  Sitemaps:");
+                for sitemap in &robots.sitemaps {
+                    println!("   {}", sitemap);
+                }
+            }
+        }
+        Commands::Suggest { url } => {
+            let html = suggest::fetch(&url).await?;
+            let Some(suggestion) = suggest::suggest(&html) else {
+                eprintln!("❌ No repeated structure found on {}", url);
+                std::process::exit(1);
+            };
+
+            // The config goes to stdout so it can be redirected into a file
+            eprintln!(
+                "🔎 Root selector `{}` matched {} elements",
+                suggestion.root_selector, suggestion.matches
+            );
+            for field in &suggestion.fields {
+                let mut sample: String = field.sample.chars().take(60).collect();
+                if sample.len() < field.sample.len() {
+                    sample.push('…');
+                }
+                eprintln!(
+                    "   {:<16} {:<32} {:>4.0}%  {:?}",
+                    field.name,
+                    field.selector,
+                    field.coverage * 100.0,
+                    sample
+                );
+            }
+            eprintln!();
+            print!("{}", serde_yaml::to_string(&suggestion.starter_config(&url))?);
+        }
     }
 
     Ok(())
 }
+
+/// The part of `url` robots.txt rules match against, e.g. `/search?q=x`.
+fn path_and_query(url: &url::Url) -> String {
+    match url.query() {
+        Some(query) => format!("{}?{}", url.path(), query),
+        None => url.path().to_string(),
+    }
+}
+
+/// A run to compare: a metrics file if `run` names one, otherwise a run ID in `state`.
+async fn load_run(run: &str, state: Option<&StateDb>) -> anyhow::Result<RunRecord> {
+    let path = std::path::Path::new(run);
+    if path.is_file() {
+        return Ok(RunRecord::from_metrics_file(path)?);
+    }
+    match state {
+        Some(state) => Ok(RunRecord::from_state(state, run).await?),
+        None => anyhow::bail!("{} is not a metrics file (pass --state to look up run IDs)", run),
+    }
+}
+
+fn print_comparison(before: &RunRecord, after: &RunRecord, comparison: &Comparison, top: usize) {
+    let name = |run: &RunRecord| match &run.spider {
+        Some(spider) => format!("{} ({})", run.run_id, spider),
+        None => run.run_id.clone(),
+    };
+    println!("🔀 Comparing {} → {}
+", name(before), name(after));
+    println!("{:<20} {:>10} {:>10} {:>18}", "", "before", "after", "change");
+    let count = |label: &str, change: &Change, unit: &str| {
+        let relative = change
+            .ratio()
+            .map(|ratio| format!(" ({:+.1}%)", ratio * 100.0))
+            .unwrap_or_default();
+        println!(
+            "{:<20} {:>10} {:>10} {:>18}",
+            label,
+            format!("{}{}", change.before, unit),
+            format!("{}{}", change.after, unit),
+            format!("{:+}{}", change.delta(), relative)
+        );
+    };
+    count("Items", &comparison.items, "");
+    count("Pages", &comparison.pages, "");
+    count("Failed requests", &comparison.requests_failed, "");
+    count("Blocked responses", &comparison.requests_blocked, "");
+    println!(
+        "{:<20} {:>10} {:>10} {:>18}",
+        "Success rate",
+        format!("{:.1}%", comparison.success_rate.before),
+        format!("{:.1}%", comparison.success_rate.after),
+        format!("{:+.1} pts", comparison.success_rate.delta())
+    );
+    count("p50 response time", &comparison.p50_response_time_ms, "ms");
+    count("p99 response time", &comparison.p99_response_time_ms, "ms");
+
+    if !comparison.fields.is_empty() {
+        println!("
+📋 Field Coverage:");
+        let percent = |value: Option<f64>| value.map(|v| format!("{:.1}%", v)).unwrap_or_else(|| "-".to_string());
+        for field in &comparison.fields {
+            let change = match (field.before, field.after) {
+                (Some(before), Some(after)) => format!("{:+.1} pts", after - before),
+                (None, Some(_)) => "new".to_string(),
+                (Some(_), None) => "gone".to_string(),
+                (None, None) => String::new(),
+            };
+            println!(
+                "   {:<24} {:>8} → {:>8}   {}",
+                field.field,
+                percent(field.before),
+                percent(field.after),
+                change
+            );
+        }
+    }
+
+    if let (Some(new_failures), Some(fixed)) = (&comparison.new_failures, comparison.fixed_failures) {
+        println!("
+❌ New Failures: {} ({} URLs no longer fail)", new_failures.len(), fixed);
+        for (url, kind) in new_failures.iter().take(top) {
+            println!("   {} ({})", url, kind);
+        }
+        if new_failures.len() > top {
+            println!("   ... and {} more", new_failures.len() - top);
+        }
+    }
+
+    if comparison.warnings.is_empty() {
+        println!("
+✅ No significant changes");
+    } else {
+        println!("
+⚠️  Warnings:");
+        for warning in &comparison.warnings {
+            println!("   {}", warning);
+        }
+    }
+}
+
+fn print_frontier(path: &str, snapshot: &FrontierSnapshot) {
+    println!("🧭 Frontier {}:", path);
+    println!("   Queued: {}", snapshot.queued);
+    println!("   In flight: {}", snapshot.in_flight);
+    if snapshot.queued == 0 && snapshot.in_flight == 0 {
+        println!("\n✅ Nothing left to crawl");
+        return;
+    }
+
+    if !snapshot.by_domain.is_empty() {
+        let mut domains: Vec<(&String, &usize)> = snapshot.by_domain.iter().collect();
+        domains.sort_by_key(|(domain, count)| (std::cmp::Reverse(**count), *domain));
+        println!("\n🌐 Queued by host:");
+        for (domain, count) in domains {
+            println!("   {:<40} {}", domain, count);
+        }
+        println!("\n📏 Queued by depth:");
+        for (depth, count) in &snapshot.by_depth {
+            println!("   {:<40} {}", depth, count);
+        }
+    }
+    if !snapshot.next.is_empty() {
+        println!("\n⏭️  Next {}:", snapshot.next.len());
+        for entry in &snapshot.next {
+            println!("   [{}] {}", entry.depth, entry.url);
+        }
+    }
+    if !snapshot.in_flight_urls.is_empty() {
+        println!("\n⏳ In flight, holding the crawl open:");
+        for entry in &snapshot.in_flight_urls {
+            println!("   [{}] {}", entry.depth, entry.url);
+        }
+    }
+}
+
+/// Runs and prints the config's extraction tests, returning whether all passed.
+fn run_extraction_tests(config: &SpiderConfig) -> anyhow::Result<bool> {
+    if config.tests.is_empty() {
+        println!("\n🧪 No tests in the config");
+        return Ok(true);
+    }
+    let results = extraction_tests::run(config)?;
+    println!("\n🧪 Tests:");
+    for result in &results {
+        if result.passed() {
+            println!("   ✅ {} ({} items)", result.file.display(), result.items);
+        } else {
+            println!("   ❌ {} ({} items)", result.file.display(), result.items);
+            for failure in &result.failures {
+                println!("      {}", failure);
+            }
+        }
+    }
+    let failed = results.iter().filter(|result| !result.passed()).count();
+    println!("   {} passed, {} failed", results.len() - failed, failed);
+    Ok(failed == 0)
+}
+
+/// One progress bar per crawled host, added to the shared `MultiProgress` as hosts show up.
+struct DomainBars {
+    multi: Arc<MultiProgress>,
+    bars: BTreeMap<String, ProgressBar>,
+    running: ProgressStyle,
+    done: ProgressStyle,
+}
+
+impl DomainBars {
+    fn new(multi: Arc<MultiProgress>) -> anyhow::Result<Self> {
+        Ok(Self {
+            multi,
+            bars: BTreeMap::new(),
+            running: ProgressStyle::default_bar()
+                .template("{spinner:.green} {prefix:.bold} [{elapsed_precise}] [{bar:40.cyan/blue}] {pos}/{len} ({eta}) {msg}")?
+                .progress_chars("#>-"),
+            done: ProgressStyle::default_bar()
+                .template("✅ {prefix:.bold} [{elapsed_precise}] [{bar:40.green/blue}] {pos}/{len} {msg}")?
+                .progress_chars("#>-"),
+        })
+    }
+
+    fn update(&mut self, snapshot: &MetricsSnapshot) {
+        for (domain, metrics) in &snapshot.domains {
+            let bar = self.bars.entry(domain.clone()).or_insert_with(|| {
+                let bar = self.multi.add(ProgressBar::new(0));
+                bar.set_style(self.running.clone());
+                bar.set_prefix(domain.clone());
+                // Metrics only arrive with crawl events, so keep the elapsed time moving
+                bar.enable_steady_tick(Duration::from_millis(250));
+                bar
+            });
+            bar.set_length(metrics.urls_queued);
+            bar.set_position(metrics.urls_done());
+            bar.set_message(format!(
+                "Items: {} | Failed: {}",
+                metrics.items_extracted, metrics.requests_failed
+            ));
+        }
+    }
+
+    fn finish(&mut self, snapshot: &MetricsSnapshot) {
+        self.update(snapshot);
+        for bar in self.bars.values() {
+            bar.set_style(self.done.clone());
+            bar.finish();
+        }
+    }
+}
@@ -0,0 +1,269 @@
+//! A memory budget for crawls of large pages. Page bodies in flight and items
+//! waiting for the output count against it. Once it is used up, workers wait
+//! before fetching more pages and new items are spilled to a temporary file,
+//! read back as the output catches up. Items keep their order: while any are
+//! spilled, the following ones are spilled after them.
+//!
+//! Items travel with the time they were extracted, for the pipeline latency.
+
+use crate::metrics::collector::MetricsCollector;
+use serde_json::Value;
+use std::collections::VecDeque;
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, BufWriter, Seek, SeekFrom, Write};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc::error::TryRecvError;
+use tokio::sync::{Notify, mpsc};
+use tokio::time::sleep;
+
+/// How often waiting workers check whether memory was freed.
+const WAIT_INTERVAL: Duration = Duration::from_millis(50);
+
+pub struct MemoryBudget {
+    limit: u64,
+    metrics: Arc<MetricsCollector>,
+    path: PathBuf,
+    /// Opened on the first spill
+    spill: Mutex<Option<SpillFile>>,
+    spilled: Notify,
+    /// Whether the budget was reached yet, to only log it once
+    reached: AtomicBool,
+}
+
+struct SpillFile {
+    writer: BufWriter<File>,
+    reader: BufReader<File>,
+    /// When the items written but not read back yet were extracted
+    pending: VecDeque<Instant>,
+}
+
+impl SpillFile {
+    fn create(path: &PathBuf) -> std::io::Result<Self> {
+        let writer = OpenOptions::new()
+            .create(true)
+            .write(true)
+            .truncate(true)
+            .open(path)?;
+        let reader = File::open(path)?;
+        Ok(Self {
+            writer: BufWriter::new(writer),
+            reader: BufReader::new(reader),
+            pending: VecDeque::new(),
+        })
+    }
+
+    fn push(&mut self, item: &Value, extracted_at: Instant) -> std::io::Result<()> {
+        serde_json::to_writer(&mut self.writer, item)?;
+        self.writer.write_all(b"\n")?;
+        // Flushed right away so the reader sees the whole line
+        self.writer.flush()?;
+        self.pending.push_back(extracted_at);
+        Ok(())
+    }
+
+    fn pop(&mut self) -> std::io::Result<Option<(Value, Instant)>> {
+        // Counted as read even if reading fails, so a bad line is skipped
+        let Some(extracted_at) = self.pending.pop_front() else {
+            return Ok(None);
+        };
+        let mut line = String::new();
+        self.reader.read_line(&mut line)?;
+        if self.pending.is_empty() {
+            // Everything was read back, so the file can start over
+            self.writer.get_ref().set_len(0)?;
+            self.writer.seek(SeekFrom::Start(0))?;
+            self.reader.seek(SeekFrom::Start(0))?;
+        }
+        Ok(Some((serde_json::from_str(&line)?, extracted_at)))
+    }
+}
+
+impl MemoryBudget {
+    /// A budget of `limit_mb` megabytes, spilling to a file named after `run_id`
+    /// in the temporary directory.
+    pub fn new(limit_mb: u64, metrics: Arc<MetricsCollector>, run_id: &str) -> Self {
+        let limit = limit_mb.saturating_mul(1024 * 1024);
+        metrics.set_memory_limit(limit);
+        Self {
+            limit,
+            metrics,
+            path: std::env::temp_dir().join(format!("crawler-spill-{}.ndjson", run_id)),
+            spill: Mutex::new(None),
+            spilled: Notify::new(),
+            reached: AtomicBool::new(false),
+        }
+    }
+
+    fn is_exceeded(&self) -> bool {
+        let exceeded = self.metrics.memory_in_use() > self.limit;
+        if exceeded && !self.reached.swap(true, Ordering::SeqCst) {
+            log::info!(
+                "Memory budget of {} MB reached, pausing fetches and spilling items to {}",
+                self.limit / (1024 * 1024),
+                self.path.display()
+            );
+        }
+        exceeded
+    }
+
+    /// Whether spilled items are waiting to be read back.
+    fn has_spilled(&self) -> bool {
+        self.spill
+            .lock()
+            .is_ok_and(|spill| spill.as_ref().is_some_and(|spill| !spill.pending.is_empty()))
+    }
+
+    /// Waits until the budget has room for another page.
+    pub async fn wait_for_room(&self) {
+        while self.is_exceeded() {
+            sleep(WAIT_INTERVAL).await;
+        }
+    }
+
+    /// Queues `item` for the output, or spills it to disk if the budget is used
+    /// up or earlier items are still spilled.
+    pub async fn send(&self, items_tx: &mpsc::Sender<(Value, Instant)>, item: Value) {
+        let extracted_at = Instant::now();
+        let item = if self.is_exceeded() || self.has_spilled() {
+            match self.spill_item(&item, extracted_at) {
+                Ok(()) => return,
+                Err(e) => {
+                    log::warn!("Failed to spill an item to {}, keeping it in memory: {}", self.path.display(), e);
+                    item
+                }
+            }
+        } else {
+            item
+        };
+        let size = estimated_size(&item);
+        self.metrics.track_queued_item(size);
+        if items_tx.send((item, extracted_at)).await.is_err() {
+            self.metrics.untrack_queued_item(size);
+        }
+    }
+
+    fn spill_item(&self, item: &Value, extracted_at: Instant) -> std::io::Result<()> {
+        let mut spill = self.spill.lock().map_err(|_| std::io::Error::other("spill file lock poisoned"))?;
+        if spill.is_none() {
+            *spill = Some(SpillFile::create(&self.path)?);
+        }
+        if let Some(spill) = spill.as_mut() {
+            spill.push(item, extracted_at)?;
+        }
+        self.metrics.increment_items_spilled();
+        self.spilled.notify_one();
+        Ok(())
+    }
+
+    fn unspill(&self) -> Option<(Value, Instant)> {
+        let mut spill = self.spill.lock().ok()?;
+        loop {
+            match spill.as_mut()?.pop() {
+                Ok(item) => {
+                    if item.is_some() {
+                        self.metrics.decrement_items_in_spill();
+                    }
+                    return item;
+                }
+                Err(e) => {
+                    log::error!("Failed to read back a spilled item from {}: {}", self.path.display(), e);
+                    self.metrics.decrement_items_in_spill();
+                }
+            }
+        }
+    }
+
+    /// The next item for the output in the order they were sent: queued ones
+    /// first, as they came before any still spilled. `None` once every sender
+    /// is gone and the spill file was read back.
+    pub async fn next(&self, items_rx: &mut mpsc::Receiver<(Value, Instant)>) -> Option<(Value, Instant)> {
+        loop {
+            match items_rx.try_recv() {
+                Ok((item, extracted_at)) => {
+                    self.metrics.untrack_queued_item(estimated_size(&item));
+                    return Some((item, extracted_at));
+                }
+                Err(TryRecvError::Empty) => {}
+                Err(TryRecvError::Disconnected) => return self.unspill(),
+            }
+            if let Some(item) = self.unspill() {
+                return Some(item);
+            }
+            tokio::select! {
+                item = items_rx.recv() => match item {
+                    Some((item, extracted_at)) => {
+                        self.metrics.untrack_queued_item(estimated_size(&item));
+                        return Some((item, extracted_at));
+                    }
+                    // Nothing is spilled once the senders are gone
+                    None => return self.unspill(),
+                },
+                _ = self.spilled.notified() => {}
+            }
+        }
+    }
+}
+
+impl Drop for MemoryBudget {
+    fn drop(&mut self) {
+        if let Ok(spill) = self.spill.get_mut()
+            && spill.take().is_some()
+        {
+            let _ = std::fs::remove_file(&self.path);
+        }
+    }
+}
+
+/// Rough number of bytes `value` takes in memory.
+fn estimated_size(value: &Value) -> u64 {
+    let own = std::mem::size_of::<Value>() as u64;
+    own + match value {
+        Value::String(s) => s.len() as u64,
+        Value::Array(values) => values.iter().map(estimated_size).sum(),
+        Value::Object(map) => map
+            .iter()
+            .map(|(key, value)| key.len() as u64 + estimated_size(value))
+            .sum(),
+        Value::Null | Value::Bool(_) | Value::Number(_) => 0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[tokio::test]
+    async fn reads_spilled_items_back_in_order() {
+        let metrics = Arc::new(MetricsCollector::new());
+        let run_id = format!("test-{}", std::process::id());
+        // Zero megabytes: full as soon as one item is queued
+        let budget = MemoryBudget::new(0, metrics.clone(), &run_id);
+        let (items_tx, mut items_rx) = mpsc::channel(16);
+        let mut next = async || budget.next(&mut items_rx).await.map(|(item, _)| item);
+
+        budget.send(&items_tx, json!({"n": 1})).await;
+        budget.send(&items_tx, json!({"n": 2})).await;
+        assert_eq!(metrics.snapshot().items_spilled, 1);
+        assert_eq!(next().await, Some(json!({"n": 1})));
+        // Room again, but item 2 is still spilled, so 3 goes after it
+        budget.send(&items_tx, json!({"n": 3})).await;
+        assert_eq!(next().await, Some(json!({"n": 2})));
+        assert_eq!(next().await, Some(json!({"n": 3})));
+        // Read back in full, so the file starts over
+        assert_eq!(std::fs::metadata(&budget.path).unwrap().len(), 0);
+
+        budget.send(&items_tx, json!({"n": 4})).await;
+        assert_eq!(metrics.snapshot().items_spilled, 2);
+        drop(items_tx);
+        assert_eq!(next().await, Some(json!({"n": 4})));
+        assert_eq!(next().await, None);
+
+        let path = budget.path.clone();
+        drop(budget);
+        assert!(!path.exists());
+    }
+}
@@ -1,7 +1,10 @@
-use crate::metrics::snapshot::MetricsSnapshot;
+use crate::metrics::histogram::LatencyHistogram;
+use crate::metrics::memory;
+use crate::metrics::snapshot::{DomainMetrics, FieldCoverage, MetricsSnapshot, ProxyMetrics};
+use std::collections::BTreeMap;
 use std::sync::{
     atomic::{AtomicU64, Ordering},
-    Arc,
+    Arc, Mutex,
 };
 use std::time::{Duration, Instant};
 
@@ -18,7 +21,47 @@ pub struct MetricsCollector {
     requests_failed: Arc<AtomicU64>,
     active_workers: Arc<AtomicU64>,
     total_response_time_ms: Arc<AtomicU64>,
-    start_time: Arc<Instant>,
+    redirects_followed: Arc<AtomicU64>,
+    requests_redirected: Arc<AtomicU64>,
+    duplicate_pages: Arc<AtomicU64>,
+    duplicate_items: Arc<AtomicU64>,
+    near_duplicate_pages: Arc<AtomicU64>,
+    external_links: Arc<AtomicU64>,
+    irrelevant_pages: Arc<AtomicU64>,
+    head_skipped: Arc<AtomicU64>,
+    requests_blocked: Arc<AtomicU64>,
+    pages_rendered: Arc<AtomicU64>,
+    cache_hits: Arc<AtomicU64>,
+    cache_revalidations: Arc<AtomicU64>,
+    fresh_pages_skipped: Arc<AtomicU64>,
+    items_dropped_language: Arc<AtomicU64>,
+    items_filtered: Arc<AtomicU64>,
+    retries: Arc<AtomicU64>,
+    bytes_received: Arc<AtomicU64>,
+    bytes_decoded: Arc<AtomicU64>,
+    oversized_bodies: Arc<AtomicU64>,
+    extraction_errors: Arc<AtomicU64>,
+    connections_opened: Arc<AtomicU64>,
+    http2_responses: Arc<AtomicU64>,
+    pages_fetched: Arc<AtomicU64>,
+    total_fetch_time_ms: Arc<AtomicU64>,
+    pages_parsed: Arc<AtomicU64>,
+    total_parse_time_ms: Arc<AtomicU64>,
+    body_bytes_in_flight: Arc<AtomicU64>,
+    peak_body_bytes_in_flight: Arc<AtomicU64>,
+    item_bytes_queued: Arc<AtomicU64>,
+    memory_limit_bytes: Arc<AtomicU64>,
+    items_spilled: Arc<AtomicU64>,
+    items_in_spill: Arc<AtomicU64>,
+    items_awaiting_output: Arc<AtomicU64>,
+    response_times: Arc<LatencyHistogram>,
+    pipeline_latency: Arc<LatencyHistogram>,
+    field_coverage: Arc<Mutex<BTreeMap<String, FieldCoverage>>>,
+    domains: Arc<Mutex<BTreeMap<String, DomainMetrics>>>,
+    proxies: Arc<Mutex<BTreeMap<String, ProxyMetrics>>>,
+    unblocked_hosts: Arc<Mutex<BTreeMap<String, String>>>,
+    run_id: Arc<Mutex<String>>,
+    start_time: Arc<Mutex<Instant>>,
 }
 
 impl Default for MetricsCollector {
@@ -35,7 +78,47 @@ impl Default for MetricsCollector {
             requests_failed: Arc::new(AtomicU64::new(0)),
             active_workers: Arc::new(AtomicU64::new(0)),
             total_response_time_ms: Arc::new(AtomicU64::new(0)),
-            start_time: Arc::new(Instant::now()),
+            redirects_followed: Arc::new(AtomicU64::new(0)),
+            requests_redirected: Arc::new(AtomicU64::new(0)),
+            duplicate_pages: Arc::new(AtomicU64::new(0)),
+            duplicate_items: Arc::new(AtomicU64::new(0)),
+            near_duplicate_pages: Arc::new(AtomicU64::new(0)),
+            external_links: Arc::new(AtomicU64::new(0)),
+            irrelevant_pages: Arc::new(AtomicU64::new(0)),
+            head_skipped: Arc::new(AtomicU64::new(0)),
+            requests_blocked: Arc::new(AtomicU64::new(0)),
+            pages_rendered: Arc::new(AtomicU64::new(0)),
+            cache_hits: Arc::new(AtomicU64::new(0)),
+            cache_revalidations: Arc::new(AtomicU64::new(0)),
+            fresh_pages_skipped: Arc::new(AtomicU64::new(0)),
+            items_dropped_language: Arc::new(AtomicU64::new(0)),
+            items_filtered: Arc::new(AtomicU64::new(0)),
+            retries: Arc::new(AtomicU64::new(0)),
+            bytes_received: Arc::new(AtomicU64::new(0)),
+            bytes_decoded: Arc::new(AtomicU64::new(0)),
+            oversized_bodies: Arc::new(AtomicU64::new(0)),
+            extraction_errors: Arc::new(AtomicU64::new(0)),
+            connections_opened: Arc::new(AtomicU64::new(0)),
+            http2_responses: Arc::new(AtomicU64::new(0)),
+            pages_fetched: Arc::new(AtomicU64::new(0)),
+            total_fetch_time_ms: Arc::new(AtomicU64::new(0)),
+            pages_parsed: Arc::new(AtomicU64::new(0)),
+            total_parse_time_ms: Arc::new(AtomicU64::new(0)),
+            body_bytes_in_flight: Arc::new(AtomicU64::new(0)),
+            peak_body_bytes_in_flight: Arc::new(AtomicU64::new(0)),
+            item_bytes_queued: Arc::new(AtomicU64::new(0)),
+            memory_limit_bytes: Arc::new(AtomicU64::new(0)),
+            items_spilled: Arc::new(AtomicU64::new(0)),
+            items_in_spill: Arc::new(AtomicU64::new(0)),
+            items_awaiting_output: Arc::new(AtomicU64::new(0)),
+            response_times: Arc::new(LatencyHistogram::new()),
+            pipeline_latency: Arc::new(LatencyHistogram::new()),
+            field_coverage: Arc::new(Mutex::new(BTreeMap::new())),
+            domains: Arc::new(Mutex::new(BTreeMap::new())),
+            proxies: Arc::new(Mutex::new(BTreeMap::new())),
+            unblocked_hosts: Arc::new(Mutex::new(BTreeMap::new())),
+            run_id: Arc::new(Mutex::new(String::new())),
+            start_time: Arc::new(Mutex::new(Instant::now())),
         }
     }
 }
@@ -45,6 +128,76 @@ impl MetricsCollector {
         Self::default()
     }
 
+    /// Zeroes the counts and restarts the clock, so that the next run of a
+    /// reused engine is measured on its own. What is in flight, the memory
+    /// limit and the hosts that were unblocked are kept.
+    pub fn reset(&self) {
+        let counters = [
+            &self.urls_queued,
+            &self.urls_processed,
+            &self.urls_pending,
+            &self.items_extracted,
+            &self.items_processed,
+            &self.items_failed,
+            &self.requests_total,
+            &self.requests_success,
+            &self.requests_failed,
+            &self.total_response_time_ms,
+            &self.redirects_followed,
+            &self.requests_redirected,
+            &self.duplicate_pages,
+            &self.duplicate_items,
+            &self.near_duplicate_pages,
+            &self.external_links,
+            &self.irrelevant_pages,
+            &self.head_skipped,
+            &self.requests_blocked,
+            &self.pages_rendered,
+            &self.cache_hits,
+            &self.cache_revalidations,
+            &self.fresh_pages_skipped,
+            &self.items_dropped_language,
+            &self.items_filtered,
+            &self.retries,
+            &self.bytes_received,
+            &self.bytes_decoded,
+            &self.oversized_bodies,
+            &self.extraction_errors,
+            &self.connections_opened,
+            &self.http2_responses,
+            &self.pages_fetched,
+            &self.total_fetch_time_ms,
+            &self.pages_parsed,
+            &self.total_parse_time_ms,
+            &self.peak_body_bytes_in_flight,
+            &self.items_spilled,
+        ];
+        for counter in counters {
+            counter.store(0, Ordering::SeqCst);
+        }
+        self.response_times.reset();
+        self.pipeline_latency.reset();
+        if let Ok(mut coverage) = self.field_coverage.lock() {
+            coverage.clear();
+        }
+        if let Ok(mut domains) = self.domains.lock() {
+            domains.clear();
+        }
+        if let Ok(mut proxies) = self.proxies.lock() {
+            proxies.clear();
+        }
+        if let Ok(mut start) = self.start_time.lock() {
+            *start = Instant::now();
+        }
+    }
+
+    /// Labels snapshots with the run they belong to.
+    pub fn set_run_id(&self, run_id: &str) {
+        if let Ok(mut current) = self.run_id.lock() {
+            *current = run_id.to_string();
+        }
+    }
+
     pub fn increment_urls_queued(&self) {
         self.urls_queued.fetch_add(1, Ordering::SeqCst);
     }
@@ -73,11 +226,206 @@ impl MetricsCollector {
         self.active_workers.fetch_sub(1, Ordering::SeqCst);
     }
 
+    pub fn record_redirects(&self, hops: usize) {
+        if hops > 0 {
+            self.requests_redirected.fetch_add(1, Ordering::SeqCst);
+            self.redirects_followed.fetch_add(hops as u64, Ordering::SeqCst);
+        }
+    }
+
+    pub fn increment_duplicate_pages(&self) {
+        self.duplicate_pages.fetch_add(1, Ordering::SeqCst);
+    }
+
+    pub fn increment_duplicate_items(&self) {
+        self.duplicate_items.fetch_add(1, Ordering::SeqCst);
+    }
+
+    pub fn increment_near_duplicate_pages(&self) {
+        self.near_duplicate_pages.fetch_add(1, Ordering::SeqCst);
+    }
+
+    pub fn record_external_links(&self, count: usize) {
+        self.external_links.fetch_add(count as u64, Ordering::SeqCst);
+    }
+
+    pub fn increment_irrelevant_pages(&self) {
+        self.irrelevant_pages.fetch_add(1, Ordering::SeqCst);
+    }
+
+    pub fn increment_head_skipped(&self) {
+        self.head_skipped.fetch_add(1, Ordering::SeqCst);
+    }
+
+    pub fn increment_requests_blocked(&self) {
+        self.requests_blocked.fetch_add(1, Ordering::SeqCst);
+    }
+
+    pub fn increment_pages_rendered(&self) {
+        self.pages_rendered.fetch_add(1, Ordering::SeqCst);
+    }
+
+    pub fn increment_cache_hits(&self) {
+        self.cache_hits.fetch_add(1, Ordering::SeqCst);
+    }
+
+    pub fn increment_cache_revalidations(&self) {
+        self.cache_revalidations.fetch_add(1, Ordering::SeqCst);
+    }
+
+    pub fn increment_fresh_pages_skipped(&self) {
+        self.fresh_pages_skipped.fetch_add(1, Ordering::SeqCst);
+    }
+
+    pub fn increment_items_dropped_language(&self) {
+        self.items_dropped_language.fetch_add(1, Ordering::SeqCst);
+    }
+
+    pub fn record_items_filtered(&self, count: usize) {
+        self.items_filtered.fetch_add(count as u64, Ordering::SeqCst);
+    }
+
+    pub fn increment_retries(&self) {
+        self.retries.fetch_add(1, Ordering::SeqCst);
+    }
+
+    /// Records, for one parsed page, whether each extraction rule matched.
+    pub fn record_field_coverage<'a>(&self, fields: impl IntoIterator<Item = (&'a str, bool)>) {
+        let Ok(mut coverage) = self.field_coverage.lock() else {
+            return;
+        };
+        for (field, matched) in fields {
+            let entry = coverage.entry(field.to_string()).or_default();
+            if matched {
+                entry.pages_matched += 1;
+            } else {
+                entry.pages_missed += 1;
+            }
+        }
+    }
+
+    /// Applies `update` to the per-domain counters for the host of `url`.
+    pub fn record_domain(&self, url: &str, update: impl FnOnce(&mut DomainMetrics)) {
+        let domain = url::Url::parse(url)
+            .ok()
+            .and_then(|u| u.host_str().map(str::to_string))
+            .unwrap_or_else(|| "(unknown)".to_string());
+        if let Ok(mut domains) = self.domains.lock() {
+            update(domains.entry(domain).or_default());
+        }
+    }
+
+    /// Applies `update` to the counters of the pooled proxy labelled `proxy`.
+    pub fn record_proxy(&self, proxy: &str, update: impl FnOnce(&mut ProxyMetrics)) {
+        if let Ok(mut proxies) = self.proxies.lock() {
+            update(proxies.entry(proxy.to_string()).or_default());
+        }
+    }
+
+    /// Records the proxy and user agent that got past a block on `host`.
+    pub fn record_unblocked(&self, host: &str, combination: &str) {
+        if let Ok(mut hosts) = self.unblocked_hosts.lock() {
+            hosts.insert(host.to_string(), combination.to_string());
+        }
+    }
+
+    pub fn record_body(&self, wire_bytes: usize, decoded_bytes: usize) {
+        self.bytes_received
+            .fetch_add(wire_bytes as u64, Ordering::SeqCst);
+        self.bytes_decoded
+            .fetch_add(decoded_bytes as u64, Ordering::SeqCst);
+    }
+
+    pub fn increment_oversized_bodies(&self) {
+        self.oversized_bodies.fetch_add(1, Ordering::SeqCst);
+    }
+
+    pub fn increment_extraction_errors(&self) {
+        self.extraction_errors.fetch_add(1, Ordering::SeqCst);
+    }
+
+    pub fn increment_connections_opened(&self) {
+        self.connections_opened.fetch_add(1, Ordering::SeqCst);
+    }
+
+    pub fn increment_http2_responses(&self) {
+        self.http2_responses.fetch_add(1, Ordering::SeqCst);
+    }
+
+    /// Time spent on the network, from sending the request to reading the full body.
+    pub fn record_fetch_time(&self, duration: Duration) {
+        self.pages_fetched.fetch_add(1, Ordering::SeqCst);
+        self.total_fetch_time_ms
+            .fetch_add(duration.as_millis() as u64, Ordering::SeqCst);
+    }
+
+    /// Time spent parsing and querying a page, excluding the network.
+    pub fn record_parse_time(&self, duration: Duration) {
+        self.pages_parsed.fetch_add(1, Ordering::SeqCst);
+        self.total_parse_time_ms
+            .fetch_add(duration.as_millis() as u64, Ordering::SeqCst);
+    }
+
+    /// Counts a page body as held in memory until the returned guard is dropped.
+    pub fn track_body(&self, bytes: usize) -> InFlightBody {
+        let bytes = bytes as u64;
+        let current = self.body_bytes_in_flight.fetch_add(bytes, Ordering::SeqCst) + bytes;
+        self.peak_body_bytes_in_flight
+            .fetch_max(current, Ordering::SeqCst);
+        InFlightBody {
+            counter: self.body_bytes_in_flight.clone(),
+            bytes,
+        }
+    }
+
+    /// Counts an item waiting for the output, of roughly `bytes` bytes.
+    pub fn track_queued_item(&self, bytes: u64) {
+        self.item_bytes_queued.fetch_add(bytes, Ordering::SeqCst);
+    }
+
+    pub fn untrack_queued_item(&self, bytes: u64) {
+        self.item_bytes_queued.fetch_sub(bytes, Ordering::SeqCst);
+    }
+
+    /// Bytes counted against the memory budget: bodies in flight and queued items.
+    pub fn memory_in_use(&self) -> u64 {
+        self.body_bytes_in_flight.load(Ordering::SeqCst) + self.item_bytes_queued.load(Ordering::SeqCst)
+    }
+
+    pub fn set_memory_limit(&self, bytes: u64) {
+        self.memory_limit_bytes.store(bytes, Ordering::SeqCst);
+    }
+
+    pub fn increment_items_spilled(&self) {
+        self.items_spilled.fetch_add(1, Ordering::SeqCst);
+        self.items_in_spill.fetch_add(1, Ordering::SeqCst);
+    }
+
+    /// A spilled item was read back from disk.
+    pub fn decrement_items_in_spill(&self) {
+        self.items_in_spill.fetch_sub(1, Ordering::SeqCst);
+    }
+
+    /// An extracted item was handed to the output queue.
+    pub fn increment_items_awaiting_output(&self) {
+        self.items_awaiting_output.fetch_add(1, Ordering::SeqCst);
+    }
+
+    /// The output is done with an item extracted at `extracted_at`. Only
+    /// written items count towards the pipeline latency.
+    pub fn record_item_output(&self, extracted_at: Instant, written: bool) {
+        self.items_awaiting_output.fetch_sub(1, Ordering::SeqCst);
+        if written {
+            self.pipeline_latency.record(extracted_at.elapsed());
+        }
+    }
+
     pub fn record_success(&self, duration: Duration) {
         self.requests_total.fetch_add(1, Ordering::SeqCst);
         self.requests_success.fetch_add(1, Ordering::SeqCst);
         self.total_response_time_ms
             .fetch_add(duration.as_millis() as u64, Ordering::SeqCst);
+        self.response_times.record(duration);
     }
 
     pub fn record_failure(&self, duration: Duration) {
@@ -85,6 +433,7 @@ impl MetricsCollector {
         self.requests_failed.fetch_add(1, Ordering::SeqCst);
         self.total_response_time_ms
             .fetch_add(duration.as_millis() as u64, Ordering::SeqCst);
+        self.response_times.record(duration);
     }
 
     pub fn snapshot(&self) -> MetricsSnapshot {
@@ -105,9 +454,30 @@ impl MetricsCollector {
             0
         };
 
-        let elapsed = self.start_time.elapsed().as_secs_f64();
+        let connections_opened = self.connections_opened.load(Ordering::SeqCst);
+        let connection_reuse_rate = if total_requests > 0 {
+            (1.0 - connections_opened as f64 / total_requests as f64).max(0.0) * 100.0
+        } else {
+            0.0
+        };
+
+        let average = |total: &AtomicU64, count: &AtomicU64| {
+            let count = count.load(Ordering::SeqCst);
+            if count > 0 {
+                total.load(Ordering::SeqCst) / count
+            } else {
+                0
+            }
+        };
+
+        let elapsed = self
+            .start_time
+            .lock()
+            .map(|start| start.elapsed().as_secs_f64())
+            .unwrap_or_default();
 
         MetricsSnapshot {
+            run_id: self.run_id.lock().map(|id| id.clone()).unwrap_or_default(),
             urls_queued: self.urls_queued.load(Ordering::SeqCst),
             urls_processed: self.urls_processed.load(Ordering::SeqCst),
             urls_pending: self.urls_pending.load(Ordering::SeqCst),
@@ -118,14 +488,84 @@ impl MetricsCollector {
             requests_success: success,
             requests_failed: failed,
             active_workers: self.active_workers.load(Ordering::SeqCst),
+            redirects_followed: self.redirects_followed.load(Ordering::SeqCst),
+            requests_redirected: self.requests_redirected.load(Ordering::SeqCst),
+            duplicate_pages: self.duplicate_pages.load(Ordering::SeqCst),
+            duplicate_items: self.duplicate_items.load(Ordering::SeqCst),
+            near_duplicate_pages: self.near_duplicate_pages.load(Ordering::SeqCst),
+            external_links: self.external_links.load(Ordering::SeqCst),
+            irrelevant_pages: self.irrelevant_pages.load(Ordering::SeqCst),
+            head_skipped: self.head_skipped.load(Ordering::SeqCst),
+            requests_blocked: self.requests_blocked.load(Ordering::SeqCst),
+            pages_rendered: self.pages_rendered.load(Ordering::SeqCst),
+            cache_hits: self.cache_hits.load(Ordering::SeqCst),
+            cache_revalidations: self.cache_revalidations.load(Ordering::SeqCst),
+            fresh_pages_skipped: self.fresh_pages_skipped.load(Ordering::SeqCst),
+            items_dropped_language: self.items_dropped_language.load(Ordering::SeqCst),
+            items_filtered: self.items_filtered.load(Ordering::SeqCst),
+            retries: self.retries.load(Ordering::SeqCst),
+            bytes_received: self.bytes_received.load(Ordering::SeqCst),
+            bytes_decoded: self.bytes_decoded.load(Ordering::SeqCst),
+            oversized_bodies: self.oversized_bodies.load(Ordering::SeqCst),
+            extraction_errors: self.extraction_errors.load(Ordering::SeqCst),
+            connections_opened,
+            http2_responses: self.http2_responses.load(Ordering::SeqCst),
+            connection_reuse_rate,
             success_rate,
             avg_response_time_ms,
+            p50_response_time_ms: self.response_times.percentile(50.0),
+            p99_response_time_ms: self.response_times.percentile(99.0),
+            avg_fetch_time_ms: average(&self.total_fetch_time_ms, &self.pages_fetched),
+            avg_parse_time_ms: average(&self.total_parse_time_ms, &self.pages_parsed),
+            body_bytes_in_flight: self.body_bytes_in_flight.load(Ordering::SeqCst),
+            peak_body_bytes_in_flight: self.peak_body_bytes_in_flight.load(Ordering::SeqCst),
+            item_bytes_queued: self.item_bytes_queued.load(Ordering::SeqCst),
+            memory_limit_bytes: self.memory_limit_bytes.load(Ordering::SeqCst),
+            items_spilled: self.items_spilled.load(Ordering::SeqCst),
+            items_in_spill: self.items_in_spill.load(Ordering::SeqCst),
+            items_awaiting_output: self.items_awaiting_output.load(Ordering::SeqCst),
+            p50_pipeline_latency_ms: self.pipeline_latency.percentile(50.0),
+            p99_pipeline_latency_ms: self.pipeline_latency.percentile(99.0),
+            rss_bytes: memory::rss_bytes().unwrap_or(0),
+            peak_rss_bytes: memory::peak_rss_bytes().unwrap_or(0),
             requests_per_second: if elapsed > 0.0 {
                 total_requests as f64 / elapsed
             } else {
                 0.0
             },
             elapsed_seconds: elapsed,
+            field_coverage: self
+                .field_coverage
+                .lock()
+                .map(|coverage| coverage.clone())
+                .unwrap_or_default(),
+            domains: self
+                .domains
+                .lock()
+                .map(|domains| domains.clone())
+                .unwrap_or_default(),
+            proxies: self
+                .proxies
+                .lock()
+                .map(|proxies| proxies.clone())
+                .unwrap_or_default(),
+            unblocked_hosts: self
+                .unblocked_hosts
+                .lock()
+                .map(|hosts| hosts.clone())
+                .unwrap_or_default(),
         }
     }
 }
+
+/// Guard returned by [`MetricsCollector::track_body`].
+pub struct InFlightBody {
+    counter: Arc<AtomicU64>,
+    bytes: u64,
+}
+
+impl Drop for InFlightBody {
+    fn drop(&mut self) {
+        self.counter.fetch_sub(self.bytes, Ordering::SeqCst);
+    }
+}
@@ -0,0 +1,71 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+/// Upper bounds (inclusive, in milliseconds) of the histogram buckets.
+const BUCKET_BOUNDS_MS: [u64; 24] = [
+    1, 2, 5, 10, 15, 20, 30, 50, 75, 100, 150, 200, 300, 500, 750, 1_000, 1_500, 2_000, 3_000,
+    5_000, 10_000, 30_000, 60_000, u64::MAX,
+];
+
+/// A lock-free latency histogram with fixed, roughly logarithmic buckets.
+///
+/// Percentiles are reported as the upper bound of the bucket they fall in, which
+/// is precise enough for dashboards and regressions without storing samples.
+#[derive(Debug)]
+pub struct LatencyHistogram {
+    buckets: Vec<AtomicU64>,
+}
+
+impl Default for LatencyHistogram {
+    fn default() -> Self {
+        Self {
+            buckets: BUCKET_BOUNDS_MS.iter().map(|_| AtomicU64::new(0)).collect(),
+        }
+    }
+}
+
+impl LatencyHistogram {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Forgets every sample.
+    pub fn reset(&self) {
+        for bucket in &self.buckets {
+            bucket.store(0, Ordering::SeqCst);
+        }
+    }
+
+    pub fn record(&self, duration: Duration) {
+        let ms = duration.as_millis() as u64;
+        let index = BUCKET_BOUNDS_MS
+            .iter()
+            .position(|bound| ms <= *bound)
+            .unwrap_or(BUCKET_BOUNDS_MS.len() - 1);
+        self.buckets[index].fetch_add(1, Ordering::SeqCst);
+    }
+
+    /// Returns the latency below which `percentile` percent of samples fall.
+    pub fn percentile(&self, percentile: f64) -> u64 {
+        let counts: Vec<u64> = self.buckets.iter().map(|b| b.load(Ordering::SeqCst)).collect();
+        let total: u64 = counts.iter().sum();
+        if total == 0 {
+            return 0;
+        }
+
+        let rank = ((percentile / 100.0) * total as f64).ceil().max(1.0) as u64;
+        let mut seen = 0;
+        for (count, bound) in counts.iter().zip(BUCKET_BOUNDS_MS) {
+            seen += count;
+            if seen >= rank {
+                // The overflow bucket has no meaningful upper bound
+                return if bound == u64::MAX {
+                    BUCKET_BOUNDS_MS[BUCKET_BOUNDS_MS.len() - 2]
+                } else {
+                    bound
+                };
+            }
+        }
+        BUCKET_BOUNDS_MS[BUCKET_BOUNDS_MS.len() - 2]
+    }
+}
@@ -1,2 +1,4 @@
 pub mod collector;
+pub mod histogram;
+pub mod memory;
 pub mod snapshot;
@@ -1,7 +1,11 @@
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 
 #[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct MetricsSnapshot {
+    /// The run these metrics belong to
+    #[serde(default)]
+    pub run_id: String,
     pub urls_queued: u64,
     pub urls_processed: u64,
     pub urls_pending: u64,
@@ -12,8 +16,157 @@ pub struct MetricsSnapshot {
     pub requests_success: u64,
     pub requests_failed: u64,
     pub active_workers: u64,
+    pub redirects_followed: u64,
+    pub requests_redirected: u64,
+    pub duplicate_pages: u64,
+    /// Items dropped because an earlier item had the same `_id`
+    #[serde(default)]
+    pub duplicate_items: u64,
+    /// Pages found to be near duplicates of an earlier page by `dedup.near_duplicates`
+    #[serde(default)]
+    pub near_duplicate_pages: u64,
+    /// Distinct external links (or domains) recorded by `external_links`
+    #[serde(default)]
+    pub external_links: u64,
+    /// Pages scoring below `relevance.min_score`, whose links weren't followed
+    #[serde(default)]
+    pub irrelevant_pages: u64,
+    /// Pages not fetched because their HEAD response ruled them out
+    #[serde(default)]
+    pub head_skipped: u64,
+    /// Responses recognized as block or captcha pages, also counted as failed requests
+    #[serde(default)]
+    pub requests_blocked: u64,
+    /// Pages fetched again through the rendering service for `render_fallback` rules
+    #[serde(default)]
+    pub pages_rendered: u64,
+    /// Responses served from the page cache without contacting the server
+    #[serde(default)]
+    pub cache_hits: u64,
+    /// Stale cached responses the server confirmed with `304 Not Modified`
+    #[serde(default)]
+    pub cache_revalidations: u64,
+    /// Pages not fetched because an earlier run's copy was still fresh
+    #[serde(default)]
+    pub fresh_pages_skipped: u64,
+    #[serde(default)]
+    pub items_dropped_language: u64,
+    /// Items dropped by `item_filter`
+    #[serde(default)]
+    pub items_filtered: u64,
+    pub retries: u64,
+    pub bytes_received: u64,
+    pub bytes_decoded: u64,
+    pub oversized_bodies: u64,
+    /// Pages whose extraction panicked or timed out
+    #[serde(default)]
+    pub extraction_errors: u64,
+    pub connections_opened: u64,
+    pub http2_responses: u64,
     pub success_rate: f64,
+    pub connection_reuse_rate: f64,
     pub avg_response_time_ms: u64,
+    pub p50_response_time_ms: u64,
+    pub p99_response_time_ms: u64,
+    pub avg_fetch_time_ms: u64,
+    pub avg_parse_time_ms: u64,
+    pub body_bytes_in_flight: u64,
+    pub peak_body_bytes_in_flight: u64,
+    /// Approximate size of the items waiting for the output
+    #[serde(default)]
+    pub item_bytes_queued: u64,
+    /// The memory budget, 0 without one
+    #[serde(default)]
+    pub memory_limit_bytes: u64,
+    /// Items spilled to disk because the memory budget was used up
+    #[serde(default)]
+    pub items_spilled: u64,
+    /// Spilled items not read back yet
+    #[serde(default)]
+    pub items_in_spill: u64,
+    /// Items extracted but not written yet, whether queued, spilled or being written
+    #[serde(default)]
+    pub items_awaiting_output: u64,
+    /// Time from extracting an item to writing it, median
+    #[serde(default)]
+    pub p50_pipeline_latency_ms: u64,
+    #[serde(default)]
+    pub p99_pipeline_latency_ms: u64,
+    pub rss_bytes: u64,
+    pub peak_rss_bytes: u64,
     pub requests_per_second: f64,
     pub elapsed_seconds: f64,
+    /// Pages each extraction rule matched on, keyed by field name
+    #[serde(default)]
+    pub field_coverage: BTreeMap<String, FieldCoverage>,
+    /// Progress broken down by host
+    #[serde(default)]
+    pub domains: BTreeMap<String, DomainMetrics>,
+    /// Outcomes per proxy of the proxy pool, keyed by proxy URL without credentials
+    #[serde(default)]
+    pub proxies: BTreeMap<String, ProxyMetrics>,
+    /// Proxy and user agent that last got past a block, per host
+    #[serde(default)]
+    pub unblocked_hosts: BTreeMap<String, String>,
+}
+
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+pub struct DomainMetrics {
+    pub urls_queued: u64,
+    pub urls_processed: u64,
+    pub requests_failed: u64,
+    pub items_extracted: u64,
+}
+
+impl DomainMetrics {
+    /// URLs that have been attempted, whether they succeeded or not.
+    pub fn urls_done(&self) -> u64 {
+        self.urls_processed + self.requests_failed
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+pub struct ProxyMetrics {
+    pub requests: u64,
+    pub failures: u64,
+    pub total_latency_ms: u64,
+    /// Times the proxy was taken out of rotation
+    pub evictions: u64,
+    /// Responses through the proxy that looked blocked
+    #[serde(default)]
+    pub blocked: u64,
+}
+
+impl ProxyMetrics {
+    /// Share of requests that succeeded, from 0.0 to 1.0.
+    pub fn success_rate(&self) -> f64 {
+        match self.requests {
+            0 => 0.0,
+            requests => (requests - self.failures) as f64 / requests as f64,
+        }
+    }
+
+    pub fn avg_latency_ms(&self) -> u64 {
+        self.total_latency_ms.checked_div(self.requests).unwrap_or(0)
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+pub struct FieldCoverage {
+    pub pages_matched: u64,
+    pub pages_missed: u64,
+}
+
+impl FieldCoverage {
+    pub fn pages(&self) -> u64 {
+        self.pages_matched + self.pages_missed
+    }
+
+    /// Fraction of pages the field was found on, from 0.0 to 1.0.
+    pub fn ratio(&self) -> f64 {
+        match self.pages() {
+            0 => 0.0,
+            pages => self.pages_matched as f64 / pages as f64,
+        }
+    }
 }
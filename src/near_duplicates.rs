@@ -0,0 +1,133 @@
+//! Near-duplicate pages within a crawl, such as mirrors and print versions.
+//! Each page's text is reduced to a MinHash signature of its word shingles,
+//! and a page whose estimated similarity to an earlier page reaches the
+//! threshold counts as a copy of it. Signatures are bucketed by bands (LSH),
+//! so a page is only compared with the earlier pages likely to be similar.
+
+use crate::cleanup::visible_text;
+use crate::config::schema::{NearDuplicateAction, NearDuplicateConfig};
+use std::collections::HashMap;
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::sync::Mutex;
+
+/// Words per shingle; pages with fewer words aren't compared.
+const SHINGLE_WORDS: usize = 5;
+
+/// Hashes per signature, split into bands of `SIGNATURE_LEN / BANDS` rows.
+const SIGNATURE_LEN: usize = 64;
+const BANDS: usize = 16;
+const ROWS: usize = SIGNATURE_LEN / BANDS;
+
+/// One seed per MinHash function.
+const SEEDS: [u64; SIGNATURE_LEN] = {
+    let mut seeds = [0; SIGNATURE_LEN];
+    let mut i = 0;
+    while i < SIGNATURE_LEN {
+        seeds[i] = mix(i as u64 + 1);
+        i += 1;
+    }
+    seeds
+};
+
+pub type Signature = [u32; SIGNATURE_LEN];
+
+#[derive(Debug)]
+pub struct NearDuplicates {
+    threshold: f64,
+    pub action: NearDuplicateAction,
+    index: Mutex<Index>,
+}
+
+#[derive(Debug, Default)]
+struct Index {
+    pages: Vec<(String, Signature)>,
+    /// Pages by the hash of each of their bands
+    buckets: HashMap<(usize, u64), Vec<usize>>,
+}
+
+impl NearDuplicates {
+    pub fn new(config: &NearDuplicateConfig) -> Self {
+        Self {
+            threshold: config.threshold,
+            action: config.action,
+            index: Mutex::new(Index::default()),
+        }
+    }
+
+    /// Forgets every page seen so far.
+    pub fn clear(&self) {
+        if let Ok(mut index) = self.index.lock() {
+            *index = Index::default();
+        }
+    }
+
+    /// The signature of the visible text of `html`, or `None` if the page has
+    /// too few words to compare.
+    pub fn signature(html: &str) -> Option<Signature> {
+        let text = visible_text(html);
+        let words: Vec<&str> = text.split(' ').filter(|word| !word.is_empty()).collect();
+        if words.len() < SHINGLE_WORDS {
+            return None;
+        }
+        let mut signature = [u32::MAX; SIGNATURE_LEN];
+        for shingle in words.windows(SHINGLE_WORDS) {
+            let mut hasher = DefaultHasher::new();
+            shingle.hash(&mut hasher);
+            let hash = hasher.finish();
+            for (min, seed) in signature.iter_mut().zip(SEEDS) {
+                *min = (*min).min((mix(hash ^ seed) >> 32) as u32);
+            }
+        }
+        Some(signature)
+    }
+
+    /// The earlier page that the page at `url` is a near duplicate of, with
+    /// their estimated similarity. Pages that aren't are remembered for later
+    /// ones to be compared with.
+    pub fn check(&self, url: &str, signature: &Signature) -> Option<(String, f64)> {
+        let mut index = self.index.lock().ok()?;
+        let bands: Vec<(usize, u64)> = signature
+            .chunks(ROWS)
+            .enumerate()
+            .map(|(band, rows)| {
+                let mut hasher = DefaultHasher::new();
+                rows.hash(&mut hasher);
+                (band, hasher.finish())
+            })
+            .collect();
+
+        let mut best: Option<(usize, f64)> = None;
+        for key in &bands {
+            for &candidate in index.buckets.get(key).into_iter().flatten() {
+                let similarity = similarity(signature, &index.pages[candidate].1);
+                if similarity >= self.threshold && best.is_none_or(|(_, s)| similarity > s) {
+                    best = Some((candidate, similarity));
+                }
+            }
+        }
+        if let Some((page, similarity)) = best {
+            return Some((index.pages[page].0.clone(), similarity));
+        }
+
+        let page = index.pages.len();
+        index.pages.push((url.to_string(), *signature));
+        for key in bands {
+            index.buckets.entry(key).or_default().push(page);
+        }
+        None
+    }
+}
+
+/// The share of equal hashes, an estimate of the Jaccard similarity of the shingle sets.
+fn similarity(a: &Signature, b: &Signature) -> f64 {
+    let equal = a.iter().zip(b).filter(|(a, b)| a == b).count();
+    equal as f64 / SIGNATURE_LEN as f64
+}
+
+/// The splitmix64 finalizer, a cheap way to derive independent hashes.
+const fn mix(mut x: u64) -> u64 {
+    x = x.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    x = (x ^ (x >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    x ^ (x >> 31)
+}
@@ -0,0 +1,173 @@
+//! Run summaries posted to a webhook when a crawl finishes or is interrupted, so
+//! scheduled crawls can alert their owners.
+
+use crate::compare::FieldChange;
+use crate::config::schema::{NotifyConfig, WebhookFormat};
+use crate::error::{Error, Result};
+use crate::http::client::client_builder;
+use crate::report::CrawlReport;
+use reqwest::Client;
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+use serde::Serialize;
+use serde_json::json;
+use url::Url;
+
+pub struct WebhookNotifier {
+    client: Client,
+    url: String,
+    /// The webhook's host, logged instead of the URL since chat webhook URLs embed a secret
+    host: String,
+    format: WebhookFormat,
+}
+
+/// The summary sent in the `json` format.
+#[derive(Debug, Serialize)]
+pub struct RunSummary {
+    pub run_id: String,
+    pub spider: String,
+    /// `finished`, or `interrupted` if the run was stopped early
+    pub status: &'static str,
+    pub items_extracted: u64,
+    pub items_written: u64,
+    pub items_failed: u64,
+    pub requests: u64,
+    pub requests_failed: u64,
+    /// URLs that still failed after all retries
+    pub failed_urls: usize,
+    pub duration_secs: f64,
+}
+
+impl RunSummary {
+    pub fn new(report: &CrawlReport) -> Self {
+        let metrics = &report.metrics;
+        Self {
+            run_id: report.run_id.clone(),
+            spider: report.spider.clone(),
+            status: if report.interrupted { "interrupted" } else { "finished" },
+            items_extracted: metrics.items_extracted,
+            items_written: metrics.items_processed.saturating_sub(metrics.items_failed),
+            items_failed: metrics.items_failed,
+            requests: metrics.requests_total,
+            requests_failed: metrics.requests_failed,
+            failed_urls: report.failures.len(),
+            duration_secs: metrics.elapsed_seconds,
+        }
+    }
+
+    /// One line of text for chat messages.
+    fn text(&self) -> String {
+        let icon = match (self.status, self.failed_urls + self.items_failed as usize) {
+            ("interrupted", _) => "⚠️",
+            (_, 0) => "✅",
+            _ => "❌",
+        };
+        format!(
+            "{} Crawl `{}` {}: {} items written, {} failed URLs, {} failed items in {:.1}s (run {})",
+            icon,
+            self.spider,
+            self.status,
+            self.items_written,
+            self.failed_urls,
+            self.items_failed,
+            self.duration_secs,
+            self.run_id
+        )
+    }
+}
+
+impl WebhookNotifier {
+    pub fn new(config: &NotifyConfig) -> Result<Self> {
+        let mut headers = HeaderMap::new();
+        for (name, value) in &config.headers {
+            let invalid = |e: &dyn std::fmt::Display| {
+                Error::Config(format!("Invalid notify header '{}': {}", name, e))
+            };
+            let name = HeaderName::from_bytes(name.as_bytes()).map_err(|e| invalid(&e))?;
+            let value = HeaderValue::from_str(value).map_err(|e| invalid(&e))?;
+            headers.insert(name, value);
+        }
+        let host = Url::parse(&config.webhook)
+            .map_err(|e| Error::Config(format!("Invalid notify webhook URL: {}", e)))?
+            .host_str()
+            .unwrap_or_default()
+            .to_string();
+        let client = client_builder()
+            .default_headers(headers)
+            .build()
+            .map_err(|e| Error::Config(format!("Failed to build the webhook client: {}", e)))?;
+
+        Ok(Self {
+            client,
+            url: config.webhook.clone(),
+            host,
+            format: config.format,
+        })
+    }
+
+    /// Posts the summary of `report`. Failures are logged, not returned, since
+    /// the crawl itself is over by then.
+    pub async fn notify(&self, report: &CrawlReport) {
+        let summary = RunSummary::new(report);
+        let body = match self.format {
+            WebhookFormat::Json => json!(summary),
+            WebhookFormat::Slack => json!({ "text": summary.text() }),
+        };
+        self.post(&body, "run summary").await;
+    }
+
+    /// Posts an alert about the fields whose coverage fell since `previous_run`.
+    pub async fn notify_coverage_drift(&self, report: &CrawlReport, previous_run: &str, drift: &[FieldChange]) {
+        let fields: Vec<_> = drift
+            .iter()
+            .map(|change| {
+                json!({
+                    "field": change.field,
+                    "before": change.before,
+                    "after": change.after,
+                })
+            })
+            .collect();
+        let body = match self.format {
+            WebhookFormat::Json => json!({
+                "alert": "coverage_drift",
+                "run_id": report.run_id,
+                "previous_run_id": previous_run,
+                "spider": report.spider,
+                "fields": fields,
+            }),
+            WebhookFormat::Slack => {
+                let changes = drift
+                    .iter()
+                    .map(|change| {
+                        format!(
+                            "`{}` {:.1}% → {:.1}%",
+                            change.field,
+                            change.before.unwrap_or_default(),
+                            change.after.unwrap_or_default()
+                        )
+                    })
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                json!({
+                    "text": format!(
+                        "⚠️ Crawl `{}`: field coverage dropped since the previous run, selectors may be broken: {} (run {})",
+                        report.spider, changes, report.run_id
+                    )
+                })
+            }
+        };
+        self.post(&body, "coverage alert").await;
+    }
+
+    async fn post(&self, body: &serde_json::Value, what: &str) {
+        match self.client.post(&self.url).json(body).send().await {
+            Ok(response) if response.status().is_success() => {
+                log::info!("Sent the {} to {}", what, self.host);
+            }
+            Ok(response) => {
+                log::warn!("Webhook at {} answered HTTP {}", self.host, response.status().as_u16());
+            }
+            Err(e) => log::warn!("Failed to send the {} to {}: {}", what, self.host, e.without_url()),
+        }
+    }
+}
@@ -1,17 +1,142 @@
 use super::OutputHandler;
+use crate::config::schema::{ConsoleConfig, ConsoleFormat};
 use crate::error::Result;
 use async_trait::async_trait;
+use console::Style;
 use indicatif::MultiProgress;
 use serde_json::Value;
 use std::sync::Arc;
 
+/// Default column width of the table format when `max_width` isn't set.
+const TABLE_COLUMN_WIDTH: usize = 24;
+
 pub struct ConsoleOutput {
     multi: Option<Arc<MultiProgress>>,
+    options: ConsoleConfig,
+    key_style: Style,
+    columns: Vec<String>,
+    header_printed: bool,
 }
 
 impl ConsoleOutput {
     pub fn new(multi: Option<Arc<MultiProgress>>) -> Self {
-        Self { multi }
+        Self::with_options(multi, ConsoleConfig::default())
+    }
+
+    pub fn with_options(multi: Option<Arc<MultiProgress>>, options: ConsoleConfig) -> Self {
+        let mut key_style = Style::new().cyan().bold();
+        if !options.color {
+            key_style = key_style.force_styling(false);
+        }
+        Self {
+            multi,
+            options,
+            key_style,
+            columns: Vec::new(),
+            header_printed: false,
+        }
+    }
+
+    /// Seeds the table header, typically with the extraction rule names. Fields
+    /// not listed here are appended from the first item.
+    pub fn with_columns(mut self, columns: Vec<String>) -> Self {
+        self.columns = columns;
+        self
+    }
+
+    fn render(&mut self, item: &Value) -> Vec<String> {
+        match self.options.format {
+            ConsoleFormat::PrettyJson => serde_json::to_string_pretty(item)
+                .unwrap_or_default()
+                .lines()
+                .map(|line| self.highlight_json_key(line))
+                .collect(),
+            ConsoleFormat::CompactJson => vec![item.to_string()],
+            ConsoleFormat::Kv => {
+                let mut lines: Vec<String> = match item {
+                    Value::Object(map) => map
+                        .iter()
+                        .map(|(key, value)| {
+                            format!(
+                                "{}: {}",
+                                self.key_style.apply_to(key),
+                                self.cell(value, self.options.max_width)
+                            )
+                        })
+                        .collect(),
+                    other => vec![self.cell(other, self.options.max_width)],
+                };
+                lines.push(String::new());
+                lines
+            }
+            ConsoleFormat::Table => {
+                let width = self.options.max_width.unwrap_or(TABLE_COLUMN_WIDTH);
+                let mut lines = Vec::new();
+                if !self.header_printed {
+                    if let Value::Object(map) = item {
+                        for key in map.keys() {
+                            if !self.columns.contains(key) {
+                                self.columns.push(key.clone());
+                            }
+                        }
+                    }
+                    let header = self
+                        .columns
+                        .iter()
+                        .map(|column| {
+                            let name = format!("{:<width$}", truncate(column, width));
+                            self.key_style.apply_to(name).to_string()
+                        })
+                        .collect::<Vec<_>>()
+                        .join("  ");
+                    lines.push(header);
+                    lines.push(vec!["-".repeat(width); self.columns.len()].join("  "));
+                    self.header_printed = true;
+                }
+                let row = self
+                    .columns
+                    .iter()
+                    .map(|column| {
+                        let cell = item
+                            .get(column)
+                            .map(|value| self.cell(value, Some(width)))
+                            .unwrap_or_default();
+                        format!("{:<width$}", cell)
+                    })
+                    .collect::<Vec<_>>()
+                    .join("  ");
+                lines.push(row.trim_end().to_string());
+                lines
+            }
+        }
+    }
+
+    /// Colors the `"key":` prefix of a pretty-printed JSON line.
+    fn highlight_json_key(&self, line: &str) -> String {
+        let indent = line.len() - line.trim_start().len();
+        let rest = &line[indent..];
+        match rest.find("\": ") {
+            Some(end) if rest.starts_with('"') => format!(
+                "{}{}{}",
+                &line[..indent],
+                self.key_style.apply_to(&rest[..=end]),
+                &rest[end + 1..]
+            ),
+            _ => line.to_string(),
+        }
+    }
+
+    fn cell(&self, value: &Value, max_width: Option<usize>) -> String {
+        let text = match value {
+            Value::String(s) => s.clone(),
+            Value::Null => String::new(),
+            other => other.to_string(),
+        };
+        let text = text.replace(['\n', '\r', '\t'], " ");
+        match max_width {
+            Some(width) => truncate(&text, width),
+            None => text,
+        }
     }
 }
 
@@ -21,17 +146,30 @@ impl Default for ConsoleOutput {
     }
 }
 
+/// Shortens `text` to at most `width` characters, marking the cut with `…`.
+fn truncate(text: &str, width: usize) -> String {
+    if text.chars().count() <= width {
+        return text.to_string();
+    }
+    let mut out: String = text.chars().take(width.saturating_sub(1)).collect();
+    out.push('…');
+    out
+}
+
 #[async_trait]
 impl OutputHandler for ConsoleOutput {
     async fn write(&mut self, item: Value) -> Result<()> {
-        let output = serde_json::to_string_pretty(&item).unwrap();
-        
+        let lines = self.render(&item);
+
         if let Some(multi) = &self.multi {
-            for line in output.lines() {
-                multi.println(line).map_err(|e| crate::error::Error::Internal(e.to_string()))?;
+            for line in lines {
+                multi.println(line).map_err(|e| crate::error::Error::Output {
+                    sink: "console".to_string(),
+                    message: e.to_string(),
+                })?;
             }
         } else {
-            for line in output.lines() {
+            for line in lines {
                 println!("{}", line);
             }
         }
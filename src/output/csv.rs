@@ -1,51 +1,113 @@
 use super::OutputHandler;
-use crate::error::Result;
+use super::flatten;
+use crate::config::schema::NestedConfig;
+use crate::error::{Error, Result};
 use async_trait::async_trait;
 use serde_json::Value;
+use std::fs::OpenOptions;
 use std::path::PathBuf;
 
 pub struct CsvOutput {
     writer: csv::Writer<std::fs::File>,
-    headers_written: bool,
+    /// Columns of the file, taken from its existing header or the first item
+    headers: Option<Vec<String>>,
+    /// Whether fields missing from the header were reported yet
+    warned: bool,
+    nested: NestedConfig,
+    path: PathBuf,
 }
 
 impl CsvOutput {
-    pub fn new(path: PathBuf) -> Result<Self> {
-        let writer = csv::Writer::from_path(path)
-            .map_err(|e| crate::error::Error::Internal(e.to_string()))?;
+    /// Writes rows to `path`. With `append`, rows are added to an existing
+    /// file under its header, and fields the header lacks are left out.
+    pub fn new(path: PathBuf, append: bool) -> Result<Self> {
+        let headers = match append {
+            true => existing_headers(&path)?,
+            false => None,
+        };
+        let file = OpenOptions::new()
+            .create(true)
+            .write(true)
+            .append(append)
+            .truncate(!append)
+            .open(&path)
+            .map_err(|e| output_error(&path, e))?;
+        let writer = csv::Writer::from_writer(file);
             
         Ok(Self {
             writer,
-            headers_written: false,
+            headers,
+            warned: false,
+            nested: NestedConfig::default(),
+            path,
         })
     }
+
+    /// Sets how nested objects and arrays are written.
+    pub fn with_nested(mut self, nested: NestedConfig) -> Self {
+        self.nested = nested;
+        self
+    }
+}
+
+/// The header row of the CSV file at `path`, if it exists and has one.
+fn existing_headers(path: &std::path::Path) -> Result<Option<Vec<String>>> {
+    if !path.exists() {
+        return Ok(None);
+    }
+    let mut reader = csv::Reader::from_path(path).map_err(|e| output_error(path, e))?;
+    let headers = reader.headers().map_err(|e| output_error(path, e))?;
+    Ok((!headers.is_empty()).then(|| headers.iter().map(str::to_string).collect()))
+}
+
+fn output_error(path: &std::path::Path, e: impl std::fmt::Display) -> Error {
+    Error::Output {
+        sink: format!("csv {}", path.display()),
+        message: e.to_string(),
+    }
 }
 
 #[async_trait]
 impl OutputHandler for CsvOutput {
     async fn write(&mut self, item: Value) -> Result<()> {
-        if let Value::Object(map) = item {
-            if !self.headers_written {
-                let headers: Vec<_> = map.keys().collect();
-                self.writer.write_record(headers)
-                    .map_err(|e| crate::error::Error::Internal(e.to_string()))?;
-                self.headers_written = true;
+        if let Value::Object(map) = flatten::normalize(item, &self.nested) {
+            let headers = match &self.headers {
+                Some(headers) => headers,
+                None => {
+                    let headers: Vec<String> = map.keys().cloned().collect();
+                    self.writer.write_record(&headers)
+                        .map_err(|e| output_error(&self.path, e))?;
+                    self.headers.insert(headers)
+                }
+            };
+            if !self.warned
+                && let Some(extra) = map.keys().find(|key| !headers.contains(key))
+            {
+                log::warn!("{} has no '{}' column, leaving such fields out", self.path.display(), extra);
+                self.warned = true;
             }
             
-            let values: Vec<_> = map.values().map(|v| match v {
-                Value::String(s) => s.clone(),
-                _ => v.to_string(),
+            let values: Vec<_> = headers.iter().map(|header| match map.get(header) {
+                Some(Value::String(s)) => s.clone(),
+                None => String::new(),
+                Some(v) => v.to_string(),
             }).collect();
             
             self.writer.write_record(values)
-                .map_err(|e| crate::error::Error::Internal(e.to_string()))?;
+                .map_err(|e| output_error(&self.path, e))?;
         }
         Ok(())
     }
 
+    async fn flush(&mut self) -> Result<()> {
+        self.writer.flush()
+            .map_err(|e| output_error(&self.path, e))?;
+        Ok(())
+    }
+
     async fn close(&mut self) -> Result<()> {
         self.writer.flush()
-            .map_err(|e| crate::error::Error::Internal(e.to_string()))?;
+            .map_err(|e| output_error(&self.path, e))?;
         Ok(())
     }
 }
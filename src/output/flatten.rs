@@ -0,0 +1,40 @@
+//! Flattening of nested items for outputs with one value per column, such as
+//! CSV and SQLite. `{"author": {"name": "A"}, "tags": ["x", "y"]}` becomes
+//! `{"author.name": "A", "tags[0]": "x", "tags[1]": "y"}`.
+
+use crate::config::schema::{NestedConfig, NestedMode};
+use serde_json::{Map, Value};
+
+/// Applies `config` to `item`. In `json` mode the item is left as it is, and
+/// handlers write nested values as JSON text.
+pub fn normalize(item: Value, config: &NestedConfig) -> Value {
+    match (config.mode, item) {
+        (NestedMode::Flatten, Value::Object(map)) => {
+            let mut flat = Map::new();
+            for (key, value) in map {
+                flatten_into(&mut flat, key, value, &config.separator);
+            }
+            Value::Object(flat)
+        }
+        (_, item) => item,
+    }
+}
+
+fn flatten_into(flat: &mut Map<String, Value>, key: String, value: Value, separator: &str) {
+    match value {
+        Value::Object(map) if !map.is_empty() => {
+            for (child, value) in map {
+                flatten_into(flat, format!("{}{}{}", key, separator, child), value, separator);
+            }
+        }
+        Value::Array(values) if !values.is_empty() => {
+            for (index, value) in values.into_iter().enumerate() {
+                flatten_into(flat, format!("{}[{}]", key, index), value, separator);
+            }
+        }
+        // Empty objects and arrays have no leaves, and stay as `{}` or `[]`
+        value => {
+            flat.insert(key, value);
+        }
+    }
+}
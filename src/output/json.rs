@@ -1,33 +1,91 @@
 use super::OutputHandler;
-use crate::error::Result;
+use crate::error::{Error, Result};
 use async_trait::async_trait;
 use serde_json::Value;
 use std::fs::{File, OpenOptions};
-use std::io::Write;
+use std::io::{Read, Seek, SeekFrom, Write};
 use std::path::PathBuf;
 
+/// How much of the end of an existing file is read to find where its array ends.
+const TAIL_BYTES: u64 = 4096;
+
 pub struct JsonOutput {
     file: File,
+    path: PathBuf,
     first: bool,
 }
 
 impl JsonOutput {
-    pub fn new(path: PathBuf) -> Result<Self> {
+    /// Writes a JSON array to `path`. With `append`, items are added to the
+    /// array already in the file, which may lack its closing `]` if the run
+    /// that wrote it was interrupted.
+    pub fn new(path: PathBuf, append: bool) -> Result<Self> {
         let mut file = OpenOptions::new()
             .create(true)
+            .read(true)
             .write(true)
-            .truncate(true)
-            .open(path)?;
+            .truncate(!append)
+            .open(&path)?;
 
-        write!(file, "[")?;
+        let first = match append {
+            true => match reopen_array(&mut file)? {
+                Some(first) => first,
+                None => {
+                    return Err(Error::Output {
+                        sink: format!("json {}", path.display()),
+                        message: "cannot append, the file doesn't hold a JSON array".to_string(),
+                    });
+                }
+            },
+            false => {
+                write!(file, "[")?;
+                true
+            }
+        };
 
         Ok(Self {
             file,
-            first: true,
+            path,
+            first,
         })
     }
 }
 
+/// Positions `file` to continue its array, after dropping the closing `]`.
+/// Returns whether the array is empty so far, or `None` if the file doesn't
+/// look like an array.
+fn reopen_array(file: &mut File) -> Result<Option<bool>> {
+    let len = file.metadata()?.len();
+    if len == 0 {
+        write!(file, "[")?;
+        return Ok(Some(true));
+    }
+    let mut head = [0u8; 64];
+    file.seek(SeekFrom::Start(0))?;
+    let read = file.read(&mut head)?;
+    if head[..read].iter().find(|b| !b.is_ascii_whitespace()) != Some(&b'[') {
+        return Ok(None);
+    }
+
+    let start = len.saturating_sub(TAIL_BYTES);
+    file.seek(SeekFrom::Start(start))?;
+    let mut tail = Vec::new();
+    file.read_to_end(&mut tail)?;
+
+    let mut end = tail.len();
+    while end > 0 && tail[end - 1].is_ascii_whitespace() {
+        end -= 1;
+    }
+    if end > 0 && tail[end - 1] == b']' {
+        end -= 1;
+    }
+    let first = tail[..end].iter().rev().find(|b| !b.is_ascii_whitespace()) == Some(&b'[');
+    let end = start + end as u64;
+    file.set_len(end)?;
+    file.seek(SeekFrom::Start(end))?;
+    Ok(Some(first))
+}
+
 #[async_trait]
 impl OutputHandler for JsonOutput {
     async fn write(&mut self, item: Value) -> Result<()> {
@@ -45,4 +103,13 @@ impl OutputHandler for JsonOutput {
         write!(self.file, "]")?;
         Ok(())
     }
+
+    /// Continues the array closed by the last run.
+    async fn reopen(&mut self) -> Result<()> {
+        self.first = reopen_array(&mut self.file)?.ok_or_else(|| Error::Output {
+            sink: format!("json {}", self.path.display()),
+            message: "cannot reopen, the file doesn't hold a JSON array anymore".to_string(),
+        })?;
+        Ok(())
+    }
 }
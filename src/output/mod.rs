@@ -5,12 +5,49 @@ use serde_json::Value;
 pub mod console;
 pub mod json;
 pub mod csv;
+pub mod flatten;
+pub mod null;
+pub mod object_storage;
+pub mod parquet;
+pub mod preview;
+pub mod schema;
 pub mod sqlite;
+pub mod stdout;
+pub mod xlsx;
 
 #[async_trait]
 pub trait OutputHandler: Send + Sync {
     async fn write(&mut self, item: Value) -> Result<()>;
+
+    /// Writes several items at once. Handlers with a cost per write, such as
+    /// databases or message queues, can override it to send them together; by
+    /// default they are written one by one, stopping at the first error.
+    async fn write_batch(&mut self, items: Vec<Value>) -> Result<()> {
+        for item in items {
+            self.write(item).await?;
+        }
+        Ok(())
+    }
+
+    /// Whether several instances of the handler can write to the same
+    /// destination at once, so that `output_concurrency` can create a pool of them.
+    fn pool_safe(&self) -> bool {
+        false
+    }
+
+    /// Pushes anything buffered to its destination. The engine calls it
+    /// periodically when output batching is on.
+    async fn flush(&mut self) -> Result<()> {
+        Ok(())
+    }
+
     async fn close(&mut self) -> Result<()> {
         Ok(())
     }
+
+    /// Readies a closed handler to write again, for the next run of a spider
+    /// that is run repeatedly. Handlers still usable after `close` need nothing.
+    async fn reopen(&mut self) -> Result<()> {
+        Ok(())
+    }
 }
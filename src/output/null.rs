@@ -0,0 +1,35 @@
+use super::OutputHandler;
+use crate::error::Result;
+use async_trait::async_trait;
+use serde_json::Value;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Discards items, only counting them. Used by benchmarks.
+#[derive(Default)]
+pub struct NullOutput {
+    count: Arc<AtomicU64>,
+}
+
+impl NullOutput {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Shared counter of items written, readable after the handler is moved into a spider.
+    pub fn counter(&self) -> Arc<AtomicU64> {
+        self.count.clone()
+    }
+}
+
+#[async_trait]
+impl OutputHandler for NullOutput {
+    async fn write(&mut self, _item: Value) -> Result<()> {
+        self.count.fetch_add(1, Ordering::Relaxed);
+        Ok(())
+    }
+
+    fn pool_safe(&self) -> bool {
+        true
+    }
+}
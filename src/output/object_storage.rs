@@ -0,0 +1,89 @@
+//! Items uploaded as NDJSON objects to S3, Google Cloud Storage or Azure Blob
+//! Storage, through the `object_store` crate.
+
+use super::OutputHandler;
+use crate::error::{Error, Result};
+use async_trait::async_trait;
+use object_store::path::Path;
+use object_store::{ObjectStore, PutPayload};
+use serde_json::Value;
+use std::collections::HashMap;
+use url::Url;
+
+/// Prefixes of the environment variables passed on to the store's configuration.
+const ENV_PREFIXES: &[&str] = &["AWS_", "GOOGLE_", "AZURE_"];
+
+pub struct ObjectStoreOutput {
+    store: Box<dyn ObjectStore>,
+    url: String,
+    /// Objects are written below this path
+    prefix: Path,
+    items_per_object: usize,
+    buffer: Vec<u8>,
+    buffered: usize,
+    part: usize,
+}
+
+impl ObjectStoreOutput {
+    /// Uploads to `url`, e.g. `gs://bucket/prefix`. Credentials come from
+    /// `AWS_*`, `GOOGLE_*` or `AZURE_*` environment variables, overridden by `options`.
+    pub fn new(url: &str, options: &HashMap<String, String>, items_per_object: usize) -> Result<Self> {
+        let parsed = Url::parse(url).map_err(|e| output_error(url, e))?;
+        let settings = std::env::vars()
+            .filter(|(key, _)| ENV_PREFIXES.iter().any(|prefix| key.starts_with(prefix)))
+            .map(|(key, value)| (key.to_ascii_lowercase(), value))
+            .chain(options.iter().map(|(key, value)| (key.to_ascii_lowercase(), value.clone())));
+        let (store, prefix) = object_store::parse_url_opts(&parsed, settings).map_err(|e| output_error(url, e))?;
+
+        Ok(Self {
+            store,
+            url: url.to_string(),
+            prefix,
+            items_per_object: items_per_object.max(1),
+            buffer: Vec::new(),
+            buffered: 0,
+            part: 0,
+        })
+    }
+
+    /// Uploads the buffered items as the next part.
+    async fn upload(&mut self) -> Result<()> {
+        if self.buffered == 0 {
+            return Ok(());
+        }
+        let location = self.prefix.child(format!("part-{:05}.ndjson", self.part));
+        let payload = PutPayload::from(std::mem::take(&mut self.buffer));
+        self.store
+            .put(&location, payload)
+            .await
+            .map_err(|e| output_error(&self.url, e))?;
+        log::debug!("Uploaded {} items to {}", self.buffered, location);
+        self.buffered = 0;
+        self.part += 1;
+        Ok(())
+    }
+}
+
+fn output_error(url: &str, e: impl std::fmt::Display) -> Error {
+    Error::Output {
+        sink: format!("object store {}", url),
+        message: e.to_string(),
+    }
+}
+
+#[async_trait]
+impl OutputHandler for ObjectStoreOutput {
+    async fn write(&mut self, item: Value) -> Result<()> {
+        serde_json::to_writer(&mut self.buffer, &item)?;
+        self.buffer.push(b'\n');
+        self.buffered += 1;
+        if self.buffered >= self.items_per_object {
+            self.upload().await?;
+        }
+        Ok(())
+    }
+
+    async fn close(&mut self) -> Result<()> {
+        self.upload().await
+    }
+}
@@ -0,0 +1,154 @@
+use super::OutputHandler;
+use crate::error::{Error, Result};
+use arrow_array::{ArrayRef, RecordBatch, StringArray};
+use arrow_schema::{DataType, Field, Schema};
+use async_trait::async_trait;
+use parquet::arrow::ArrowWriter;
+use parquet::basic::Compression;
+use parquet::file::properties::WriterProperties;
+use serde_json::{Map, Value};
+use std::fs::File;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+const BATCH_SIZE: usize = 8192;
+
+/// Writes items as a Parquet file with one nullable string column per field.
+///
+/// Items are buffered and written in row groups of [`BATCH_SIZE`]. The schema
+/// is fixed by the fields seen before the first row group is written; fields
+/// that only appear later are dropped with a warning.
+pub struct ParquetOutput {
+    path: PathBuf,
+    // ArrowWriter isn't Sync; only ever accessed through `get_mut`
+    writer: Option<Mutex<ArrowWriter<File>>>,
+    schema: Option<Arc<Schema>>,
+    columns: Vec<String>,
+    rows: Vec<Map<String, Value>>,
+    dropped_fields: Vec<String>,
+}
+
+impl ParquetOutput {
+    pub fn new(path: PathBuf) -> Result<Self> {
+        // Fail early on an unwritable path rather than at the end of the crawl
+        File::create(&path)?;
+        Ok(Self {
+            path,
+            writer: None,
+            schema: None,
+            columns: Vec::new(),
+            rows: Vec::new(),
+            dropped_fields: Vec::new(),
+        })
+    }
+
+    fn output_error(&self, e: impl std::fmt::Display) -> Error {
+        Error::Output {
+            sink: format!("parquet {}", self.path.display()),
+            message: e.to_string(),
+        }
+    }
+
+    fn flush_rows(&mut self) -> Result<()> {
+        if self.rows.is_empty() && self.writer.is_some() {
+            return Ok(());
+        }
+
+        if self.writer.is_none() {
+            let schema = Arc::new(Schema::new(
+                self.columns
+                    .iter()
+                    .map(|name| Field::new(name, DataType::Utf8, true))
+                    .collect::<Vec<_>>(),
+            ));
+            let props = WriterProperties::builder()
+                .set_compression(Compression::SNAPPY)
+                .build();
+            let file = File::create(&self.path)?;
+            let writer = ArrowWriter::try_new(file, schema.clone(), Some(props))
+                .map_err(|e| self.output_error(e))?;
+            self.writer = Some(Mutex::new(writer));
+            self.schema = Some(schema);
+        }
+
+        if self.rows.is_empty() {
+            return Ok(());
+        }
+
+        let arrays: Vec<ArrayRef> = self
+            .columns
+            .iter()
+            .map(|column| {
+                let values: StringArray = self
+                    .rows
+                    .iter()
+                    .map(|row| match row.get(column) {
+                        None | Some(Value::Null) => None,
+                        Some(Value::String(s)) => Some(s.clone()),
+                        Some(other) => Some(other.to_string()),
+                    })
+                    .collect();
+                Arc::new(values) as ArrayRef
+            })
+            .collect();
+        let schema = self.schema.clone().expect("schema is set with the writer");
+        let batch = RecordBatch::try_new(schema, arrays).map_err(|e| self.output_error(e))?;
+        self.rows.clear();
+
+        let result = match self.writer.as_mut().map(Mutex::get_mut) {
+            Some(Ok(writer)) => writer.write(&batch).map_err(|e| e.to_string()),
+            Some(Err(e)) => Err(e.to_string()),
+            None => Ok(()),
+        };
+        result.map_err(|e| self.output_error(e))
+    }
+}
+
+#[async_trait]
+impl OutputHandler for ParquetOutput {
+    async fn write(&mut self, item: Value) -> Result<()> {
+        if let Value::Object(map) = item {
+            for key in map.keys() {
+                if self.columns.contains(key) {
+                    continue;
+                }
+                if self.writer.is_none() {
+                    self.columns.push(key.clone());
+                } else if !self.dropped_fields.contains(key) {
+                    log::warn!(
+                        "Field '{}' first appeared after the Parquet schema was written; dropping it",
+                        key
+                    );
+                    self.dropped_fields.push(key.clone());
+                }
+            }
+            self.rows.push(map);
+            if self.rows.len() >= BATCH_SIZE {
+                self.flush_rows()?;
+            }
+        }
+        Ok(())
+    }
+
+    async fn close(&mut self) -> Result<()> {
+        self.flush_rows()?;
+        if let Some(writer) = self.writer.take() {
+            let result = match writer.into_inner() {
+                Ok(writer) => writer.close().map(|_| ()).map_err(|e| e.to_string()),
+                Err(e) => Err(e.to_string()),
+            };
+            result.map_err(|e| self.output_error(e))?;
+        }
+        Ok(())
+    }
+
+    /// Starts a new file with a new schema; Parquet files can't be appended
+    /// to, so it holds the items of the latest run only.
+    async fn reopen(&mut self) -> Result<()> {
+        log::warn!("{} is replaced by every run", self.path.display());
+        self.schema = None;
+        self.columns.clear();
+        self.dropped_fields.clear();
+        Ok(())
+    }
+}
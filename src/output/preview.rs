@@ -0,0 +1,45 @@
+use super::console::ConsoleOutput;
+use super::OutputHandler;
+use crate::error::Result;
+use async_trait::async_trait;
+use indicatif::MultiProgress;
+use serde_json::Value;
+use std::sync::Arc;
+
+/// Prints the first `limit` items and only counts the rest. Used by `--dry-run`
+/// so a config can be validated without touching the configured output.
+pub struct PreviewOutput {
+    console: ConsoleOutput,
+    limit: usize,
+    count: usize,
+}
+
+impl PreviewOutput {
+    pub fn new(limit: usize, multi: Option<Arc<MultiProgress>>) -> Self {
+        Self {
+            console: ConsoleOutput::new(multi),
+            limit,
+            count: 0,
+        }
+    }
+}
+
+#[async_trait]
+impl OutputHandler for PreviewOutput {
+    async fn write(&mut self, item: Value) -> Result<()> {
+        self.count += 1;
+        if self.count <= self.limit {
+            self.console.write(item).await?;
+        }
+        Ok(())
+    }
+
+    async fn close(&mut self) -> Result<()> {
+        log::info!(
+            "Dry run: {} items extracted, {} shown, nothing written",
+            self.count,
+            self.count.min(self.limit)
+        );
+        Ok(())
+    }
+}
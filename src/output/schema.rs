@@ -0,0 +1,110 @@
+//! Column schemas declared for SQL outputs. Items are checked against the
+//! schema before they are written, and those that don't fit go to a dead-letter
+//! file along with the reason, instead of into the table.
+
+use crate::config::schema::{ColumnConfig, ColumnType};
+use crate::error::Result;
+use serde_json::{json, Map, Value};
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// A value checked against its column's type, ready to bind.
+#[derive(Debug, Clone)]
+pub enum ColumnValue {
+    Null,
+    Text(String),
+    Integer(i64),
+    Real(f64),
+    Boolean(bool),
+}
+
+#[derive(Debug, Clone)]
+pub struct TableSchema {
+    pub columns: Vec<ColumnConfig>,
+}
+
+impl TableSchema {
+    pub fn new(columns: Vec<ColumnConfig>) -> Self {
+        Self { columns }
+    }
+
+    /// The item's values in column order, or why it doesn't fit the schema.
+    /// Metadata fields such as `_run_id` are dropped unless declared.
+    pub fn validate(&self, item: &Map<String, Value>) -> std::result::Result<Vec<ColumnValue>, String> {
+        if let Some(unknown) = item
+            .keys()
+            .filter(|key| !key.starts_with('_'))
+            .find(|key| !self.columns.iter().any(|column| &column.name == *key))
+        {
+            return Err(format!("field '{}' is not a column", unknown));
+        }
+        self.columns
+            .iter()
+            .map(|column| match item.get(&column.name) {
+                None | Some(Value::Null) if column.nullable => Ok(ColumnValue::Null),
+                None | Some(Value::Null) => Err(format!("column '{}' is not nullable", column.name)),
+                Some(value) => column_value(column.column_type, value).ok_or_else(|| {
+                    format!(
+                        "column '{}' expects {}, got {}",
+                        column.name,
+                        type_name(column.column_type),
+                        value
+                    )
+                }),
+            })
+            .collect()
+    }
+}
+
+fn column_value(column_type: ColumnType, value: &Value) -> Option<ColumnValue> {
+    match (column_type, value) {
+        (ColumnType::Text, Value::String(s)) => Some(ColumnValue::Text(s.clone())),
+        (ColumnType::Text, Value::Number(_) | Value::Bool(_)) => Some(ColumnValue::Text(value.to_string())),
+        (ColumnType::Integer, Value::Number(n)) => n.as_i64().map(ColumnValue::Integer),
+        (ColumnType::Real, Value::Number(n)) => n.as_f64().map(ColumnValue::Real),
+        (ColumnType::Boolean, Value::Bool(b)) => Some(ColumnValue::Boolean(*b)),
+        (ColumnType::Json, value) => Some(ColumnValue::Text(value.to_string())),
+        _ => None,
+    }
+}
+
+fn type_name(column_type: ColumnType) -> &'static str {
+    match column_type {
+        ColumnType::Text => "text",
+        ColumnType::Integer => "an integer",
+        ColumnType::Real => "a number",
+        ColumnType::Boolean => "a boolean",
+        ColumnType::Json => "JSON",
+    }
+}
+
+/// NDJSON file of rejected items, created on the first rejection.
+pub struct DeadLetter {
+    path: PathBuf,
+    file: Option<File>,
+}
+
+impl DeadLetter {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            file: None,
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn write(&mut self, item: &Value, reason: &str) -> Result<()> {
+        if self.file.is_none() {
+            self.file = Some(OpenOptions::new().create(true).append(true).open(&self.path)?);
+        }
+        if let Some(file) = self.file.as_mut() {
+            serde_json::to_writer(&mut *file, &json!({ "reason": reason, "item": item }))?;
+            file.write_all(b"\n")?;
+        }
+        Ok(())
+    }
+}
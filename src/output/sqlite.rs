@@ -1,69 +1,271 @@
 use super::OutputHandler;
+use super::flatten;
+use super::schema::{ColumnValue, DeadLetter, TableSchema};
+use crate::config::schema::{ColumnConfig, ColumnType, NestedConfig, SqliteJournal, SqlitePragmas, SqliteSync};
 use crate::error::{Error, Result};
+use crate::item_id::ITEM_ID_FIELD;
 use async_trait::async_trait;
 use serde_json::Value;
-use sqlx::sqlite::SqlitePool;
+use sqlx::sqlite::{SqliteConnectOptions, SqliteJournalMode, SqlitePool, SqliteSynchronous};
 use std::path::PathBuf;
+use std::time::Duration;
 
 pub struct SqliteOutput {
     pool: SqlitePool,
     table_name: String,
     initialized: bool,
+    /// Columns the table has, including those of earlier runs
+    columns: Vec<String>,
+    /// Declared columns, with the file that receives items not matching them
+    schema: Option<(TableSchema, DeadLetter)>,
+    nested: NestedConfig,
+    /// Whether the unique index on `_id` that upserts rely on exists
+    id_indexed: bool,
 }
 
 impl SqliteOutput {
-    pub async fn new(path: PathBuf, table_name: String) -> Result<Self> {
-        let conn_str = format!("sqlite:{}?mode=rwc", path.display());
-        let pool = SqlitePool::connect(&conn_str).await
-            .map_err(|e| Error::Database(e))?;
-            
+    pub async fn new(path: PathBuf, table_name: String, pragmas: &SqlitePragmas) -> Result<Self> {
+        let options = SqliteConnectOptions::new()
+            .filename(&path)
+            .create_if_missing(true)
+            .journal_mode(match pragmas.journal_mode {
+                SqliteJournal::Delete => SqliteJournalMode::Delete,
+                SqliteJournal::Truncate => SqliteJournalMode::Truncate,
+                SqliteJournal::Persist => SqliteJournalMode::Persist,
+                SqliteJournal::Memory => SqliteJournalMode::Memory,
+                SqliteJournal::Wal => SqliteJournalMode::Wal,
+                SqliteJournal::Off => SqliteJournalMode::Off,
+            })
+            .synchronous(match pragmas.synchronous {
+                SqliteSync::Off => SqliteSynchronous::Off,
+                SqliteSync::Normal => SqliteSynchronous::Normal,
+                SqliteSync::Full => SqliteSynchronous::Full,
+                SqliteSync::Extra => SqliteSynchronous::Extra,
+            })
+            .busy_timeout(Duration::from_millis(pragmas.busy_timeout_ms))
+            .pragma("cache_size", pragmas.cache_size.to_string());
+        let pool = SqlitePool::connect_with(options).await
+            .map_err(|e| Error::Output {
+                sink: format!("sqlite {}", path.display()),
+                message: e.to_string(),
+            })?;
+
         Ok(Self {
             pool,
             table_name,
             initialized: false,
+            columns: Vec::new(),
+            schema: None,
+            nested: NestedConfig::default(),
+            id_indexed: false,
         })
     }
 
-    async fn ensure_table(&mut self, item: &serde_json::Map<String, Value>) -> Result<()> {
-        if self.initialized {
-            return Ok(());
-        }
+    /// Sets how nested objects and arrays are stored.
+    pub fn with_nested(mut self, nested: NestedConfig) -> Self {
+        self.nested = nested;
+        self
+    }
 
-        let mut columns = Vec::new();
-        for key in item.keys() {
-            columns.push(format!("{} TEXT", key));
-        }
-        
+    /// Creates the table with `columns` up front, or checks that an existing
+    /// table has them, and rejects items that don't match to `dead_letter`.
+    pub async fn with_schema(mut self, columns: Vec<ColumnConfig>, dead_letter: PathBuf) -> Result<Self> {
+        let definitions: Vec<String> = columns
+            .iter()
+            .map(|column| {
+                let not_null = if column.nullable { "" } else { " NOT NULL" };
+                format!("{} {}{}", quote(&column.name), sql_type(column.column_type), not_null)
+            })
+            .collect();
         let query = format!(
             "CREATE TABLE IF NOT EXISTS {} (id INTEGER PRIMARY KEY, {})",
             self.table_name,
-            columns.join(", ")
+            definitions.join(", ")
         );
-        
         sqlx::query(&query).execute(&self.pool).await
-            .map_err(|e| Error::Database(e))?;
-            
+            .map_err(|e| self.output_error(e))?;
+
+        let existing: Vec<String> = sqlx::query_scalar("SELECT name FROM pragma_table_info(?)")
+            .bind(&self.table_name)
+            .fetch_all(&self.pool)
+            .await
+            .map_err(|e| self.output_error(e))?;
+        if let Some(missing) = columns.iter().find(|column| !existing.contains(&column.name)) {
+            return Err(Error::Output {
+                sink: format!("sqlite table {}", self.table_name),
+                message: format!("existing table has no column '{}'", missing.name),
+            });
+        }
+
         self.initialized = true;
+        self.schema = Some((TableSchema::new(columns), DeadLetter::new(dead_letter)));
+        Ok(self)
+    }
+
+    /// Creates the unique index on `_id`, so items carrying one replace the
+    /// row of the same item instead of adding another.
+    async fn ensure_id_index(&mut self) -> Result<()> {
+        if self.id_indexed {
+            return Ok(());
+        }
+        let query = format!(
+            "CREATE UNIQUE INDEX IF NOT EXISTS {} ON {} ({})",
+            quote(&format!("{}_item_id", self.table_name)),
+            self.table_name,
+            quote(ITEM_ID_FIELD)
+        );
+        sqlx::query(&query).execute(&self.pool).await
+            .map_err(|e| self.output_error(e))?;
+        self.id_indexed = true;
         Ok(())
     }
+
+    /// An `INSERT` of `names` (unquoted), which updates the existing row
+    /// instead when `names` include `_id`.
+    fn insert_query(&self, names: &[&str]) -> String {
+        let columns: Vec<String> = names.iter().map(|name| quote(name)).collect();
+        let placeholders: Vec<_> = (1..=names.len()).map(|i| format!("?{}", i)).collect();
+        let mut query = format!(
+            "INSERT INTO {} ({}) VALUES ({})",
+            self.table_name,
+            columns.join(", "),
+            placeholders.join(", ")
+        );
+        if names.contains(&ITEM_ID_FIELD) {
+            let updates: Vec<String> = names
+                .iter()
+                .filter(|name| **name != ITEM_ID_FIELD)
+                .map(|name| format!("{0} = excluded.{0}", quote(name)))
+                .collect();
+            query.push_str(&format!(" ON CONFLICT ({}) DO ", quote(ITEM_ID_FIELD)));
+            if updates.is_empty() {
+                query.push_str("NOTHING");
+            } else {
+                query.push_str(&format!("UPDATE SET {}", updates.join(", ")));
+            }
+        }
+        query
+    }
+
+    fn output_error(&self, e: sqlx::Error) -> Error {
+        Error::Output {
+            sink: format!("sqlite table {}", self.table_name),
+            message: e.to_string(),
+        }
+    }
+
+    /// Creates the table from the first item, then adds a `TEXT` column for
+    /// each field the table lacks, whether it was created by this run or an earlier one.
+    async fn ensure_table(&mut self, item: &serde_json::Map<String, Value>) -> Result<()> {
+        if !self.initialized {
+            let mut columns = Vec::new();
+            for key in item.keys() {
+                columns.push(format!("{} TEXT", quote(key)));
+            }
+
+            let query = format!(
+                "CREATE TABLE IF NOT EXISTS {} (id INTEGER PRIMARY KEY, {})",
+                self.table_name,
+                columns.join(", ")
+            );
+
+            sqlx::query(&query).execute(&self.pool).await
+                .map_err(|e| self.output_error(e))?;
+            self.columns = sqlx::query_scalar("SELECT name FROM pragma_table_info(?)")
+                .bind(&self.table_name)
+                .fetch_all(&self.pool)
+                .await
+                .map_err(|e| self.output_error(e))?;
+            self.initialized = true;
+        }
+
+        for key in item.keys() {
+            if self.columns.contains(key) {
+                continue;
+            }
+            log::info!("Adding column {} to table {}", key, self.table_name);
+            let query = format!("ALTER TABLE {} ADD COLUMN {} TEXT", self.table_name, quote(key));
+            match sqlx::query(&query).execute(&self.pool).await {
+                Ok(_) => {}
+                // Another instance of a pooled output added it first
+                Err(e) if e.to_string().contains("duplicate column name") => {}
+                Err(e) => return Err(self.output_error(e)),
+            }
+            self.columns.push(key.clone());
+        }
+        Ok(())
+    }
+
+    async fn write_checked(&mut self, item: Value) -> Result<()> {
+        let Some((schema, dead_letter)) = self.schema.as_mut() else {
+            return Ok(());
+        };
+        let checked = match &item {
+            Value::Object(map) => schema.validate(map),
+            _ => Err("item is not an object".to_string()),
+        };
+        let values = match checked {
+            Ok(values) => values,
+            Err(reason) => {
+                dead_letter.write(&item, &reason)?;
+                return Err(Error::Output {
+                    sink: format!("sqlite table {}", self.table_name),
+                    message: format!("rejected item, {}; written to {}", reason, dead_letter.path().display()),
+                });
+            }
+        };
+
+        let names: Vec<String> = schema.columns.iter().map(|column| column.name.clone()).collect();
+        let names: Vec<&str> = names.iter().map(String::as_str).collect();
+        if names.contains(&ITEM_ID_FIELD) {
+            self.ensure_id_index().await?;
+        }
+        let query = self.insert_query(&names);
+        let mut q = sqlx::query(&query);
+        for value in values {
+            q = match value {
+                ColumnValue::Null => q.bind(None::<String>),
+                ColumnValue::Text(s) => q.bind(s),
+                ColumnValue::Integer(n) => q.bind(n),
+                ColumnValue::Real(n) => q.bind(n),
+                ColumnValue::Boolean(b) => q.bind(b),
+            };
+        }
+        q.execute(&self.pool).await
+            .map_err(|e| self.output_error(e))?;
+        Ok(())
+    }
+}
+
+/// Quotes a column name, which may contain `.` or `[` once items are flattened.
+fn quote(name: &str) -> String {
+    format!("\"{}\"", name.replace('"', "\"\""))
+}
+
+fn sql_type(column_type: ColumnType) -> &'static str {
+    match column_type {
+        ColumnType::Text | ColumnType::Json => "TEXT",
+        ColumnType::Integer | ColumnType::Boolean => "INTEGER",
+        ColumnType::Real => "REAL",
+    }
 }
 
 #[async_trait]
 impl OutputHandler for SqliteOutput {
     async fn write(&mut self, item: Value) -> Result<()> {
+        let item = flatten::normalize(item, &self.nested);
+        if self.schema.is_some() {
+            return self.write_checked(item).await;
+        }
         if let Value::Object(map) = item {
             self.ensure_table(&map).await?;
-            
+            if map.contains_key(ITEM_ID_FIELD) {
+                self.ensure_id_index().await?;
+            }
+
             let keys: Vec<_> = map.keys().map(|k| k.as_str()).collect();
-            let placeholders: Vec<_> = (1..=keys.len()).map(|i| format!("?{}", i)).collect();
-            
-            let query = format!(
-                "INSERT INTO {} ({}) VALUES ({})",
-                self.table_name,
-                keys.join(", "),
-                placeholders.join(", ")
-            );
-            
+            let query = self.insert_query(&keys);
+
             let mut q = sqlx::query(&query);
             for key in keys {
                 let val = match map.get(key).unwrap() {
@@ -74,13 +276,26 @@ impl OutputHandler for SqliteOutput {
             }
             
             q.execute(&self.pool).await
-                .map_err(|e| Error::Database(e))?;
+                .map_err(|e| self.output_error(e))?;
         }
         Ok(())
     }
 
+    /// Instances wait on each other through SQLite's busy timeout.
+    fn pool_safe(&self) -> bool {
+        true
+    }
+
     async fn close(&mut self) -> Result<()> {
         self.pool.close().await;
         Ok(())
     }
+
+    async fn reopen(&mut self) -> Result<()> {
+        let options = (*self.pool.connect_options()).clone();
+        self.pool = SqlitePool::connect_with(options)
+            .await
+            .map_err(|e| self.output_error(e))?;
+        Ok(())
+    }
 }
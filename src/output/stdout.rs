@@ -0,0 +1,45 @@
+use super::OutputHandler;
+use crate::config::schema::StdoutFormat;
+use crate::error::{Error, Result};
+use async_trait::async_trait;
+use serde_json::Value;
+use std::io::{BufWriter, Stdout, Write};
+
+/// Writes items, and nothing else, to stdout so the crawler can feed a pipeline.
+pub struct StdoutOutput {
+    writer: BufWriter<Stdout>,
+    format: StdoutFormat,
+}
+
+impl StdoutOutput {
+    pub fn new(format: StdoutFormat) -> Self {
+        Self {
+            writer: BufWriter::new(std::io::stdout()),
+            format,
+        }
+    }
+}
+
+fn output_error(e: impl std::fmt::Display) -> Error {
+    Error::Output {
+        sink: "stdout".to_string(),
+        message: e.to_string(),
+    }
+}
+
+#[async_trait]
+impl OutputHandler for StdoutOutput {
+    async fn write(&mut self, item: Value) -> Result<()> {
+        match self.format {
+            StdoutFormat::Ndjson => {
+                serde_json::to_writer(&mut self.writer, &item)?;
+                writeln!(self.writer).map_err(output_error)?;
+            }
+        }
+        Ok(())
+    }
+
+    async fn close(&mut self) -> Result<()> {
+        self.writer.flush().map_err(output_error)
+    }
+}
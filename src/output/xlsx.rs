@@ -0,0 +1,120 @@
+use super::OutputHandler;
+use crate::error::{Error, Result};
+use async_trait::async_trait;
+use rust_xlsxwriter::{Format, Workbook, Worksheet, XlsxError};
+use serde_json::Value;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+/// Writes items to a single-sheet Excel workbook, saved when the handler closes.
+///
+/// Columns are added as new fields are seen, so items don't need identical keys.
+pub struct XlsxOutput {
+    path: PathBuf,
+    // Worksheet isn't Sync; only ever accessed through `get_mut`
+    worksheet: Mutex<Worksheet>,
+    columns: Vec<String>,
+    next_row: u32,
+}
+
+impl XlsxOutput {
+    pub fn new(path: PathBuf) -> Result<Self> {
+        // Fail early on an unwritable path rather than at the end of the crawl
+        std::fs::File::create(&path)?;
+        Ok(Self {
+            path,
+            worksheet: Mutex::new(Worksheet::new()),
+            columns: Vec::new(),
+            next_row: 1,
+        })
+    }
+}
+
+fn output_error(path: &Path, e: impl std::fmt::Display) -> Error {
+    Error::Output {
+        sink: format!("xlsx {}", path.display()),
+        message: e.to_string(),
+    }
+}
+
+fn write_cell(
+    worksheet: &mut Worksheet,
+    row: u32,
+    col: u16,
+    value: &Value,
+) -> std::result::Result<(), XlsxError> {
+    match value {
+        Value::Null => {}
+        Value::Bool(b) => {
+            worksheet.write_boolean(row, col, *b)?;
+        }
+        Value::Number(n) => match n.as_f64() {
+            Some(f) => {
+                worksheet.write_number(row, col, f)?;
+            }
+            None => {
+                worksheet.write_string(row, col, n.to_string())?;
+            }
+        },
+        Value::String(s) => {
+            worksheet.write_string(row, col, s)?;
+        }
+        other => {
+            worksheet.write_string(row, col, other.to_string())?;
+        }
+    }
+    Ok(())
+}
+
+#[async_trait]
+impl OutputHandler for XlsxOutput {
+    async fn write(&mut self, item: Value) -> Result<()> {
+        let Value::Object(map) = item else {
+            return Ok(());
+        };
+        let worksheet = self
+            .worksheet
+            .get_mut()
+            .map_err(|e| output_error(&self.path, e))?;
+        let row = self.next_row;
+        for (key, value) in &map {
+            let col = match self.columns.iter().position(|c| c == key) {
+                Some(index) => index as u16,
+                None => {
+                    let index = self.columns.len() as u16;
+                    worksheet
+                        .write_string_with_format(0, index, key, &Format::new().set_bold())
+                        .map_err(|e| output_error(&self.path, e))?;
+                    self.columns.push(key.clone());
+                    index
+                }
+            };
+            write_cell(worksheet, row, col, value).map_err(|e| output_error(&self.path, e))?;
+        }
+        self.next_row += 1;
+        Ok(())
+    }
+
+    async fn close(&mut self) -> Result<()> {
+        let worksheet = self
+            .worksheet
+            .get_mut()
+            .map(|ws| std::mem::replace(ws, Worksheet::new()))
+            .map_err(|e| output_error(&self.path, e))?;
+        let mut workbook = Workbook::new();
+        workbook.push_worksheet(worksheet);
+        workbook
+            .save(&self.path)
+            .map_err(|e| output_error(&self.path, e))?;
+        Ok(())
+    }
+
+    /// Starts a new sheet; the workbook is saved whole, so it ends up with
+    /// the items of the latest run only.
+    async fn reopen(&mut self) -> Result<()> {
+        log::warn!("{} is replaced by every run", self.path.display());
+        self.columns.clear();
+        self.next_row = 1;
+        Ok(())
+    }
+}
@@ -0,0 +1,209 @@
+//! Rule sets picked by URL pattern and crawl depth, so one spider can handle
+//! several kinds of pages (e.g. categories it only follows, products it extracts).
+
+use crate::config::schema::{HeadCheckConfig, PageTypeConfig};
+use crate::error::{Error, Result};
+use crate::extract::Extractor;
+use crate::url_rewrite::UrlRewrite;
+use regex::Regex;
+use serde::Serialize;
+use select::document::Document;
+use select::node::Node;
+use select::predicate::Name;
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+use url::Url;
+
+#[derive(Debug)]
+pub struct PageType {
+    pub name: Option<String>,
+    pattern: Option<Regex>,
+    depth: Option<usize>,
+    /// `None` for `follow_only` page types
+    pub extractor: Option<Arc<Extractor>>,
+    /// Whether links found on the page are queued
+    pub follow: bool,
+    /// Overrides the spider's HEAD check for pages of this type
+    pub head_check: Option<HeadCheckConfig>,
+    /// Whether followed links record their [`LinkContext`]
+    pub link_context: bool,
+}
+
+impl PageType {
+    pub fn new(config: &PageTypeConfig) -> Result<Self> {
+        let pattern = config
+            .pattern
+            .as_deref()
+            .map(|pattern| {
+                Regex::new(pattern).map_err(|e| {
+                    Error::Config(format!("Invalid page type pattern '{}': {}", pattern, e))
+                })
+            })
+            .transpose()?;
+        let extractor = (!config.follow_only).then(|| {
+            Arc::new(Extractor::new(config.root_selector.clone(), config.rules.clone()))
+        });
+
+        Ok(Self {
+            name: config.name.clone(),
+            pattern,
+            depth: config.depth,
+            extractor,
+            follow: config.follow || config.follow_only,
+            head_check: config.head_check.clone(),
+            link_context: config.link_context,
+        })
+    }
+
+    pub fn matches(&self, url: &str, depth: usize) -> bool {
+        self.depth.is_none_or(|d| d == depth)
+            && self.pattern.as_ref().is_none_or(|pattern| pattern.is_match(url))
+    }
+}
+
+/// How a followed link appeared on the page it was found on.
+#[derive(Debug, Clone, Serialize)]
+pub struct LinkContext {
+    /// The page the link was found on
+    pub from: String,
+    /// The link's text, with whitespace collapsed
+    pub anchor_text: String,
+    /// The closest heading before the link, or around it
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub heading: Option<String>,
+    /// The link's `rel` attribute, e.g. `nofollow`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rel: Option<String>,
+}
+
+impl LinkContext {
+    fn new(node: &Node, base: &Url) -> Self {
+        Self {
+            from: base.to_string(),
+            anchor_text: collapse_text(node),
+            heading: nearest_heading(node),
+            rel: node.attr("rel").map(|rel| rel.trim().to_string()),
+        }
+    }
+}
+
+/// The last heading in document order before `node`, including a heading the node is in.
+fn nearest_heading(node: &Node) -> Option<String> {
+    let mut current = *node;
+    loop {
+        let mut sibling = current.prev();
+        while let Some(node) = sibling {
+            if let Some(heading) = std::iter::once(node).chain(node.descendants()).filter(is_heading).last() {
+                return Some(collapse_text(&heading));
+            }
+            sibling = node.prev();
+        }
+        current = current.parent()?;
+        if is_heading(&current) {
+            return Some(collapse_text(&current));
+        }
+    }
+}
+
+fn is_heading(node: &Node) -> bool {
+    matches!(node.name(), Some("h1" | "h2" | "h3" | "h4" | "h5" | "h6"))
+}
+
+fn collapse_text(node: &Node) -> String {
+    node.text().split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// The configured page types, plus the crawl depth of every URL they queued.
+#[derive(Debug)]
+pub struct PageTypes {
+    types: Vec<PageType>,
+    depths: Mutex<HashMap<String, usize>>,
+    /// How the URLs queued with `link_context` were linked to
+    contexts: Mutex<HashMap<String, LinkContext>>,
+    url_rewrite: Option<UrlRewrite>,
+}
+
+impl PageTypes {
+    pub fn new(configs: &[PageTypeConfig]) -> Result<Self> {
+        Ok(Self {
+            types: configs.iter().map(PageType::new).collect::<Result<_>>()?,
+            depths: Mutex::new(HashMap::new()),
+            contexts: Mutex::new(HashMap::new()),
+            url_rewrite: None,
+        })
+    }
+
+    /// Rewrites found links before they are matched against page types and queued.
+    pub fn with_url_rewrite(mut self, rewrite: UrlRewrite) -> Self {
+        self.url_rewrite = Some(rewrite);
+        self
+    }
+
+    /// Links away from a start URL; URLs that weren't queued by a followed page are at 0.
+    pub fn depth(&self, url: &str) -> usize {
+        self.depths
+            .lock()
+            .ok()
+            .and_then(|depths| depths.get(url).copied())
+            .unwrap_or(0)
+    }
+
+    /// The first page type matching `url` at `depth`, if any.
+    pub fn find(&self, url: &str, depth: usize) -> Option<&PageType> {
+        self.types.iter().find(|page_type| page_type.matches(url, depth))
+    }
+
+    /// Absolute links in `html` that match a page type one level below `depth`,
+    /// recording their depth, and their context if `link_context` is set.
+    /// Links matching no page type are not followed.
+    pub fn links_to_follow(&self, html: &str, base: &Url, depth: usize, link_context: bool) -> Vec<String> {
+        let document = Document::from(html);
+        let mut found = HashSet::new();
+        let links: Vec<(Node, String)> = document
+            .find(Name("a"))
+            .filter_map(|node| {
+                let href = node.attr("href")?;
+                base.join(href.trim()).ok().map(|url| (node, url))
+            })
+            .filter(|(_, url)| matches!(url.scheme(), "http" | "https"))
+            .map(|(node, mut url)| {
+                url.set_fragment(None);
+                let url = match &self.url_rewrite {
+                    Some(rewrite) => rewrite.apply(url.as_str()),
+                    None => url.to_string(),
+                };
+                (node, url)
+            })
+            .filter(|(_, url)| self.find(url, depth + 1).is_some())
+            .filter(|(_, url)| found.insert(url.clone()))
+            .collect();
+
+        if link_context && let Ok(mut contexts) = self.contexts.lock() {
+            for (node, url) in &links {
+                contexts
+                    .entry(url.clone())
+                    .or_insert_with(|| LinkContext::new(node, base));
+            }
+        }
+        let links: Vec<String> = links.into_iter().map(|(_, url)| url).collect();
+        self.record_depth(&links, depth + 1);
+        links
+    }
+
+    /// How the page at `url` was linked to, if it was queued by a page type with `link_context`.
+    pub fn link_context(&self, url: &str) -> Option<LinkContext> {
+        self.contexts
+            .lock()
+            .ok()
+            .and_then(|contexts| contexts.get(url).cloned())
+    }
+
+    /// Records the depth of links queued by other means than page types.
+    pub fn record_depth(&self, links: &[String], depth: usize) {
+        if let Ok(mut depths) = self.depths.lock() {
+            for link in links {
+                depths.entry(link.clone()).or_insert(depth);
+            }
+        }
+    }
+}
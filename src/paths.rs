@@ -0,0 +1,197 @@
+//! Path handling that holds up on Windows: drive letters, backslashes, UNC
+//! shares and the `\\?\` paths `fs::canonicalize` returns there, as well as
+//! paths that aren't valid UTF-8.
+
+use crate::error::{Error, Result};
+use std::ffi::OsString;
+use std::fs;
+use std::path::{Component, Path, PathBuf, Prefix};
+
+/// Longest path Windows accepts without the `\\?\` prefix.
+const MAX_PATH: usize = 260;
+
+/// `relative` resolved against the directory `base`, e.g. an `extends` or a
+/// preset file against the directory of the config naming it.
+///
+/// Absolute paths are returned as they are. Otherwise `.` and `..` are
+/// resolved here rather than left to the OS, since `\\?\` paths take them
+/// literally, and either separator works on Windows.
+pub fn resolve(base: &Path, relative: &Path) -> PathBuf {
+    if relative.is_absolute() || relative.has_root() {
+        return base.join(relative);
+    }
+    let mut resolved = base.to_path_buf();
+    for component in relative.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => match resolved.components().next_back() {
+                Some(Component::Normal(_)) => {
+                    resolved.pop();
+                }
+                // `..` can't leave the root, and stacks onto a relative base's own `..`
+                Some(Component::RootDir | Component::Prefix(_)) => {}
+                _ => resolved.push(component),
+            },
+            component => resolved.push(component),
+        }
+    }
+    resolved
+}
+
+/// The absolute, symlink-free form of `path`, without the `\\?\` prefix
+/// Windows adds where the path works without it.
+pub fn canonicalize(path: &Path) -> Result<PathBuf> {
+    let canonical =
+        fs::canonicalize(path).map_err(|e| Error::Config(format!("{}: {}", path.display(), e)))?;
+    Ok(simplify(canonical))
+}
+
+/// `\\?\C:\dir` as `C:\dir` and `\\?\UNC\server\share` as `\\server\share`.
+fn simplify(path: PathBuf) -> PathBuf {
+    if path.as_os_str().len() >= MAX_PATH {
+        return path;
+    }
+    let mut components = path.components();
+    let simple: OsString = match components.next() {
+        Some(Component::Prefix(prefix)) => match prefix.kind() {
+            Prefix::VerbatimDisk(drive) => format!("{}:", drive as char).into(),
+            Prefix::VerbatimUNC(server, share) => {
+                let mut unc = OsString::from(r"\\");
+                unc.push(server);
+                unc.push(r"\");
+                unc.push(share);
+                unc
+            }
+            _ => return path,
+        },
+        _ => return path,
+    };
+    let mut simple = PathBuf::from(simple);
+    for component in components {
+        simple.push(component);
+    }
+    simple
+}
+
+/// The extension of `path` in lowercase, so `Spider.YAML` reads as YAML.
+pub fn extension(path: &Path) -> Option<String> {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .map(str::to_ascii_lowercase)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resolves_dots_against_the_base() {
+        assert_eq!(
+            resolve(Path::new("/etc/crawler"), Path::new("./presets/../shared/a.yaml")),
+            PathBuf::from("/etc/crawler/shared/a.yaml")
+        );
+        assert_eq!(
+            resolve(Path::new("/etc/crawler"), Path::new("../a.yaml")),
+            PathBuf::from("/etc/a.yaml")
+        );
+    }
+
+    #[test]
+    fn parent_dirs_stop_at_the_root() {
+        assert_eq!(resolve(Path::new("/"), Path::new("../../a.yaml")), PathBuf::from("/a.yaml"));
+        assert_eq!(resolve(Path::new("/etc"), Path::new("../../../a.yaml")), PathBuf::from("/a.yaml"));
+    }
+
+    #[test]
+    fn parent_dirs_stack_onto_a_relative_base() {
+        assert_eq!(resolve(Path::new(".."), Path::new("../a.yaml")), PathBuf::from("../../a.yaml"));
+        assert_eq!(resolve(Path::new(""), Path::new("../a.yaml")), PathBuf::from("../a.yaml"));
+    }
+
+    #[test]
+    fn keeps_absolute_paths() {
+        assert_eq!(resolve(Path::new("/etc/crawler"), Path::new("/srv/a.yaml")), PathBuf::from("/srv/a.yaml"));
+    }
+
+    #[test]
+    fn lowercases_extensions() {
+        assert_eq!(extension(Path::new("configs/Spider.YAML")).as_deref(), Some("yaml"));
+        assert_eq!(extension(Path::new("configs/spider")), None);
+    }
+
+    #[test]
+    fn canonicalizes_existing_paths_only() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("spider.yaml");
+        fs::write(&file, "name: x").unwrap();
+        let canonical = canonicalize(&dir.path().join(".").join("spider.yaml")).unwrap();
+        assert!(canonical.is_absolute());
+        assert_eq!(canonical, canonicalize(&file).unwrap());
+        assert!(!canonical.to_string_lossy().starts_with(r"\\?\"));
+        assert!(matches!(canonicalize(&dir.path().join("missing.yaml")), Err(Error::Config(_))));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn handles_non_utf8_names() {
+        use std::ffi::OsStr;
+        use std::os::unix::ffi::OsStrExt;
+
+        let name = Path::new(OsStr::from_bytes(b"spi\xffder.yaml"));
+        let resolved = resolve(Path::new("/etc/crawler"), &Path::new("..").join(name));
+        assert_eq!(resolved, Path::new("/etc").join(name));
+        assert_eq!(extension(name).as_deref(), Some("yaml"));
+        assert_eq!(extension(Path::new(OsStr::from_bytes(b"spider.\xff"))), None);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn leaves_unix_paths_alone() {
+        assert_eq!(simplify(PathBuf::from("/etc/crawler")), PathBuf::from("/etc/crawler"));
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn resolves_windows_paths() {
+        assert_eq!(
+            resolve(Path::new(r"C:\configs\spiders"), Path::new(r"..\presets/a.yaml")),
+            PathBuf::from(r"C:\configs\presets\a.yaml")
+        );
+        assert_eq!(
+            resolve(Path::new(r"C:\configs"), Path::new(r"..\..\..\a.yaml")),
+            PathBuf::from(r"C:\a.yaml")
+        );
+        assert_eq!(resolve(Path::new(r"C:\configs"), Path::new(r"D:\a.yaml")), PathBuf::from(r"D:\a.yaml"));
+        assert_eq!(
+            resolve(Path::new(r"\\server\share\configs"), Path::new(r"..\..\a.yaml")),
+            PathBuf::from(r"\\server\share\a.yaml")
+        );
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn simplifies_verbatim_paths() {
+        assert_eq!(simplify(PathBuf::from(r"\\?\C:\configs\a.yaml")), PathBuf::from(r"C:\configs\a.yaml"));
+        assert_eq!(
+            simplify(PathBuf::from(r"\\?\UNC\server\share\a.yaml")),
+            PathBuf::from(r"\\server\share\a.yaml")
+        );
+        assert_eq!(simplify(PathBuf::from(r"C:\configs")), PathBuf::from(r"C:\configs"));
+        let long = PathBuf::from(format!(r"\\?\C:\{}", "a".repeat(MAX_PATH)));
+        assert_eq!(simplify(long.clone()), long);
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn handles_non_utf16_names() {
+        use std::os::windows::ffi::OsStringExt;
+
+        // An unpaired surrogate
+        let mut name = OsString::from_wide(&[0x73, 0xD800]);
+        name.push(".yaml");
+        let name = Path::new(&name);
+        let resolved = resolve(Path::new(r"C:\configs"), &Path::new("..").join(name));
+        assert_eq!(resolved, Path::new(r"C:\").join(name));
+        assert_eq!(extension(name).as_deref(), Some("yaml"));
+    }
+}
@@ -0,0 +1,53 @@
+//! Text extraction for PDF responses.
+
+use crate::config::schema::PdfConfig;
+use crate::error::{Error, Result};
+use crate::extract::{Extractor, PageResponse};
+use serde_json::{json, Value};
+
+/// Extracts the text of PDF bodies and applies the `pdf` rules to it.
+#[derive(Debug, Clone)]
+pub struct PdfExtractor {
+    extractor: Extractor,
+    include_text: bool,
+}
+
+impl PdfExtractor {
+    pub fn new(config: &PdfConfig) -> Self {
+        Self {
+            extractor: Extractor::new(config.root_selector.clone(), config.rules.clone()),
+            include_text: config.include_text,
+        }
+    }
+
+    /// True for `application/pdf` responses, or bodies that start like a PDF.
+    pub fn is_pdf(content_type: Option<&str>, bytes: &[u8]) -> bool {
+        content_type.is_some_and(|ct| ct.trim_start().starts_with("application/pdf"))
+            || bytes.starts_with(b"%PDF-")
+    }
+
+    /// Returns the items found in the PDF at `url`.
+    ///
+    /// With `include_text`, the full text is added as `_text`, producing one item
+    /// even when no rule matched.
+    pub fn extract(&self, bytes: &[u8], url: &str, response: Option<&PageResponse>) -> Result<Vec<Value>> {
+        let text = pdf_extract::extract_text_from_mem(bytes).map_err(|e| Error::Extraction {
+            url: url.to_string(),
+            message: format!("PDF text extraction failed: {}", e),
+        })?;
+        log::debug!("Extracted {} characters of text from PDF {}", text.len(), url);
+
+        let mut items = self.extractor.extract_text(text.clone(), url, response);
+        if self.include_text {
+            if items.is_empty() {
+                items.push(json!({}));
+            }
+            for item in items.iter_mut() {
+                if let Value::Object(map) = item {
+                    map.insert("_text".to_string(), json!(text));
+                }
+            }
+        }
+        Ok(items)
+    }
+}
@@ -0,0 +1,95 @@
+//! Per-host request rates shared across engines. A [`RateLimiter`] behind an
+//! `Arc` can be handed to several [`CrawlerEngine`](crate::CrawlerEngine)s, or
+//! awaited before any other request, so crawls running side by side in one
+//! process together stay under one rate per host instead of each having its
+//! own.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Duration;
+use tokio::time::{Instant, sleep_until};
+use url::Url;
+
+/// Hosts remembered before those without a request scheduled are forgotten.
+const PRUNE_AT: usize = 1024;
+
+/// Spaces out the requests to each host by at least a fixed interval.
+pub struct RateLimiter {
+    interval: Duration,
+    /// Intervals overriding `interval` for single hosts
+    overrides: HashMap<String, Duration>,
+    /// When each host may get its next request
+    next: Mutex<HashMap<String, Instant>>,
+}
+
+impl RateLimiter {
+    /// Allows one request per `interval` to each host.
+    pub fn new(interval: Duration) -> Self {
+        Self {
+            interval,
+            overrides: HashMap::new(),
+            next: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Allows `requests` requests per second to each host; zero or less means
+    /// no limit.
+    pub fn per_second(requests: f64) -> Self {
+        Self::new(interval_of(requests))
+    }
+
+    /// Spaces out the requests to `host` by `interval` instead.
+    pub fn with_host_interval(mut self, host: impl Into<String>, interval: Duration) -> Self {
+        self.overrides.insert(host.into().to_ascii_lowercase(), interval);
+        self
+    }
+
+    /// The interval between requests to `host`.
+    pub fn interval(&self, host: &str) -> Duration {
+        self.overrides
+            .get(&host.to_ascii_lowercase())
+            .copied()
+            .unwrap_or(self.interval)
+    }
+
+    /// Waits for the turn of a request to `url`'s host. URLs without a host
+    /// aren't limited.
+    pub async fn acquire(&self, url: &Url) {
+        if let Some(host) = url.host_str() {
+            self.acquire_host(host).await;
+        }
+    }
+
+    /// Waits for the turn of a request to `host`. Turns are handed out in the
+    /// order of the calls, so waiting requests don't starve each other.
+    pub async fn acquire_host(&self, host: &str) {
+        let host = host.to_ascii_lowercase();
+        let interval = self.interval(&host);
+        if interval.is_zero() {
+            return;
+        }
+        let now = Instant::now();
+        let turn = match self.next.lock() {
+            Ok(mut next) => {
+                if next.len() >= PRUNE_AT {
+                    next.retain(|_, at| *at > now);
+                }
+                let slot = next.entry(host).or_insert(now);
+                let turn = (*slot).max(now);
+                *slot = turn + interval;
+                turn
+            }
+            Err(_) => now,
+        };
+        sleep_until(turn).await;
+    }
+}
+
+/// The interval between requests at `requests` per second.
+fn interval_of(requests: f64) -> Duration {
+    if requests.is_finite() && requests > 0.0 {
+        Duration::from_secs_f64(1.0 / requests)
+    } else {
+        Duration::ZERO
+    }
+}
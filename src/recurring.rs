@@ -0,0 +1,172 @@
+//! Recurring crawls for services embedding the crate: one engine and spider
+//! run again and again on an interval, see [`CrawlerEngine::run_every`].
+
+use crate::crawler::{CrawlerEngine, CrawlerState};
+use crate::report::CrawlReport;
+use crate::run_id::new_run_id;
+use crate::spider::Spider;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{Notify, mpsc, watch};
+use tokio::task::JoinHandle;
+use tokio::time::{Instant, sleep_until};
+
+/// Reports buffered for a reader that is behind before later ones are dropped.
+const REPORT_CAPACITY: usize = 16;
+
+/// Controls a crawl started with [`CrawlerEngine::run_every`]. Dropping the
+/// handle stops it like [`RecurringCrawl::stop`].
+pub struct RecurringCrawl {
+    stop: watch::Sender<bool>,
+    trigger: Arc<Notify>,
+    reports: mpsc::Receiver<CrawlReport>,
+    task: JoinHandle<()>,
+}
+
+impl RecurringCrawl {
+    pub(crate) fn start(
+        engine: CrawlerEngine,
+        spider: Arc<dyn Spider>,
+        interval: Duration,
+        jitter: Duration,
+    ) -> Self {
+        let (stop, stop_rx) = watch::channel(false);
+        let trigger = Arc::new(Notify::new());
+        let (reports_tx, reports) = mpsc::channel(REPORT_CAPACITY);
+        let task = tokio::spawn(schedule(engine, spider, interval, jitter, stop_rx, trigger.clone(), reports_tx));
+        Self {
+            stop,
+            trigger,
+            reports,
+            task,
+        }
+    }
+
+    /// Starts the next iteration now instead of at its scheduled time. During
+    /// an iteration, the next one starts as soon as it finishes.
+    pub fn trigger_now(&self) {
+        self.trigger.notify_one();
+    }
+
+    /// Stops scheduling iterations. An iteration in progress stops gracefully,
+    /// like on Ctrl-C, and still sends its report.
+    pub fn stop(&self) {
+        self.stop.send_replace(true);
+    }
+
+    /// The report of the next finished iteration, or `None` once the crawl is
+    /// stopped and every report was received.
+    pub async fn next_report(&mut self) -> Option<CrawlReport> {
+        self.reports.recv().await
+    }
+
+    /// Whether no further iteration will run.
+    pub fn is_finished(&self) -> bool {
+        self.task.is_finished()
+    }
+}
+
+impl Drop for RecurringCrawl {
+    fn drop(&mut self) {
+        self.stop.send_replace(true);
+    }
+}
+
+async fn schedule(
+    engine: CrawlerEngine,
+    spider: Arc<dyn Spider>,
+    interval: Duration,
+    jitter: Duration,
+    mut stop: watch::Receiver<bool>,
+    trigger: Arc<Notify>,
+    reports: mpsc::Sender<CrawlReport>,
+) {
+    let mut first = true;
+    loop {
+        if !std::mem::take(&mut first) {
+            engine.reset_metrics();
+        }
+        let started = Instant::now();
+        let crawl = engine.crawl(spider.clone(), new_run_id());
+        tokio::pin!(crawl);
+        let report = tokio::select! {
+            report = &mut crawl => report,
+            _ = stop_requested(&mut stop) => {
+                engine.set_state(CrawlerState::Stopped).await;
+                (&mut crawl).await
+            }
+        };
+        // The error was logged by the engine, and would recur on every iteration
+        let Ok(report) = report else { break };
+        let interrupted = report.interrupted;
+        if let Err(mpsc::error::TrySendError::Full(report)) = reports.try_send(report) {
+            log::warn!("Dropping the report of run {}: {} reports not received", report.run_id, REPORT_CAPACITY);
+        }
+        // Ctrl-C, SIGTERM or `stop` ends the schedule along with the iteration
+        if interrupted || *stop.borrow() {
+            break;
+        }
+
+        let next = started + interval + jitter.mul_f64(rand::random::<f64>());
+        log::info!("Next run of {} in {}s", spider.name(), next.saturating_duration_since(Instant::now()).as_secs());
+        tokio::select! {
+            _ = sleep_until(next) => {}
+            _ = trigger.notified() => log::info!("Run of {} triggered", spider.name()),
+            _ = stop_requested(&mut stop) => break,
+        }
+    }
+    log::info!("Stopped running {}", spider.name());
+}
+
+/// Resolves once a stop is requested or the handle is dropped.
+async fn stop_requested(stop: &mut watch::Receiver<bool>) {
+    let _ = stop.wait_for(|stopped| *stopped).await;
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config::ConfigLoader;
+    use crate::http::{MockResponse, MockTransport};
+    use crate::metrics::collector::MetricsCollector;
+    use serde_json::{Value, json};
+    use std::collections::BTreeSet;
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn every_run_extracts_and_writes_its_items() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("items.json");
+        let config = ConfigLoader::from_json(json!({
+            "name": "recurring",
+            "start_urls": ["http://example.com/a", "http://example.com/b"],
+            "delay_ms": 0,
+            "extraction_rules": {"status": {"source": "status"}},
+            "output": {"type": "json", "path": path},
+        }))
+        .unwrap();
+        let transport = MockTransport::new()
+            .with_response("http://example.com/a", MockResponse::new(200, "<html></html>"))
+            .with_response("http://example.com/b", MockResponse::new(200, "<html></html>"));
+        let metrics = Arc::new(MetricsCollector::new());
+        let spider = ConfigLoader::create_spider(&config, None, metrics.clone())
+            .await
+            .unwrap()
+            .with_transport(Arc::new(transport));
+        let engine = ConfigLoader::create_engine(&config, metrics).await.unwrap();
+
+        let mut crawl = engine.run_every(Arc::new(spider), Duration::from_millis(10), Duration::ZERO);
+        let first = crawl.next_report().await.unwrap();
+        let second = crawl.next_report().await.unwrap();
+        crawl.stop();
+
+        assert_eq!(first.metrics.items_extracted, 2);
+        assert_eq!(second.metrics.items_extracted, 2);
+        assert_eq!(second.metrics.urls_processed, 2);
+
+        let items: Vec<Value> = serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(items.len(), 4);
+        let runs: BTreeSet<&str> = items.iter().filter_map(|item| item["_run_id"].as_str()).collect();
+        assert_eq!(runs, BTreeSet::from([first.run_id.as_str(), second.run_id.as_str()]));
+    }
+}
@@ -0,0 +1,103 @@
+//! Relevance scoring for focused crawls. Each page's text is scored against
+//! the `relevance` keywords; links on pages scoring below `min_score` aren't
+//! followed, and links on better pages are crawled first.
+
+use crate::cleanup::{normalize_words, visible_text};
+use crate::config::schema::{RelevanceConfig, RelevanceScoring};
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+/// Scores are kept in the frontier as integers, to this many decimal places.
+const PRIORITY_SCALE: f64 = 1_000_000.0;
+
+#[derive(Debug)]
+pub struct RelevanceScorer {
+    /// Lowercased, with whitespace collapsed
+    keywords: Vec<String>,
+    scoring: RelevanceScoring,
+    min_score: f64,
+    /// Scores of scraped pages, until the engine takes their link priority
+    scores: Mutex<HashMap<String, f64>>,
+}
+
+impl RelevanceScorer {
+    pub fn new(config: &RelevanceConfig) -> Self {
+        Self {
+            keywords: config
+                .keywords
+                .iter()
+                .map(|keyword| normalize_words(keyword))
+                .filter(|keyword| !keyword.is_empty())
+                .collect(),
+            scoring: config.scoring,
+            min_score: config.min_score,
+            scores: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Scores the visible text of `html`: the share of keywords it contains,
+    /// or with `tf` scoring, keyword occurrences per word.
+    pub fn score(&self, html: &str) -> f64 {
+        let text = visible_text(html);
+        if self.keywords.is_empty() || text.is_empty() {
+            return 0.0;
+        }
+        // Padded so keywords only match whole words
+        let text = format!(" {} ", text);
+        let pattern = |keyword: &str| format!(" {} ", keyword);
+        match self.scoring {
+            RelevanceScoring::Keywords => {
+                let found = self
+                    .keywords
+                    .iter()
+                    .filter(|keyword| text.contains(&pattern(keyword)))
+                    .count();
+                found as f64 / self.keywords.len() as f64
+            }
+            RelevanceScoring::Tf => {
+                let words = text.split_whitespace().count();
+                let occurrences: usize = self
+                    .keywords
+                    .iter()
+                    .map(|keyword| count_matches(&text, &pattern(keyword)))
+                    .sum();
+                occurrences as f64 / words as f64
+            }
+        }
+    }
+
+    /// Whether links on a page with `score` are followed.
+    pub fn follows(&self, score: f64) -> bool {
+        score >= self.min_score
+    }
+
+    /// Remembers the score of the page at `url` until [`RelevanceScorer::take_priority`].
+    pub fn record(&self, url: &str, score: f64) {
+        if let Ok(mut scores) = self.scores.lock() {
+            scores.insert(url.to_string(), score);
+        }
+    }
+
+    /// Frontier priority of the links found on `url`, from the page's score.
+    pub fn take_priority(&self, url: &str) -> i64 {
+        let score = self
+            .scores
+            .lock()
+            .ok()
+            .and_then(|mut scores| scores.remove(url))
+            .unwrap_or_default();
+        (score * PRIORITY_SCALE).round() as i64
+    }
+}
+
+/// Occurrences of `pattern` in `text`, where consecutive matches may share the
+/// space between them.
+fn count_matches(text: &str, pattern: &str) -> usize {
+    let mut count = 0;
+    let mut rest = text;
+    while let Some(start) = rest.find(pattern) {
+        count += 1;
+        rest = &rest[start + pattern.len() - 1..];
+    }
+    count
+}
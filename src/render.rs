@@ -0,0 +1,48 @@
+//! Rendered-DOM fallback for pages built client-side. When rules marked
+//! `render_fallback` find nothing in the static HTML, the page is fetched again
+//! through a rendering service such as Splash or Browserless, which returns
+//! the DOM after scripts ran.
+
+use crate::config::schema::RenderConfig;
+use crate::error::{Error, Result};
+use crate::http::client::client_builder;
+use reqwest::Client;
+use std::time::Duration;
+
+pub struct Renderer {
+    client: Client,
+    endpoint: String,
+}
+
+impl Renderer {
+    pub fn new(config: &RenderConfig) -> Result<Self> {
+        if !config.endpoint.contains("{url}") {
+            return Err(Error::Config(
+                "render.endpoint must contain a {url} placeholder".to_string(),
+            ));
+        }
+        let client = client_builder()
+            .timeout(Duration::from_secs(config.timeout_secs))
+            .build()
+            .map_err(|e| Error::Config(format!("Failed to build the render client: {}", e)))?;
+        Ok(Self {
+            client,
+            endpoint: config.endpoint.clone(),
+        })
+    }
+
+    /// The HTML of `url` after rendering.
+    pub async fn render(&self, url: &str) -> Result<String> {
+        let encoded: String = url::form_urlencoded::byte_serialize(url.as_bytes()).collect();
+        let endpoint = self.endpoint.replace("{url}", &encoded);
+        let response = self.client.get(&endpoint).send().await?;
+        let status = response.status();
+        if !status.is_success() {
+            return Err(Error::HttpStatus {
+                url: endpoint,
+                status: status.as_u16(),
+            });
+        }
+        Ok(response.text().await?)
+    }
+}
@@ -0,0 +1,109 @@
+use crate::error::{Error, Result};
+use crate::metrics::snapshot::MetricsSnapshot;
+use serde::Serialize;
+use std::path::Path;
+
+/// A URL that still failed after all of its attempts.
+#[derive(Debug, Clone, Serialize)]
+pub struct FailedUrl {
+    pub url: String,
+    pub attempts: u32,
+    /// HTTP status of the last attempt, if a response was received
+    pub status: Option<u16>,
+    /// Category of the final error, see [`Error::kind`]
+    pub error_kind: String,
+    pub error: String,
+}
+
+impl FailedUrl {
+    pub fn new(url: String, attempts: u32, error: &Error) -> Self {
+        Self {
+            url,
+            attempts,
+            status: error.status(),
+            error_kind: error.kind().to_string(),
+            error: error.to_string(),
+        }
+    }
+}
+
+/// Summary of a finished run, returned by [`crate::CrawlerEngine::run`] and
+/// handed to [`crate::Spider::on_finish`].
+#[derive(Debug, Clone, Serialize)]
+pub struct CrawlReport {
+    pub run_id: String,
+    pub spider: String,
+    /// True if the run was stopped (e.g. by Ctrl-C) before it ran out of URLs
+    pub interrupted: bool,
+    /// What ended the run
+    pub end_reason: EndReason,
+    /// Final metrics, with per-domain stats in `metrics.domains`
+    pub metrics: MetricsSnapshot,
+    pub failures: Vec<FailedUrl>,
+    pub durations: PhaseDurations,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EndReason {
+    /// No URLs were left
+    Finished,
+    /// `limits.max_pages` pages were fetched
+    PageLimit,
+    /// `limits.max_items` items were emitted
+    ItemLimit,
+    /// `request_budget` was used up, with `on_exhausted: stop`
+    RequestBudget,
+    /// The engine was set to `Stopped`
+    Stopped,
+    /// Ctrl-C or SIGTERM
+    Interrupted,
+}
+
+impl std::fmt::Display for EndReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            EndReason::Finished => "no URLs left",
+            EndReason::PageLimit => "page limit reached",
+            EndReason::ItemLimit => "item limit reached",
+            EndReason::RequestBudget => "request budget used up",
+            EndReason::Stopped => "stopped",
+            EndReason::Interrupted => "interrupted",
+        })
+    }
+}
+
+/// Wall-clock time spent in each phase of a run, in milliseconds.
+#[derive(Debug, Clone, Copy, Default, Serialize)]
+pub struct PhaseDurations {
+    /// Opening the frontier and `Spider::on_start`, before the first URL is seeded
+    pub setup_ms: u64,
+    /// Fetching and extracting, until the last page was scraped
+    pub crawl_ms: u64,
+    /// Writing the remaining items and closing the output after that
+    pub output_ms: u64,
+    /// Writing the event log and failure report
+    pub finish_ms: u64,
+    pub total_ms: u64,
+}
+
+/// Writes the failed URLs of a run as CSV, replacing any previous report so a
+/// stale file never outlives the run it describes.
+pub fn write_failed_urls(path: &Path, failures: &[FailedUrl]) -> Result<()> {
+    let output_error = |e: csv::Error| Error::Output {
+        sink: format!("csv {}", path.display()),
+        message: e.to_string(),
+    };
+
+    let mut writer = csv::Writer::from_path(path).map_err(output_error)?;
+    if failures.is_empty() {
+        writer
+            .write_record(["url", "attempts", "status", "error_kind", "error"])
+            .map_err(output_error)?;
+    }
+    for failure in failures {
+        writer.serialize(failure).map_err(output_error)?;
+    }
+    writer.flush()?;
+    Ok(())
+}
@@ -0,0 +1,217 @@
+//! robots.txt parsing and matching per RFC 9309, for `crawler robots`, which
+//! explains what a host's robots.txt allows a user agent to crawl.
+
+use crate::config::schema::CompressionConfig;
+use crate::error::Result;
+use crate::http::body::read_body;
+use crate::http::client::client_builder;
+use reqwest::header::USER_AGENT;
+use reqwest::redirect;
+use url::Url;
+
+/// Bytes of robots.txt parsed at most; RFC 9309 allows ignoring the rest past 500 KiB.
+const MAX_ROBOTS_BYTES: usize = 500 * 1024;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Rule {
+    pub allow: bool,
+    pub pattern: String,
+    /// Line of robots.txt the rule is on
+    pub line: usize,
+}
+
+/// Rules for the user agents listed together before them.
+#[derive(Debug, Clone, Default)]
+struct Group {
+    agents: Vec<String>,
+    rules: Vec<Rule>,
+    crawl_delay: Option<f64>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Robots {
+    groups: Vec<Group>,
+    pub sitemaps: Vec<String>,
+}
+
+/// How robots.txt applies to one user agent.
+#[derive(Debug, Clone, Default)]
+pub struct AgentRules<'a> {
+    /// User-agent lines of the groups that apply, `*` when none names the agent
+    pub matched_agents: Vec<&'a str>,
+    pub rules: Vec<&'a Rule>,
+    pub crawl_delay: Option<f64>,
+}
+
+/// Whether a path may be crawled, and the rule deciding it.
+#[derive(Debug, Clone, Copy)]
+pub struct Verdict<'a> {
+    pub allowed: bool,
+    /// `None` when no rule matched, which allows the path
+    pub rule: Option<&'a Rule>,
+}
+
+impl Robots {
+    pub fn parse(content: &str) -> Self {
+        let mut robots = Robots::default();
+        let mut group: Option<Group> = None;
+        // Consecutive user-agent lines share the rules that follow them
+        let mut collecting_agents = false;
+
+        for (index, line) in content.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or_default().trim();
+            let Some((key, value)) = line.split_once(':') else { continue };
+            let (key, value) = (key.trim().to_ascii_lowercase(), value.trim());
+            match key.as_str() {
+                "user-agent" => {
+                    if !collecting_agents {
+                        robots.groups.extend(group.take());
+                    }
+                    group.get_or_insert_with(Group::default).agents.push(value.to_string());
+                    collecting_agents = true;
+                }
+                "allow" | "disallow" => {
+                    collecting_agents = false;
+                    // An empty `Disallow:` allows everything, like no rule at all
+                    if let Some(group) = &mut group
+                        && !value.is_empty()
+                    {
+                        group.rules.push(Rule {
+                            allow: key == "allow",
+                            pattern: value.to_string(),
+                            line: index + 1,
+                        });
+                    }
+                }
+                "crawl-delay" => {
+                    collecting_agents = false;
+                    if let Some(group) = &mut group {
+                        group.crawl_delay = value.parse().ok();
+                    }
+                }
+                "sitemap" => robots.sitemaps.push(value.to_string()),
+                _ => {}
+            }
+        }
+        robots.groups.extend(group);
+        robots
+    }
+
+    /// The rules for `user_agent`: those of every group naming its product
+    /// token (e.g. `Unified-Crawler` for `Unified-Crawler/1.0`), or else of the
+    /// `*` groups.
+    pub fn for_agent(&self, user_agent: &str) -> AgentRules<'_> {
+        let token = product_token(user_agent);
+        let named = |agent: &str| !agent.is_empty() && agent != "*" && product_token(agent).eq_ignore_ascii_case(token);
+        let mut groups: Vec<&Group> = self.groups.iter().filter(|g| g.agents.iter().any(|a| named(a))).collect();
+        let wildcard = groups.is_empty();
+        if wildcard {
+            groups = self.groups.iter().filter(|g| g.agents.iter().any(|a| a == "*")).collect();
+        }
+
+        let mut rules = AgentRules::default();
+        for group in groups {
+            let agent = group
+                .agents
+                .iter()
+                .find(|a| if wildcard { a.as_str() == "*" } else { named(a) });
+            rules.matched_agents.extend(agent.map(String::as_str));
+            rules.rules.extend(&group.rules);
+            rules.crawl_delay = rules.crawl_delay.or(group.crawl_delay);
+        }
+        rules
+    }
+}
+
+impl AgentRules<'_> {
+    /// The verdict for `path` (with its query), per the longest matching rule;
+    /// an `allow` wins a tie.
+    pub fn check(&self, path: &str) -> Verdict<'_> {
+        if path == "/robots.txt" {
+            return Verdict { allowed: true, rule: None };
+        }
+        let rule = self
+            .rules
+            .iter()
+            .filter(|rule| matches(&rule.pattern, path))
+            .max_by_key(|rule| (rule.pattern.len(), rule.allow))
+            .copied();
+        Verdict {
+            allowed: rule.is_none_or(|rule| rule.allow),
+            rule,
+        }
+    }
+}
+
+/// The name part of a user agent, `Googlebot` in `Googlebot/2.1 (+http://...)`.
+fn product_token(user_agent: &str) -> &str {
+    user_agent
+        .split(|c: char| c == '/' || c.is_whitespace())
+        .next()
+        .unwrap_or_default()
+}
+
+/// Whether `pattern` matches the start of `path`; `*` matches any run of
+/// characters and a trailing `$` anchors the end.
+fn matches(pattern: &str, path: &str) -> bool {
+    let (pattern, anchored) = match pattern.strip_suffix('$') {
+        Some(pattern) => (pattern, true),
+        None => (pattern, false),
+    };
+    let mut parts = pattern.split('*');
+    let Some(first) = parts.next() else { return true };
+    let Some(mut rest) = path.strip_prefix(first) else { return false };
+    let parts: Vec<&str> = parts.collect();
+    for (i, part) in parts.iter().enumerate() {
+        let last = i == parts.len() - 1;
+        if last && anchored {
+            return rest.ends_with(part);
+        }
+        match rest.find(part) {
+            Some(at) => rest = &rest[at + part.len()..],
+            None => return false,
+        }
+    }
+    !anchored || rest.is_empty()
+}
+
+/// What fetching a host's robots.txt gave.
+#[derive(Debug)]
+pub enum RobotsFile {
+    Found(Robots),
+    /// A 4xx: crawlers may crawl everything
+    Missing(u16),
+    /// A 5xx: crawlers must assume everything is disallowed
+    Unreachable(u16),
+}
+
+/// The URL of the robots.txt governing `url`.
+pub fn robots_url(url: &Url) -> Url {
+    let mut robots = url.clone();
+    robots.set_path("/robots.txt");
+    robots.set_query(None);
+    robots.set_fragment(None);
+    robots
+}
+
+/// Fetches the robots.txt at `url` as `user_agent`, following redirects.
+pub async fn fetch(url: &Url, user_agent: &str) -> Result<RobotsFile> {
+    let client = client_builder()
+        .redirect(redirect::Policy::limited(5))
+        .build()?;
+    let res = client.get(url.clone()).header(USER_AGENT, user_agent).send().await?;
+    let status = res.status();
+    if status.is_client_error() {
+        return Ok(RobotsFile::Missing(status.as_u16()));
+    }
+    if !status.is_success() {
+        return Ok(RobotsFile::Unreachable(status.as_u16()));
+    }
+    let max_bytes = CompressionConfig::default().max_body_bytes;
+    let body = read_body(res, max_bytes, None).await?.into_text();
+    let mut end = body.len().min(MAX_ROBOTS_BYTES);
+    while !body.is_char_boundary(end) {
+        end -= 1;
+    }
+    Ok(RobotsFile::Found(Robots::parse(&body[..end])))
+}
@@ -0,0 +1,19 @@
+//! Run identifiers. ULIDs sort by creation time, so runs list in order, while
+//! staying unique across processes sharing an output or state database.
+
+const CROCKFORD: &[u8; 32] = b"0123456789ABCDEFGHJKMNPQRSTVWXYZ";
+
+/// A new ULID, e.g. `01JA8YQ3C5M0V6N7R2K9T4B1XZ`: 48 bits of Unix milliseconds
+/// followed by 80 random bits, in Crockford base32.
+pub fn new_run_id() -> String {
+    let millis = chrono::Utc::now().timestamp_millis() as u128 & ((1 << 48) - 1);
+    let random = rand::random::<u128>() & ((1 << 80) - 1);
+    let mut value = (millis << 80) | random;
+
+    let mut id = [0u8; 26];
+    for digit in id.iter_mut().rev() {
+        *digit = CROCKFORD[(value & 31) as usize];
+        value >>= 5;
+    }
+    id.iter().map(|&digit| digit as char).collect()
+}
@@ -0,0 +1,529 @@
+//! `run --report`: a post-crawl report to attach to a ticket or send to
+//! stakeholders, in HTML (with charts) or Markdown, chosen by the file's
+//! extension.
+//!
+//! It covers the overall metrics, pages, items and errors over time, a table
+//! per domain, the most common errors, the slowest URLs and the fill rate of
+//! every field.
+
+use crate::crawler::CrawlerEngine;
+use crate::error::{Error, Result};
+use crate::events::CrawlEvent;
+use crate::metrics::snapshot::{DomainMetrics, MetricsSnapshot};
+use crate::paths;
+use crate::report::FailedUrl;
+use chrono::{DateTime, Utc};
+use std::cmp::Reverse;
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::path::Path;
+use std::time::Instant;
+use tokio::sync::broadcast::error::{RecvError, TryRecvError};
+use tokio::sync::oneshot;
+use tokio::task::JoinHandle;
+
+/// URLs listed under "Slowest URLs".
+const SLOWEST_URLS: usize = 20;
+/// Error groups listed under "Top Errors".
+const TOP_ERRORS: usize = 10;
+/// Most bars in a chart over time; longer crawls get wider intervals.
+const TIMELINE_BARS: usize = 60;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReportFormat {
+    Html,
+    Markdown,
+}
+
+impl ReportFormat {
+    /// The format for `path`: `.html`/`.htm` or `.md`/`.markdown`.
+    pub fn from_path(path: &Path) -> Result<Self> {
+        match paths::extension(path).as_deref() {
+            Some("html" | "htm") => Ok(ReportFormat::Html),
+            Some("md" | "markdown") => Ok(ReportFormat::Markdown),
+            _ => Err(Error::Config(format!(
+                "{}: unsupported report format (expected .html or .md)",
+                path.display()
+            ))),
+        }
+    }
+}
+
+/// Pages, items and errors within one second of the crawl.
+#[derive(Debug, Clone, Copy, Default)]
+struct Tick {
+    pages: u64,
+    items: u64,
+    errors: u64,
+}
+
+/// What the report needs beyond the final metrics, gathered from crawl events.
+#[derive(Debug, Default)]
+struct Activity {
+    /// Slowest responses, slowest first, as (duration in ms, URL)
+    slowest: Vec<(u64, String)>,
+    /// One tick per second since the recorder started
+    timeline: Vec<Tick>,
+}
+
+impl Activity {
+    fn record(&mut self, event: CrawlEvent, second: usize) {
+        let tick: fn(&mut Tick) = match event {
+            CrawlEvent::ResponseReceived { url, duration_ms, .. } => {
+                if self.slowest.len() < SLOWEST_URLS
+                    || self.slowest.last().is_some_and(|(slowest, _)| duration_ms > *slowest)
+                {
+                    let at = self.slowest.partition_point(|(other, _)| *other >= duration_ms);
+                    self.slowest.insert(at, (duration_ms, url));
+                    self.slowest.truncate(SLOWEST_URLS);
+                }
+                |tick: &mut Tick| tick.pages += 1
+            }
+            CrawlEvent::ItemExtracted { .. } => |tick: &mut Tick| tick.items += 1,
+            CrawlEvent::Error { url: Some(_), .. } => |tick: &mut Tick| tick.errors += 1,
+            _ => return,
+        };
+        if self.timeline.len() <= second {
+            self.timeline.resize(second + 1, Tick::default());
+        }
+        tick(&mut self.timeline[second]);
+    }
+}
+
+/// Follows a crawl's events until the crawl ends, then writes the report.
+pub struct ReportRecorder {
+    started_at: DateTime<Utc>,
+    stop: oneshot::Sender<()>,
+    task: JoinHandle<Activity>,
+}
+
+impl ReportRecorder {
+    /// Starts following `engine`; call before running it.
+    pub fn start(engine: &CrawlerEngine) -> Self {
+        let mut events = engine.subscribe();
+        let (stop, mut stopped) = oneshot::channel();
+        let started = Instant::now();
+        let task = tokio::spawn(async move {
+            let mut activity = Activity::default();
+            loop {
+                tokio::select! {
+                    event = events.recv() => match event {
+                        Ok(event) => activity.record(event, started.elapsed().as_secs() as usize),
+                        Err(RecvError::Lagged(_)) => {}
+                        Err(RecvError::Closed) => break,
+                    },
+                    _ = &mut stopped => break,
+                }
+            }
+            // Events sent just before the crawl ended may still be queued
+            loop {
+                match events.try_recv() {
+                    Ok(event) => activity.record(event, started.elapsed().as_secs() as usize),
+                    Err(TryRecvError::Lagged(_)) => {}
+                    Err(_) => break,
+                }
+            }
+            activity
+        });
+        Self {
+            started_at: Utc::now(),
+            stop,
+            task,
+        }
+    }
+
+    /// Writes the report of the finished crawl to `path`.
+    pub async fn write(
+        self,
+        path: &Path,
+        spider: &str,
+        run_id: &str,
+        metrics: &MetricsSnapshot,
+        failures: &[FailedUrl],
+    ) -> Result<()> {
+        let format = ReportFormat::from_path(path)?;
+        let _ = self.stop.send(());
+        let activity = self.task.await.unwrap_or_default();
+        let report = Report {
+            spider,
+            run_id,
+            started_at: self.started_at,
+            finished_at: Utc::now(),
+            metrics,
+            failures,
+            activity,
+        };
+        let content = match format {
+            ReportFormat::Html => report.html(),
+            ReportFormat::Markdown => report.markdown(),
+        };
+        std::fs::write(path, content)?;
+        Ok(())
+    }
+}
+
+struct Report<'a> {
+    spider: &'a str,
+    run_id: &'a str,
+    started_at: DateTime<Utc>,
+    finished_at: DateTime<Utc>,
+    metrics: &'a MetricsSnapshot,
+    failures: &'a [FailedUrl],
+    activity: Activity,
+}
+
+/// Failures with the same error kind and status.
+struct ErrorGroup<'a> {
+    kind: &'a str,
+    status: Option<u16>,
+    count: usize,
+    example: &'a FailedUrl,
+}
+
+/// Per-interval totals of a timeline, at most [`TIMELINE_BARS`] of them.
+struct Timeline {
+    interval_secs: usize,
+    pages: Vec<u64>,
+    items: Vec<u64>,
+    errors: Vec<u64>,
+}
+
+impl Report<'_> {
+    fn overview(&self) -> Vec<(&'static str, String)> {
+        let m = self.metrics;
+        vec![
+            ("Pages crawled", m.urls_processed.to_string()),
+            ("Items extracted", m.items_extracted.to_string()),
+            ("Requests", m.requests_total.to_string()),
+            ("Failed requests", m.requests_failed.to_string()),
+            ("Success rate", format!("{:.1}%", m.success_rate)),
+            ("Requests per second", format!("{:.2}", m.requests_per_second)),
+            (
+                "Response time",
+                format!(
+                    "avg {}ms, p50 {}ms, p99 {}ms",
+                    m.avg_response_time_ms, m.p50_response_time_ms, m.p99_response_time_ms
+                ),
+            ),
+            ("Retries", m.retries.to_string()),
+            ("Blocked responses", m.requests_blocked.to_string()),
+            ("Data received", format!("{:.1} MB", m.bytes_received as f64 / 1_048_576.0)),
+            ("Duration", format!("{:.1}s", m.elapsed_seconds)),
+        ]
+    }
+
+    fn top_errors(&self) -> Vec<ErrorGroup<'_>> {
+        let mut groups: BTreeMap<(&str, Option<u16>), ErrorGroup<'_>> = BTreeMap::new();
+        for failure in self.failures {
+            groups
+                .entry((failure.error_kind.as_str(), failure.status))
+                .or_insert_with(|| ErrorGroup {
+                    kind: &failure.error_kind,
+                    status: failure.status,
+                    count: 0,
+                    example: failure,
+                })
+                .count += 1;
+        }
+        let mut groups: Vec<_> = groups.into_values().collect();
+        groups.sort_by_key(|group| Reverse(group.count));
+        groups.truncate(TOP_ERRORS);
+        groups
+    }
+
+    /// Domains with the most URLs done first.
+    fn domains(&self) -> Vec<(&String, &DomainMetrics)> {
+        let mut domains: Vec<_> = self.metrics.domains.iter().collect();
+        domains.sort_by_key(|(_, metrics)| Reverse(metrics.urls_done()));
+        domains
+    }
+
+    fn timeline(&self) -> Timeline {
+        let ticks = &self.activity.timeline;
+        let interval_secs = ticks.len().div_ceil(TIMELINE_BARS).max(1);
+        let mut timeline = Timeline {
+            interval_secs,
+            pages: Vec::new(),
+            items: Vec::new(),
+            errors: Vec::new(),
+        };
+        for chunk in ticks.chunks(interval_secs) {
+            timeline.pages.push(chunk.iter().map(|tick| tick.pages).sum());
+            timeline.items.push(chunk.iter().map(|tick| tick.items).sum());
+            timeline.errors.push(chunk.iter().map(|tick| tick.errors).sum());
+        }
+        timeline
+    }
+
+    fn title(&self) -> String {
+        format!("Crawl report: {}", self.spider)
+    }
+
+    fn subtitle(&self) -> String {
+        format!(
+            "Run {}, {} to {}",
+            self.run_id,
+            self.started_at.format("%Y-%m-%d %H:%M:%S UTC"),
+            self.finished_at.format("%Y-%m-%d %H:%M:%S UTC")
+        )
+    }
+
+    fn html(&self) -> String {
+        let mut out = String::new();
+        let _ = write!(
+            out,
+            "<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n<style>{}</style>\n</head>\n<body>\n",
+            escape(&self.title()),
+            STYLE
+        );
+        let _ = writeln!(out, "<h1>{}</h1>\n<p class=\"muted\">{}</p>", escape(&self.title()), escape(&self.subtitle()));
+
+        out.push_str("<h2>Overview</h2>\n<div class=\"cards\">\n");
+        for (label, value) in self.overview() {
+            let _ = writeln!(
+                out,
+                "<div class=\"card\"><div class=\"value\">{}</div><div class=\"label\">{}</div></div>",
+                escape(&value),
+                label
+            );
+        }
+        out.push_str("</div>\n");
+
+        let timeline = self.timeline();
+        if !timeline.pages.is_empty() {
+            let _ = writeln!(out, "<h2>Over Time</h2>\n<p class=\"muted\">Per {}s interval</p>", timeline.interval_secs);
+            out.push_str(&svg_chart("Pages", &timeline.pages, "#3b82f6"));
+            out.push_str(&svg_chart("Items", &timeline.items, "#10b981"));
+            if timeline.errors.iter().any(|&errors| errors > 0) {
+                out.push_str(&svg_chart("Errors", &timeline.errors, "#ef4444"));
+            }
+        }
+
+        let domains = self.domains();
+        if !domains.is_empty() {
+            out.push_str("<h2>Domains</h2>\n<table>\n<tr><th>Domain</th><th>Queued</th><th>Crawled</th><th>Failed</th><th>Items</th><th>Success</th></tr>\n");
+            for (domain, metrics) in domains {
+                let _ = writeln!(
+                    out,
+                    "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>",
+                    escape(domain),
+                    metrics.urls_queued,
+                    metrics.urls_processed,
+                    metrics.requests_failed,
+                    metrics.items_extracted,
+                    percent(metrics.urls_processed, metrics.urls_done())
+                );
+            }
+            out.push_str("</table>\n");
+        }
+
+        let errors = self.top_errors();
+        if !errors.is_empty() {
+            out.push_str("<h2>Top Errors</h2>\n<table>\n<tr><th>Kind</th><th>Status</th><th>URLs</th><th>Example</th></tr>\n");
+            for group in errors {
+                let _ = writeln!(
+                    out,
+                    "<tr><td>{}</td><td>{}</td><td>{}</td><td><a href=\"{url}\">{url}</a><br><span class=\"muted\">{}</span></td></tr>",
+                    escape(group.kind),
+                    group.status.map(|status| status.to_string()).unwrap_or_default(),
+                    group.count,
+                    escape(&group.example.error),
+                    url = escape(&group.example.url)
+                );
+            }
+            out.push_str("</table>\n");
+        }
+
+        if !self.activity.slowest.is_empty() {
+            out.push_str("<h2>Slowest URLs</h2>\n<table>\n<tr><th>Time</th><th>URL</th></tr>\n");
+            for (duration_ms, url) in &self.activity.slowest {
+                let _ = writeln!(
+                    out,
+                    "<tr><td>{}ms</td><td><a href=\"{url}\">{url}</a></td></tr>",
+                    duration_ms,
+                    url = escape(url)
+                );
+            }
+            out.push_str("</table>\n");
+        }
+
+        if !self.metrics.field_coverage.is_empty() {
+            out.push_str("<h2>Field Fill Rates</h2>\n<table>\n<tr><th>Field</th><th>Pages</th><th>Fill rate</th></tr>\n");
+            for (field, coverage) in &self.metrics.field_coverage {
+                let ratio = coverage.ratio();
+                let _ = writeln!(
+                    out,
+                    "<tr><td>{}</td><td>{} of {}</td><td><div class=\"bar\"><div style=\"width: {:.1}%; background: {}\"></div></div> {:.1}%</td></tr>",
+                    escape(field),
+                    coverage.pages_matched,
+                    coverage.pages(),
+                    ratio * 100.0,
+                    if ratio < 0.5 { "#ef4444" } else { "#10b981" },
+                    ratio * 100.0
+                );
+            }
+            out.push_str("</table>\n");
+        }
+
+        out.push_str("</body>\n</html>\n");
+        out
+    }
+
+    fn markdown(&self) -> String {
+        let mut out = String::new();
+        let _ = writeln!(out, "# {}\n\n{}\n", self.title(), self.subtitle());
+
+        out.push_str("## Overview\n\n| Metric | Value |\n|---|---|\n");
+        for (label, value) in self.overview() {
+            let _ = writeln!(out, "| {} | {} |", label, cell(&value));
+        }
+
+        let timeline = self.timeline();
+        if !timeline.pages.is_empty() {
+            let _ = writeln!(out, "\n## Over Time\n\nPer {}s interval:\n", timeline.interval_secs);
+            let _ = writeln!(out, "| Series | Trend | Peak |\n|---|---|---|");
+            let mut series = vec![("Pages", &timeline.pages), ("Items", &timeline.items)];
+            if timeline.errors.iter().any(|&errors| errors > 0) {
+                series.push(("Errors", &timeline.errors));
+            }
+            for (name, values) in series {
+                let _ = writeln!(
+                    out,
+                    "| {} | `{}` | {} |",
+                    name,
+                    sparkline(values),
+                    values.iter().max().copied().unwrap_or(0)
+                );
+            }
+        }
+
+        let domains = self.domains();
+        if !domains.is_empty() {
+            out.push_str("\n## Domains\n\n| Domain | Queued | Crawled | Failed | Items | Success |\n|---|---:|---:|---:|---:|---:|\n");
+            for (domain, metrics) in domains {
+                let _ = writeln!(
+                    out,
+                    "| {} | {} | {} | {} | {} | {} |",
+                    cell(domain),
+                    metrics.urls_queued,
+                    metrics.urls_processed,
+                    metrics.requests_failed,
+                    metrics.items_extracted,
+                    percent(metrics.urls_processed, metrics.urls_done())
+                );
+            }
+        }
+
+        let errors = self.top_errors();
+        if !errors.is_empty() {
+            out.push_str("\n## Top Errors\n\n| Kind | Status | URLs | Example |\n|---|---|---:|---|\n");
+            for group in errors {
+                let _ = writeln!(
+                    out,
+                    "| {} | {} | {} | {} — {} |",
+                    cell(group.kind),
+                    group.status.map(|status| status.to_string()).unwrap_or_default(),
+                    group.count,
+                    cell(&group.example.url),
+                    cell(&group.example.error)
+                );
+            }
+        }
+
+        if !self.activity.slowest.is_empty() {
+            out.push_str("\n## Slowest URLs\n\n| Time | URL |\n|---:|---|\n");
+            for (duration_ms, url) in &self.activity.slowest {
+                let _ = writeln!(out, "| {}ms | {} |", duration_ms, cell(url));
+            }
+        }
+
+        if !self.metrics.field_coverage.is_empty() {
+            out.push_str("\n## Field Fill Rates\n\n| Field | Pages | Fill rate |\n|---|---:|---|\n");
+            for (field, coverage) in &self.metrics.field_coverage {
+                let ratio = coverage.ratio();
+                let filled = (ratio * 10.0).round() as usize;
+                let _ = writeln!(
+                    out,
+                    "| {} | {} of {} | `{}{}` {:.1}% |",
+                    cell(field),
+                    coverage.pages_matched,
+                    coverage.pages(),
+                    "█".repeat(filled),
+                    "░".repeat(10 - filled),
+                    ratio * 100.0
+                );
+            }
+        }
+        out
+    }
+}
+
+const STYLE: &str = "body{font-family:system-ui,sans-serif;max-width:1100px;margin:2em auto;padding:0 1em;color:#1f2937}\
+h2{margin-top:2em;border-bottom:1px solid #e5e7eb;padding-bottom:.3em}\
+.muted{color:#6b7280;font-size:.9em}\
+.cards{display:flex;flex-wrap:wrap;gap:.8em}\
+.card{border:1px solid #e5e7eb;border-radius:6px;padding:.8em 1em;min-width:140px}\
+.value{font-size:1.3em;font-weight:600}.label{color:#6b7280;font-size:.85em}\
+table{border-collapse:collapse;width:100%}th,td{text-align:left;padding:.35em .6em;border-bottom:1px solid #f3f4f6;vertical-align:top}\
+td{word-break:break-all}a{color:#2563eb}\
+.bar{display:inline-block;width:160px;height:.8em;background:#f3f4f6;vertical-align:middle}.bar div{height:100%}\
+svg{display:block;margin:.5em 0 1.5em}";
+
+/// A bar chart of `values` over time as inline SVG.
+fn svg_chart(title: &str, values: &[u64], color: &str) -> String {
+    const WIDTH: f64 = 900.0;
+    const HEIGHT: f64 = 120.0;
+    let peak = values.iter().max().copied().unwrap_or(0).max(1) as f64;
+    let slot = WIDTH / values.len().max(1) as f64;
+    let mut svg = format!(
+        "<svg viewBox=\"0 0 {w} {h}\" width=\"100%\" height=\"{h}\" role=\"img\" aria-label=\"{t}\">\n<text x=\"0\" y=\"12\" font-size=\"12\" fill=\"#6b7280\">{t} (peak {p})</text>\n",
+        w = WIDTH,
+        h = HEIGHT + 20.0,
+        t = escape(title),
+        p = peak as u64
+    );
+    for (i, &value) in values.iter().enumerate() {
+        let height = value as f64 / peak * HEIGHT;
+        let _ = writeln!(
+            svg,
+            "<rect x=\"{:.1}\" y=\"{:.1}\" width=\"{:.1}\" height=\"{:.1}\" fill=\"{}\"><title>{}</title></rect>",
+            i as f64 * slot,
+            20.0 + HEIGHT - height,
+            (slot - 1.0).max(1.0),
+            height,
+            color,
+            value
+        );
+    }
+    svg.push_str("</svg>\n");
+    svg
+}
+
+/// `values` as a row of block characters, e.g. `▁▃▇█▅`.
+fn sparkline(values: &[u64]) -> String {
+    const BLOCKS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+    let peak = values.iter().max().copied().unwrap_or(0).max(1);
+    values
+        .iter()
+        .map(|&value| BLOCKS[(value * 7 / peak) as usize])
+        .collect()
+}
+
+fn percent(part: u64, whole: u64) -> String {
+    match whole {
+        0 => "-".to_string(),
+        whole => format!("{:.1}%", part as f64 / whole as f64 * 100.0),
+    }
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// `text` made safe for a Markdown table cell.
+fn cell(text: &str) -> String {
+    text.replace('|', "\\|").replace(['\n', '\r'], " ")
+}
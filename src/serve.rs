@@ -0,0 +1,482 @@
+//! `crawler serve`: a small scraping service. Crawl jobs are submitted over
+//! HTTP and run side by side, each within a concurrency quota, while a shared
+//! limit caps the requests in flight across all of them.
+//!
+//! - `POST /jobs` starts a job from `{"config": "<path>"}` or `{"spider": {...}}`
+//! - `GET /jobs` and `GET /jobs/{id}` report jobs with their metrics
+//! - `DELETE /jobs/{id}` cancels a running job
+//! - `GET /jobs/{id}/queue?next=20` reports what is left in a job's frontier
+//!
+//! Jobs read config files and write outputs on the server's machine, so with
+//! `CRAWLER_SERVE_TOKEN` set every call but `GET /healthz` must carry
+//! `Authorization: Bearer <token>`. Without a token the server only listens on
+//! loopback addresses.
+//!
+//! Built with the `grpc` feature, the same jobs can also be run over gRPC, see
+//! [`crate::grpc`].
+
+use crate::config::ConfigLoader;
+use crate::config::schema::SpiderConfig;
+use crate::crawler::{CrawlerEngine, CrawlerState, shutdown_signal};
+use crate::error::{Error, Result};
+use crate::frontier::FrontierSnapshot;
+use crate::metrics::collector::MetricsCollector;
+use crate::metrics::snapshot::MetricsSnapshot;
+use crate::notify::WebhookNotifier;
+use crate::run_id::new_run_id;
+use crate::spider::Spider;
+use crate::state::StateDb;
+use axum::Json;
+use axum::Router;
+use axum::extract::{Path, Query, Request, State};
+use axum::http::{StatusCode, header};
+use axum::middleware::Next;
+use axum::response::Response;
+use axum::routing::get;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value, json};
+use std::collections::BTreeMap;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+/// How often a shutting down server checks whether its jobs have stopped.
+const SHUTDOWN_POLL_INTERVAL: Duration = Duration::from_millis(200);
+
+/// Variable holding the token API callers must send as a bearer token.
+pub const TOKEN_VAR: &str = "CRAWLER_SERVE_TOKEN";
+
+/// Queued URLs listed by `GET /jobs/{id}/queue` unless `next` is given.
+const DEFAULT_QUEUE_NEXT: usize = 20;
+
+#[derive(Debug, Clone, Copy)]
+pub struct ServeOptions {
+    /// Jobs allowed to run at once; further submissions are refused
+    pub max_jobs: usize,
+    /// Highest concurrency a single job may use
+    pub job_concurrency: usize,
+    /// Requests in flight across all jobs
+    pub max_requests_in_flight: usize,
+    /// Where to serve the gRPC API as well
+    #[cfg(feature = "grpc")]
+    pub grpc_addr: Option<SocketAddr>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum JobStatus {
+    Running,
+    Finished,
+    Cancelled,
+}
+
+struct Job {
+    spider: String,
+    engine: Arc<CrawlerEngine>,
+    status: JobStatus,
+    concurrency: usize,
+    started_at: DateTime<Utc>,
+    finished_at: Option<DateTime<Utc>>,
+}
+
+/// A job as reported by the API.
+#[derive(Debug, Serialize)]
+pub struct JobInfo {
+    pub id: String,
+    pub spider: String,
+    pub status: JobStatus,
+    pub concurrency: usize,
+    pub started_at: String,
+    pub finished_at: Option<String>,
+    pub metrics: MetricsSnapshot,
+}
+
+impl Job {
+    fn info(&self, id: &str) -> JobInfo {
+        JobInfo {
+            id: id.to_string(),
+            spider: self.spider.clone(),
+            status: self.status,
+            concurrency: self.concurrency,
+            started_at: self.started_at.to_rfc3339(),
+            finished_at: self.finished_at.map(|at| at.to_rfc3339()),
+            metrics: self.engine.get_metrics(),
+        }
+    }
+}
+
+/// What to crawl, as in the body of `POST /jobs` or a message from a job queue.
+#[derive(Debug, Clone, Deserialize)]
+pub struct JobSpec {
+    /// Config file on the server
+    #[serde(default)]
+    pub config: Option<PathBuf>,
+    /// Config given inline
+    #[serde(default)]
+    pub spider: Option<Value>,
+    /// Top-level settings replacing those of the config, e.g. `start_urls`
+    #[serde(default)]
+    pub params: Map<String, Value>,
+    /// Overrides the config's concurrency, within the job quota
+    #[serde(default)]
+    pub concurrency: Option<usize>,
+}
+
+impl JobSpec {
+    /// Loads the job's config, with its `params` applied.
+    pub fn load(self) -> Result<SpiderConfig> {
+        match (self.config, self.spider) {
+            (Some(path), None) => ConfigLoader::load_with_params(path, &self.params),
+            (None, Some(mut spider)) => {
+                if let Value::Object(map) = &mut spider {
+                    map.extend(self.params);
+                }
+                ConfigLoader::from_json(spider)
+            }
+            _ => Err(Error::Config(
+                "A job needs either `config` (a file path) or `spider` (an inline config)".to_string(),
+            )),
+        }
+    }
+}
+
+/// Why a job couldn't be started, found or cancelled.
+#[derive(Debug)]
+pub(crate) enum JobError {
+    /// As many jobs as allowed are running or being set up
+    Busy(usize),
+    /// The job's config couldn't be loaded
+    Invalid(Error),
+    NotFound(String),
+    /// The job has finished or was cancelled
+    NotRunning(String, JobStatus),
+    /// The job list's lock was poisoned
+    Unavailable,
+}
+
+impl std::fmt::Display for JobError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            JobError::Busy(max) => write!(f, "{} jobs are running or starting already, the most allowed", max),
+            JobError::Invalid(e) => write!(f, "{}", e),
+            JobError::NotFound(id) => write!(f, "no job {}", id),
+            JobError::NotRunning(id, status) => write!(f, "job {} is {:?} already", id, status),
+            JobError::Unavailable => f.write_str("job list unavailable"),
+        }
+    }
+}
+
+/// The jobs of a server, shared by its REST and gRPC APIs.
+#[derive(Clone)]
+pub(crate) struct Server {
+    options: ServeOptions,
+    jobs: Arc<Mutex<BTreeMap<String, Job>>>,
+    /// One permit per job allowed to run, held from submission to its end
+    job_slots: Arc<Semaphore>,
+    request_slots: Arc<Semaphore>,
+    /// Bearer token every API call must carry, if set
+    token: Option<Arc<str>>,
+}
+
+type ApiError = (StatusCode, Json<Value>);
+
+fn api_error(status: StatusCode, message: impl std::fmt::Display) -> ApiError {
+    (status, Json(json!({ "error": message.to_string() })))
+}
+
+impl From<JobError> for ApiError {
+    fn from(e: JobError) -> Self {
+        let status = match e {
+            JobError::Busy(_) => StatusCode::TOO_MANY_REQUESTS,
+            JobError::Invalid(_) => StatusCode::BAD_REQUEST,
+            JobError::NotFound(_) => StatusCode::NOT_FOUND,
+            JobError::NotRunning(..) => StatusCode::CONFLICT,
+            JobError::Unavailable => StatusCode::INTERNAL_SERVER_ERROR,
+        };
+        api_error(status, e)
+    }
+}
+
+impl Server {
+    /// Whether an `Authorization` header value lets the caller in.
+    pub(crate) fn authorized(&self, authorization: Option<&str>) -> bool {
+        let Some(token) = &self.token else {
+            return true;
+        };
+        let Some(given) = authorization.and_then(|value| value.strip_prefix("Bearer ")) else {
+            return false;
+        };
+        // Compared in full whatever the first difference, so the time taken
+        // doesn't give the token away
+        given.len() == token.len()
+            && given.bytes().zip(token.bytes()).fold(0, |diff, (a, b)| diff | (a ^ b)) == 0
+    }
+
+    /// Jobs whose run hasn't ended yet, including cancelled ones still
+    /// finishing their requests and output.
+    fn unfinished_jobs(&self) -> usize {
+        self.jobs
+            .lock()
+            .map(|jobs| jobs.values().filter(|job| job.finished_at.is_none()).count())
+            .unwrap_or(0)
+    }
+
+    /// Starts a job from `spec` unless as many as allowed are running.
+    pub(crate) async fn submit(&self, spec: JobSpec) -> std::result::Result<JobInfo, JobError> {
+        // Taken before the job is set up, so concurrent submissions can't
+        // all pass the check
+        let slot = self
+            .job_slots
+            .clone()
+            .try_acquire_owned()
+            .map_err(|_| JobError::Busy(self.options.max_jobs))?;
+        self.start(spec, slot).await.map_err(JobError::Invalid)
+    }
+
+    /// The job `id` as reported by the API.
+    pub(crate) fn job(&self, id: &str) -> std::result::Result<JobInfo, JobError> {
+        let jobs = self.jobs.lock().map_err(|_| JobError::Unavailable)?;
+        match jobs.get(id) {
+            Some(job) => Ok(job.info(id)),
+            None => Err(JobError::NotFound(id.to_string())),
+        }
+    }
+
+    /// The engine of the job `id`, if it is still running.
+    #[cfg(feature = "grpc")]
+    pub(crate) fn running_engine(&self, id: &str) -> std::result::Result<Arc<CrawlerEngine>, JobError> {
+        let jobs = self.jobs.lock().map_err(|_| JobError::Unavailable)?;
+        match jobs.get(id) {
+            Some(job) if job.status == JobStatus::Running => Ok(job.engine.clone()),
+            Some(job) => Err(JobError::NotRunning(id.to_string(), job.status)),
+            None => Err(JobError::NotFound(id.to_string())),
+        }
+    }
+
+    /// Stops the job `id` if it is running.
+    pub(crate) async fn cancel(&self, id: &str) -> std::result::Result<JobInfo, JobError> {
+        let engine = {
+            let mut jobs = self.jobs.lock().map_err(|_| JobError::Unavailable)?;
+            let Some(job) = jobs.get_mut(id) else {
+                return Err(JobError::NotFound(id.to_string()));
+            };
+            if job.status != JobStatus::Running {
+                return Err(JobError::NotRunning(id.to_string(), job.status));
+            }
+            job.status = JobStatus::Cancelled;
+            job.engine.clone()
+        };
+        log::info!("Cancelling job {}", id);
+        engine.set_state(CrawlerState::Stopped).await;
+        self.job(id)
+    }
+
+    async fn start(&self, spec: JobSpec, slot: OwnedSemaphorePermit) -> Result<JobInfo> {
+        let id = new_run_id();
+        let concurrency = spec.concurrency;
+        let mut config = spec.load()?;
+        if let Some(output) = &mut config.output {
+            output.expand_run_id(&id);
+        }
+        config.concurrency = concurrency
+            .unwrap_or(config.concurrency)
+            .clamp(1, self.options.job_concurrency.max(1));
+
+        let (engine, spider) = build_job(&config, &id, Some(self.request_slots.clone())).await?;
+        let engine = Arc::new(engine);
+        let job = Job {
+            spider: config.name.clone(),
+            engine: engine.clone(),
+            status: JobStatus::Running,
+            concurrency: config.concurrency,
+            started_at: Utc::now(),
+            finished_at: None,
+        };
+        let info = job.info(&id);
+        if let Ok(mut jobs) = self.jobs.lock() {
+            jobs.insert(id.clone(), job);
+        }
+        log::info!("Started job {} ({}) with concurrency {}", id, config.name, config.concurrency);
+
+        let jobs = self.jobs.clone();
+        tokio::spawn(async move {
+            let _slot = slot;
+            let end_reason = engine.run(spider).await.map(|report| report.end_reason);
+            if let Ok(mut jobs) = jobs.lock()
+                && let Some(job) = jobs.get_mut(&id)
+            {
+                if job.status == JobStatus::Running {
+                    job.status = JobStatus::Finished;
+                }
+                job.finished_at = Some(Utc::now());
+                match end_reason {
+                    Ok(reason) => log::info!("Job {} ({}) ended: {:?}, {}", id, job.spider, job.status, reason),
+                    Err(e) => log::error!("Job {} ({}) failed: {}", id, job.spider, e),
+                }
+            }
+        });
+        Ok(info)
+    }
+}
+
+/// The engine and spider of a job, set up like `crawler run` does, with `id`
+/// as the run ID.
+pub async fn build_job(
+    config: &SpiderConfig,
+    id: &str,
+    request_slots: Option<Arc<Semaphore>>,
+) -> Result<(CrawlerEngine, Arc<dyn Spider>)> {
+    let metrics = Arc::new(MetricsCollector::new());
+    let mut spider = ConfigLoader::create_spider(config, None, metrics.clone()).await?;
+    let mut engine = ConfigLoader::create_engine(config, metrics).await?.with_run_id(id);
+    if let Some(slots) = request_slots {
+        engine = engine.with_request_slots(slots);
+    }
+    if let Some(incremental) = &config.incremental {
+        let state = StateDb::open(incremental).await?;
+        spider = spider.with_state(state.clone());
+        engine = engine.with_state(state, true);
+    }
+    if let Some(notify) = &config.notify {
+        engine = engine.with_notifier(WebhookNotifier::new(notify)?);
+    }
+    Ok((engine, Arc::new(spider)))
+}
+
+/// Serves the jobs API on `addr` until Ctrl-C or SIGTERM, then waits for the
+/// running jobs, which stop on the same signal.
+pub async fn serve(addr: SocketAddr, options: ServeOptions) -> Result<()> {
+    let token = std::env::var(TOKEN_VAR).ok().filter(|token| !token.is_empty());
+    if token.is_none() {
+        #[cfg(feature = "grpc")]
+        let grpc_addr = options.grpc_addr;
+        #[cfg(not(feature = "grpc"))]
+        let grpc_addr: Option<SocketAddr> = None;
+        if let Some(exposed) = std::iter::once(addr).chain(grpc_addr).find(|addr| !addr.ip().is_loopback()) {
+            return Err(Error::Config(format!(
+                "Refusing to serve jobs on {} without a token: set {}, or listen on 127.0.0.1",
+                exposed, TOKEN_VAR
+            )));
+        }
+    }
+    let server = Server {
+        options,
+        jobs: Arc::new(Mutex::new(BTreeMap::new())),
+        job_slots: Arc::new(Semaphore::new(options.max_jobs)),
+        request_slots: Arc::new(Semaphore::new(options.max_requests_in_flight.max(1))),
+        token: token.map(Arc::from),
+    };
+    let app = Router::new()
+        .route("/jobs", get(list_jobs).post(submit_job))
+        .route("/jobs/{id}", get(get_job).delete(cancel_job))
+        .route("/jobs/{id}/queue", get(get_queue))
+        .route_layer(axum::middleware::from_fn_with_state(server.clone(), require_token))
+        .route("/healthz", get(|| async { "ok\n" }))
+        .with_state(server.clone());
+    let listener = tokio::net::TcpListener::bind(addr).await?;
+    log::info!(
+        "Serving crawl jobs on http://{} (up to {} jobs, {} requests in flight)",
+        listener.local_addr()?,
+        options.max_jobs,
+        options.max_requests_in_flight
+    );
+    #[cfg(feature = "grpc")]
+    let grpc = match options.grpc_addr {
+        Some(grpc_addr) => {
+            let listener = tokio::net::TcpListener::bind(grpc_addr).await?;
+            log::info!("Serving the gRPC API on {}", listener.local_addr()?);
+            Some(tokio::spawn(crate::grpc::serve(listener, server.clone())))
+        }
+        None => None,
+    };
+    axum::serve(listener, app)
+        .with_graceful_shutdown(async {
+            let signal = shutdown_signal().await;
+            log::info!("Received {}, no longer taking jobs", signal);
+        })
+        .await?;
+    #[cfg(feature = "grpc")]
+    if let Some(grpc) = grpc {
+        grpc.await
+            .map_err(|e| Error::Internal(format!("The gRPC server panicked: {}", e)))??;
+    }
+
+    let mut unfinished = server.unfinished_jobs();
+    if unfinished > 0 {
+        log::info!("Waiting for {} jobs to stop", unfinished);
+    }
+    while unfinished > 0 {
+        tokio::time::sleep(SHUTDOWN_POLL_INTERVAL).await;
+        unfinished = server.unfinished_jobs();
+    }
+    Ok(())
+}
+
+async fn require_token(
+    State(server): State<Server>,
+    request: Request,
+    next: Next,
+) -> std::result::Result<Response, ApiError> {
+    let authorization = request.headers().get(header::AUTHORIZATION).and_then(|value| value.to_str().ok());
+    if !server.authorized(authorization) {
+        return Err(api_error(StatusCode::UNAUTHORIZED, "missing or wrong bearer token"));
+    }
+    Ok(next.run(request).await)
+}
+
+async fn list_jobs(State(server): State<Server>) -> Json<Vec<JobInfo>> {
+    let jobs = match server.jobs.lock() {
+        Ok(jobs) => jobs.iter().map(|(id, job)| job.info(id)).collect(),
+        Err(_) => Vec::new(),
+    };
+    Json(jobs)
+}
+
+async fn submit_job(
+    State(server): State<Server>,
+    Json(spec): Json<JobSpec>,
+) -> std::result::Result<(StatusCode, Json<JobInfo>), ApiError> {
+    let info = server.submit(spec).await?;
+    Ok((StatusCode::CREATED, Json(info)))
+}
+
+async fn get_job(
+    State(server): State<Server>,
+    Path(id): Path<String>,
+) -> std::result::Result<Json<JobInfo>, ApiError> {
+    Ok(Json(server.job(&id)?))
+}
+
+#[derive(Debug, Deserialize)]
+struct QueueParams {
+    next: Option<usize>,
+}
+
+async fn get_queue(
+    State(server): State<Server>,
+    Path(id): Path<String>,
+    Query(params): Query<QueueParams>,
+) -> std::result::Result<Json<FrontierSnapshot>, ApiError> {
+    let engine = {
+        let jobs = server.jobs.lock().map_err(|_| api_error(StatusCode::INTERNAL_SERVER_ERROR, "job list unavailable"))?;
+        match jobs.get(&id) {
+            Some(job) => job.engine.clone(),
+            None => return Err(api_error(StatusCode::NOT_FOUND, format!("no job {}", id))),
+        }
+    };
+    match engine.frontier_snapshot(params.next.unwrap_or(DEFAULT_QUEUE_NEXT)).await {
+        Ok(Some(snapshot)) => Ok(Json(snapshot)),
+        Ok(None) => Err(api_error(StatusCode::CONFLICT, format!("job {} has no frontier yet", id))),
+        Err(e) => Err(api_error(StatusCode::INTERNAL_SERVER_ERROR, e)),
+    }
+}
+
+async fn cancel_job(
+    State(server): State<Server>,
+    Path(id): Path<String>,
+) -> std::result::Result<Json<JobInfo>, ApiError> {
+    Ok(Json(server.cancel(&id).await?))
+}
@@ -0,0 +1,59 @@
+use crate::error::{Error, Result};
+use std::fmt;
+use std::str::FromStr;
+
+/// A deterministic slice of the URL space, written as `index/total` (1-based).
+///
+/// Independent processes given `1/8` … `8/8` of the same config crawl disjoint
+/// sets of URLs without coordinating through a shared queue.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Shard {
+    pub index: u64,
+    pub total: u64,
+}
+
+impl Shard {
+    pub fn new(index: u64, total: u64) -> Result<Self> {
+        if total == 0 || index == 0 || index > total {
+            return Err(Error::Config(format!(
+                "Invalid shard {}/{}: expected 1 <= index <= total",
+                index, total
+            )));
+        }
+        Ok(Self { index, total })
+    }
+
+    /// Returns true if `url` belongs to this shard.
+    pub fn owns(&self, url: &str) -> bool {
+        fnv1a(url.as_bytes()) % self.total == self.index - 1
+    }
+}
+
+impl FromStr for Shard {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let (index, total) = s
+            .split_once('/')
+            .ok_or_else(|| Error::Config(format!("Invalid shard '{}': expected N/M", s)))?;
+        let parse = |v: &str| {
+            v.trim()
+                .parse::<u64>()
+                .map_err(|e| Error::Config(format!("Invalid shard '{}': {}", s, e)))
+        };
+        Shard::new(parse(index)?, parse(total)?)
+    }
+}
+
+impl fmt::Display for Shard {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.index, self.total)
+    }
+}
+
+/// FNV-1a, chosen over `DefaultHasher` because it is stable across builds and platforms.
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, b| {
+        (hash ^ u64::from(*b)).wrapping_mul(0x0000_0100_0000_01b3)
+    })
+}
@@ -1,49 +1,359 @@
 use crate::error::{Error, Result};
+use crate::http::bandwidth::BandwidthLimiter;
+use crate::http::body::read_body;
+use crate::http::block::BlockDetector;
+use crate::http::fingerprint::{Fingerprinter, wildcard_match};
+use crate::http::transport::{HttpTransport, Transport};
+use crate::metrics::collector::MetricsCollector;
 use crate::output::OutputHandler;
+use crate::extract::{Extractor, PageResponse};
+use crate::external_links::ExternalLinks;
+use crate::relevance::RelevanceScorer;
+use crate::item_filter::ItemFilter;
+use crate::item_id::ItemId;
+use crate::near_duplicates::NearDuplicates;
+use crate::language::LanguageFilter;
+use crate::page_types::PageTypes;
+use crate::rate_limit::RateLimiter;
+use crate::pdf::PdfExtractor;
+use crate::render::Renderer;
+use crate::report::CrawlReport;
+use crate::state::{Freshness, StateDb};
+use crate::transform::{number_value, parse_number, Transform};
+use crate::visited::VisitedSet;
 use async_trait::async_trait;
-use chadselect::ChadSelect;
-use reqwest::Client;
+use reqwest::{Method, Request, Response, StatusCode, header};
+use select::document::Document;
+use select::predicate::{Attr, Name, Predicate};
 use serde::{Deserialize, Serialize};
 use serde_json::{json, Value};
 use std::collections::HashMap;
 use std::sync::Arc;
-use std::time::Duration;
-use tokio::sync::Mutex;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::panic::AssertUnwindSafe;
+use std::time::{Duration, Instant};
+use tokio::sync::{Mutex, OwnedSemaphorePermit, Semaphore};
+use url::Url;
 
-use crate::config::schema::SelectorConfig;
+use crate::cleanup::clean_html;
+use crate::config::schema::{
+    CompressionConfig, DedupConfig, HeadCheckConfig, HtmlCleanupConfig, NearDuplicateAction, RedirectConfig,
+    SelectorConfig,
+};
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 #[serde(rename_all = "lowercase")]
 pub enum ExtractionType {
+    #[default]
     Text,
     Attribute(String),
     Html,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// Where a rule takes its value from.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RuleSource {
+    /// The page's content, through `selector`
+    #[default]
+    Html,
+    /// The response header called `name`
+    Header,
+    /// The final status code, after redirects
+    Status,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct ExtractionRule {
+    /// Required unless the rule is an `if` condition or reads the response
+    #[serde(default)]
     pub selector: SelectorConfig,
+
+    /// `header` or `status` to read the response instead of the page
+    #[serde(default)]
+    pub source: RuleSource,
+
+    /// Header read by `source: header`, e.g. `Last-Modified`
+    #[serde(default)]
+    pub name: Option<String>,
+
+    #[serde(default)]
     pub extract: ExtractionType,
+
+    /// Which match to keep when the selector matches several elements
+    #[serde(default)]
+    pub index: Option<RuleIndex>,
+
+    /// Selectors tried in order when `selector` yields nothing
+    #[serde(default)]
+    pub fallback_selectors: Vec<SelectorConfig>,
+
+    /// Extract from the rendered page when no selector matches the static HTML
+    #[serde(default)]
+    pub render_fallback: bool,
+
+    /// Concatenate all matches with this separator instead of keeping one
+    #[serde(default)]
+    pub join: Option<String>,
+
+    /// Reduce all matches to a single number
+    #[serde(default)]
+    pub aggregate: Option<Aggregate>,
+
+    /// Convert the extracted text into a typed value
+    #[serde(default)]
+    pub transform: Option<Transform>,
+
+    /// Locale of the page, e.g. `de-DE`, for number and date parsing
+    #[serde(default)]
+    pub locale: Option<String>,
+
+    /// Emit one of two values depending on whether the selector matches at all
+    #[serde(default)]
+    pub exists_as: Option<ExistsAs>,
+
+    /// Derive the field from another field's value instead of a selector
+    #[serde(default, rename = "if")]
+    pub condition: Option<Condition>,
+
+    /// Value when `if` holds
+    #[serde(default)]
+    pub then: Option<Value>,
+
+    /// Value when `if` doesn't hold; the field is left out if unset
+    #[serde(default, rename = "else")]
+    pub otherwise: Option<Value>,
+
+    /// Store the HTML of the matched element under `_evidence`, for CSS selectors
+    #[serde(default)]
+    pub keep_evidence: bool,
+}
+
+impl ExtractionRule {
+    /// Whether the rule applies selectors to the page, i.e. is neither an `if`
+    /// condition nor reads the response.
+    pub fn reads_page(&self) -> bool {
+        self.condition.is_none() && self.source == RuleSource::Html
+    }
+
+    /// The primary selector followed by its fallbacks, in the order they are tried.
+    pub fn selectors(&self) -> impl Iterator<Item = &SelectorConfig> {
+        std::iter::once(&self.selector).chain(&self.fallback_selectors)
+    }
+
+    /// The value emitted for extracted `text`; `null` if `transform` can't parse it.
+    pub fn typed_value(&self, text: &str) -> Value {
+        match &self.transform {
+            Some(transform) => transform
+                .apply(text, self.locale.as_deref())
+                .unwrap_or(Value::Null),
+            None => json!(text),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExistsAs {
+    #[serde(rename = "true")]
+    pub when_present: Value,
+    #[serde(rename = "false")]
+    pub when_absent: Value,
+}
+
+/// A test on another field of the same item.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Condition {
+    pub field: String,
+
+    /// Compared as text; without it the field only needs a non-empty value
+    #[serde(default)]
+    pub equals: Option<Value>,
+}
+
+impl Condition {
+    pub fn holds(&self, item: &serde_json::Map<String, Value>) -> bool {
+        let Some(value) = item.get(&self.field) else {
+            return false;
+        };
+        match &self.equals {
+            Some(expected) => as_text(value) == as_text(expected),
+            None => !matches!(value, Value::Null | Value::Bool(false)) && !as_text(value).is_empty(),
+        }
+    }
+}
+
+fn as_text(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        other => other.to_string(),
+    }
+}
+
+/// Position of the match a rule keeps: `first`, `last`, or a 0-based index.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum RuleIndex {
+    Named(NamedIndex),
+    Nth(usize),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum NamedIndex {
+    First,
+    Last,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Aggregate {
+    Count,
+    Min,
+    Max,
+    Sum,
+}
+
+impl Aggregate {
+    /// Reduces `matches` to a number, or `None` if there is nothing to reduce.
+    ///
+    /// `min`, `max` and `sum` read the first number in each match, so `"$1,299.00"`
+    /// counts as `1299`; matches without a number are skipped. See
+    /// [`parse_number`] for how `locale` is used.
+    pub fn apply(&self, matches: &[String], locale: Option<&str>) -> Option<Value> {
+        if matches.is_empty() {
+            return None;
+        }
+        if *self == Aggregate::Count {
+            return Some(json!(matches.len()));
+        }
+
+        let numbers = matches.iter().filter_map(|m| parse_number(m, locale));
+        let result = match self {
+            Aggregate::Min => numbers.reduce(f64::min)?,
+            Aggregate::Max => numbers.reduce(f64::max)?,
+            Aggregate::Sum => numbers.reduce(|a, b| a + b)?,
+            Aggregate::Count => unreachable!(),
+        };
+        Some(number_value(result))
+    }
+}
+
+impl RuleIndex {
+    pub fn pick<'a>(&self, matches: &'a [String]) -> Option<&'a String> {
+        match self {
+            RuleIndex::Named(NamedIndex::First) => matches.first(),
+            RuleIndex::Named(NamedIndex::Last) => matches.last(),
+            RuleIndex::Nth(n) => matches.get(*n),
+        }
+    }
 }
 
 #[async_trait]
 pub trait Spider: Send + Sync {
     fn name(&self) -> String;
     fn start_urls(&self) -> Vec<String>;
+
+    /// Key the engine uses to skip URLs it already queued. Defaults to the URL itself.
+    fn fingerprint(&self, url: &str) -> String {
+        url.to_string()
+    }
+
     async fn scrape(&self, url: String) -> Result<(Vec<Value>, Vec<String>)>;
     async fn process(&self, item: Value) -> Result<()>;
+
+    /// Priority of the links found by the last scrape of `url`, asked once they
+    /// are queued; links with a higher one are crawled first. Defaults to 0.
+    fn link_priority(&self, _url: &str) -> i64 {
+        0
+    }
+
+    /// Processes several items at once; by default one by one.
+    async fn process_batch(&self, items: Vec<Value>) -> Result<()> {
+        for item in items {
+            self.process(item).await?;
+        }
+        Ok(())
+    }
+
+    /// How many batches the engine may hand to [`Spider::process_batch`] at once.
+    fn output_concurrency(&self) -> usize {
+        1
+    }
+
+    /// Flushes what the output buffered so far.
+    async fn flush(&self) -> Result<()> {
+        Ok(())
+    }
+
     async fn close(&self) -> Result<()> {
         Ok(())
     }
+
+    /// Called by the engine before the first request, e.g. to fetch an auth token.
+    async fn on_start(&self) {}
+
+    /// Hands the spider the engine's [`RateLimiter`], for the requests it sends
+    /// beyond the one the engine waited for, such as redirect hops.
+    fn use_rate_limiter(&self, _limiter: Arc<RateLimiter>) {}
+
+    /// Called by the engine once the run is over and the output was closed,
+    /// e.g. to send a summary to a webhook.
+    async fn on_finish(&self, _report: &CrawlReport) {}
 }
 
 pub struct GenericSpider {
     pub name: String,
     pub start_urls: Vec<String>,
-    pub client: Client,
-    pub root_selector: Option<SelectorConfig>,
-    pub extraction_rules: HashMap<String, ExtractionRule>,
+    pub transport: Arc<dyn Transport>,
+    pub extractor: Arc<Extractor>,
+    /// Extracts page-level fields for the per-page item, if enabled
+    pub page_extractor: Option<Arc<Extractor>>,
+    /// Per-URL rule sets and link following; `extractor` handles pages matching none
+    pub page_types: Option<Arc<PageTypes>>,
     pub output_handler: Arc<Mutex<Box<dyn OutputHandler>>>,
+    /// Further instances of the output, written to in parallel with `output_handler`
+    pub output_pool: Vec<Arc<Mutex<Box<dyn OutputHandler>>>>,
+    pub redirects: RedirectConfig,
+    /// Checked with a HEAD request before each GET; page types can override it
+    pub head_check: Option<HeadCheckConfig>,
+    /// Recognizes block and captcha pages, which then fail like error statuses
+    pub block_detection: Option<BlockDetector>,
+    /// Decides which requests count as duplicates
+    pub fingerprinter: Fingerprinter,
+    pub compression: CompressionConfig,
+    /// Caps how fast response bodies are read
+    pub bandwidth: Option<Arc<BandwidthLimiter>>,
+    pub max_streams_per_origin: Option<usize>,
+    pub html_cleanup: Option<HtmlCleanupConfig>,
+    pub language: Option<LanguageFilter>,
+    pub pdf: Option<Arc<PdfExtractor>>,
+    /// Fetches rendered pages for rules with `render_fallback`
+    pub renderer: Option<Renderer>,
+    pub metrics: Arc<MetricsCollector>,
+    /// Records fetched pages and their freshness for incremental crawls
+    pub state: Option<StateDb>,
+    /// How long a page's extraction may run before the page fails
+    pub extraction_timeout: Duration,
+    /// Drops junk items right after extraction
+    pub item_filter: Option<ItemFilter>,
+    /// Gives items their `_id`, by which repeated items are dropped
+    pub item_id: Option<ItemId>,
+    /// Recognizes pages nearly the same as an earlier one
+    pub near_duplicates: Option<Arc<NearDuplicates>>,
+    /// Records links to other sites, written out when the spider closes
+    pub external_links: Option<Arc<ExternalLinks>>,
+    /// Scores pages for a focused crawl
+    pub relevance: Option<Arc<RelevanceScorer>>,
+    seen_pages: std::sync::Mutex<VisitedSet>,
+    seen_items: std::sync::Mutex<VisitedSet>,
+    /// Output instance to wait for when all of them are busy
+    next_output: AtomicUsize,
+    /// Whether the outputs were closed by the end of a run
+    closed: AtomicBool,
+    origin_limits: std::sync::Mutex<HashMap<String, Arc<Semaphore>>>,
+    parser_slots: Arc<Semaphore>,
+    /// Shared with the engine, for redirect hops
+    rate_limiter: std::sync::Mutex<Option<Arc<RateLimiter>>>,
 }
 
 impl GenericSpider {
@@ -54,54 +364,375 @@ impl GenericSpider {
         extraction_rules: HashMap<String, ExtractionRule>,
         output_handler: Box<dyn OutputHandler>,
     ) -> Self {
-        let client = Client::builder()
-            .timeout(Duration::from_secs(10))
-            .user_agent("Unified-Crawler/1.0")
+        let client = crate::http::client::client_builder()
             .build()
             .expect("Building HTTP client");
 
         Self {
             name,
             start_urls,
-            client,
-            root_selector,
-            extraction_rules,
+            transport: Arc::new(HttpTransport::new(client)),
+            extractor: Arc::new(Extractor::new(root_selector, extraction_rules)),
+            page_extractor: None,
+            page_types: None,
             output_handler: Arc::new(Mutex::new(output_handler)),
+            output_pool: Vec::new(),
+            redirects: RedirectConfig::default(),
+            head_check: None,
+            block_detection: None,
+            fingerprinter: Fingerprinter::default(),
+            compression: CompressionConfig::default(),
+            bandwidth: None,
+            max_streams_per_origin: None,
+            html_cleanup: None,
+            language: None,
+            pdf: None,
+            renderer: None,
+            metrics: Arc::new(MetricsCollector::new()),
+            state: None,
+            extraction_timeout: DEFAULT_EXTRACTION_TIMEOUT,
+            item_filter: None,
+            item_id: None,
+            near_duplicates: None,
+            external_links: None,
+            relevance: None,
+            seen_pages: std::sync::Mutex::new(VisitedSet::default()),
+            seen_items: std::sync::Mutex::new(VisitedSet::default()),
+            next_output: AtomicUsize::new(0),
+            closed: AtomicBool::new(false),
+            origin_limits: std::sync::Mutex::new(HashMap::new()),
+            parser_slots: Arc::new(Semaphore::new(default_parser_threads())),
+            rate_limiter: std::sync::Mutex::new(None),
+        }
+    }
+
+    /// Sends requests through `transport` instead of the default HTTP client.
+    pub fn with_transport(mut self, transport: Arc<dyn Transport>) -> Self {
+        self.transport = transport;
+        self
+    }
+
+    /// Sends extracted items to `handler` instead of the one given to [`GenericSpider::new`].
+    pub fn with_output(mut self, handler: Box<dyn OutputHandler>) -> Self {
+        self.output_handler = Arc::new(Mutex::new(handler));
+        self
+    }
+
+    /// Adds further instances of the output, so that batches are written to
+    /// several of them at once. Only for handlers that are [`OutputHandler::pool_safe`].
+    pub fn with_output_pool(mut self, handlers: Vec<Box<dyn OutputHandler>>) -> Self {
+        self.output_pool = handlers
+            .into_iter()
+            .map(|handler| Arc::new(Mutex::new(handler)))
+            .collect();
+        self
+    }
+
+    fn outputs(&self) -> impl Iterator<Item = &Arc<Mutex<Box<dyn OutputHandler>>>> {
+        std::iter::once(&self.output_handler).chain(&self.output_pool)
+    }
+
+    /// An output instance to write to, preferring an idle one.
+    async fn output(&self) -> tokio::sync::MutexGuard<'_, Box<dyn OutputHandler>> {
+        if let Some(handler) = self.outputs().find_map(|handler| handler.try_lock().ok()) {
+            return handler;
+        }
+        let next = self.next_output.fetch_add(1, Ordering::Relaxed) % (1 + self.output_pool.len());
+        match next {
+            0 => self.output_handler.lock().await,
+            i => self.output_pool[i - 1].lock().await,
         }
     }
 
-    fn extract_data(&self, cs: &ChadSelect, doc_index: i32) -> Result<Value> {
-        let mut item = serde_json::Map::new();
-        let mut found_data = false;
+    /// Fingerprints requests and remembers seen pages as configured in `dedup`.
+    pub fn with_dedup(mut self, dedup: &DedupConfig) -> Self {
+        self.fingerprinter = Fingerprinter::new(dedup);
+        self.seen_pages = std::sync::Mutex::new(VisitedSet::new(dedup));
+        self.seen_items = std::sync::Mutex::new(VisitedSet::new(dedup));
+        self.near_duplicates = dedup
+            .near_duplicates
+            .as_ref()
+            .map(|config| Arc::new(NearDuplicates::new(config)));
+        self
+    }
+
+    /// Caps how many pages are parsed at once on the blocking pool.
+    pub fn with_parser_threads(mut self, threads: usize) -> Self {
+        self.parser_slots = Arc::new(Semaphore::new(threads.max(1)));
+        self
+    }
+
+    /// Shares the engine's metrics collector so spider-level counters show up in snapshots.
+    pub fn with_metrics(mut self, metrics: Arc<MetricsCollector>) -> Self {
+        self.metrics = metrics;
+        self
+    }
+
+    /// Records every fetched page and its freshness headers in `state`.
+    pub fn with_state(mut self, state: StateDb) -> Self {
+        self.state = Some(state);
+        self
+    }
+
+    /// Sends a request, following redirects up to `max_redirects` hops.
+    /// Returns the final response along with every URL visited before it.
+    async fn fetch(&self, method: Method, url: &str) -> Result<(Response, Vec<String>)> {
+        let mut current = Url::parse(url)
+            .map_err(|e| Error::InvalidUrl {
+                url: url.to_string(),
+                message: e.to_string(),
+            })?;
+        let mut chain = Vec::new();
 
-        for (field_name, rule) in &self.extraction_rules {
-            // ChadSelect select returns a String
-            // We might need to handle different extraction types if ChadSelect supports them directly,
-            // but for now we follow the rule.extract.
-            
-            // NOTE: ChadSelect's select(index, query) might need the prefix (css:, xpath:, regex:)
-            // We assume rule.selector already has it or we could add a default.
-            let raw_selector = rule.selector.to_query_string();
-            let query = if raw_selector.contains(':') {
-                raw_selector
-            } else {
-                format!("css:{}", raw_selector)
+        loop {
+            let res = self
+                .transport
+                .execute(Request::new(method.clone(), current.clone()))
+                .await?;
+            if !res.status().is_redirection() {
+                return Ok((res, chain));
+            }
+
+            let Some(location) = res
+                .headers()
+                .get(header::LOCATION)
+                .and_then(|v| v.to_str().ok())
+            else {
+                return Ok((res, chain));
             };
 
-            let val = cs.select(doc_index, &query);
-            
-            if !val.is_empty() {
-                item.insert(field_name.clone(), json!(val));
-                found_data = true;
+            if chain.len() >= self.redirects.max_redirects {
+                return Err(Error::Redirect {
+                    url: url.to_string(),
+                    message: format!("too many redirects ({})", chain.len()),
+                });
+            }
+
+            let next = current
+                .join(location)
+                .map_err(|e| Error::Redirect {
+                    url: current.to_string(),
+                    message: format!("invalid target {}: {}", location, e),
+                })?;
+            log::debug!("Redirect {} -> {} ({})", current, next, res.status());
+            chain.push(current.to_string());
+            current = next;
+            let limiter = self.rate_limiter.lock().ok().and_then(|limiter| limiter.clone());
+            if let Some(limiter) = limiter {
+                limiter.acquire(&current).await;
             }
         }
+    }
 
-        if found_data {
-            Ok(Value::Object(item))
-        } else {
-            Err(Error::Extraction("No data found for item".to_string()))
+    /// Sends a HEAD request for `url` and returns why it shouldn't be fetched, if
+    /// its type or size rule it out. Error statuses fail the page right away.
+    async fn head_check(&self, url: &str, check: &HeadCheckConfig) -> Result<Option<String>> {
+        let (res, _) = self.fetch(Method::HEAD, url).await?;
+        let status = res.status();
+        // Servers that don't implement HEAD get the GET anyway
+        if status == StatusCode::METHOD_NOT_ALLOWED || status == StatusCode::NOT_IMPLEMENTED {
+            return Ok(None);
+        }
+        if !status.is_success() {
+            return Err(Error::HttpStatus {
+                url: res.url().to_string(),
+                status: status.as_u16(),
+            });
+        }
+
+        let content_type = res
+            .headers()
+            .get(header::CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.split(';').next())
+            .map(|v| v.trim().to_string());
+        if let Some(content_type) = content_type
+            && !check.content_types.is_empty()
+            && !check.content_types.iter().any(|pattern| wildcard_match(pattern, &content_type))
+        {
+            return Ok(Some(format!("content type {}", content_type)));
+        }
+        let length = res
+            .headers()
+            .get(header::CONTENT_LENGTH)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.trim().parse::<u64>().ok());
+        if let (Some(length), Some(max)) = (length, check.max_bytes)
+            && length > max
+        {
+            return Ok(Some(format!("{} bytes", length)));
+        }
+        Ok(None)
+    }
+
+    /// Returns the stream limiter for the origin of `url`, if per-origin limits are enabled.
+    fn origin_limit(&self, url: &str) -> Option<Arc<Semaphore>> {
+        let max = self.max_streams_per_origin?;
+        let origin = Url::parse(url).ok()?.origin().ascii_serialization();
+        let mut limits = self.origin_limits.lock().ok()?;
+        Some(
+            limits
+                .entry(origin)
+                .or_insert_with(|| Arc::new(Semaphore::new(max.max(1))))
+                .clone(),
+        )
+    }
+
+    /// Returns the absolute `<link rel="canonical">` URL of a page, if declared.
+    ///
+    /// Only the `<head>` is parsed, so large bodies aren't duplicated into a second DOM.
+    fn canonical_url(html: &str, base: &Url) -> Option<String> {
+        let head_end = html
+            .as_bytes()
+            .windows(7)
+            .position(|w| w.eq_ignore_ascii_case(b"</head>"))
+            .unwrap_or(html.len());
+        let document = Document::from(&html[..head_end]);
+        let href = document
+            .find(Name("link").and(Attr("rel", "canonical")))
+            .find_map(|node| node.attr("href").map(str::to_string))?;
+        base.join(href.trim()).ok().map(|u| u.to_string())
+    }
+
+    /// Items extracted from the rendered version of the page at `url`, whose
+    /// rules reading the response still see the original `response`.
+    async fn render_items(
+        &self,
+        renderer: &Renderer,
+        extractor: Arc<Extractor>,
+        url: &str,
+        response: PageResponse,
+    ) -> Result<Vec<Value>> {
+        log::info!(url = url; "Rendering {}, the static HTML lacks fields", url);
+        let html = renderer.render(url).await?;
+        self.metrics.increment_pages_rendered();
+        let html = match &self.html_cleanup {
+            Some(options) => clean_html(html, options),
+            None => html,
+        };
+        let page_url = url.to_string();
+        self.run_extraction(url, move || extractor.extract_with_response(html, &page_url, &response)).await
+    }
+
+    /// Waits for a free slot on the blocking pool for parsing.
+    async fn parser_slot(&self) -> Result<OwnedSemaphorePermit> {
+        self.parser_slots
+            .clone()
+            .acquire_owned()
+            .await
+            .map_err(|e| Error::Internal(e.to_string()))
+    }
+
+    /// Runs `extract` on the blocking pool with a parser slot, isolating the
+    /// crawl from pages that make it panic or run past the extraction timeout.
+    /// A timed-out run can't be stopped and keeps its slot until it ends.
+    async fn run_extraction<T, F>(&self, url: &str, extract: F) -> Result<T>
+    where
+        T: Send + 'static,
+        F: FnOnce() -> T + Send + 'static,
+    {
+        let parse_permit = self.parser_slot().await?;
+        let parse_start = Instant::now();
+        let task = tokio::task::spawn_blocking(move || {
+            let _parse_permit = parse_permit;
+            std::panic::catch_unwind(AssertUnwindSafe(extract))
+        });
+        let message = match tokio::time::timeout(self.extraction_timeout, task).await {
+            Ok(Ok(Ok(value))) => {
+                self.metrics.record_parse_time(parse_start.elapsed());
+                return Ok(value);
+            }
+            Ok(Ok(Err(panic))) => format!("extraction panicked: {}", panic_message(&*panic)),
+            Ok(Err(e)) => format!("extraction task failed: {}", e),
+            Err(_) => format!("extraction timed out after {:?}", self.extraction_timeout),
+        };
+        log::warn!(url = url; "Extraction failed for {}: {}", url, message);
+        self.metrics.increment_extraction_errors();
+        Err(Error::Extraction {
+            url: url.to_string(),
+            message,
+        })
+    }
+
+    /// Applies the item and language filters, adds the response metadata
+    /// fields to `items` and gives them their `_id`, dropping items seen before.
+    fn finish_items(
+        &self,
+        mut items: Vec<Value>,
+        fingerprint: &str,
+        final_url: &Url,
+        chain: &[String],
+    ) -> Vec<Value> {
+        if let Some(filter) = &self.item_filter {
+            let dropped = filter.apply(&mut items);
+            if dropped > 0 {
+                log::debug!("item_filter dropped {} items from {}", dropped, final_url);
+                self.metrics.record_items_filtered(dropped);
+            }
+        }
+        if let Some(filter) = &self.language {
+            let before = items.len();
+            items.retain_mut(|item| filter.tag(item));
+            for _ in items.len()..before {
+                self.metrics.increment_items_dropped_language();
+            }
         }
+
+        for item in items.iter_mut() {
+            if let Value::Object(map) = item {
+                map.insert("_final_url".to_string(), json!(final_url.as_str()));
+                map.insert("_fingerprint".to_string(), json!(fingerprint));
+                if self.redirects.record_chain {
+                    map.insert("_redirect_chain".to_string(), json!(chain));
+                }
+            }
+        }
+
+        if let Some(item_id) = &self.item_id {
+            items.retain_mut(|item| match item_id.attach(item) {
+                Some(id) if !self.mark_item_seen(&id) => {
+                    self.metrics.increment_duplicate_items();
+                    false
+                }
+                _ => true,
+            });
+        }
+        items
     }
+
+    /// Records an item's `_id`, returning `false` if an earlier item had it.
+    fn mark_item_seen(&self, id: &str) -> bool {
+        self.seen_items
+            .lock()
+            .map(|mut seen| seen.insert(id))
+            .unwrap_or(true)
+    }
+
+    /// Records the page under its dedup key, returning `false` if it was already seen.
+    fn mark_seen(&self, key: String) -> bool {
+        self.seen_pages
+            .lock()
+            .map(|mut seen| seen.insert(&key))
+            .unwrap_or(true)
+    }
+}
+
+/// Extraction time after which a page is given up on, unless configured.
+const DEFAULT_EXTRACTION_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// The message a panic was raised with, if it was a string.
+fn panic_message(panic: &(dyn std::any::Any + Send)) -> &str {
+    panic
+        .downcast_ref::<&str>()
+        .copied()
+        .or_else(|| panic.downcast_ref::<String>().map(String::as_str))
+        .unwrap_or("unknown cause")
+}
+
+fn default_parser_threads() -> usize {
+    std::thread::available_parallelism()
+        .map(|n| n.get())
+        .unwrap_or(4)
 }
 
 #[async_trait]
@@ -114,87 +745,381 @@ impl Spider for GenericSpider {
         self.start_urls.clone()
     }
 
+    fn fingerprint(&self, url: &str) -> String {
+        self.fingerprinter.fingerprint_url(url)
+    }
+
     async fn scrape(&self, url: String) -> Result<(Vec<Value>, Vec<String>)> {
-        log::info!("Visiting: {}", url);
+        log::info!(url = url.as_str(); "Visiting: {}", url);
+        let fingerprint = self.fingerprinter.fingerprint_url(&url);
+
+        // Held until the body is read, since the stream stays busy until then
+        let stream_permit = match self.origin_limit(&url) {
+            Some(limit) => Some(
+                limit
+                    .acquire_owned()
+                    .await
+                    .map_err(|e| Error::Internal(e.to_string()))?,
+            ),
+            None => None,
+        };
+
+        let head_check = self
+            .page_types
+            .as_ref()
+            .and_then(|types| types.find(&url, types.depth(&url)))
+            .and_then(|page_type| page_type.head_check.as_ref())
+            .or(self.head_check.as_ref());
+        if let Some(check) = head_check
+            && let Some(reason) = self.head_check(&url, check).await?
+        {
+            log::info!(url = url.as_str(); "Skipping {} after HEAD: {}", url, reason);
+            self.metrics.increment_head_skipped();
+            return Ok((vec![], vec![]));
+        }
+
+        let fetch_start = Instant::now();
+        let (res, chain) = self.fetch(Method::GET, &url).await?;
+        if res.version() == reqwest::Version::HTTP_2 {
+            self.metrics.increment_http2_responses();
+        }
+        self.metrics.record_redirects(chain.len());
+        let final_url = res.url().clone();
+        if !chain.is_empty() {
+            log::info!(
+                url = url.as_str(), final_url = final_url.as_str(), hops = chain.len();
+                "Redirected: {} -> {} ({} hops)", url, final_url, chain.len()
+            );
+        }
 
-        let res = self.client.get(&url).send().await?;
         let status = res.status();
+        if let Some(reason) = self
+            .block_detection
+            .as_ref()
+            .and_then(|detector| detector.check_response(status, res.headers()))
+        {
+            self.metrics.increment_requests_blocked();
+            return Err(Error::Blocked {
+                url: final_url.to_string(),
+                reason,
+            });
+        }
         if !status.is_success() {
-            return Err(Error::Internal(format!("HTTP error: {}", status)));
+            return Err(Error::HttpStatus {
+                url: final_url.to_string(),
+                status: status.as_u16(),
+            });
+        }
+        let freshness = self
+            .state
+            .as_ref()
+            .map(|_| Freshness::from_headers(status.as_u16(), res.headers(), chrono::Utc::now()));
+        let response = PageResponse {
+            status: status.as_u16(),
+            headers: res.headers().clone(),
+        };
+
+        let body = match read_body(res, self.compression.max_body_bytes, self.bandwidth.as_deref()).await {
+            Ok(body) => body,
+            Err(e @ Error::BodyTooLarge { .. }) => {
+                self.metrics.increment_oversized_bodies();
+                return Err(e);
+            }
+            Err(e) => return Err(e),
+        };
+        self.metrics.record_body(body.wire_bytes, body.bytes.len());
+        // Checked before the page is recorded, so a block page isn't taken as a fresh copy
+        if let Some(detector) = &self.block_detection
+            && !PdfExtractor::is_pdf(body.content_type.as_deref(), &body.bytes)
+            && let Some(reason) = detector.check_body(&String::from_utf8_lossy(&body.bytes))
+        {
+            self.metrics.increment_requests_blocked();
+            return Err(Error::Blocked {
+                url: final_url.to_string(),
+                reason,
+            });
+        }
+        if let (Some(state), Some(freshness)) = (&self.state, &freshness)
+            && let Err(e) = state.record(&url, freshness).await
+        {
+            log::warn!("Failed to record {} in the state database: {}", url, e);
+        }
+        let load_time = fetch_start.elapsed();
+        self.metrics.record_fetch_time(load_time);
+        drop(stream_permit);
+
+        // Counted until extraction finishes and the page buffer is released
+        let _in_flight = self.metrics.track_body(body.bytes.len());
+
+        if let Some(pdf) = self.pdf.clone()
+            && PdfExtractor::is_pdf(body.content_type.as_deref(), &body.bytes)
+        {
+            if !self.mark_seen(self.fingerprinter.fingerprint_url(final_url.as_str())) {
+                log::info!(url = url.as_str(); "Skipping duplicate PDF {}", url);
+                self.metrics.increment_duplicate_pages();
+                return Ok((vec![], vec![]));
+            }
+
+            let page_url = url.clone();
+            let bytes = body.bytes;
+            let items = self
+                .run_extraction(&url, move || pdf.extract(&bytes, &page_url, Some(&response)))
+                .await??;
+
+            return Ok((self.finish_items(items, &fingerprint, &final_url, &chain), vec![]));
+        }
+
+        let wire_bytes = body.wire_bytes;
+        let html = body.into_text();
+        log::debug!(
+            "HTML length: {} bytes ({} bytes on the wire)",
+            html.len(),
+            wire_bytes
+        );
+
+        let canonical = if self.redirects.honor_canonical {
+            Self::canonical_url(&html, &final_url)
+        } else {
+            None
+        };
+        let dedup_url = canonical.unwrap_or_else(|| final_url.to_string());
+        if !self.mark_seen(self.fingerprinter.fingerprint_url(&dedup_url)) {
+            log::info!(
+                url = url.as_str(), canonical = dedup_url.as_str();
+                "Skipping duplicate page {} (same as {})", url, dedup_url
+            );
+            self.metrics.increment_duplicate_pages();
+            return Ok((vec![], vec![]));
         }
         
-        let html = res.text().await?;
-        log::debug!("HTML length: {} bytes", html.len());
-        
-        let mut cs = ChadSelect::new();
-        cs.add_html(html);
-        
-        let mut items = Vec::new();
-        
-        if let Some(root_config) = &self.root_selector {
-            let root = root_config.to_query_string();
-            let root_query = if root.contains(':') {
-                root
-            } else {
-                format!("css:{}", root)
+        let depth = self.page_types.as_ref().map_or(0, |types| types.depth(&url));
+        let page_type = self.page_types.as_ref().and_then(|types| types.find(&url, depth));
+        let extractor = match page_type {
+            Some(page_type) => page_type.extractor.clone(),
+            None => Some(self.extractor.clone()),
+        };
+        let page_type_name = page_type.and_then(|page_type| page_type.name.clone());
+        if let Some(name) = &page_type_name {
+            log::debug!("{} is a '{}' page at depth {}", url, name, depth);
+        }
+        // Links are taken from the page before cleanup, which may remove navigation
+        let follow = page_type
+            .filter(|page_type| page_type.follow)
+            .and_then(|page_type| Some((self.page_types.clone()?, page_type.link_context)))
+            .map(|(types, link_context)| (types, html.clone(), final_url.clone(), link_context));
+        let harvest = self
+            .external_links
+            .clone()
+            .map(|external| (external, html.clone(), final_url.clone()));
+
+        let html = match &self.html_cleanup {
+            Some(options) => {
+                let original_len = html.len();
+                let cleaned = clean_html(html, options);
+                log::debug!("Cleanup reduced HTML from {} to {} bytes", original_len, cleaned.len());
+                cleaned
+            }
+            None => html,
+        };
+
+        // `follow_only` pages don't get a page item either
+        let page_extractor = self.page_extractor.clone().filter(|_| extractor.is_some());
+        let page_url = url.clone();
+        let field_names: Vec<String> = extractor
+            .iter()
+            .flat_map(|extractor| extractor.extraction_rules.keys().cloned())
+            .collect();
+        let render_extractor = extractor.clone().filter(|_| self.renderer.is_some());
+        let relevance = self.relevance.clone();
+        let compare = self.near_duplicates.is_some();
+        let page_response = response.clone();
+        let (mut items, page_fields, mut links, external, score, signature) = self.run_extraction(&url, move || {
+            let links = follow
+                .map(|(types, html, base, link_context)| types.links_to_follow(&html, &base, depth, link_context))
+                .unwrap_or_default();
+            let external = harvest.map(|(external, html, base)| external.harvest(&html, &base, &page_url));
+            let score = relevance.map(|relevance| relevance.score(&html));
+            let signature = compare.then(|| NearDuplicates::signature(&html)).flatten();
+            let page_fields =
+                page_extractor.map(|page| page.extract_with_response(html.clone(), &page_url, &page_response).pop());
+            let items = extractor
+                .map(|extractor| extractor.extract_with_response(html, &page_url, &page_response))
+                .unwrap_or_default();
+            (items, page_fields, links, external, score, signature)
+        })
+        .await?;
+
+        let near_duplicate_of = match (&self.near_duplicates, signature) {
+            (Some(near_duplicates), Some(signature)) => near_duplicates.check(&url, &signature),
+            _ => None,
+        };
+        if let (Some(near_duplicates), Some((original, similarity))) = (&self.near_duplicates, &near_duplicate_of) {
+            self.metrics.increment_near_duplicate_pages();
+            if near_duplicates.action == NearDuplicateAction::Skip {
+                log::info!(
+                    url = url.as_str(), original = original.as_str();
+                    "Skipping near-duplicate page {} ({:.0}% like {})", url, similarity * 100.0, original
+                );
+                return Ok((vec![], vec![]));
+            }
+        }
+
+        if let Some((external, new)) = external {
+            self.metrics.record_external_links(new);
+            if self.external_links.as_ref().is_some_and(|links| links.follow) {
+                let external: Vec<String> = external.into_iter().filter(|link| !links.contains(link)).collect();
+                if let Some(types) = &self.page_types {
+                    types.record_depth(&external, depth + 1);
+                }
+                links.extend(external);
+            }
+        }
+
+        if let (Some(relevance), Some(score)) = (&self.relevance, score) {
+            if !relevance.follows(score) && !self.start_urls.contains(&url) {
+                log::debug!("Not following {} links from {}, which scored {:.4}", links.len(), url, score);
+                self.metrics.increment_irrelevant_pages();
+                links.clear();
+            }
+            relevance.record(&url, score);
+            for item in items.iter_mut() {
+                if let Value::Object(map) = item {
+                    map.insert("_relevance".to_string(), json!(score));
+                }
+            }
+        }
+
+        if let (Some(renderer), Some(extractor)) = (&self.renderer, render_extractor)
+            && extractor.needs_render(&items)
+        {
+            match self.render_items(renderer, extractor, final_url.as_str(), response).await {
+                Ok(rendered) if !rendered.is_empty() => items = rendered,
+                Ok(_) => log::debug!("The rendered page of {} has no items either", url),
+                Err(e) => log::warn!("Failed to render {}: {}", url, e),
+            }
+        }
+
+        if !links.is_empty() {
+            log::debug!("Following {} links from {}", links.len(), url);
+        }
+
+        self.metrics.record_field_coverage(field_names.iter().map(|field| {
+            let matched = items.iter().any(|item| {
+                item.get(field)
+                    .is_some_and(|value| !value.is_null() && value.as_str() != Some(""))
+            });
+            (field.as_str(), matched)
+        }));
+
+        if let Some(fields) = page_fields {
+            for item in items.iter_mut() {
+                if let Value::Object(map) = item {
+                    map.insert("_type".to_string(), json!("element"));
+                }
+            }
+            let mut page = match fields {
+                Some(Value::Object(map)) => map,
+                _ => serde_json::Map::new(),
             };
-            
-            log::debug!("Processing with root selector: {}", root_query);
-            
-            let roots = cs.query(-1, &root_query);
-            log::debug!("Root selector '{}' found {} matches", root_query, roots.len());
-
-            let mut field_results = HashMap::new();
-            let mut max_len = 0;
-
-            for (field_name, rule) in &self.extraction_rules {
-                let raw_rule = rule.selector.to_query_string();
-                let rule_selector = raw_rule.split_once(':').map(|s| s.1).unwrap_or(&raw_rule);
-                
-                // Try combined selector first: root + space + rule
-                let full_query = format!("{} {}", root_query, rule_selector);
-                let results = cs.query(-1, &full_query);
-                
-                log::debug!("Field '{}' with query '{}' found {} results", field_name, full_query, results.len());
-                
-                max_len = max_len.max(results.len());
-                field_results.insert(field_name.clone(), results);
-            }
-            
-            if max_len == 0 && !roots.is_empty() {
-                log::warn!("Found {} roots but 0 items. Checking if rules should be absolute...", roots.len());
-            }
-            
-            log::info!("Extracted {} items from {}", max_len, url);
-
-            for i in 0..max_len {
-                let mut item = serde_json::Map::new();
-                for (field_name, results) in &field_results {
-                    if let Some(val) = results.get(i) {
-                        item.insert(field_name.clone(), json!(val));
-                    }
+            page.insert("_type".to_string(), json!("page"));
+            page.insert("_status".to_string(), json!(status.as_u16()));
+            page.insert("_load_time_ms".to_string(), json!(load_time.as_millis() as u64));
+            items.insert(0, Value::Object(page));
+        }
+
+        if let Some((original, _)) = near_duplicate_of {
+            for item in items.iter_mut() {
+                if let Value::Object(map) = item {
+                    map.insert("_near_duplicate_of".to_string(), json!(original));
                 }
-                if !item.is_empty() {
-                    items.push(Value::Object(item));
+            }
+        }
+
+        if let Some(name) = page_type_name {
+            for item in items.iter_mut() {
+                if let Value::Object(map) = item {
+                    map.insert("_page_type".to_string(), json!(name));
                 }
             }
-        } else {
-            // Single item mode
-            if let Ok(item) = self.extract_data(&cs, 0) {
-                items.push(item);
+        }
+
+        if let Some(context) = self.page_types.as_ref().and_then(|types| types.link_context(&url)) {
+            let context = json!(context);
+            for item in items.iter_mut() {
+                if let Value::Object(map) = item {
+                    map.insert("_link".to_string(), context.clone());
+                }
             }
         }
 
-        Ok((items, vec![]))
+        Ok((self.finish_items(items, &fingerprint, &final_url, &chain), links))
     }
 
     async fn process(&self, item: Value) -> Result<()> {
-        let mut handler = self.output_handler.lock().await;
-        handler.write(item).await
+        self.output().await.write(item).await
+    }
+
+    async fn process_batch(&self, items: Vec<Value>) -> Result<()> {
+        self.output().await.write_batch(items).await
+    }
+
+    fn link_priority(&self, url: &str) -> i64 {
+        self.relevance
+            .as_ref()
+            .map_or(0, |relevance| relevance.take_priority(url))
+    }
+
+    fn output_concurrency(&self) -> usize {
+        1 + self.output_pool.len()
+    }
+
+    async fn flush(&self) -> Result<()> {
+        let mut result = Ok(());
+        for handler in self.outputs() {
+            let flushed = handler.lock().await.flush().await;
+            result = result.and(flushed);
+        }
+        result
+    }
+
+    fn use_rate_limiter(&self, limiter: Arc<RateLimiter>) {
+        if let Ok(mut current) = self.rate_limiter.lock() {
+            *current = Some(limiter);
+        }
+    }
+
+    /// After an earlier run on the same spider, forgets the pages and items
+    /// it saw and reopens the outputs it closed, so a recurring crawl starts
+    /// every run afresh.
+    async fn on_start(&self) {
+        if !self.closed.swap(false, Ordering::SeqCst) {
+            return;
+        }
+        for seen in [&self.seen_pages, &self.seen_items] {
+            if let Ok(mut seen) = seen.lock() {
+                seen.clear();
+            }
+        }
+        if let Some(near_duplicates) = &self.near_duplicates {
+            near_duplicates.clear();
+        }
+        for handler in self.outputs() {
+            if let Err(e) = handler.lock().await.reopen().await {
+                log::error!("Failed to reopen the output of {}: {}", self.name, e);
+            }
+        }
     }
 
     async fn close(&self) -> Result<()> {
-        let mut handler = self.output_handler.lock().await;
-        handler.close().await
+        self.closed.store(true, Ordering::SeqCst);
+        let mut result = Ok(());
+        for handler in self.outputs() {
+            let closed = handler.lock().await.close().await;
+            result = result.and(closed);
+        }
+        if let Some(external) = &self.external_links {
+            result = result.and(external.write());
+        }
+        result
     }
 }
@@ -0,0 +1,324 @@
+//! The state database of incremental crawls. It records when each page was
+//! fetched along with its `Cache-Control` and `Expires` headers, so a later run
+//! can skip pages whose copy is still fresh, a summary of every run, and the
+//! requests counted against each spider's request budget.
+
+use crate::config::schema::IncrementalConfig;
+use crate::error::{Error, Result};
+use crate::metrics::snapshot::MetricsSnapshot;
+use crate::report::CrawlReport;
+use chrono::{DateTime, Utc};
+use reqwest::header::{AGE, CACHE_CONTROL, DATE, EXPIRES, HeaderMap};
+use sqlx::sqlite::{
+    SqliteConnectOptions, SqliteJournalMode, SqlitePool, SqlitePoolOptions, SqliteSynchronous,
+};
+use std::sync::{Arc, Mutex};
+
+/// An open state database. Clones share the connection and the current run.
+#[derive(Debug, Clone)]
+pub struct StateDb {
+    pool: SqlitePool,
+    path: String,
+    /// Run that pages are recorded for, set by [`StateDb::start_run`]
+    run_id: Arc<Mutex<Option<String>>>,
+}
+
+impl StateDb {
+    pub async fn open(config: &IncrementalConfig) -> Result<Self> {
+        let error = |e: sqlx::Error| Error::State {
+            path: config.state_db.clone(),
+            message: e.to_string(),
+        };
+        let options = SqliteConnectOptions::new()
+            .filename(&config.state_db)
+            .create_if_missing(true)
+            .journal_mode(SqliteJournalMode::Wal)
+            .synchronous(SqliteSynchronous::Normal);
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect_with(options)
+            .await
+            .map_err(error)?;
+
+        for statement in [
+            "CREATE TABLE IF NOT EXISTS pages (
+                url TEXT PRIMARY KEY,
+                status INTEGER NOT NULL,
+                fetched_at TEXT NOT NULL,
+                cache_control TEXT,
+                expires TEXT,
+                fresh_until INTEGER,
+                run_id TEXT
+            )",
+            "CREATE TABLE IF NOT EXISTS runs (
+                run_id TEXT PRIMARY KEY,
+                spider TEXT NOT NULL,
+                started_at TEXT NOT NULL,
+                finished_at TEXT,
+                interrupted INTEGER,
+                metrics TEXT
+            )",
+            "CREATE TABLE IF NOT EXISTS request_budget (
+                spider TEXT NOT NULL,
+                window_start TEXT NOT NULL,
+                requests INTEGER NOT NULL,
+                PRIMARY KEY (spider, window_start)
+            )",
+        ] {
+            sqlx::query(statement).execute(&pool).await.map_err(error)?;
+        }
+        // Files written before runs were recorded lack the column
+        let has_run_id: i64 =
+            sqlx::query_scalar("SELECT COUNT(*) FROM pragma_table_info('pages') WHERE name = 'run_id'")
+                .fetch_one(&pool)
+                .await
+                .map_err(error)?;
+        if has_run_id == 0 {
+            sqlx::query("ALTER TABLE pages ADD COLUMN run_id TEXT")
+                .execute(&pool)
+                .await
+                .map_err(error)?;
+        }
+
+        Ok(Self {
+            pool,
+            path: config.state_db.clone(),
+            run_id: Arc::new(Mutex::new(None)),
+        })
+    }
+
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    fn error(&self, e: sqlx::Error) -> Error {
+        Error::State {
+            path: self.path.clone(),
+            message: e.to_string(),
+        }
+    }
+
+    /// Records the start of a run; pages recorded from now on belong to it.
+    pub async fn start_run(&self, run_id: &str, spider: &str) -> Result<()> {
+        if let Ok(mut current) = self.run_id.lock() {
+            *current = Some(run_id.to_string());
+        }
+        sqlx::query("INSERT OR REPLACE INTO runs (run_id, spider, started_at) VALUES (?, ?, ?)")
+            .bind(run_id)
+            .bind(spider)
+            .bind(Utc::now().to_rfc3339())
+            .execute(&self.pool)
+            .await
+            .map_err(|e| self.error(e))?;
+        Ok(())
+    }
+
+    /// Records how a run ended, with its final metrics as JSON.
+    pub async fn finish_run(&self, report: &CrawlReport) -> Result<()> {
+        let metrics = serde_json::to_string(&report.metrics)?;
+        sqlx::query("UPDATE runs SET finished_at = ?, interrupted = ?, metrics = ? WHERE run_id = ?")
+            .bind(Utc::now().to_rfc3339())
+            .bind(report.interrupted)
+            .bind(metrics)
+            .bind(&report.run_id)
+            .execute(&self.pool)
+            .await
+            .map_err(|e| self.error(e))?;
+        Ok(())
+    }
+
+    /// The spider and final metrics of a finished run, or `None` if the run is
+    /// unknown or never finished.
+    pub async fn run_metrics(&self, run_id: &str) -> Result<Option<(String, MetricsSnapshot)>> {
+        let row: Option<(String, Option<String>)> =
+            sqlx::query_as("SELECT spider, metrics FROM runs WHERE run_id = ?")
+                .bind(run_id)
+                .fetch_optional(&self.pool)
+                .await
+                .map_err(|e| self.error(e))?;
+        match row {
+            Some((spider, Some(metrics))) => Ok(Some((spider, serde_json::from_str(&metrics)?))),
+            _ => Ok(None),
+        }
+    }
+
+    /// The ID and final metrics of the last run of `spider` before `run_id` that
+    /// finished without being interrupted.
+    pub async fn previous_run(&self, spider: &str, run_id: &str) -> Result<Option<(String, MetricsSnapshot)>> {
+        let row: Option<(String, String)> = sqlx::query_as(
+            "SELECT run_id, metrics FROM runs
+             WHERE spider = ? AND run_id != ? AND metrics IS NOT NULL AND NOT interrupted
+             ORDER BY started_at DESC LIMIT 1",
+        )
+        .bind(spider)
+        .bind(run_id)
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|e| self.error(e))?;
+        match row {
+            Some((previous, metrics)) => Ok(Some((previous, serde_json::from_str(&metrics)?))),
+            None => Ok(None),
+        }
+    }
+
+    /// IDs of the last `limit` finished runs, most recent first.
+    pub async fn finished_runs(&self, limit: usize) -> Result<Vec<String>> {
+        sqlx::query_scalar(
+            "SELECT run_id FROM runs WHERE metrics IS NOT NULL ORDER BY started_at DESC LIMIT ?",
+        )
+        .bind(limit as i64)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| self.error(e))
+    }
+
+    /// The start of the latest budget window of `spider` that began at or
+    /// before `now`, with the requests counted in it.
+    pub async fn budget_window(&self, spider: &str, now: DateTime<Utc>) -> Result<Option<(DateTime<Utc>, u64)>> {
+        let row: Option<(String, i64)> = sqlx::query_as(
+            "SELECT window_start, requests FROM request_budget
+             WHERE spider = ? AND window_start <= ?
+             ORDER BY window_start DESC LIMIT 1",
+        )
+        .bind(spider)
+        .bind(budget_timestamp(now))
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|e| self.error(e))?;
+        Ok(row.and_then(|(start, requests)| {
+            let start = DateTime::parse_from_rfc3339(&start).ok()?.with_timezone(&Utc);
+            Some((start, requests.max(0) as u64))
+        }))
+    }
+
+    /// Adds `requests` to the budget window of `spider` starting at `start`,
+    /// creating the window if needed. Adding 0 just creates it.
+    pub async fn add_budget_requests(&self, spider: &str, start: DateTime<Utc>, requests: u64) -> Result<()> {
+        sqlx::query(
+            "INSERT INTO request_budget (spider, window_start, requests) VALUES (?, ?, ?)
+            ON CONFLICT (spider, window_start) DO UPDATE SET requests = requests + excluded.requests",
+        )
+        .bind(spider)
+        .bind(budget_timestamp(start))
+        .bind(requests as i64)
+        .execute(&self.pool)
+        .await
+        .map_err(|e| self.error(e))?;
+        Ok(())
+    }
+
+    /// Whether the copy of `url` fetched by an earlier run is still fresh at `now`.
+    pub async fn is_fresh(&self, url: &str, now: DateTime<Utc>) -> Result<bool> {
+        let fresh_until: Option<Option<i64>> =
+            sqlx::query_scalar("SELECT fresh_until FROM pages WHERE url = ?")
+                .bind(url)
+                .fetch_optional(&self.pool)
+                .await
+                .map_err(|e| self.error(e))?;
+        Ok(fresh_until.flatten().is_some_and(|until| now.timestamp() < until))
+    }
+
+    /// Records that `url` was fetched by the current run.
+    pub async fn record(&self, url: &str, page: &Freshness) -> Result<()> {
+        let run_id = self.run_id.lock().ok().and_then(|run_id| run_id.clone());
+        sqlx::query(
+            "INSERT INTO pages (url, status, fetched_at, cache_control, expires, fresh_until, run_id)
+            VALUES (?, ?, ?, ?, ?, ?, ?)
+            ON CONFLICT (url) DO UPDATE SET
+                status = excluded.status,
+                fetched_at = excluded.fetched_at,
+                cache_control = excluded.cache_control,
+                expires = excluded.expires,
+                fresh_until = excluded.fresh_until,
+                run_id = excluded.run_id",
+        )
+        .bind(url)
+        .bind(page.status as i64)
+        .bind(page.fetched_at.to_rfc3339())
+        .bind(&page.cache_control)
+        .bind(&page.expires)
+        .bind(page.fresh_until.map(|until| until.timestamp()))
+        .bind(run_id)
+        .execute(&self.pool)
+        .await
+        .map_err(|e| self.error(e))?;
+        Ok(())
+    }
+}
+
+/// The freshness headers of a response and what they mean for re-crawling it.
+#[derive(Debug, Clone)]
+pub struct Freshness {
+    pub status: u16,
+    pub fetched_at: DateTime<Utc>,
+    pub cache_control: Option<String>,
+    pub expires: Option<String>,
+    /// Until when the page needn't be fetched again; `None` if it always should
+    pub fresh_until: Option<DateTime<Utc>>,
+}
+
+impl Freshness {
+    /// Reads the freshness of a response received at `now`.
+    ///
+    /// `no-store` and `no-cache` make a page stale right away. Otherwise
+    /// `max-age` (minus the `Age` the response already had) takes precedence
+    /// over `Expires`. Pages with neither are always fetched again.
+    pub fn from_headers(status: u16, headers: &HeaderMap, now: DateTime<Utc>) -> Self {
+        let header = |name| {
+            headers
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+                .map(str::to_string)
+        };
+        let cache_control = header(CACHE_CONTROL);
+        let expires = header(EXPIRES);
+
+        let directives: Vec<String> = cache_control
+            .iter()
+            .flat_map(|value| value.split(','))
+            .map(|directive| directive.trim().to_ascii_lowercase())
+            .collect();
+        let max_age = directives
+            .iter()
+            .find_map(|directive| directive.strip_prefix("max-age="))
+            .and_then(|secs| secs.trim_matches('"').parse::<i64>().ok());
+
+        let fresh_until = if directives
+            .iter()
+            .any(|directive| directive == "no-store" || directive == "no-cache")
+        {
+            None
+        } else if let Some(max_age) = max_age {
+            let age = header(AGE).and_then(|age| age.trim().parse::<i64>().ok()).unwrap_or(0);
+            Some(now + chrono::Duration::seconds(max_age - age))
+        } else {
+            // Relative to the server's clock, so a skewed local clock doesn't matter
+            let date = header(DATE).as_deref().and_then(http_date);
+            expires
+                .as_deref()
+                .and_then(http_date)
+                .map(|expires| now + (expires - date.unwrap_or(now)))
+        };
+
+        Self {
+            status,
+            fetched_at: now,
+            cache_control,
+            expires,
+            fresh_until: fresh_until.filter(|until| *until > now),
+        }
+    }
+}
+
+/// `time` in a fixed-width UTC format, so windows compare correctly as text.
+fn budget_timestamp(time: DateTime<Utc>) -> String {
+    time.to_rfc3339_opts(chrono::SecondsFormat::Secs, true)
+}
+
+/// Parses an HTTP date such as `Wed, 21 Oct 2015 07:28:00 GMT`. Invalid dates,
+/// like the common `Expires: 0`, give `None`.
+fn http_date(value: &str) -> Option<DateTime<Utc>> {
+    DateTime::parse_from_rfc2822(value.trim())
+        .ok()
+        .map(|date| date.with_timezone(&Utc))
+}
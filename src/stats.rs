@@ -0,0 +1,127 @@
+//! Crawl history derived from the NDJSON event log.
+
+use crate::events::EventRecord;
+use std::collections::HashMap;
+
+/// Summary of one run in an event log.
+#[derive(Debug, Clone, Default)]
+pub struct RunStats {
+    pub run_id: String,
+    pub spider: Option<String>,
+    pub started: Option<String>,
+    pub requests: u64,
+    pub failed: u64,
+    pub items: u64,
+    /// Set once the run's `run_finished` event is seen
+    pub duration_ms: Option<u64>,
+    /// `(url, error kind)` of every failed request
+    pub failures: Vec<(String, String)>,
+}
+
+impl RunStats {
+    pub fn succeeded(&self) -> u64 {
+        self.requests - self.failed
+    }
+
+    pub fn success_rate(&self) -> f64 {
+        if self.requests == 0 {
+            0.0
+        } else {
+            self.succeeded() as f64 / self.requests as f64 * 100.0
+        }
+    }
+}
+
+/// Where failures concentrate, most frequent first.
+#[derive(Debug, Clone, Default)]
+pub struct FailureHotspots {
+    pub by_host: Vec<(String, u64)>,
+    pub by_kind: Vec<(String, u64)>,
+    pub by_url: Vec<(String, u64)>,
+}
+
+/// Splits an event log into runs. Request events written before run markers
+/// existed are grouped into a single unlabelled run.
+pub fn summarize_runs(events: &[EventRecord]) -> Vec<RunStats> {
+    let mut runs: Vec<RunStats> = Vec::new();
+
+    for event in events {
+        match event {
+            EventRecord::RunStarted {
+                run_id,
+                spider,
+                timestamp,
+            } => runs.push(RunStats {
+                run_id: run_id.clone(),
+                spider: Some(spider.clone()),
+                started: Some(timestamp.clone()),
+                ..Default::default()
+            }),
+            EventRecord::RunFinished {
+                run_id,
+                duration_ms,
+                ..
+            } => {
+                if let Some(run) = runs.iter_mut().rev().find(|r| &r.run_id == run_id) {
+                    run.duration_ms = Some(*duration_ms);
+                }
+            }
+            EventRecord::RequestSucceeded { items, .. } => {
+                let run = current_run(&mut runs);
+                run.requests += 1;
+                run.items += *items as u64;
+            }
+            EventRecord::RequestFailed {
+                url, error_kind, ..
+            } => {
+                let run = current_run(&mut runs);
+                run.requests += 1;
+                run.failed += 1;
+                let kind = error_kind.clone().unwrap_or_else(|| "unknown".to_string());
+                run.failures.push((url.clone(), kind));
+            }
+        }
+    }
+
+    runs
+}
+
+fn current_run(runs: &mut Vec<RunStats>) -> &mut RunStats {
+    if runs.is_empty() {
+        runs.push(RunStats {
+            run_id: "(unlabelled)".to_string(),
+            ..Default::default()
+        });
+    }
+    runs.last_mut().expect("at least one run")
+}
+
+/// Counts failures across `runs` by host, error kind and URL, keeping the `top` of each.
+pub fn failure_hotspots(runs: &[RunStats], top: usize) -> FailureHotspots {
+    let mut by_host: HashMap<String, u64> = HashMap::new();
+    let mut by_kind: HashMap<String, u64> = HashMap::new();
+    let mut by_url: HashMap<String, u64> = HashMap::new();
+
+    for (url, kind) in runs.iter().flat_map(|run| &run.failures) {
+        let host = url::Url::parse(url)
+            .ok()
+            .and_then(|u| u.host_str().map(str::to_string))
+            .unwrap_or_else(|| url.clone());
+        *by_host.entry(host).or_default() += 1;
+        *by_kind.entry(kind.clone()).or_default() += 1;
+        *by_url.entry(url.clone()).or_default() += 1;
+    }
+
+    FailureHotspots {
+        by_host: top_n(by_host, top),
+        by_kind: top_n(by_kind, top),
+        by_url: top_n(by_url, top),
+    }
+}
+
+fn top_n(counts: HashMap<String, u64>, n: usize) -> Vec<(String, u64)> {
+    let mut counts: Vec<_> = counts.into_iter().collect();
+    counts.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    counts.truncate(n);
+    counts
+}
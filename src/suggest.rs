@@ -0,0 +1,304 @@
+//! Guesses a root selector and field rules from a page's most repeated structure,
+//! for `crawler suggest`.
+
+use crate::config::schema::CompressionConfig;
+use crate::error::{Error, Result};
+use crate::http::body::read_body;
+use crate::http::client::client_builder;
+use crate::spider::ExtractionType;
+use reqwest::redirect;
+use select::document::Document;
+use select::node::Node;
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+/// Siblings needed before a structure counts as repeated.
+const MIN_REPEATS: usize = 3;
+/// Instances inspected when looking for fields.
+const MAX_SAMPLED: usize = 25;
+/// Fields proposed at most.
+const MAX_FIELDS: usize = 12;
+
+/// Tags that never hold scrapeable content.
+const IGNORED_TAGS: &[&str] = &[
+    "html", "head", "script", "style", "noscript", "template", "meta", "link", "br", "hr",
+    "option", "svg", "path", "iframe",
+];
+
+/// A proposed root selector and the fields found inside it.
+#[derive(Debug, Clone)]
+pub struct Suggestion {
+    /// CSS selector for the repeated element, without the `css:` prefix
+    pub root_selector: String,
+    /// Number of elements the root selector matched
+    pub matches: usize,
+    pub fields: Vec<SuggestedField>,
+}
+
+#[derive(Debug, Clone)]
+pub struct SuggestedField {
+    pub name: String,
+    /// CSS selector relative to the root, without the `css:` prefix
+    pub selector: String,
+    pub extract: ExtractionType,
+    /// Value found in the first matched element
+    pub sample: String,
+    /// Share of matched elements (0.0-1.0) containing this field
+    pub coverage: f64,
+}
+
+/// The subset of a spider config printed by `crawler suggest`.
+#[derive(Debug, Serialize)]
+pub struct StarterConfig {
+    pub name: String,
+    pub start_urls: Vec<String>,
+    pub root_selector: String,
+    pub extraction_rules: BTreeMap<String, StarterRule>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct StarterRule {
+    pub selector: String,
+    pub extract: ExtractionType,
+}
+
+impl Suggestion {
+    /// A config that crawls `url` with the suggested selectors.
+    pub fn starter_config(&self, url: &str) -> StarterConfig {
+        let name = reqwest::Url::parse(url)
+            .ok()
+            .and_then(|u| u.host_str().map(|host| host.trim_start_matches("www.").replace('.', "-")))
+            .unwrap_or_else(|| "my-spider".to_string());
+
+        StarterConfig {
+            name,
+            start_urls: vec![url.to_string()],
+            root_selector: format!("css:{}", self.root_selector),
+            extraction_rules: self
+                .fields
+                .iter()
+                .map(|field| {
+                    let rule = StarterRule {
+                        selector: format!("css:{}", field.selector),
+                        extract: field.extract.clone(),
+                    };
+                    (field.name.clone(), rule)
+                })
+                .collect(),
+        }
+    }
+}
+
+/// Fetches `url` as HTML, following redirects.
+pub async fn fetch(url: &str) -> Result<String> {
+    let client = client_builder()
+        .redirect(redirect::Policy::limited(10))
+        .build()?;
+    let res = client.get(url).send().await?;
+    if !res.status().is_success() {
+        return Err(Error::HttpStatus {
+            url: url.to_string(),
+            status: res.status().as_u16(),
+        });
+    }
+    let body = read_body(res, CompressionConfig::default().max_body_bytes, None).await?;
+    Ok(body.into_text())
+}
+
+/// Finds the most repeated sibling structure in `html` and proposes fields for it,
+/// or `None` if nothing repeats at least three times.
+pub fn suggest(html: &str) -> Option<Suggestion> {
+    let document = Document::from(html);
+
+    // Sibling elements grouped by parent and tag/class signature
+    let mut groups: HashMap<(usize, String), Vec<Node>> = HashMap::new();
+    let mut signature_counts: HashMap<String, usize> = HashMap::new();
+    for node in elements(&document) {
+        let Some(parent) = node.parent() else {
+            continue;
+        };
+        let signature = signature(&node);
+        *signature_counts.entry(signature.clone()).or_default() += 1;
+        groups.entry((parent.index(), signature)).or_default().push(node);
+    }
+
+    let (root_selector, members, fields) = groups
+        .into_iter()
+        .filter(|(_, members)| members.len() >= MIN_REPEATS)
+        .map(|((_, signature), members)| {
+            let fields = find_fields(&members);
+            (signature, members, fields)
+        })
+        .filter(|(_, _, fields)| !fields.is_empty())
+        .max_by(|a, b| score(&a.1, &a.2).total_cmp(&score(&b.1, &b.2)))?;
+
+    // Qualify the selector with the parent if the signature also occurs elsewhere
+    let root_selector = if signature_counts[&root_selector] > members.len() {
+        let parent = members[0].parent()?;
+        match parent.attr("id").filter(|id| is_css_ident(id)) {
+            Some(id) => format!("#{} > {}", id, root_selector),
+            None => format!("{} > {}", signature(&parent), root_selector),
+        }
+    } else {
+        root_selector
+    };
+
+    Some(Suggestion {
+        root_selector,
+        matches: members.len(),
+        fields,
+    })
+}
+
+/// Repeated structures with more fields and more text win; navigation lists
+/// of short links lose to content blocks.
+fn score(members: &[Node], fields: &[SuggestedField]) -> f64 {
+    let text: usize = members
+        .iter()
+        .take(MAX_SAMPLED)
+        .map(|node| node.text().split_whitespace().map(str::len).sum::<usize>().min(500))
+        .sum();
+    let sampled = members.len().min(MAX_SAMPLED) as f64;
+    let coverage: f64 = fields.iter().map(|field| field.coverage).sum();
+    (members.len() as f64).sqrt() * (text as f64 / sampled).max(1.0) * (1.0 + coverage)
+}
+
+fn elements(document: &Document) -> impl Iterator<Item = Node<'_>> {
+    (0..document.nodes.len())
+        .filter_map(|i| document.nth(i))
+        .filter(|node| node.name().is_some_and(|name| !IGNORED_TAGS.contains(&name)))
+}
+
+/// `tag.class1.class2`, leaving out classes that look generated.
+fn signature(node: &Node) -> String {
+    let mut classes: Vec<&str> = node
+        .attr("class")
+        .unwrap_or_default()
+        .split_whitespace()
+        .filter(|class| is_css_ident(class) && !class.chars().any(|c| c.is_ascii_digit()))
+        .collect();
+    classes.sort_unstable();
+    classes.dedup();
+
+    let mut signature = node.name().unwrap_or_default().to_string();
+    for class in classes {
+        signature.push('.');
+        signature.push_str(class);
+    }
+    signature
+}
+
+fn is_css_ident(s: &str) -> bool {
+    s.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_' || c == '-')
+        && s.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+}
+
+/// Own text of `node`, ignoring the text of child elements.
+fn own_text(node: &Node) -> String {
+    node.children()
+        .filter_map(|child| child.as_text())
+        .collect::<Vec<_>>()
+        .join(" ")
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Fields present in at least half of the sampled `members`: text-bearing
+/// elements, link targets and image sources whose value varies between members.
+fn find_fields(members: &[Node]) -> Vec<SuggestedField> {
+    let sampled = &members[..members.len().min(MAX_SAMPLED)];
+    // (selector, attribute) -> (first sample, members containing it, whether the
+    // value differs between members), in first-seen order
+    let mut order: Vec<(String, Option<&'static str>)> = Vec::new();
+    let mut found: HashMap<(String, Option<&'static str>), (String, usize, bool)> = HashMap::new();
+
+    for member in sampled {
+        let mut seen = HashSet::new();
+        for node in member.descendants().filter(|node| {
+            node.name().is_some_and(|name| !IGNORED_TAGS.contains(&name))
+        }) {
+            let selector = signature(&node);
+            let mut candidates = Vec::new();
+            let text = own_text(&node);
+            if !text.is_empty() {
+                candidates.push((None, text));
+            }
+            match node.name() {
+                Some("a") => candidates.extend(node.attr("href").map(|href| (Some("href"), href.to_string()))),
+                Some("img") => candidates.extend(node.attr("src").map(|src| (Some("src"), src.to_string()))),
+                _ => {}
+            }
+
+            for (attribute, value) in candidates {
+                let key = (selector.clone(), attribute);
+                if !seen.insert(key.clone()) {
+                    continue;
+                }
+                let entry = found.entry(key.clone()).or_insert_with(|| {
+                    order.push(key);
+                    (value.clone(), 0, false)
+                });
+                entry.1 += 1;
+                entry.2 |= entry.0 != value;
+            }
+        }
+    }
+
+    let mut names = HashSet::new();
+    order
+        .into_iter()
+        .filter_map(|key| {
+            let (sample, count, varies) = found.remove(&key)?;
+            let coverage = count as f64 / sampled.len() as f64;
+            // Values shared by every member are labels like "Read more", not data
+            (coverage >= 0.5 && (varies || sampled.len() == 1)).then(|| {
+                let (selector, attribute) = key;
+                let name = unique_name(&mut names, field_name(&selector, attribute));
+                let extract = match attribute {
+                    Some(attribute) => ExtractionType::Attribute(attribute.to_string()),
+                    None => ExtractionType::Text,
+                };
+                SuggestedField {
+                    name,
+                    selector,
+                    extract,
+                    sample,
+                    coverage,
+                }
+            })
+        })
+        .take(MAX_FIELDS)
+        .collect()
+}
+
+/// A readable field name from the element's first class, or from its tag.
+fn field_name(selector: &str, attribute: Option<&str>) -> String {
+    let mut parts = selector.split('.');
+    let tag = parts.next().unwrap_or_default();
+    let class = parts.next().map(|class| class.replace('-', "_").to_ascii_lowercase());
+    match (attribute, class) {
+        (Some("href"), Some(class)) => format!("{}_link", class),
+        (Some("href"), None) => "link".to_string(),
+        (Some("src"), Some(class)) => format!("{}_image", class),
+        (Some("src"), None) => "image".to_string(),
+        (_, Some(class)) => class,
+        (_, None) => match tag {
+            "h1" | "h2" | "h3" | "h4" | "h5" | "h6" => "title".to_string(),
+            "p" => "text".to_string(),
+            "a" => "link_text".to_string(),
+            "time" => "date".to_string(),
+            other => other.to_string(),
+        },
+    }
+}
+
+fn unique_name(names: &mut HashSet<String>, name: String) -> String {
+    let mut candidate = name.clone();
+    let mut n = 2;
+    while !names.insert(candidate.clone()) {
+        candidate = format!("{}_{}", name, n);
+        n += 1;
+    }
+    candidate
+}
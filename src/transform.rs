@@ -0,0 +1,304 @@
+//! Transforms that turn extracted text into typed JSON values.
+
+use chrono::{DateTime, Duration, NaiveDate, NaiveDateTime, SecondsFormat, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Transform {
+    /// `"1.299,00 €"` → `{"amount": 1299.0, "currency": "EUR"}`
+    ParsePrice,
+    /// `"12,345"` → `12345`
+    ParseNumber,
+    /// `"March 5, 2024"` → `"2024-03-05"`, `"2 hours ago"` → an RFC 3339 timestamp
+    ParseDate,
+}
+
+impl Transform {
+    /// Applies the transform, or returns `None` if `text` can't be parsed.
+    ///
+    /// `locale` (e.g. `de-DE`) decides the decimal separator and whether
+    /// `01/02/2024` is day- or month-first; without it both are guessed.
+    pub fn apply(&self, text: &str, locale: Option<&str>) -> Option<Value> {
+        match self {
+            Transform::ParsePrice => {
+                let amount = parse_number(text, locale)?;
+                Some(json!({ "amount": amount, "currency": currency_code(text) }))
+            }
+            Transform::ParseNumber => parse_number(text, locale).map(number_value),
+            Transform::ParseDate => parse_date(text, locale, Utc::now()).map(Value::String),
+        }
+    }
+}
+
+/// Integral values as JSON integers, everything else as floats.
+pub fn number_value(value: f64) -> Value {
+    if value.fract() == 0.0 && value.abs() < 9_007_199_254_740_992.0 {
+        json!(value as i64)
+    } else {
+        json!(value)
+    }
+}
+
+/// Languages that write `1.234,56` rather than `1,234.56`.
+const DECIMAL_COMMA_LANGUAGES: &[&str] = &[
+    "de", "fr", "es", "it", "pt", "nl", "ru", "pl", "tr", "sv", "da", "nb", "no", "fi", "cs", "sk",
+    "hu", "ro", "uk", "el", "id", "vi",
+];
+
+fn language(locale: &str) -> String {
+    locale
+        .split(['-', '_'])
+        .next()
+        .unwrap_or_default()
+        .to_ascii_lowercase()
+}
+
+fn decimal_comma(locale: &str) -> bool {
+    DECIMAL_COMMA_LANGUAGES.contains(&language(locale).as_str())
+}
+
+/// Parses the first number in `text`, handling thousands separators.
+pub fn parse_number(text: &str, locale: Option<&str>) -> Option<f64> {
+    let start = text.find(|c: char| c.is_ascii_digit())?;
+    let negative = text[..start].trim_end().ends_with('-');
+    let raw: String = text[start..]
+        .chars()
+        .take_while(|c| c.is_ascii_digit() || matches!(c, '.' | ',' | '\'' | ' ' | '\u{a0}' | '\u{202f}'))
+        .filter(|c| !matches!(c, '\'' | ' ' | '\u{a0}' | '\u{202f}'))
+        .collect();
+    let raw = raw.trim_end_matches(['.', ',']);
+
+    let decimal = match locale {
+        Some(locale) if decimal_comma(locale) => Some(','),
+        Some(_) => Some('.'),
+        None => guess_decimal_separator(raw),
+    };
+    let normalized: String = raw
+        .chars()
+        .filter_map(|c| match c {
+            c if c.is_ascii_digit() => Some(c),
+            c if Some(c) == decimal => Some('.'),
+            _ => None,
+        })
+        .collect();
+
+    let value: f64 = normalized.parse().ok()?;
+    Some(if negative { -value } else { value })
+}
+
+/// Picks the decimal separator of `raw` (digits, `.` and `,` only), if it has one.
+///
+/// With both separators the last one is the decimal. With one kind, it is a
+/// thousands separator if it repeats or is followed by exactly three digits,
+/// unless only zeros come before it, as in `0.125`. A rule's `locale` settles
+/// the cases this gets wrong, such as `3.141`.
+fn guess_decimal_separator(raw: &str) -> Option<char> {
+    let last = raw.rfind(['.', ','])?;
+    let separator = raw[last..].chars().next()?;
+    if raw.contains('.') && raw.contains(',') {
+        return Some(separator);
+    }
+    let digits_after = raw.len() - last - 1;
+    let leading_zero = raw[..last].chars().all(|c| c == '0');
+    if raw.matches(separator).count() > 1 || (digits_after == 3 && !leading_zero) {
+        None
+    } else {
+        Some(separator)
+    }
+}
+
+/// ISO 4217 code from an explicit code or a currency symbol in `text`.
+fn currency_code(text: &str) -> Option<&'static str> {
+    const CODES: &[&str] = &[
+        "USD", "EUR", "GBP", "JPY", "CNY", "CHF", "CAD", "AUD", "NZD", "SEK", "NOK", "DKK", "PLN",
+        "CZK", "HUF", "RUB", "TRY", "INR", "BRL", "MXN", "ZAR", "KRW", "SGD", "HKD",
+    ];
+    // Longer symbols first, so `R$` isn't read as `$`
+    const SYMBOLS: &[(&str, &str)] = &[
+        ("US$", "USD"),
+        ("R$", "BRL"),
+        ("C$", "CAD"),
+        ("A$", "AUD"),
+        ("NZ$", "NZD"),
+        ("HK$", "HKD"),
+        ("zł", "PLN"),
+        ("Kč", "CZK"),
+        ("€", "EUR"),
+        ("£", "GBP"),
+        ("¥", "JPY"),
+        ("₹", "INR"),
+        ("₽", "RUB"),
+        ("₺", "TRY"),
+        ("₩", "KRW"),
+        ("$", "USD"),
+    ];
+
+    let upper = text.to_ascii_uppercase();
+    let words = upper.split(|c: char| !c.is_ascii_alphabetic());
+    if let Some(code) = words
+        .filter_map(|word| CODES.iter().find(|code| **code == word))
+        .next()
+    {
+        return Some(code);
+    }
+    SYMBOLS
+        .iter()
+        .find(|(symbol, _)| text.contains(symbol))
+        .map(|(_, code)| *code)
+}
+
+/// Parses absolute and relative dates, returning `YYYY-MM-DD` or an RFC 3339 timestamp.
+pub fn parse_date(text: &str, locale: Option<&str>, now: DateTime<Utc>) -> Option<String> {
+    let text = text.trim();
+    if let Some(date) = parse_relative(text, now) {
+        return Some(date);
+    }
+
+    if let Ok(dt) = DateTime::parse_from_rfc3339(text) {
+        return Some(dt.to_rfc3339());
+    }
+    if let Ok(dt) = DateTime::parse_from_rfc2822(text) {
+        return Some(dt.to_rfc3339());
+    }
+    for format in ["%Y-%m-%d %H:%M:%S", "%Y-%m-%dT%H:%M:%S", "%Y-%m-%d %H:%M"] {
+        if let Ok(dt) = NaiveDateTime::parse_from_str(text, format) {
+            return Some(dt.and_utc().to_rfc3339());
+        }
+    }
+
+    let month_first = ["%m/%d/%Y", "%m/%d/%y"];
+    let day_first = ["%d/%m/%Y", "%d/%m/%y"];
+    let (first, second) = match locale {
+        Some(locale) if !locale.eq_ignore_ascii_case("en-US") && !locale.eq_ignore_ascii_case("en") => {
+            (day_first, month_first)
+        }
+        _ => (month_first, day_first),
+    };
+    let formats = ["%Y-%m-%d", "%Y/%m/%d", "%d.%m.%Y", "%B %d, %Y", "%b %d, %Y", "%B %d %Y", "%d %B %Y", "%d %b %Y"]
+        .into_iter()
+        .chain(first)
+        .chain(second);
+    // Ordinals like "5th" and "1st" aren't understood by chrono
+    let cleaned = strip_ordinals(text);
+    formats
+        .filter_map(|format| NaiveDate::parse_from_str(&cleaned, format).ok())
+        .map(|date| date.format("%Y-%m-%d").to_string())
+        .next()
+}
+
+fn strip_ordinals(text: &str) -> String {
+    text.split(' ')
+        .map(|word| {
+            let trimmed = word.trim_end_matches(',');
+            ["st", "nd", "rd", "th"]
+                .iter()
+                .find_map(|suffix| trimmed.strip_suffix(suffix))
+                .filter(|digits| !digits.is_empty() && digits.chars().all(|c| c.is_ascii_digit()))
+                .map(|digits| format!("{}{}", digits, &word[trimmed.len()..]))
+                .unwrap_or_else(|| word.to_string())
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// `today`, `yesterday`, `just now` and `N <unit>s ago`.
+fn parse_relative(text: &str, now: DateTime<Utc>) -> Option<String> {
+    let lower = text.to_lowercase();
+    match lower.as_str() {
+        "just now" | "now" => return Some(now.to_rfc3339_opts(SecondsFormat::Secs, true)),
+        "today" => return Some(now.format("%Y-%m-%d").to_string()),
+        "yesterday" => return Some((now - Duration::days(1)).format("%Y-%m-%d").to_string()),
+        _ => {}
+    }
+
+    let rest = lower.strip_suffix(" ago")?;
+    let (amount, unit) = rest.split_once(' ')?;
+    let amount: i64 = match amount {
+        "a" | "an" | "one" => 1,
+        n => n.parse().ok()?,
+    };
+    let unit = unit.trim_end_matches('s');
+    let (delta, date_only) = match unit {
+        "second" | "sec" => (Duration::seconds(amount), false),
+        "minute" | "min" => (Duration::minutes(amount), false),
+        "hour" | "hr" => (Duration::hours(amount), false),
+        "day" => (Duration::days(amount), true),
+        "week" => (Duration::weeks(amount), true),
+        "month" => (Duration::days(30 * amount), true),
+        "year" => (Duration::days(365 * amount), true),
+        _ => return None,
+    };
+    let then = now - delta;
+    Some(if date_only {
+        then.format("%Y-%m-%d").to_string()
+    } else {
+        then.to_rfc3339_opts(SecondsFormat::Secs, true)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn parses_numbers_with_guessed_separators() {
+        assert_eq!(parse_number("12,345", None), Some(12345.0));
+        assert_eq!(parse_number("1.234.567", None), Some(1234567.0));
+        assert_eq!(parse_number("1,299.50 USD", None), Some(1299.5));
+        assert_eq!(parse_number("1.299,50 €", None), Some(1299.5));
+        assert_eq!(parse_number("12,5", None), Some(12.5));
+        assert_eq!(parse_number("1 234,5", None), Some(1234.5));
+        assert_eq!(parse_number("-3.5", None), Some(-3.5));
+        assert_eq!(parse_number("no digits", None), None);
+    }
+
+    #[test]
+    fn reads_a_leading_zero_group_as_decimal() {
+        assert_eq!(parse_number("0.125", None), Some(0.125));
+        assert_eq!(parse_number("0,125", None), Some(0.125));
+        assert_eq!(parse_number("00.250", None), Some(0.25));
+    }
+
+    #[test]
+    fn follows_the_locale() {
+        assert_eq!(parse_number("4.125", Some("en-US")), Some(4.125));
+        assert_eq!(parse_number("4.125", Some("de-DE")), Some(4125.0));
+        assert_eq!(parse_number("4,125", Some("de")), Some(4.125));
+        assert_eq!(parse_number("4,125", Some("en")), Some(4125.0));
+    }
+
+    #[test]
+    fn parses_absolute_dates() {
+        let now = Utc.with_ymd_and_hms(2024, 6, 1, 12, 0, 0).unwrap();
+        assert_eq!(parse_date("2024-03-05", None, now).as_deref(), Some("2024-03-05"));
+        assert_eq!(parse_date("March 5th, 2024", None, now).as_deref(), Some("2024-03-05"));
+        assert_eq!(parse_date("5 Mar 2024", None, now).as_deref(), Some("2024-03-05"));
+        assert_eq!(parse_date("05.03.2024", None, now).as_deref(), Some("2024-03-05"));
+        assert_eq!(
+            parse_date("2024-03-05T10:00:00+02:00", None, now).as_deref(),
+            Some("2024-03-05T10:00:00+02:00")
+        );
+        assert_eq!(parse_date("not a date", None, now), None);
+    }
+
+    #[test]
+    fn orders_slashed_dates_by_locale() {
+        let now = Utc.with_ymd_and_hms(2024, 6, 1, 12, 0, 0).unwrap();
+        assert_eq!(parse_date("01/02/2024", None, now).as_deref(), Some("2024-01-02"));
+        assert_eq!(parse_date("01/02/2024", Some("en-US"), now).as_deref(), Some("2024-01-02"));
+        assert_eq!(parse_date("01/02/2024", Some("en-GB"), now).as_deref(), Some("2024-02-01"));
+        assert_eq!(parse_date("25/12/2024", None, now).as_deref(), Some("2024-12-25"));
+    }
+
+    #[test]
+    fn parses_relative_dates() {
+        let now = Utc.with_ymd_and_hms(2024, 6, 1, 12, 0, 0).unwrap();
+        assert_eq!(parse_date("yesterday", None, now).as_deref(), Some("2024-05-31"));
+        assert_eq!(parse_date("3 days ago", None, now).as_deref(), Some("2024-05-29"));
+        assert_eq!(parse_date("2 hours ago", None, now).as_deref(), Some("2024-06-01T10:00:00Z"));
+        assert_eq!(parse_date("an hour ago", None, now).as_deref(), Some("2024-06-01T11:00:00Z"));
+    }
+}
@@ -0,0 +1,76 @@
+//! Canonicalization of start and discovered URLs before they are deduplicated
+//! and queued, so tracking parameters and mirror hosts don't multiply the frontier.
+
+use crate::config::schema::UrlRewriteConfig;
+use crate::error::{Error, Result};
+use crate::http::fingerprint::wildcard_match;
+use regex::Regex;
+use url::Url;
+
+#[derive(Debug, Clone)]
+pub struct UrlRewrite {
+    rules: Vec<(Regex, String)>,
+    strip_params: Vec<String>,
+    force_https: bool,
+    lowercase_path: bool,
+}
+
+impl UrlRewrite {
+    pub fn new(config: &UrlRewriteConfig) -> Result<Self> {
+        let rules = config
+            .rewrites
+            .iter()
+            .map(|rule| {
+                let pattern = Regex::new(&rule.pattern).map_err(|e| {
+                    Error::Config(format!("Invalid url_rewrite pattern '{}': {}", rule.pattern, e))
+                })?;
+                Ok((pattern, rule.replace.clone()))
+            })
+            .collect::<Result<_>>()?;
+
+        Ok(Self {
+            rules,
+            strip_params: config.strip_params.clone(),
+            force_https: config.force_https,
+            lowercase_path: config.lowercase_path,
+        })
+    }
+
+    /// `url` with the regex rewrites applied in order, then the scheme, path
+    /// and query rules. URLs that don't parse, before or after the regex
+    /// rewrites, are returned unchanged.
+    pub fn apply(&self, url: &str) -> String {
+        let mut rewritten = url.to_string();
+        for (pattern, replace) in &self.rules {
+            rewritten = pattern.replace(&rewritten, replace.as_str()).into_owned();
+        }
+        let mut parsed = match Url::parse(&rewritten) {
+            Ok(parsed) => parsed,
+            Err(e) => {
+                log::warn!("Keeping {} as is, it was rewritten to an invalid URL {}: {}", url, rewritten, e);
+                return url.to_string();
+            }
+        };
+
+        if self.force_https && parsed.scheme() == "http" {
+            let _ = parsed.set_scheme("https");
+        }
+        if self.lowercase_path {
+            let path = parsed.path().to_lowercase();
+            parsed.set_path(&path);
+        }
+        if !self.strip_params.is_empty() && parsed.query().is_some() {
+            let params: Vec<(String, String)> = parsed
+                .query_pairs()
+                .filter(|(key, _)| !self.strip_params.iter().any(|pattern| wildcard_match(pattern, key)))
+                .map(|(key, value)| (key.into_owned(), value.into_owned()))
+                .collect();
+            if params.is_empty() {
+                parsed.set_query(None);
+            } else {
+                parsed.query_pairs_mut().clear().extend_pairs(params);
+            }
+        }
+        parsed.to_string()
+    }
+}
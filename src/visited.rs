@@ -0,0 +1,158 @@
+//! Sets of already-seen request fingerprints, exact or probabilistic.
+
+use crate::config::schema::{DedupConfig, DedupKind};
+use std::collections::HashSet;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+
+/// Each added Bloom layer gets this fraction of the previous layer's error
+/// budget, so the combined false-positive rate stays below the configured one.
+const TIGHTENING_RATIO: f64 = 0.5;
+
+/// Remembers which fingerprints were seen.
+#[derive(Debug)]
+pub enum VisitedSet {
+    Exact(HashSet<String>),
+    /// Bounded memory for very large crawls, at the cost of occasionally
+    /// treating a new URL as already seen
+    Bloom(ScalableBloomFilter),
+}
+
+impl Default for VisitedSet {
+    fn default() -> Self {
+        VisitedSet::Exact(HashSet::new())
+    }
+}
+
+impl VisitedSet {
+    pub fn new(config: &DedupConfig) -> Self {
+        match config.kind {
+            DedupKind::Exact => VisitedSet::Exact(HashSet::new()),
+            DedupKind::Bloom => {
+                VisitedSet::Bloom(ScalableBloomFilter::new(config.expected_items, config.fp_rate))
+            }
+        }
+    }
+
+    /// Forgets every key, keeping the kind of set and its sizing.
+    pub fn clear(&mut self) {
+        match self {
+            VisitedSet::Exact(set) => set.clear(),
+            VisitedSet::Bloom(filter) => *filter = ScalableBloomFilter::new(filter.initial_capacity, filter.fp_rate),
+        }
+    }
+
+    /// Adds `key`, returning `true` if it wasn't seen before.
+    pub fn insert(&mut self, key: &str) -> bool {
+        match self {
+            VisitedSet::Exact(set) => set.insert(key.to_string()),
+            VisitedSet::Bloom(filter) => filter.insert(key),
+        }
+    }
+}
+
+/// A Bloom filter that adds larger layers as it fills up, so it needs no fixed
+/// capacity while keeping the false-positive rate bounded.
+#[derive(Debug)]
+pub struct ScalableBloomFilter {
+    layers: Vec<BloomLayer>,
+    initial_capacity: usize,
+    fp_rate: f64,
+}
+
+impl ScalableBloomFilter {
+    /// A filter sized for `expected_items` that grows past it, with an overall
+    /// false-positive rate of at most `fp_rate`.
+    pub fn new(expected_items: usize, fp_rate: f64) -> Self {
+        let initial_capacity = expected_items.max(1);
+        let fp_rate = fp_rate.clamp(f64::MIN_POSITIVE, 0.5);
+        Self {
+            layers: vec![BloomLayer::new(initial_capacity, fp_rate * (1.0 - TIGHTENING_RATIO))],
+            initial_capacity,
+            fp_rate,
+        }
+    }
+
+    pub fn contains(&self, key: &str) -> bool {
+        let (h1, h2) = hashes(key);
+        self.layers.iter().any(|layer| layer.contains(h1, h2))
+    }
+
+    /// Adds `key`, returning `false` if it was (probably) already present.
+    pub fn insert(&mut self, key: &str) -> bool {
+        let (h1, h2) = hashes(key);
+        if self.layers.iter().any(|layer| layer.contains(h1, h2)) {
+            return false;
+        }
+
+        if self.layers.last().is_some_and(|layer| layer.len >= layer.capacity) {
+            let n = self.layers.len() as i32;
+            let capacity = self.initial_capacity.saturating_mul(1 << n.min(32));
+            let fp_rate = self.fp_rate * (1.0 - TIGHTENING_RATIO) * TIGHTENING_RATIO.powi(n);
+            self.layers.push(BloomLayer::new(capacity, fp_rate));
+        }
+        if let Some(layer) = self.layers.last_mut() {
+            layer.insert(h1, h2);
+        }
+        true
+    }
+
+    /// Memory used by the bit arrays.
+    pub fn size_bytes(&self) -> usize {
+        self.layers.iter().map(|layer| layer.bits.len() * 8).sum()
+    }
+}
+
+#[derive(Debug)]
+struct BloomLayer {
+    bits: Vec<u64>,
+    num_bits: u64,
+    num_hashes: u32,
+    capacity: usize,
+    len: usize,
+}
+
+impl BloomLayer {
+    fn new(capacity: usize, fp_rate: f64) -> Self {
+        let ln2 = std::f64::consts::LN_2;
+        let num_bits = (-(capacity as f64) * fp_rate.ln() / (ln2 * ln2)).ceil().max(64.0) as u64;
+        let num_hashes = (-fp_rate.log2()).ceil().max(1.0) as u32;
+        Self {
+            bits: vec![0; num_bits.div_ceil(64) as usize],
+            num_bits,
+            num_hashes,
+            capacity,
+            len: 0,
+        }
+    }
+
+    /// Bit positions by double hashing: `h1 + i * h2`.
+    fn positions(&self, h1: u64, h2: u64) -> impl Iterator<Item = u64> + '_ {
+        (0..self.num_hashes as u64).map(move |i| h1.wrapping_add(i.wrapping_mul(h2)) % self.num_bits)
+    }
+
+    fn contains(&self, h1: u64, h2: u64) -> bool {
+        self.positions(h1, h2)
+            .all(|bit| self.bits[(bit / 64) as usize] & (1 << (bit % 64)) != 0)
+    }
+
+    fn insert(&mut self, h1: u64, h2: u64) {
+        let positions: Vec<u64> = self.positions(h1, h2).collect();
+        for bit in positions {
+            self.bits[(bit / 64) as usize] |= 1 << (bit % 64);
+        }
+        self.len += 1;
+    }
+}
+
+/// Two independent 64-bit hashes of `key`.
+fn hashes(key: &str) -> (u64, u64) {
+    let hash = |seed: u8| {
+        let mut hasher = DefaultHasher::new();
+        seed.hash(&mut hasher);
+        key.hash(&mut hasher);
+        hasher.finish()
+    };
+    // A zero step would make every hash function pick the same bit
+    (hash(0), hash(1) | 1)
+}
@@ -0,0 +1,213 @@
+//! `crawler worker`: takes crawl jobs from a message queue, runs them one at a
+//! time and reports each outcome to a results queue, so any number of workers
+//! can share the jobs of a scraping farm.
+//!
+//! A job is a [`JobSpec`] as JSON, with an optional `id` that is echoed in its
+//! results. Each job gets two results: `started`, then `finished` with the
+//! final metrics, or `failed` with the error if it couldn't run.
+//!
+//! Jobs are moved to a processing list while they run and removed once their
+//! outcome is reported, so those of a worker that crashed can be found there
+//! and queued again.
+
+use crate::crawler::shutdown_signal;
+use crate::error::{Error, Result};
+use crate::run_id::new_run_id;
+use crate::serve::{JobSpec, build_job};
+use async_trait::async_trait;
+use chrono::Utc;
+use redis::{AsyncCommands, Direction};
+use redis::aio::MultiplexedConnection;
+use serde::Deserialize;
+use serde_json::{Value, json};
+use std::time::Duration;
+
+/// How long a worker waits on an empty queue before checking for a signal again.
+const POLL_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// A queue of jobs and their results.
+#[async_trait]
+pub trait JobQueue: Send {
+    /// The next job's payload, or `None` if none arrived within `timeout`. The
+    /// job stays claimed by this worker until [`JobQueue::done`].
+    async fn next(&mut self, timeout: Duration) -> Result<Option<String>>;
+
+    async fn report(&mut self, result: &Value) -> Result<()>;
+
+    /// Releases the job `payload` once its outcome was reported.
+    async fn done(&mut self, _payload: &str) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// Redis lists: jobs are moved from the head of one to a processing list
+/// while they run, results pushed to another.
+pub struct RedisQueue {
+    connection: MultiplexedConnection,
+    jobs_key: String,
+    /// `<jobs_key>:processing`
+    processing_key: String,
+    results_key: String,
+}
+
+impl RedisQueue {
+    pub async fn connect(url: &str, jobs_key: &str, results_key: &str) -> Result<Self> {
+        let client = redis::Client::open(url).map_err(|e| queue_error(url, e))?;
+        let connection = client
+            .get_multiplexed_tokio_connection()
+            .await
+            .map_err(|e| queue_error(url, e))?;
+        Ok(Self {
+            connection,
+            jobs_key: jobs_key.to_string(),
+            processing_key: format!("{}:processing", jobs_key),
+            results_key: results_key.to_string(),
+        })
+    }
+}
+
+fn queue_error(queue: &str, e: impl std::fmt::Display) -> Error {
+    Error::Queue {
+        queue: queue.to_string(),
+        message: e.to_string(),
+    }
+}
+
+#[async_trait]
+impl JobQueue for RedisQueue {
+    async fn next(&mut self, timeout: Duration) -> Result<Option<String>> {
+        self.connection
+            .blmove(
+                &self.jobs_key,
+                &self.processing_key,
+                Direction::Left,
+                Direction::Right,
+                timeout.as_secs_f64(),
+            )
+            .await
+            .map_err(|e| queue_error(&self.jobs_key, e))
+    }
+
+    async fn report(&mut self, result: &Value) -> Result<()> {
+        let _: () = self
+            .connection
+            .rpush(&self.results_key, result.to_string())
+            .await
+            .map_err(|e| queue_error(&self.results_key, e))?;
+        Ok(())
+    }
+
+    async fn done(&mut self, payload: &str) -> Result<()> {
+        let _: () = self
+            .connection
+            .lrem(&self.processing_key, 1, payload)
+            .await
+            .map_err(|e| queue_error(&self.processing_key, e))?;
+        Ok(())
+    }
+}
+
+/// Opens the queue at `url`. Only `redis://` and `rediss://` queues are supported.
+pub async fn open_queue(url: &str, jobs_key: &str, results_key: &str) -> Result<Box<dyn JobQueue>> {
+    match url.split_once("://").map(|(scheme, _)| scheme) {
+        Some("redis" | "rediss") => Ok(Box::new(RedisQueue::connect(url, jobs_key, results_key).await?)),
+        _ => Err(Error::Config(format!(
+            "Unsupported job queue '{}' (expected redis:// or rediss://)",
+            url
+        ))),
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct QueuedJob {
+    #[serde(default)]
+    id: Option<Value>,
+    #[serde(flatten)]
+    spec: JobSpec,
+}
+
+/// Runs jobs from `queue` until Ctrl-C or SIGTERM, capping each job's
+/// concurrency at `max_concurrency`. A job already running is stopped by the
+/// same signal and reported, and no further job is taken.
+pub async fn run(mut queue: Box<dyn JobQueue>, max_concurrency: usize) -> Result<()> {
+    let mut shutdown = Box::pin(shutdown_signal());
+    loop {
+        let payload = tokio::select! {
+            // A signal received during the last job wins over the next one
+            biased;
+            signal = &mut shutdown => {
+                log::info!("Received {}, taking no more jobs", signal);
+                break;
+            }
+            payload = queue.next(POLL_TIMEOUT) => payload?,
+        };
+        let Some(payload) = payload else { continue };
+
+        let run_id = new_run_id();
+        let job: QueuedJob = match serde_json::from_str(&payload) {
+            Ok(job) => job,
+            Err(e) => {
+                log::error!("Skipping a malformed job: {}", e);
+                queue.report(&json!({ "id": null, "status": "failed", "error": e.to_string() })).await?;
+                queue.done(&payload).await?;
+                continue;
+            }
+        };
+        let id = job.id.clone().unwrap_or(Value::Null);
+        let result = match run_job(&mut *queue, job, &id, &run_id, max_concurrency).await {
+            Ok(result) => result,
+            Err(e) => {
+                log::error!("Job {} failed: {}", id, e);
+                json!({ "id": id, "run_id": run_id, "status": "failed", "error": e.to_string() })
+            }
+        };
+        queue.report(&result).await?;
+        queue.done(&payload).await?;
+    }
+    log::info!("Worker stopped");
+    Ok(())
+}
+
+async fn run_job(
+    queue: &mut dyn JobQueue,
+    job: QueuedJob,
+    id: &Value,
+    run_id: &str,
+    max_concurrency: usize,
+) -> Result<Value> {
+    let concurrency = job.spec.concurrency;
+    let mut config = job.spec.load()?;
+    if let Some(output) = &mut config.output {
+        output.expand_run_id(run_id);
+    }
+    config.concurrency = concurrency
+        .unwrap_or(config.concurrency)
+        .clamp(1, max_concurrency.max(1));
+    let (engine, spider) = build_job(&config, run_id, None).await?;
+
+    let started_at = Utc::now();
+    log::info!("Starting job {} ({}) as run {}", id, config.name, run_id);
+    queue
+        .report(&json!({
+            "id": id,
+            "run_id": run_id,
+            "spider": config.name,
+            "status": "started",
+            "started_at": started_at.to_rfc3339(),
+        }))
+        .await?;
+    let report = engine.run(spider).await?;
+
+    Ok(json!({
+        "id": id,
+        "run_id": run_id,
+        "spider": config.name,
+        "status": "finished",
+        "end_reason": report.end_reason,
+        "started_at": started_at.to_rfc3339(),
+        "finished_at": Utc::now().to_rfc3339(),
+        "failed_urls": report.failures,
+        "metrics": report.metrics,
+        "durations": report.durations,
+    }))
+}